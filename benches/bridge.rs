@@ -0,0 +1,165 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use deck_robot_remote::{
+    foxglove::{LaserScan, PackedElementField, PointCloud},
+    foxglove_server::{json_payload, proto_payload, transformed_json_payload},
+    transform::{transform_proto, PayloadTransform},
+    DESCRIPTOR_POOL,
+};
+use prost::Message;
+use zenoh::prelude::r#async::*;
+
+const POINT_COUNT: usize = 1024;
+const RANGE_COUNT: usize = 1024;
+
+fn synthetic_point_cloud() -> Vec<u8> {
+    let fields = ["x", "y", "z"]
+        .iter()
+        .enumerate()
+        .map(|(index, name)| PackedElementField {
+            name: name.to_string(),
+            offset: index as u32 * 4,
+            r#type: 7,
+        })
+        .collect();
+    let data = (0..POINT_COUNT * 3)
+        .flat_map(|index| (index as f32).to_le_bytes())
+        .collect();
+    PointCloud {
+        timestamp: None,
+        frame_id: String::from("lidar"),
+        pose: None,
+        point_stride: 12,
+        fields,
+        data,
+    }
+    .encode_to_vec()
+}
+
+fn synthetic_json() -> String {
+    String::from(
+        r#"{"battery":100,"humidity":45.5,"linkquality":120,"temperature":21.3,"voltage":3000}"#,
+    )
+}
+
+fn synthetic_laser_scan() -> Vec<u8> {
+    LaserScan {
+        timestamp: None,
+        frame_id: String::from("lidar"),
+        pose: None,
+        start_angle: -std::f64::consts::PI,
+        end_angle: std::f64::consts::PI,
+        ranges: (0..RANGE_COUNT).map(|index| index as f64).collect(),
+        intensities: vec![1.0; RANGE_COUNT],
+    }
+    .encode_to_vec()
+}
+
+fn payload_extraction(c: &mut Criterion) {
+    let key_expr = KeyExpr::<'static>::try_from("bench/topic").unwrap();
+
+    let mut group = c.benchmark_group("bridge_payload");
+
+    let point_cloud = synthetic_point_cloud();
+    group.throughput(Throughput::Bytes(point_cloud.len() as u64));
+    group.bench_function("proto_point_cloud", |b| {
+        b.iter(|| {
+            let sample = Sample::new(key_expr.clone(), point_cloud.clone());
+            proto_payload(sample).unwrap()
+        })
+    });
+
+    let json = synthetic_json();
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    group.bench_function("json_text_plain", |b| {
+        b.iter(|| {
+            let sample = Sample::new(key_expr.clone(), json.clone());
+            json_payload(sample).unwrap()
+        })
+    });
+    group.bench_function("json_octet_stream", |b| {
+        b.iter(|| {
+            let sample = Sample::new(key_expr.clone(), json.as_bytes().to_vec());
+            json_payload(sample).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+/// Sample to foxglove channel, including transforms and the websocket send
+///
+/// Without a connected client `send` stops after looking up subscribers, so this measures
+/// the bridge's own overhead.
+fn forward_path(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let key_expr = KeyExpr::<'static>::try_from("bench/topic").unwrap();
+    let server = foxglove_ws::FoxgloveWebSocket::new("bench");
+
+    let mut group = c.benchmark_group("bridge_forward");
+
+    let descriptor = DESCRIPTOR_POOL
+        .get_message_by_name("foxglove.LaserScan")
+        .unwrap();
+    let proto_channel = runtime
+        .block_on(server.create_publisher(
+            "bench/scan",
+            "protobuf",
+            descriptor.full_name(),
+            descriptor.parent_pool().encode_to_vec(),
+            Some("protobuf"),
+            false,
+        ))
+        .unwrap();
+    let scan = synthetic_laser_scan();
+    group.throughput(Throughput::Bytes(scan.len() as u64));
+    group.bench_function("proto_laser_scan", |b| {
+        b.iter(|| {
+            let sample = Sample::new(key_expr.clone(), scan.clone());
+            let payload = proto_payload(sample).unwrap();
+            runtime.block_on(proto_channel.send(0, &payload)).unwrap();
+        })
+    });
+    // decoded into a dynamic message and encoded again
+    let scale_ranges = [PayloadTransform::Scale {
+        field: String::from("ranges"),
+        factor: 0.001,
+    }];
+    group.bench_function("proto_laser_scan_transformed", |b| {
+        b.iter(|| {
+            let sample = Sample::new(key_expr.clone(), scan.clone());
+            let payload =
+                transform_proto(&proto_payload(sample).unwrap(), &descriptor, &scale_ranges)
+                    .unwrap();
+            runtime.block_on(proto_channel.send(0, &payload)).unwrap();
+        })
+    });
+
+    let json_channel = runtime
+        .block_on(server.create_publisher(
+            "bench/json",
+            "json",
+            "Sensor",
+            "{}",
+            Some("jsonschema"),
+            false,
+        ))
+        .unwrap();
+    let json = synthetic_json();
+    let scale_voltage = [PayloadTransform::Scale {
+        field: String::from("voltage"),
+        factor: 0.001,
+    }];
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    group.bench_function("json_transformed", |b| {
+        b.iter(|| {
+            let sample = Sample::new(key_expr.clone(), json.clone());
+            let payload = transformed_json_payload(sample, &scale_voltage).unwrap();
+            runtime.block_on(json_channel.send(0, &payload)).unwrap();
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, payload_extraction, forward_path);
+criterion_main!(benches);
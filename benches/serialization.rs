@@ -0,0 +1,92 @@
+use std::collections::{BTreeMap, HashMap};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use deck_robot_remote::{
+    gamepad::{serialize_cbor, serialize_json, serialize_msgpack, serialize_protobuf},
+    messages::{Axis, Button, ButtonCounterPolicy, GamepadMessage, InputMessage, PowerState},
+};
+
+fn synthetic_input_message(gamepad_count: usize) -> InputMessage {
+    let buttons: Vec<Button> = Button::all_gilrs_buttons()
+        .iter()
+        .map(|button| Button::from(*button))
+        .collect();
+
+    let gamepads = (0..gamepad_count)
+        .map(|gamepad_id| {
+            let gamepad = GamepadMessage {
+                name: format!("Steam Deck {gamepad_id}"),
+                connected: true,
+                last_event_time: chrono::Utc::now(),
+                button_down_event_counter: buttons.iter().map(|button| (*button, 42)).collect(),
+                button_up_event_counter: buttons.iter().map(|button| (*button, 41)).collect(),
+                button_down: buttons.iter().map(|button| (*button, false)).collect(),
+                axis_state: Axis::all_axes()
+                    .iter()
+                    .map(|axis| (*axis, 0.5))
+                    .collect::<BTreeMap<_, _>>(),
+                disabled: false,
+                button_double_press_counter: buttons.iter().map(|button| (*button, 3)).collect(),
+                button_hold_ms: BTreeMap::new(),
+                power_state: PowerState::Discharging,
+                battery_percent: Some(80),
+                mode: None,
+            };
+            (gamepad_id, gamepad)
+        })
+        .collect::<HashMap<_, _>>();
+
+    InputMessage {
+        gamepads,
+        time: chrono::Utc::now(),
+        button_counter_policy: ButtonCounterPolicy::Wrapping,
+        estop_engaged: false,
+    }
+}
+
+fn input_message_serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("input_message");
+    for gamepad_count in [1, 4] {
+        let message = synthetic_input_message(gamepad_count);
+        let json_size = serde_json::to_vec(&message).unwrap().len();
+        group.throughput(Throughput::Bytes(json_size as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("json_string", gamepad_count),
+            &message,
+            |b, message| b.iter(|| serde_json::to_string(message).unwrap()),
+        );
+
+        let mut buffer = Vec::new();
+        group.bench_with_input(
+            BenchmarkId::new("json_reused_buffer", gamepad_count),
+            &message,
+            |b, message| b.iter(|| serialize_json(&mut buffer, message).unwrap()),
+        );
+
+        let mut buffer = Vec::new();
+        group.bench_with_input(
+            BenchmarkId::new("protobuf", gamepad_count),
+            &message,
+            |b, message| b.iter(|| serialize_protobuf(&mut buffer, message).unwrap()),
+        );
+
+        let mut buffer = Vec::new();
+        group.bench_with_input(
+            BenchmarkId::new("cbor", gamepad_count),
+            &message,
+            |b, message| b.iter(|| serialize_cbor(&mut buffer, message).unwrap()),
+        );
+
+        let mut buffer = Vec::new();
+        group.bench_with_input(
+            BenchmarkId::new("msgpack", gamepad_count),
+            &message,
+            |b, message| b.iter(|| serialize_msgpack(&mut buffer, message).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, input_message_serialization);
+criterion_main!(benches);
@@ -5,6 +5,7 @@ fn main() {
 
     let mut proto_files = get_proto_files("proto/foxglove").unwrap();
     proto_files.extend_from_slice(&get_proto_files("proto/hopper").unwrap());
+    proto_files.extend_from_slice(&get_proto_files("proto/remote_control").unwrap());
 
     prost_reflect_build::Builder::new()
         .descriptor_pool("crate::DESCRIPTOR_POOL")
@@ -0,0 +1,8 @@
+#![no_main]
+
+use deck_robot_remote::tailscale::TailscaleStatus;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = TailscaleStatus::parse(data);
+});
@@ -0,0 +1,146 @@
+//! Operator notes marking moments of a run
+//!
+//! Pressing the annotation button, or publishing an [`Annotation`] on
+//! `remote-control/annotations`, records a timestamped note such as "robot tripped here".
+//! Notes are appended to the session log given with `--annotation-log` and bridged to
+//! Foxglove as `foxglove.Log`, so they show up in recordings next to the robot logs.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    error::ZenohError,
+    foxglove,
+    health::{Heartbeat, HEARTBEAT_INTERVAL},
+    supervisor::Supervisor,
+};
+
+pub const ANNOTATION_TOPIC: &str = "remote-control/annotations";
+
+/// Note recorded by the annotation button
+pub const DEFAULT_ANNOTATION_NOTE: &str = "Operator marker";
+
+/// Name of annotations in the Foxglove Log panel
+const ANNOTATION_LOG_NAME: &str = "operator";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Annotation {
+    pub time: DateTime<Utc>,
+    pub note: String,
+    /// Robot that was active when the note was taken
+    #[serde(default)]
+    pub robot: Option<String>,
+}
+
+impl Annotation {
+    pub fn to_log(&self) -> foxglove::Log {
+        let name = match &self.robot {
+            Some(robot) => format!("{ANNOTATION_LOG_NAME}/{robot}"),
+            None => ANNOTATION_LOG_NAME.to_owned(),
+        };
+        foxglove::Log {
+            timestamp: Some(SystemTime::from(self.time).into()),
+            level: foxglove::log::Level::Info as i32,
+            message: self.note.clone(),
+            name,
+            file: String::new(),
+            line: 0,
+        }
+    }
+}
+
+/// Append every annotation to `path` as JSON lines
+pub fn start_annotation_log(supervisor: &Supervisor, zenoh_session: Arc<Session>, path: PathBuf) {
+    let heartbeat = supervisor.health().heartbeat("annotation_log");
+    supervisor.spawn("annotation_log", move |shutdown| {
+        run_annotation_log(
+            zenoh_session.clone(),
+            path.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_annotation_log(
+    zenoh_session: Arc<Session>,
+    path: PathBuf,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut file = open_annotation_log(&path).await?;
+    let subscriber = zenoh_session
+        .declare_subscriber(ANNOTATION_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    info!("Recording annotations to {}", path.display());
+
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        let sample = tokio::select! {
+            sample = subscriber.recv_async() => sample?,
+            _ = heartbeat_interval.tick() => {
+                heartbeat.beat();
+                continue;
+            }
+            _ = shutdown.cancelled() => break,
+        };
+        let payload: String = match sample.value.try_into() {
+            Ok(payload) => payload,
+            Err(err) => {
+                warn!("Failed to read annotation {err:?}");
+                continue;
+            }
+        };
+        let annotation: Annotation = match serde_json::from_str(&payload) {
+            Ok(annotation) => annotation,
+            Err(err) => {
+                warn!("Invalid annotation {err}");
+                continue;
+            }
+        };
+        info!(note = annotation.note, "Annotation");
+        let mut line = serde_json::to_string(&annotation)?;
+        line.push('\n');
+        file.write_all(line.as_bytes()).await?;
+        // flushed right away so notes survive a crash of the remote
+        file.flush().await?;
+    }
+    Ok(())
+}
+
+async fn open_annotation_log(path: &Path) -> anyhow::Result<tokio::fs::File> {
+    tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open annotation log {}", path.display()))
+}
+
+/// Annotations of a session log
+pub fn read_annotation_log(path: &Path) -> anyhow::Result<Vec<Annotation>> {
+    let log = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    log.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid annotation on line {}", index + 1))
+        })
+        .collect()
+}
@@ -0,0 +1,359 @@
+//! Operator intercom, Deck microphone to the robot and optionally robot audio back
+//!
+//! Audio is 48 kHz mono Opus with one 20 ms packet per zenoh sample. cpal streams aren't
+//! `Send` on every platform so each stream lives on its own thread for as long as the
+//! task runs, and exchanges samples with the async side over channels.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    SampleFormat, SampleRate, StreamConfig, SupportedStreamConfigRange,
+};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    error::ZenohError,
+    health::{Heartbeat, HEARTBEAT_INTERVAL},
+    messages::Button,
+    stats::GamepadStats,
+    supervisor::Supervisor,
+};
+
+pub const AUDIO_UPLINK_TOPIC: &str = "remote-control/audio/uplink";
+pub const AUDIO_SAMPLE_RATE: u32 = 48_000;
+/// 20 ms, the usual Opus frame for voice
+pub const AUDIO_FRAME_SAMPLES: usize = 960;
+const AUDIO_ENCODING: &str = "audio/opus";
+/// Largest packet the encoder may produce
+const MAX_OPUS_PACKET: usize = 4000;
+/// 120 ms, the longest Opus frame
+const MAX_DECODED_SAMPLES: usize = 5760;
+/// Playback drops the oldest audio beyond this so latency doesn't build up
+const MAX_PLAYBACK_SAMPLES: usize = AUDIO_SAMPLE_RATE as usize / 5;
+const CAPTURE_QUEUE_SIZE: usize = 64;
+const STREAM_THREAD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Average interleaved `channels` into mono
+pub fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+/// Cuts a continuous sample stream into Opus frames
+#[derive(Debug, Default)]
+pub struct FrameAssembler {
+    pending: Vec<f32>,
+}
+
+impl FrameAssembler {
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Vec<f32>> {
+        self.pending.extend_from_slice(samples);
+        let mut frames = Vec::new();
+        while self.pending.len() >= AUDIO_FRAME_SAMPLES {
+            frames.push(self.pending.drain(..AUDIO_FRAME_SAMPLES).collect());
+        }
+        frames
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+/// Decoded audio waiting for the output device
+#[derive(Debug, Clone, Default)]
+pub struct PlaybackBuffer {
+    samples: Arc<Mutex<VecDeque<f32>>>,
+}
+
+impl PlaybackBuffer {
+    /// Drops the oldest samples once more than 200 ms are queued
+    pub fn push(&self, samples: &[f32]) {
+        let mut queue = self.samples.lock().unwrap();
+        queue.extend(samples);
+        let excess = queue.len().saturating_sub(MAX_PLAYBACK_SAMPLES);
+        queue.drain(..excess);
+    }
+
+    /// Fill interleaved `output` with mono samples, silence once the buffer runs dry
+    pub fn fill(&self, output: &mut [f32], channels: usize) {
+        let mut queue = self.samples.lock().unwrap();
+        for frame in output.chunks_mut(channels.max(1)) {
+            let sample = queue.pop_front().unwrap_or_default();
+            frame.fill(sample);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// 48 kHz in a sample format this module converts
+fn find_config(
+    configs: impl Iterator<Item = SupportedStreamConfigRange>,
+) -> Option<(StreamConfig, SampleFormat)> {
+    configs
+        .filter(|config| {
+            matches!(
+                config.sample_format(),
+                SampleFormat::F32 | SampleFormat::I16
+            ) && config.min_sample_rate().0 <= AUDIO_SAMPLE_RATE
+                && config.max_sample_rate().0 >= AUDIO_SAMPLE_RATE
+        })
+        .min_by_key(|config| config.channels())
+        .map(|config| {
+            let config = config.with_sample_rate(SampleRate(AUDIO_SAMPLE_RATE));
+            (config.config(), config.sample_format())
+        })
+}
+
+fn i16_to_f32(samples: &[i16]) -> Vec<f32> {
+    samples
+        .iter()
+        .map(|sample| f32::from(*sample) / f32::from(i16::MAX))
+        .collect()
+}
+
+/// Run `build_stream` on its own thread and keep the stream playing until `shutdown`
+fn spawn_stream_thread(
+    name: &str,
+    shutdown: CancellationToken,
+    build_stream: impl FnOnce() -> anyhow::Result<cpal::Stream> + Send + 'static,
+) -> anyhow::Result<oneshot::Receiver<anyhow::Result<()>>> {
+    let (started_sender, started) = oneshot::channel();
+    std::thread::Builder::new()
+        .name(name.to_owned())
+        .spawn(move || {
+            let stream = match build_stream().and_then(|stream| {
+                stream.play()?;
+                Ok(stream)
+            }) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    _ = started_sender.send(Err(err));
+                    return;
+                }
+            };
+            _ = started_sender.send(Ok(()));
+            while !shutdown.is_cancelled() {
+                std::thread::sleep(STREAM_THREAD_POLL_INTERVAL);
+            }
+            drop(stream);
+        })?;
+    Ok(started)
+}
+
+/// Publish the default microphone on `remote-control/audio/uplink`
+///
+/// With `push_to_talk` audio is only sent while that button is held on any gamepad.
+pub fn start_audio_uplink(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    push_to_talk: Option<Button>,
+    gamepad_stats: Arc<GamepadStats>,
+) {
+    let heartbeat = supervisor.health().heartbeat("audio_uplink");
+    supervisor.spawn("audio_uplink", move |shutdown| {
+        run_audio_uplink(
+            zenoh_session.clone(),
+            push_to_talk,
+            gamepad_stats.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_audio_uplink(
+    zenoh_session: Arc<Session>,
+    push_to_talk: Option<Button>,
+    gamepad_stats: Arc<GamepadStats>,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let publisher = zenoh_session
+        .declare_publisher(AUDIO_UPLINK_TOPIC)
+        .priority(Priority::InteractiveHigh)
+        .congestion_control(CongestionControl::Drop)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let (sample_sender, mut captured) = mpsc::channel::<Vec<f32>>(CAPTURE_QUEUE_SIZE);
+    // stops the capture thread when this task ends for any reason
+    let capture_shutdown = shutdown.child_token();
+    let _capture_guard = capture_shutdown.clone().drop_guard();
+    let started = spawn_stream_thread("audio_capture", capture_shutdown, move || {
+        let device = cpal::default_host()
+            .default_input_device()
+            .context("No microphone")?;
+        let (config, sample_format) = find_config(device.supported_input_configs()?)
+            .context("Microphone doesn't support 48 kHz")?;
+        let channels = usize::from(config.channels);
+        let on_error = |err| warn!("Microphone stream error {err:?}");
+        let stream = match sample_format {
+            SampleFormat::I16 => device.build_input_stream(
+                &config,
+                move |data: &[i16], _| {
+                    _ = sample_sender.try_send(downmix(&i16_to_f32(data), channels));
+                },
+                on_error,
+                None,
+            )?,
+            _ => device.build_input_stream(
+                &config,
+                move |data: &[f32], _| {
+                    _ = sample_sender.try_send(downmix(data, channels));
+                },
+                on_error,
+                None,
+            )?,
+        };
+        info!(?config, "Capturing microphone");
+        Ok(stream)
+    })?;
+    started.await??;
+
+    let mut encoder = opus::Encoder::new(
+        AUDIO_SAMPLE_RATE,
+        opus::Channels::Mono,
+        opus::Application::Voip,
+    )?;
+    let mut frames = FrameAssembler::default();
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        let samples = tokio::select! {
+            samples = captured.recv() => samples.context("Microphone stream stopped")?,
+            _ = heartbeat_interval.tick() => {
+                heartbeat.beat();
+                continue;
+            }
+            _ = shutdown.cancelled() => break,
+        };
+        let talking = push_to_talk.is_none_or(|button| {
+            gamepad_stats.last_message().is_some_and(|message| {
+                message
+                    .gamepads
+                    .values()
+                    .any(|gamepad| gamepad.button_down.get(&button) == Some(&true))
+            })
+        });
+        if !talking {
+            frames.clear();
+            continue;
+        }
+        for frame in frames.push(&samples) {
+            let packet = encoder.encode_vec_float(&frame, MAX_OPUS_PACKET)?;
+            publisher
+                .put(Value::from(packet).encoding(Encoding::from(AUDIO_ENCODING)))
+                .res()
+                .await
+                .map_err(ZenohError)?;
+        }
+    }
+    Ok(())
+}
+
+/// Play Opus packets from `topic` on the default output device
+pub fn start_audio_downlink(supervisor: &Supervisor, zenoh_session: Arc<Session>, topic: String) {
+    let heartbeat = supervisor.health().heartbeat("audio_downlink");
+    supervisor.spawn("audio_downlink", move |shutdown| {
+        run_audio_downlink(
+            zenoh_session.clone(),
+            topic.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_audio_downlink(
+    zenoh_session: Arc<Session>,
+    topic: String,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let subscriber = zenoh_session
+        .declare_subscriber(&topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let playback = PlaybackBuffer::default();
+    let playback_shutdown = shutdown.child_token();
+    let _playback_guard = playback_shutdown.clone().drop_guard();
+    let started = spawn_stream_thread("audio_playback", playback_shutdown, {
+        let playback = playback.clone();
+        move || {
+            let device = cpal::default_host()
+                .default_output_device()
+                .context("No speaker")?;
+            let (config, sample_format) = find_config(device.supported_output_configs()?)
+                .context("Speaker doesn't support 48 kHz")?;
+            let channels = usize::from(config.channels);
+            let on_error = |err| warn!("Speaker stream error {err:?}");
+            let stream = match sample_format {
+                SampleFormat::I16 => device.build_output_stream(
+                    &config,
+                    move |data: &mut [i16], _| {
+                        let mut samples = vec![0.0; data.len()];
+                        playback.fill(&mut samples, channels);
+                        for (output, sample) in data.iter_mut().zip(samples) {
+                            *output = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+                        }
+                    },
+                    on_error,
+                    None,
+                )?,
+                _ => device.build_output_stream(
+                    &config,
+                    move |data: &mut [f32], _| playback.fill(data, channels),
+                    on_error,
+                    None,
+                )?,
+            };
+            info!(?config, "Playing robot audio");
+            Ok(stream)
+        }
+    })?;
+    started.await??;
+
+    let mut decoder = opus::Decoder::new(AUDIO_SAMPLE_RATE, opus::Channels::Mono)?;
+    let mut decoded = vec![0.0; MAX_DECODED_SAMPLES];
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        let sample = tokio::select! {
+            sample = subscriber.recv_async() => sample.context("Zenoh subscriber closed")?,
+            _ = heartbeat_interval.tick() => {
+                heartbeat.beat();
+                continue;
+            }
+            _ = shutdown.cancelled() => break,
+        };
+        let packet: Vec<u8> = sample.value.try_into()?;
+        match decoder.decode_float(&packet, &mut decoded, false) {
+            Ok(count) => playback.push(&decoded[..count]),
+            Err(err) => debug!(topic, "Invalid opus packet {err:?}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Exponential backoff with jitter
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            current: initial,
+        }
+    }
+
+    /// Delay to wait before the next attempt
+    ///
+    /// Returns a random delay between half and all of the current backoff and doubles it.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        let half = delay / 2;
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
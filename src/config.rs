@@ -0,0 +1,417 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+use tracing::*;
+
+use crate::{
+    deadman::DeadmanConfig,
+    error::ConfigError,
+    failover::FailoverConfig,
+    foxglove_server::{FoxgloveHost, FoxgloveServerConfiguration},
+    gamepad_events::{validate_thresholds, AxisThresholds},
+    input_pipeline::AxisSmoothing,
+    input_script::ScriptSource,
+    macros::MacroConfig,
+    messages::{Axis, Button},
+    mixer::TwistMixerConfig,
+    modifiers::ModifierConfig,
+    mqtt_bridge::MqttBridgeConfig,
+    ros2::Ros2JoyConfig,
+    tailscale::PeerMatch,
+    zenoh_auth::ZenohAuth,
+};
+
+const BUILTIN_PROFILES: &[(&str, &str)] = &[
+    (
+        "hamilton.yaml",
+        include_str!("../config/profiles/hamilton.yaml"),
+    ),
+    ("guppy.yaml", include_str!("../config/profiles/guppy.yaml")),
+    (
+        "hopper.yaml",
+        include_str!("../config/profiles/hopper.yaml"),
+    ),
+];
+
+/// Everything that differs between robots
+#[derive(Debug, Deserialize)]
+pub struct RobotProfile {
+    pub name: String,
+    pub foxglove_layout_id: String,
+    /// Studio link template for self-hosted Foxglove or Lichtblick, `{user}` is replaced
+    #[serde(default)]
+    pub foxglove_studio_url: Option<String>,
+    /// Prepended to the gamepad topic, the profile name if not set
+    #[serde(default)]
+    pub topic_prefix: Option<String>,
+    /// Tailscale peers with this in their host name are connected to
+    #[serde(default)]
+    pub tailscale_host_filter: Option<String>,
+    /// Tailscale peers matching any of these are connected to, instead of the host filter
+    #[serde(default)]
+    pub tailscale_peers: Vec<PeerMatch>,
+    /// Connect to one `tailscale_peers` rule at a time and move on when telemetry stops
+    #[serde(default)]
+    pub failover: Option<FailoverConfig>,
+    /// Shaping applied to raw axis values before they are published
+    #[serde(default)]
+    pub axis_mapping: AxisMapping,
+    /// Low-pass filtering and slew-rate limits applied after axis mapping
+    #[serde(default)]
+    pub axis_smoothing: AxisSmoothing,
+    /// Buttons published under another name, for pads with a different layout
+    #[serde(default)]
+    pub button_mapping: ButtonMapping,
+    /// Axes that publish an event on `<gamepad topic>/events` when they pass the threshold
+    #[serde(default)]
+    pub axis_event_thresholds: AxisThresholds,
+    /// Gamepads that are also published on `<gamepad topic>/<role>`
+    #[serde(default)]
+    pub gamepad_roles: GamepadRoles,
+    /// Velocity commands mixed from the sticks
+    #[serde(default)]
+    pub twist: Option<TwistMixerConfig>,
+    /// Buttons that record gamepad input and replay it with its original timing
+    #[serde(default)]
+    pub macros: Option<MacroConfig>,
+    /// Gamepad state mirrored as a ROS 2 `sensor_msgs/Joy`
+    #[serde(default)]
+    pub ros2_joy: Option<Ros2JoyConfig>,
+    /// Named button chords published on `<gamepad topic>/combos`
+    #[serde(default)]
+    pub combos: ButtonCombos,
+    /// Button that has to be held for motion axes to be forwarded
+    #[serde(default)]
+    pub deadman: Option<DeadmanConfig>,
+    /// Named speed modes selected by holding a button
+    #[serde(default)]
+    pub modifiers: Option<ModifierConfig>,
+    /// Rhai script run on every input event and publish tick
+    #[serde(default)]
+    pub script: Option<ScriptSource>,
+    /// MQTT broker whose topics are republished onto zenoh
+    #[serde(default)]
+    pub mqtt: Option<MqttBridgeConfig>,
+    #[serde(flatten)]
+    pub foxglove: FoxgloveServerConfiguration,
+}
+
+impl RobotProfile {
+    /// Rules selecting the robot's tailscale peers
+    pub fn peer_rules(&self) -> Vec<PeerMatch> {
+        match &self.tailscale_host_filter {
+            Some(host_filter) if self.tailscale_peers.is_empty() => {
+                vec![PeerMatch::Contains(host_filter.clone())]
+            }
+            _ => self.tailscale_peers.clone(),
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.tailscale_host_filter.is_some() && !self.tailscale_peers.is_empty() {
+            anyhow::bail!("Use either tailscale_host_filter or tailscale_peers, not both");
+        }
+        if self.peer_rules().is_empty() {
+            anyhow::bail!("Missing tailscale_host_filter or tailscale_peers");
+        }
+        self.foxglove.validate()?;
+        for rule in &self.tailscale_peers {
+            rule.validate().context("Invalid tailscale peer rule")?;
+        }
+        if let Some(failover) = &self.failover {
+            if self.tailscale_peers.len() < 2 {
+                anyhow::bail!("failover needs at least two tailscale_peers rules");
+            }
+            failover.validate().context("Invalid failover")?;
+        }
+        for (axis, curve) in &self.axis_mapping {
+            curve
+                .validate()
+                .with_context(|| format!("Invalid axis mapping for {axis:?}"))?;
+        }
+        for (axis, filter) in &self.axis_smoothing {
+            filter
+                .validate()
+                .with_context(|| format!("Invalid axis smoothing for {axis:?}"))?;
+        }
+        validate_thresholds(&self.axis_event_thresholds)
+            .context("Invalid axis event thresholds")?;
+        for (role, selector) in &self.gamepad_roles {
+            validate_role_name(role)?;
+            selector
+                .validate()
+                .with_context(|| format!("Invalid gamepad role {role:?}"))?;
+        }
+        for (name, combo) in &self.combos {
+            combo
+                .validate()
+                .with_context(|| format!("Invalid combo {name:?}"))?;
+        }
+        if let Some(twist) = &self.twist {
+            twist.validate().context("Invalid twist mixer")?;
+        }
+        if let Some(macros) = &self.macros {
+            macros.validate().context("Invalid macros")?;
+        }
+        if let Some(modifiers) = &self.modifiers {
+            modifiers.validate().context("Invalid modifiers")?;
+        }
+        if let Some(script) = &self.script {
+            script.validate()?;
+        }
+        Ok(())
+    }
+}
+
+pub type AxisMapping = BTreeMap<Axis, AxisCurve>;
+
+/// Maps the button gilrs reports to the button that is published
+pub type ButtonMapping = BTreeMap<Button, Button>;
+
+/// Role name such as `driver` mapped to the gamepad that fills it
+pub type GamepadRoles = BTreeMap<String, GamepadSelector>;
+
+/// Sub-topics of the gamepad topic that are already taken
+const RESERVED_ROLE_NAMES: &[&str] = &[
+    "__schema__",
+    "combos",
+    "events",
+    "feedback",
+    "imu",
+    "touchpad",
+];
+
+fn validate_role_name(role: &str) -> anyhow::Result<()> {
+    if role.is_empty()
+        || !role
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        anyhow::bail!(
+            "Gamepad role {role:?} must be a non-empty name of letters, digits, '_' and '-'"
+        );
+    }
+    if RESERVED_ROLE_NAMES.contains(&role) {
+        anyhow::bail!("Gamepad role {role:?} collides with the {role} sub-topic");
+    }
+    Ok(())
+}
+
+/// Picks a gamepad by name, gilrs id or both
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadSelector {
+    /// Case insensitive part of the gamepad name
+    pub name: Option<String>,
+    pub id: Option<usize>,
+}
+
+impl GamepadSelector {
+    pub fn matches(&self, gamepad_id: usize, name: &str) -> bool {
+        self.id.is_none_or(|id| id == gamepad_id)
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|pattern| name.to_lowercase().contains(&pattern.to_lowercase()))
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.name.is_none() && self.id.is_none() {
+            anyhow::bail!("name or id must be set");
+        }
+        Ok(())
+    }
+}
+
+/// Combo name such as `dock` mapped to the buttons that trigger it
+pub type ButtonCombos = BTreeMap<String, ButtonCombo>;
+
+/// Buttons that have to be held together, optionally for some time
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ButtonCombo {
+    pub buttons: Vec<Button>,
+    /// How long all buttons have to be held, the combo fires on the last press by default
+    #[serde(default)]
+    pub hold_ms: u64,
+}
+
+impl ButtonCombo {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.buttons.is_empty() {
+            anyhow::bail!("buttons must not be empty");
+        }
+        for (index, button) in self.buttons.iter().enumerate() {
+            if self.buttons[..index].contains(button) {
+                anyhow::bail!("{button:?} is listed twice");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deadzone, expo and inversion for a single axis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AxisCurve {
+    /// Values with a smaller magnitude are reported as zero, the rest is rescaled to the full range
+    pub deadzone: f32,
+    /// Blend between linear (0.0) and cubic (1.0) response
+    pub expo: f32,
+    pub invert: bool,
+}
+
+impl AxisCurve {
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value.clamp(-1.0, 1.0);
+        let magnitude = value.abs();
+        if magnitude <= self.deadzone {
+            return 0.0;
+        }
+        let scaled = (magnitude - self.deadzone) / (1.0 - self.deadzone);
+        let shaped = (1.0 - self.expo) * scaled + self.expo * scaled.powi(3);
+        let shaped = shaped.copysign(value);
+        if self.invert {
+            -shaped
+        } else {
+            shaped
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if !(0.0..1.0).contains(&self.deadzone) {
+            anyhow::bail!("deadzone must be in [0, 1), got {}", self.deadzone);
+        }
+        if !(0.0..=1.0).contains(&self.expo) {
+            anyhow::bail!("expo must be in [0, 1], got {}", self.expo);
+        }
+        Ok(())
+    }
+}
+
+/// Built-in profiles plus all `*.yaml` profiles in `profile_dir`
+///
+/// Profiles from `profile_dir` replace built-in profiles with the same name.
+pub fn load_profiles(
+    profile_dir: Option<&Path>,
+) -> Result<BTreeMap<String, RobotProfile>, ConfigError> {
+    let mut profiles = BTreeMap::new();
+    for (file_name, profile) in BUILTIN_PROFILES {
+        let profile = parse_profile(profile, &format!("built-in profile {file_name}"))?;
+        profiles.insert(profile.name.clone(), profile);
+    }
+
+    if let Some(profile_dir) = profile_dir {
+        for path in profile_files(profile_dir)? {
+            let profile = std::fs::read_to_string(&path).map_err(|error| ConfigError::Read {
+                path: path.clone(),
+                error,
+            })?;
+            let profile = parse_profile(&profile, &format!("profile {}", path.display()))?;
+            debug!("Loaded profile {} from {}", profile.name, path.display());
+            profiles.insert(profile.name.clone(), profile);
+        }
+    }
+    Ok(profiles)
+}
+
+/// `name` describes where the profile came from in errors
+fn parse_profile(profile: &str, name: &str) -> Result<RobotProfile, ConfigError> {
+    let profile: RobotProfile =
+        serde_yaml::from_str(profile).map_err(|error| ConfigError::Parse {
+            name: name.to_owned(),
+            error,
+        })?;
+    validate_profile(&profile, name)?;
+    Ok(profile)
+}
+
+fn validate_profile(profile: &RobotProfile, name: &str) -> Result<(), ConfigError> {
+    profile.validate().map_err(|err| ConfigError::Invalid {
+        name: name.to_owned(),
+        reason: format!("{err:#}"),
+    })
+}
+
+/// Add profiles that are defined inline in the application config, replacing loaded ones
+pub fn add_profiles(
+    profiles: &mut BTreeMap<String, RobotProfile>,
+    extra_profiles: Vec<RobotProfile>,
+) -> Result<(), ConfigError> {
+    for profile in extra_profiles {
+        validate_profile(&profile, &format!("profile {}", profile.name))?;
+        profiles.insert(profile.name.clone(), profile);
+    }
+    Ok(())
+}
+
+/// Application settings loaded with `--config`
+///
+/// Everything is optional, flags given on the command line take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig {
+    pub mode: Option<String>,
+    pub profile_dir: Option<PathBuf>,
+    pub schema_dir: Option<PathBuf>,
+    pub gamepad_topic: Option<String>,
+    pub global_gamepad_topic: Option<bool>,
+    pub sleep_ms: Option<u64>,
+    pub connect: Option<Vec<zenoh_config::EndPoint>>,
+    pub listen: Option<Vec<zenoh_config::EndPoint>>,
+    pub shared_memory: Option<bool>,
+    /// Overridden field by field by the `--zenoh-*` flags
+    pub zenoh_auth: Option<ZenohAuth>,
+    /// Foxglove bind address
+    pub host: Option<FoxgloveHost>,
+    pub foxglove_user: Option<String>,
+    /// Robot profiles in the same format as the files in `config/profiles`
+    #[serde(default)]
+    pub profiles: Vec<RobotProfile>,
+}
+
+pub fn load_app_config(path: &Path) -> Result<AppConfig, ConfigError> {
+    let config = std::fs::read_to_string(path).map_err(|error| ConfigError::Read {
+        path: path.to_owned(),
+        error,
+    })?;
+    serde_yaml::from_str(&config).map_err(|error| ConfigError::Parse {
+        name: format!("config {}", path.display()),
+        error,
+    })
+}
+
+/// Load the profile called `name`
+pub fn load_profile(profile_dir: Option<&Path>, name: &str) -> Result<RobotProfile, ConfigError> {
+    let mut profiles = load_profiles(profile_dir)?;
+    let available: Vec<_> = profiles.keys().cloned().collect();
+    profiles
+        .remove(name)
+        .ok_or_else(|| ConfigError::UnknownProfile {
+            name: name.to_owned(),
+            available,
+        })
+}
+
+fn profile_files(profile_dir: &Path) -> Result<Vec<PathBuf>, ConfigError> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(profile_dir)
+        .map_err(|error| ConfigError::Read {
+            path: profile_dir.to_owned(),
+            error,
+        })?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == "yaml" || extension == "yml")
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,169 @@
+//! Single "are we actually connected" summary of the link to the active robot
+
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    error::ZenohError,
+    health::Heartbeat,
+    log_throttle::{LogThrottle, DEFAULT_THROTTLE_WINDOW},
+    robot_registry::{RobotRegistry, RobotTarget},
+    stats::StatsRegistry,
+    supervisor::Supervisor,
+    tailscale::{matching_peers, TailscaleStatus},
+};
+
+pub const CONNECTION_TOPIC: &str = "remote-control/connection";
+
+const CONNECTION_PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+/// Reading tailscale status may spawn the CLI so it's polled less often
+const TAILSCALE_STATUS_INTERVAL: Duration = Duration::from_secs(5);
+/// Without robot telemetry for this long the link counts as down
+pub const TELEMETRY_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConnectionMessage {
+    /// Zenoh has a peer or router and robot telemetry arrived within the timeout
+    pub connected: bool,
+    pub robot: String,
+    pub zenoh_peers: Vec<String>,
+    pub zenoh_routers: Vec<String>,
+    /// Online state of the tailscale peers matching the robot's host filter
+    pub tailscale_peers: BTreeMap<String, bool>,
+    /// Not set if no telemetry was received yet
+    pub last_telemetry_age_seconds: Option<f64>,
+    pub time: DateTime<Utc>,
+}
+
+/// Zenoh session identifiers of the connected peers and routers
+#[derive(Debug, Clone, Default)]
+pub struct ZenohPeers {
+    pub peers: Vec<String>,
+    pub routers: Vec<String>,
+}
+
+impl ZenohPeers {
+    pub async fn read(zenoh_session: &Session) -> Self {
+        let peers = zenoh_session
+            .info()
+            .peers_zid()
+            .res()
+            .await
+            .map(|zid| zid.to_string())
+            .collect();
+        let routers = zenoh_session
+            .info()
+            .routers_zid()
+            .res()
+            .await
+            .map(|zid| zid.to_string())
+            .collect();
+        Self { peers, routers }
+    }
+}
+
+pub fn connection_message(
+    robot: &RobotTarget,
+    zenoh_peers: ZenohPeers,
+    tailscale_status: &TailscaleStatus,
+    last_telemetry_age: Option<Duration>,
+    time: DateTime<Utc>,
+) -> ConnectionMessage {
+    let tailscale_peers = matching_peers(tailscale_status, &robot.peer_rules)
+        .map(|peer| (peer.host_name.clone(), peer.online))
+        .collect();
+    let has_zenoh_link = !zenoh_peers.peers.is_empty() || !zenoh_peers.routers.is_empty();
+    ConnectionMessage {
+        connected: has_zenoh_link && last_telemetry_age.is_some_and(|age| age < TELEMETRY_TIMEOUT),
+        robot: robot.name.clone(),
+        zenoh_peers: zenoh_peers.peers,
+        zenoh_routers: zenoh_peers.routers,
+        tailscale_peers,
+        last_telemetry_age_seconds: last_telemetry_age.map(|age| age.as_secs_f64()),
+        time,
+    }
+}
+
+/// Publish the connection state of the active robot on `remote-control/connection`
+///
+/// Telemetry is the most recent sample of any of `telemetry_topics`, the bridged robot topics.
+pub fn start_connection_monitor(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    registry: RobotRegistry,
+    stats: StatsRegistry,
+    telemetry_topics: Vec<String>,
+    clock: SharedClock,
+) {
+    let heartbeat = supervisor.health().heartbeat("connection_monitor");
+    supervisor.spawn("connection_monitor", move |shutdown| {
+        run_connection_monitor(
+            zenoh_session.clone(),
+            registry.clone(),
+            stats.clone(),
+            telemetry_topics.clone(),
+            clock.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_connection_monitor(
+    zenoh_session: Arc<Session>,
+    registry: RobotRegistry,
+    stats: StatsRegistry,
+    telemetry_topics: Vec<String>,
+    clock: SharedClock,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let publisher = zenoh_session
+        .declare_publisher(CONNECTION_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut error_log = LogThrottle::new("connection_monitor", DEFAULT_THROTTLE_WINDOW);
+    let mut tailscale_status = TailscaleStatus::default();
+    let mut tailscale_interval = tokio::time::interval(TAILSCALE_STATUS_INTERVAL);
+    tailscale_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut interval = tokio::time::interval(CONNECTION_PUBLISH_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = tailscale_interval.tick() => {
+                match TailscaleStatus::read().await {
+                    Ok(status) => tailscale_status = status,
+                    Err(err) => error_log.error(&format!("Failed to read tailscale status {err:?}")),
+                }
+            }
+            _ = interval.tick() => {
+                heartbeat.beat();
+                error_log.flush();
+                let message = connection_message(
+                    &registry.active(),
+                    ZenohPeers::read(&zenoh_session).await,
+                    &tailscale_status,
+                    stats
+                        .last_received(&telemetry_topics)
+                        .map(|last_received| last_received.elapsed()),
+                    clock.now().into(),
+                );
+                publisher
+                    .put(serde_json::to_string(&message)?)
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
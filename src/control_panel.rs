@@ -0,0 +1,227 @@
+//! Web control panel served next to the Foxglove socket
+//!
+//! A small page for the Deck's touchscreen or a phone on the tailnet. It shows the
+//! connection to the active robot, engages and re-arms the emergency stop and links to
+//! Foxglove with the active robot's layout.
+
+use std::{
+    collections::BTreeMap,
+    future::IntoFuture,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    clock::SharedClock,
+    connection::{ConnectionMessage, CONNECTION_TOPIC},
+    error::ZenohError,
+    gamepad::{EstopLatch, ESTOP_TOPIC},
+    health::{Heartbeat, HEARTBEAT_INTERVAL},
+    messages::EstopMessage,
+    robot_registry::RobotRegistry,
+    supervisor::Supervisor,
+};
+
+const CONTROL_PANEL_PAGE: &str = include_str!("control_panel.html");
+
+/// Returned by `GET /api/status` and the emergency stop requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlPanelStatus {
+    pub active_robot: String,
+    /// Not set until the first connection message arrived
+    pub connection: Option<ConnectionMessage>,
+    pub estop_engaged: bool,
+    /// Foxglove with the active robot's layout
+    pub foxglove_link: Option<String>,
+}
+
+/// Body of `POST /api/estop`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EstopRequest {
+    pub engaged: bool,
+}
+
+/// State shared by the panel's request handlers
+#[derive(Clone)]
+pub struct ControlPanel {
+    zenoh_session: Arc<Session>,
+    robot_registry: RobotRegistry,
+    estop: EstopLatch,
+    /// Foxglove link of every robot by name
+    foxglove_links: Arc<BTreeMap<String, String>>,
+    connection: Arc<Mutex<Option<ConnectionMessage>>>,
+    /// Stamps emergency stop messages like the gamepad reader's clock
+    clock: SharedClock,
+}
+
+impl ControlPanel {
+    pub fn new(
+        zenoh_session: Arc<Session>,
+        robot_registry: RobotRegistry,
+        estop: EstopLatch,
+        foxglove_links: BTreeMap<String, String>,
+        clock: SharedClock,
+    ) -> Self {
+        Self {
+            zenoh_session,
+            robot_registry,
+            estop,
+            foxglove_links: Arc::new(foxglove_links),
+            connection: Arc::default(),
+            clock,
+        }
+    }
+
+    pub fn status(&self) -> ControlPanelStatus {
+        let active_robot = self.robot_registry.active().name;
+        ControlPanelStatus {
+            foxglove_link: self.foxglove_links.get(&active_robot).cloned(),
+            connection: self.connection.lock().unwrap().clone(),
+            estop_engaged: self.estop.is_engaged(),
+            active_robot,
+        }
+    }
+
+    /// Engage or re-arm the emergency stop and announce changes like the gamepad does
+    pub async fn set_estop(&self, engaged: bool) -> anyhow::Result<()> {
+        if !self.estop.set(engaged) {
+            return Ok(());
+        }
+        if engaged {
+            warn!("Emergency stop engaged from the control panel");
+        } else {
+            info!("Emergency stop re-armed from the control panel");
+        }
+        let estop_message = EstopMessage {
+            engaged,
+            time: self.clock.now().into(),
+        };
+        self.zenoh_session
+            .put(ESTOP_TOPIC, serde_json::to_string(&estop_message)?)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+        Ok(())
+    }
+
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/", get(|| async { Html(CONTROL_PANEL_PAGE) }))
+            .route("/api/status", get(status))
+            .route("/api/estop", post(set_estop))
+            .with_state(self)
+    }
+}
+
+async fn status(State(panel): State<ControlPanel>) -> Json<ControlPanelStatus> {
+    Json(panel.status())
+}
+
+async fn set_estop(
+    State(panel): State<ControlPanel>,
+    Json(request): Json<EstopRequest>,
+) -> Result<Json<ControlPanelStatus>, ApiError> {
+    panel.set_estop(request.engaged).await?;
+    Ok(Json(panel.status()))
+}
+
+/// Failed requests answer with the error chain as text
+struct ApiError(anyhow::Error);
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", self.0)).into_response()
+    }
+}
+
+/// Serve the control panel on `address` and keep its connection state current
+///
+/// The server is left running across zenoh reconnects so open pages stay connected.
+pub fn start_control_panel(supervisor: &Supervisor, panel: ControlPanel, address: SocketAddr) {
+    supervisor.spawn_persistent("control_panel_server", {
+        let panel = panel.clone();
+        let heartbeat = supervisor.health().heartbeat("control_panel_server");
+        move |shutdown| {
+            let router = panel.clone().router();
+            let heartbeat = heartbeat.clone();
+            async move { serve(router, address, heartbeat, shutdown).await }
+        }
+    });
+    let heartbeat = supervisor.health().heartbeat("control_panel");
+    supervisor.spawn("control_panel", move |shutdown| {
+        track_connection(panel.clone(), heartbeat.clone(), shutdown)
+    });
+}
+
+async fn serve(
+    router: Router,
+    address: SocketAddr,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Control panel can't bind {address}"))?;
+    info!("Serving control panel on http://{address}");
+    let serve = axum::serve(listener, router).into_future();
+    tokio::pin!(serve);
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        tokio::select! {
+            result = &mut serve => {
+                result?;
+                anyhow::bail!("Control panel on {address} stopped");
+            }
+            _ = heartbeat_interval.tick() => heartbeat.beat(),
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
+
+async fn track_connection(
+    panel: ControlPanel,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let connection_subscriber = panel
+        .zenoh_session
+        .declare_subscriber(CONNECTION_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        tokio::select! {
+            sample = connection_subscriber.recv_async() => {
+                let payload: Vec<u8> = Vec::<u8>::try_from(sample?.value)?;
+                match serde_json::from_slice::<ConnectionMessage>(&payload) {
+                    Ok(connection) => *panel.connection.lock().unwrap() = Some(connection),
+                    Err(err) => debug!("Invalid connection message {err:?}"),
+                }
+            }
+            _ = heartbeat_interval.tick() => heartbeat.beat(),
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::{
+    backtrace::Backtrace,
+    io::Write,
+    panic::PanicHookInfo,
+    sync::{Arc, OnceLock, Weak},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::*;
+use zenoh::prelude::{r#async::*, sync::SyncResolve};
+
+use crate::error::ZenohError;
+
+const CRASH_REPORT_TOPIC: &str = "remote-control/diagnostics/crash";
+
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+static DEFAULT_HOOK: OnceLock<PanicHook> = OnceLock::new();
+
+/// Published when any thread or task panics
+#[derive(Debug, Clone, Serialize)]
+pub struct CrashReport {
+    /// Zenoh id of the session, identifies this run of the remote
+    pub session_id: String,
+    pub thread: Option<String>,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+    pub time: DateTime<Utc>,
+}
+
+impl CrashReport {
+    fn from_panic(session_id: String, panic_info: &PanicHookInfo) -> Self {
+        let payload = panic_info.payload();
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            String::from("unknown panic payload")
+        };
+        Self {
+            session_id,
+            thread: std::thread::current().name().map(str::to_owned),
+            message,
+            location: panic_info.location().map(ToString::to_string),
+            backtrace: Backtrace::force_capture().to_string(),
+            time: Utc::now(),
+        }
+    }
+}
+
+/// Publish a crash report over zenoh before running the default panic hook
+///
+/// Panics inside tokio tasks also go through the hook so supervised tasks that are
+/// restarted still leave a report behind.
+pub fn install_panic_hook(zenoh_session: &Arc<Session>) {
+    // weak so that the hook doesn't keep the session from being closed
+    let zenoh_session: Weak<Session> = Arc::downgrade(zenoh_session);
+    let session_id = zenoh_session
+        .upgrade()
+        .map(|session| session.zid().to_string())
+        .unwrap_or_default();
+    // taken once so that installing again after a reload replaces the previous hook
+    let default_hook = DEFAULT_HOOK.get_or_init(std::panic::take_hook);
+    std::panic::set_hook(Box::new(move |panic_info| {
+        let report = CrashReport::from_panic(session_id.clone(), panic_info);
+        error!(
+            thread = ?report.thread,
+            location = ?report.location,
+            "Panic: {}",
+            report.message
+        );
+        if let Some(zenoh_session) = zenoh_session.upgrade() {
+            if let Err(err) = publish_report(&zenoh_session, &report) {
+                error!("Failed to publish crash report {err:?}");
+            }
+        }
+        default_hook(panic_info);
+        _ = std::io::stdout().flush();
+        _ = std::io::stderr().flush();
+    }));
+}
+
+fn publish_report(zenoh_session: &Session, report: &CrashReport) -> anyhow::Result<()> {
+    let json = serde_json::to_string(report)?;
+    zenoh_session
+        .put(CRASH_REPORT_TOPIC, json)
+        .res_sync()
+        .map_err(ZenohError)?;
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Hold-to-enable switch for motion input
+//!
+//! Motion axes of a gamepad are only forwarded while its enable button is held. Released,
+//! the axes are published as zero and the gamepad is marked `disabled`, so a dropped
+//! controller or an operator letting go stops the robot.
+
+use serde::Deserialize;
+
+use crate::messages::{Axis, Button, InputMessage};
+
+/// `deadman` section of a robot profile
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeadmanConfig {
+    /// Has to be held for motion, analog triggers are `LeftTrigger2` and `RightTrigger2`
+    pub button: Button,
+    /// Axes that count as motion, all axes if empty
+    #[serde(default)]
+    pub axes: Vec<Axis>,
+}
+
+impl DeadmanConfig {
+    /// Zero the motion axes of every gamepad that doesn't hold the enable button
+    ///
+    /// Whether the button is held is read from `live` input, so replayed macros and
+    /// scripts can't hold it for the operator.
+    pub fn apply(&self, live: &InputMessage, message: &mut InputMessage) {
+        for (gamepad_id, gamepad) in message.gamepads.iter_mut() {
+            gamepad.disabled = !live
+                .gamepads
+                .get(gamepad_id)
+                .is_some_and(|live| live.button_down.get(&self.button) == Some(&true));
+            if !gamepad.disabled {
+                continue;
+            }
+            for (axis, value) in gamepad.axis_state.iter_mut() {
+                if self.axes.is_empty() || self.axes.contains(axis) {
+                    *value = 0.0;
+                }
+            }
+        }
+    }
+}
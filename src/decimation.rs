@@ -0,0 +1,131 @@
+//! Rate limiting bridged protobuf topics without breaking keyframe based streams
+//!
+//! Video encoders send a keyframe followed by deltas that only decode on top of every
+//! message since that keyframe, so dropping a single delta corrupts the picture until the
+//! next keyframe. With a `keyframe_field` keyframes are always forwarded and once a delta
+//! is dropped the rest of its group is dropped too.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use prost::Message;
+use prost_reflect::{DynamicMessage, Kind, MessageDescriptor, Value};
+use serde::Deserialize;
+
+/// `decimation` section of a protobuf subscription
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DecimationConfig {
+    /// Most messages forwarded per second
+    pub max_rate_hz: f64,
+    /// Dotted path of a bool, number or enum field that is set on keyframes
+    #[serde(default)]
+    pub keyframe_field: Option<String>,
+}
+
+/// Decides which messages of one topic are forwarded
+#[derive(Debug, Clone)]
+pub struct Decimator {
+    min_interval: Duration,
+    keyframe_field: Option<Vec<String>>,
+    descriptor: MessageDescriptor,
+    last_forwarded: Option<Instant>,
+    /// A delta since the last keyframe was dropped so the following ones can't be decoded
+    broken_group: bool,
+}
+
+impl Decimator {
+    /// Fails if the rate isn't positive or `keyframe_field` isn't a field of `descriptor`
+    pub fn new(config: &DecimationConfig, descriptor: &MessageDescriptor) -> anyhow::Result<Self> {
+        if !(config.max_rate_hz.is_finite() && config.max_rate_hz > 0.0) {
+            anyhow::bail!("max_rate_hz has to be a positive number");
+        }
+        let keyframe_field = config
+            .keyframe_field
+            .as_deref()
+            .map(|field| keyframe_path(descriptor, field))
+            .transpose()?;
+        Ok(Self {
+            min_interval: Duration::from_secs_f64(1.0 / config.max_rate_hz),
+            keyframe_field,
+            descriptor: descriptor.clone(),
+            last_forwarded: None,
+            broken_group: false,
+        })
+    }
+
+    /// Whether the message `payload` received at `now` is forwarded
+    ///
+    /// Payloads that don't decode aren't keyframes.
+    pub fn keep(&mut self, payload: &[u8], now: Instant) -> bool {
+        let due = match self.last_forwarded {
+            Some(last) => now.duration_since(last) >= self.min_interval,
+            None => true,
+        };
+        let keep = match &self.keyframe_field {
+            None => due,
+            Some(path) if is_keyframe(&self.descriptor, path, payload) => {
+                self.broken_group = false;
+                true
+            }
+            Some(_) => {
+                if !due {
+                    self.broken_group = true;
+                }
+                !self.broken_group
+            }
+        };
+        if keep {
+            self.last_forwarded = Some(now);
+        }
+        keep
+    }
+}
+
+fn keyframe_path(descriptor: &MessageDescriptor, field: &str) -> anyhow::Result<Vec<String>> {
+    let path: Vec<String> = field.split('.').map(str::to_owned).collect();
+    let (last, parents) = path.split_last().context("Empty field path")?;
+    let mut descriptor = descriptor.clone();
+    for name in parents {
+        let parent = descriptor
+            .get_field_by_name(name)
+            .with_context(|| format!("{} has no field {name:?}", descriptor.full_name()))?;
+        match parent.kind() {
+            Kind::Message(nested) if !parent.is_list() => descriptor = nested,
+            _ => anyhow::bail!("{name:?} in {field:?} isn't a message"),
+        }
+    }
+    let keyframe = descriptor
+        .get_field_by_name(last)
+        .with_context(|| format!("{} has no field {last:?}", descriptor.full_name()))?;
+    if keyframe.is_list() || matches!(keyframe.kind(), Kind::Message(_)) {
+        anyhow::bail!("Keyframe field {field:?} has to be a single bool, number or enum");
+    }
+    Ok(path)
+}
+
+fn is_keyframe(descriptor: &MessageDescriptor, path: &[String], payload: &[u8]) -> bool {
+    let Ok(mut message) = DynamicMessage::decode(descriptor.clone(), payload) else {
+        return false;
+    };
+    let Some((last, parents)) = path.split_last() else {
+        return false;
+    };
+    for name in parents {
+        let nested = match message.get_field_by_name(name).as_deref() {
+            Some(Value::Message(nested)) => nested.clone(),
+            _ => return false,
+        };
+        message = nested;
+    }
+    match message.get_field_by_name(last).as_deref() {
+        Some(Value::Bool(value)) => *value,
+        Some(Value::EnumNumber(value) | Value::I32(value)) => *value != 0,
+        Some(Value::I64(value)) => *value != 0,
+        Some(Value::U32(value)) => *value != 0,
+        Some(Value::U64(value)) => *value != 0,
+        Some(Value::F32(value)) => *value != 0.0,
+        Some(Value::F64(value)) => *value != 0.0,
+        _ => false,
+    }
+}
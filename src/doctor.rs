@@ -0,0 +1,249 @@
+//! Checks behind the `doctor` subcommand
+//!
+//! Every step a teleop session depends on is checked on its own, so a dead session can be
+//! traced to the first step that failed instead of guessed from logs.
+
+use std::net::SocketAddr;
+
+use serde::Serialize;
+
+use crate::{
+    connection::ZenohPeers,
+    error::{Diagnostic, DiscoveryError},
+    foxglove_server::{check_bind, FoxgloveHost},
+    robot_registry::RobotTarget,
+    self_test::{TopicCheck, TopicStatus},
+    tailscale::{matching_peers, TailscalePeer, TailscaleStatus},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    /// Not checked because it doesn't apply or an earlier check failed
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl DoctorCheck {
+    pub fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_owned(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DoctorReport {
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    pub fn push(&mut self, check: DoctorCheck) {
+        self.checks.push(check);
+    }
+
+    pub fn extend(&mut self, checks: impl IntoIterator<Item = DoctorCheck>) {
+        self.checks.extend(checks);
+    }
+
+    /// Nothing failed
+    pub fn is_ok(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Failed)
+    }
+
+    /// Table of every check
+    pub fn print(&self) {
+        let width = self
+            .checks
+            .iter()
+            .map(|check| check.name.len())
+            .max()
+            .unwrap_or_default();
+        for check in &self.checks {
+            let status = match check.status {
+                CheckStatus::Ok => "OK",
+                CheckStatus::Failed => "FAIL",
+                CheckStatus::Skipped => "SKIP",
+            };
+            println!("{status:4}  {:width$}  {}", check.name, check.detail);
+        }
+    }
+}
+
+/// Gamepads gilrs can see, blocking
+pub fn check_gamepads() -> DoctorCheck {
+    let gilrs = match gilrs::Gilrs::new() {
+        Ok(gilrs) => gilrs,
+        Err(err) => {
+            return DoctorCheck::new(
+                "gamepad",
+                CheckStatus::Failed,
+                format!("gilrs failed: {err}"),
+            )
+        }
+    };
+    let names: Vec<String> = gilrs
+        .gamepads()
+        .filter(|(_, gamepad)| gamepad.is_connected())
+        .map(|(_, gamepad)| gamepad.name().to_owned())
+        .collect();
+    if names.is_empty() {
+        DoctorCheck::new(
+            "gamepad",
+            CheckStatus::Failed,
+            "No gamepad visible to gilrs, run check-permissions",
+        )
+    } else {
+        DoctorCheck::new("gamepad", CheckStatus::Ok, names.join(", "))
+    }
+}
+
+/// Whether tailscale answered and the robots of `targets` are online peers
+pub fn tailscale_checks(
+    tailscale_status: &Result<TailscaleStatus, DiscoveryError>,
+    targets: &[RobotTarget],
+) -> Vec<DoctorCheck> {
+    let tailscale_status = match tailscale_status {
+        Ok(tailscale_status) => tailscale_status,
+        Err(err) => {
+            let mut checks = vec![DoctorCheck::new(
+                "tailscale",
+                CheckStatus::Failed,
+                failure_detail(&err.diagnostic()),
+            )];
+            checks.extend(targets.iter().map(|target| {
+                DoctorCheck::new(
+                    &format!("robot peer {}", target.name),
+                    CheckStatus::Skipped,
+                    "Tailscale status unavailable",
+                )
+            }));
+            return checks;
+        }
+    };
+
+    let mut checks = vec![match tailscale_status.local_ipv4() {
+        Some(address) => DoctorCheck::new(
+            "tailscale",
+            CheckStatus::Ok,
+            format!("{} at {address}", tailscale_status.self_status.host_name),
+        ),
+        None => DoctorCheck::new(
+            "tailscale",
+            CheckStatus::Failed,
+            "No tailscale IPv4 address, is tailscale up?",
+        ),
+    }];
+    for target in targets {
+        let name = format!("robot peer {}", target.name);
+        let (online, offline): (Vec<_>, Vec<_>) =
+            matching_peers(tailscale_status, &target.peer_rules).partition(|peer| peer.online);
+        let check = match (online.as_slice(), offline.as_slice()) {
+            ([], []) => DoctorCheck::new(
+                &name,
+                CheckStatus::Failed,
+                "No tailscale peer matches the profile",
+            ),
+            ([], offline) => DoctorCheck::new(
+                &name,
+                CheckStatus::Failed,
+                format!("{} offline", host_names(offline)),
+            ),
+            (online, _) => DoctorCheck::new(&name, CheckStatus::Ok, host_names(online)),
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+fn host_names(peers: &[&TailscalePeer]) -> String {
+    let mut names: Vec<&str> = peers.iter().map(|peer| peer.host_name.as_str()).collect();
+    names.sort();
+    names.join(", ")
+}
+
+/// Whether the session found a peer or router to talk to
+pub fn zenoh_link_check(zenoh_peers: &ZenohPeers) -> DoctorCheck {
+    if zenoh_peers.peers.is_empty() && zenoh_peers.routers.is_empty() {
+        DoctorCheck::new(
+            "zenoh link",
+            CheckStatus::Failed,
+            "No zenoh peers or routers connected",
+        )
+    } else {
+        DoctorCheck::new(
+            "zenoh link",
+            CheckStatus::Ok,
+            format!(
+                "{} peer(s), {} router(s)",
+                zenoh_peers.peers.len(),
+                zenoh_peers.routers.len()
+            ),
+        )
+    }
+}
+
+/// One check per bridged topic of the self-test
+pub fn topic_checks(topic_checks: &[TopicCheck]) -> Vec<DoctorCheck> {
+    if topic_checks.is_empty() {
+        return vec![DoctorCheck::new(
+            "telemetry",
+            CheckStatus::Skipped,
+            "Profile bridges no topics",
+        )];
+    }
+    topic_checks
+        .iter()
+        .map(|check| {
+            let name = format!("topic {}", check.topic);
+            match check.status {
+                TopicStatus::Publisher => DoctorCheck::new(&name, CheckStatus::Ok, "publisher"),
+                TopicStatus::Queryable => DoctorCheck::new(&name, CheckStatus::Ok, "queryable"),
+                TopicStatus::Missing => {
+                    DoctorCheck::new(&name, CheckStatus::Failed, "No messages received")
+                }
+            }
+        })
+        .collect()
+}
+
+/// Whether the Foxglove server could bind its address
+pub async fn foxglove_port_check(
+    host: &FoxgloveHost,
+    tailscale_status: &TailscaleStatus,
+) -> DoctorCheck {
+    let address: SocketAddr = match host.resolve(tailscale_status) {
+        Ok((address, _)) => address,
+        Err(err) => {
+            return DoctorCheck::new("foxglove port", CheckStatus::Failed, format!("{err:#}"))
+        }
+    };
+    match check_bind(address).await {
+        Ok(()) => DoctorCheck::new("foxglove port", CheckStatus::Ok, address.to_string()),
+        Err(err) => DoctorCheck::new(
+            "foxglove port",
+            CheckStatus::Failed,
+            failure_detail(&err.diagnostic()),
+        ),
+    }
+}
+
+/// Error message followed by what the operator can do about it
+fn failure_detail(diagnostic: &Diagnostic) -> String {
+    match &diagnostic.hint {
+        Some(hint) => format!("{}. {hint}", diagnostic.message),
+        None => diagnostic.message.clone(),
+    }
+}
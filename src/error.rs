@@ -1,7 +1,349 @@
-use thiserror::Error;
+//! Errors of the remote's subsystems
+//!
+//! Gamepad input, the Foxglove bridge, tailscale discovery and configuration loading return
+//! their own error types so library users can tell a missing gamepad from a bad profile and
+//! decide whether trying again can help. Zenoh errors of any of them are a [`ZenohError`].
+//! Tasks still report `anyhow` errors, [`diagnose`] finds these types behind the added
+//! context and turns them into a [`Diagnostic`].
 
-#[derive(Error, Debug)]
-pub enum ErrorWrapper {
-    #[error("Zenoh error {0:?}")]
-    ZenohError(#[from] zenoh::Error),
+use std::{io, net::SocketAddr, path::PathBuf};
+
+use serde::Serialize;
+
+/// zenoh errors aren't `std::error::Error`, wrap them to use them with `?`
+#[derive(thiserror::Error, Debug)]
+#[error("Zenoh error {0:?}")]
+pub struct ZenohError(#[from] pub zenoh::Error);
+
+impl ZenohError {
+    /// Declarations and puts fail while the session is closing or the router is unreachable
+    pub fn is_retryable(&self) -> bool {
+        true
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let hint = "Check that the zenoh router or the robot is reachable".to_owned();
+        Diagnostic::new("zenoh", self, Some(hint), self.is_retryable())
+    }
+}
+
+/// Error of any subsystem
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Gamepad(#[from] GamepadError),
+    #[error(transparent)]
+    Bridge(#[from] BridgeError),
+    #[error(transparent)]
+    Discovery(#[from] DiscoveryError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Zenoh(#[from] ZenohError),
+}
+
+impl Error {
+    /// Whether the same call can succeed later without changing the configuration
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Gamepad(err) => err.is_retryable(),
+            Self::Bridge(err) => err.is_retryable(),
+            Self::Discovery(err) => err.is_retryable(),
+            Self::Config(err) => err.is_retryable(),
+            Self::Zenoh(err) => err.is_retryable(),
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::Gamepad(err) => err.diagnostic(),
+            Self::Bridge(err) => err.diagnostic(),
+            Self::Discovery(err) => err.diagnostic(),
+            Self::Config(err) => err.diagnostic(),
+            Self::Zenoh(err) => err.diagnostic(),
+        }
+    }
+}
+
+/// Reading gamepads and encoding their state
+#[derive(thiserror::Error, Debug)]
+pub enum GamepadError {
+    #[error("Failed to open gamepads: {0}")]
+    Backend(String),
+    #[error("Gamepad backend isn't running")]
+    BackendStopped,
+    #[error("Failed to encode {encoding} gamepad message: {reason}")]
+    Encoding {
+        encoding: &'static str,
+        reason: String,
+    },
+    #[error("Failed to load input script: {0}")]
+    Script(String),
+    #[error(transparent)]
+    Zenoh(#[from] ZenohError),
+}
+
+impl From<serde_json::Error> for GamepadError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::encoding("json", err)
+    }
+}
+
+impl GamepadError {
+    pub fn encoding(encoding: &'static str, err: impl std::fmt::Display) -> Self {
+        Self::Encoding {
+            encoding,
+            reason: err.to_string(),
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Backend(_) | Self::BackendStopped => true,
+            Self::Encoding { .. } | Self::Script(_) => false,
+            Self::Zenoh(err) => err.is_retryable(),
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let hint = match self {
+            Self::Zenoh(err) => return err.diagnostic(),
+            Self::Backend(_) => Some(
+                "Check that input devices are readable with `deck-robot-remote check-permissions`"
+                    .to_owned(),
+            ),
+            Self::BackendStopped => None,
+            Self::Encoding { .. } => Some("Try another --gamepad-encoding".to_owned()),
+            Self::Script(_) => Some("Fix the `script` of the robot profile".to_owned()),
+        };
+        Diagnostic::new("gamepad", self, hint, self.is_retryable())
+    }
+}
+
+/// Starting the Foxglove bridge
+#[derive(thiserror::Error, Debug)]
+pub enum BridgeError {
+    #[error("Can't bind Foxglove server to {address}: {error}")]
+    Bind {
+        address: SocketAddr,
+        error: io::Error,
+    },
+    #[error(
+        "Unknown protobuf message types in foxglove config:\n{}\nAvailable packages: {}",
+        unknown.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"),
+        packages.join(", ")
+    )]
+    UnknownProtoTypes {
+        unknown: Vec<UnknownProtoType>,
+        packages: Vec<String>,
+    },
+    #[error("Invalid subscription {topic}: {reason}")]
+    InvalidSubscription { topic: String, reason: String },
+    #[error("Failed to create Foxglove channel {topic}: {reason}")]
+    Channel { topic: String, reason: String },
+    #[error("Failed to start foxglove-ws: {0}")]
+    Server(io::Error),
+    #[error(transparent)]
+    Zenoh(#[from] ZenohError),
+}
+
+/// Proto type of a protobuf subscription that isn't in the descriptor pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownProtoType {
+    pub topic: String,
+    pub proto_type: String,
+    /// Known types with a similar name
+    pub suggestions: Vec<String>,
+}
+
+impl std::fmt::Display for UnknownProtoType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "  {:?} for topic {:?}", self.proto_type, self.topic)?;
+        if !self.suggestions.is_empty() {
+            write!(f, ", did you mean {}?", self.suggestions.join(" or "))?;
+        }
+        Ok(())
+    }
+}
+
+impl BridgeError {
+    pub fn invalid_subscription(topic: &str, err: impl std::fmt::Display) -> Self {
+        Self::InvalidSubscription {
+            topic: topic.to_owned(),
+            reason: err.to_string(),
+        }
+    }
+
+    pub fn channel(topic: &str, err: impl std::fmt::Display) -> Self {
+        Self::Channel {
+            topic: topic.to_owned(),
+            reason: err.to_string(),
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            // the port is freed when the other process exits
+            Self::Bind { error, .. } => error.kind() == io::ErrorKind::AddrInUse,
+            Self::UnknownProtoTypes { .. } | Self::InvalidSubscription { .. } => false,
+            Self::Channel { .. } | Self::Server(_) => true,
+            Self::Zenoh(err) => err.is_retryable(),
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let hint = match self {
+            Self::Zenoh(err) => return err.diagnostic(),
+            Self::Bind { error, .. } if error.kind() == io::ErrorKind::AddrInUse => {
+                "Another process is using the port, stop it or pick another --host".to_owned()
+            }
+            Self::Bind { .. } => "Pick an address of this machine with --host".to_owned(),
+            Self::UnknownProtoTypes { .. } => {
+                "Fix the proto_type or add the message to descriptor_files of the robot profile"
+                    .to_owned()
+            }
+            Self::InvalidSubscription { .. } => {
+                "Fix the subscription in the foxglove section of the robot profile".to_owned()
+            }
+            Self::Channel { .. } | Self::Server(_) => "Restart the remote".to_owned(),
+        };
+        Diagnostic::new("foxglove bridge", self, Some(hint), self.is_retryable())
+    }
+}
+
+/// Reading the tailscale status and turning peers into zenoh endpoints
+#[derive(thiserror::Error, Debug)]
+pub enum DiscoveryError {
+    #[error("Tailscale status unavailable: {0}")]
+    Unavailable(String),
+    #[error("Failed to parse tailscale status: {0}")]
+    InvalidStatus(serde_json::Error),
+    #[error("Invalid peer address {address:?}: {reason}")]
+    InvalidAddress { address: String, reason: String },
+}
+
+impl DiscoveryError {
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            // tailscaled restarting or still logging in
+            Self::Unavailable(_) => true,
+            Self::InvalidStatus(_) | Self::InvalidAddress { .. } => false,
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let hint = match self {
+            Self::Unavailable(_) => Some("Check that tailscale is up with `tailscale status`"),
+            Self::InvalidStatus(_) => Some("Update tailscale, this version's status isn't known"),
+            Self::InvalidAddress { .. } => None,
+        };
+        Diagnostic::new(
+            "tailscale",
+            self,
+            hint.map(str::to_owned),
+            self.is_retryable(),
+        )
+    }
+}
+
+/// Loading robot profiles and the config file
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read {}: {error}", path.display())]
+    Read { path: PathBuf, error: io::Error },
+    #[error("Invalid {name}: {error}")]
+    Parse {
+        /// Config file or profile that failed to parse
+        name: String,
+        error: serde_yaml::Error,
+    },
+    #[error("Invalid {name}: {reason}")]
+    Invalid { name: String, reason: String },
+    #[error("Unknown robot profile {name:?}, available profiles: {}", available.join(", "))]
+    UnknownProfile {
+        name: String,
+        available: Vec<String>,
+    },
+    #[error("Failed to compile {}: {reason}", path.display())]
+    Protoc { path: PathBuf, reason: String },
+}
+
+impl ConfigError {
+    /// Configuration is read once, it has to be fixed first
+    pub fn is_retryable(&self) -> bool {
+        false
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let hint = match self {
+            Self::Read { .. } => None,
+            Self::Parse { .. } | Self::Invalid { .. } => {
+                Some("Compare with the profiles in config/profiles".to_owned())
+            }
+            Self::UnknownProfile { .. } => {
+                Some("Add a profile with --profile-dir or profiles in --config".to_owned())
+            }
+            Self::Protoc { .. } => Some(
+                "Install protoc, set PROTOC or list a compiled descriptor set instead".to_owned(),
+            ),
+        };
+        Diagnostic::new("config", self, hint, self.is_retryable())
+    }
+}
+
+/// Error description for operators, in logs, supervisor events and the doctor report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub subsystem: &'static str,
+    pub message: String,
+    /// What the operator can do about it
+    pub hint: Option<String>,
+    pub retryable: bool,
+}
+
+impl Diagnostic {
+    fn new(
+        subsystem: &'static str,
+        err: &impl std::fmt::Display,
+        hint: Option<String>,
+        retryable: bool,
+    ) -> Self {
+        Self {
+            subsystem,
+            message: err.to_string(),
+            hint,
+            retryable,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.subsystem, self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, " ({hint})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Diagnostic of the first subsystem error in the chain of `err`
+pub fn diagnose(err: &anyhow::Error) -> Option<Diagnostic> {
+    err.chain().find_map(|cause| {
+        if let Some(err) = cause.downcast_ref::<Error>() {
+            Some(err.diagnostic())
+        } else if let Some(err) = cause.downcast_ref::<GamepadError>() {
+            Some(err.diagnostic())
+        } else if let Some(err) = cause.downcast_ref::<BridgeError>() {
+            Some(err.diagnostic())
+        } else if let Some(err) = cause.downcast_ref::<DiscoveryError>() {
+            Some(err.diagnostic())
+        } else if let Some(err) = cause.downcast_ref::<ZenohError>() {
+            Some(err.diagnostic())
+        } else {
+            cause
+                .downcast_ref::<ConfigError>()
+                .map(ConfigError::diagnostic)
+        }
+    })
 }
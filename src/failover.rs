@@ -0,0 +1,189 @@
+//! Failing over between the tailscale peers of one robot
+//!
+//! Robots with an onboard computer and a backup list both in `tailscale_peers`, in order of
+//! priority. With a `failover` section only the peers of one rule are connected at a time,
+//! and when the active robot's telemetry stops for `telemetry_timeout_ms` the connect
+//! endpoints are switched to the next rule with online peers.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+use zenoh_config::EndPoint;
+
+use crate::{
+    error::ZenohError,
+    health::Heartbeat,
+    robot_registry::RobotRegistry,
+    stats::StatsRegistry,
+    supervisor::Supervisor,
+    tailscale::{online_peer_endpoints, peer_endpoints, TailscaleStatus},
+};
+
+const FAILOVER_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `failover` section of a robot profile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailoverConfig {
+    /// Telemetry silence after which the next peer rule is tried
+    #[serde(default = "default_telemetry_timeout_ms")]
+    pub telemetry_timeout_ms: u64,
+}
+
+fn default_telemetry_timeout_ms() -> u64 {
+    5000
+}
+
+impl FailoverConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.telemetry_timeout_ms == 0 {
+            anyhow::bail!("telemetry_timeout_ms has to be positive");
+        }
+        Ok(())
+    }
+
+    pub fn telemetry_timeout(&self) -> Duration {
+        Duration::from_millis(self.telemetry_timeout_ms)
+    }
+}
+
+/// Whether nothing arrived for `timeout` since telemetry or the last switch, whichever is later
+///
+/// The switch counts so a peer that was just connected gets the whole timeout.
+pub fn telemetry_stale(
+    last_telemetry: Option<Instant>,
+    last_switch: Instant,
+    now: Instant,
+    timeout: Duration,
+) -> bool {
+    let since = last_telemetry.map_or(last_switch, |last| last.max(last_switch));
+    now.saturating_duration_since(since) >= timeout
+}
+
+/// `current` connect endpoints with the robot's `all_peers` replaced by `selected`
+///
+/// Endpoints that don't belong to the robot, such as `--connect` ones, are kept.
+pub fn replace_peer_endpoints(
+    current: &[EndPoint],
+    all_peers: &[EndPoint],
+    selected: &[EndPoint],
+) -> Vec<EndPoint> {
+    let mut endpoints: Vec<EndPoint> = current
+        .iter()
+        .filter(|endpoint| !all_peers.contains(endpoint))
+        .cloned()
+        .collect();
+    endpoints.extend(selected.iter().cloned());
+    endpoints
+}
+
+/// Switch the active robot's peers when its telemetry, the newest sample of any of
+/// `telemetry_topics`, goes quiet
+pub fn start_failover_monitor(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    registry: RobotRegistry,
+    stats: StatsRegistry,
+    telemetry_topics: Vec<String>,
+    ipv6: bool,
+) {
+    let heartbeat = supervisor.health().heartbeat("failover_monitor");
+    supervisor.spawn("failover_monitor", move |shutdown| {
+        run_failover_monitor(
+            zenoh_session.clone(),
+            registry.clone(),
+            stats.clone(),
+            telemetry_topics.clone(),
+            ipv6,
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_failover_monitor(
+    zenoh_session: Arc<Session>,
+    registry: RobotRegistry,
+    stats: StatsRegistry,
+    telemetry_topics: Vec<String>,
+    ipv6: bool,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut active = registry.subscribe();
+    let mut last_switch = Instant::now();
+    let mut interval = tokio::time::interval(FAILOVER_POLL_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => heartbeat.beat(),
+            changed = active.changed() => {
+                changed?;
+                // a newly selected robot gets the whole timeout
+                last_switch = Instant::now();
+                continue;
+            }
+            _ = shutdown.cancelled() => break,
+        }
+        let target = active.borrow_and_update().clone();
+        let Some(failover) = &target.failover else {
+            continue;
+        };
+        let last_telemetry = stats.last_received(&telemetry_topics);
+        let timeout = failover.telemetry_timeout();
+        if !telemetry_stale(last_telemetry, last_switch, Instant::now(), timeout) {
+            continue;
+        }
+        last_switch = Instant::now();
+
+        let tailscale_status = match TailscaleStatus::read().await {
+            Ok(tailscale_status) => tailscale_status,
+            Err(err) => {
+                warn!("Failed to read tailscale status for failover {err:?}");
+                continue;
+            }
+        };
+        let all_peers = peer_endpoints(&tailscale_status, &target.peer_rules, ipv6)?;
+        // rules without online peers are skipped, back to the current one if none has any
+        let mut selected = vec![];
+        for _ in 0..target.peer_rules.len() {
+            let rules = registry.fail_over(&target.name);
+            selected = online_peer_endpoints(&tailscale_status, &rules, ipv6)?;
+            if !selected.is_empty() {
+                break;
+            }
+        }
+        if selected.is_empty() {
+            warn!(
+                "No telemetry from {} and no peer to fail over to",
+                target.name
+            );
+            continue;
+        }
+        warn!(
+            "No telemetry from {} for {timeout:?}, failing over to {:?}",
+            target.name, selected
+        );
+        let current = zenoh_session.config().lock().connect.endpoints.clone();
+        let connect_endpoints: Vec<String> =
+            replace_peer_endpoints(&current, &all_peers, &selected)
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+        zenoh_session
+            .config()
+            .insert_json5(
+                "connect/endpoints",
+                &serde_json::to_string(&connect_endpoints)?,
+            )
+            .map_err(ZenohError)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Foxglove clients attached to the bridge
+//!
+//! foxglove-ws doesn't report connects and disconnects, so established connections to the
+//! server's port are polled from `/proc/net/tcp`. Subscriptions marked `on_demand` are only
+//! declared on zenoh while a client is attached, so camera topics don't use the robot's
+//! LTE data with nobody watching.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use tokio::sync::watch;
+
+use crate::{health::HEARTBEAT_INTERVAL, supervisor::Supervisor};
+
+const CLIENT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// `st` column of `/proc/net/tcp` for established connections
+const TCP_ESTABLISHED: &str = "01";
+
+/// Whether a Foxglove client is attached, updated by [`start_client_watch`]
+pub type ClientWatch = watch::Receiver<bool>;
+
+/// Established connections to local `port` in the contents of `/proc/net/tcp` or `/proc/net/tcp6`
+pub fn count_established_connections(proc_net_tcp: &str, port: u16) -> usize {
+    proc_net_tcp
+        .lines()
+        .skip(1)
+        .filter(|line| {
+            let mut columns = line.split_whitespace().skip(1);
+            let local_port = columns
+                .next()
+                .and_then(|local_address| local_address.rsplit_once(':'))
+                .and_then(|(_, port)| u16::from_str_radix(port, 16).ok());
+            // remote address
+            columns.next();
+            local_port == Some(port) && columns.next() == Some(TCP_ESTABLISHED)
+        })
+        .count()
+}
+
+/// Clients connected to the Foxglove server, through the TLS proxy they connect to its backend
+pub fn foxglove_client_count(port: u16) -> Option<usize> {
+    let mut count = None;
+    for path in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        if let Ok(proc_net_tcp) = std::fs::read_to_string(path) {
+            *count.get_or_insert(0) += count_established_connections(&proc_net_tcp, port);
+        }
+    }
+    count
+}
+
+/// Without `/proc` every client is assumed to be watching
+fn clients_attached(port: u16) -> bool {
+    foxglove_client_count(port).map_or(true, |count| count > 0)
+}
+
+/// Wait until a client is attached, or none is if `attached` is false
+pub async fn wait_for_clients(
+    client_watch: &mut ClientWatch,
+    attached: bool,
+) -> anyhow::Result<()> {
+    client_watch
+        .wait_for(|value| *value == attached)
+        .await
+        .context("Foxglove client watch stopped")?;
+    Ok(())
+}
+
+/// Poll the clients of the Foxglove server on `port`
+pub fn start_client_watch(supervisor: &Supervisor, port: u16) -> ClientWatch {
+    let (sender, receiver) = watch::channel(clients_attached(port));
+    let sender = Arc::new(sender);
+    supervisor.spawn_persistent("foxglove_clients", {
+        let heartbeat = supervisor.health().heartbeat("foxglove_clients");
+        move |shutdown| {
+            let sender = sender.clone();
+            let heartbeat = heartbeat.clone();
+            async move {
+                let mut poll_interval = tokio::time::interval(CLIENT_POLL_INTERVAL);
+                let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = poll_interval.tick() => {
+                            let attached = clients_attached(port);
+                            // receivers only wake up on a change
+                            sender.send_if_modified(|previous| {
+                                std::mem::replace(previous, attached) != attached
+                            });
+                        }
+                        _ = heartbeat_interval.tick() => heartbeat.beat(),
+                        _ = shutdown.cancelled() => break,
+                    }
+                }
+                Ok(())
+            }
+        }
+    });
+    receiver
+}
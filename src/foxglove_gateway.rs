@@ -0,0 +1,981 @@
+//! Foxglove protocol features on top of foxglove-ws
+//!
+//! foxglove-ws only advertises channels and sends message data. The gateway accepts
+//! Foxglove clients itself and relays their websocket to the server on loopback, so the
+//! bridge can speak more of the protocol without changes to foxglove-ws.
+//! The gateway adds its capabilities to the server's `serverInfo` and answers the client
+//! operations behind them, everything else is relayed untouched. Client publishing, service
+//! calls and parameters are translated to zenoh puts and queries. Assets are read from a
+//! directory or fetched from a zenoh queryable. Server time comes from the Deck's clock or
+//! from a robot clock topic.
+//!
+//! On shutdown every client is sent an `unadvertise` for its channels and a close frame,
+//! so Foxglove shows the bridge as disconnected instead of waiting on stale channels.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::ErrorKind,
+    net::SocketAddr,
+    path::{Component, Path},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use futures_util::{stream::SplitSink, SinkExt, StreamExt};
+use serde::Deserialize;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast::{self, error::RecvError},
+    task::JoinSet,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        client::IntoClientRequest,
+        handshake::server::{ErrorResponse, Request, Response},
+        http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue},
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    clock::SharedClock,
+    error::ZenohError,
+    foxglove_protocol::{
+        advertise_services, extend_server_info, fetch_asset_response, parameter_values,
+        server_time, service_call_failure, service_call_response, status, AdvertisedService,
+        ChannelTracker, ClientBinary, ClientChannel, ClientRequest, Parameter, StatusLevel,
+        SUBPROTOCOL,
+    },
+    foxglove_server::FoxgloveServerConfiguration,
+    gamepad::EstopLatch,
+    health::HEARTBEAT_INTERVAL,
+    supervisor::Supervisor,
+};
+
+/// How long sending the goodbye to a client may take on shutdown
+pub const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Encoding of messages published from Foxglove panels
+const CLIENT_PUBLISH_ENCODING: &str = "json";
+const DEFAULT_SERVICE_TIMEOUT_MS: u64 = 2000;
+const PARAMETER_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+const PARAMETER_UPDATE_QUEUE_SIZE: usize = 64;
+/// Meshes can take a while to arrive over a slow link
+const ASSET_QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// URI scheme of the files a URDF references
+const ASSET_URI_SCHEME: &str = "package://";
+/// How often clients are sent the server time, often enough for smooth plots
+const TIME_INTERVAL: Duration = Duration::from_millis(100);
+
+type ClientSink = SplitSink<WebSocketStream<TcpStream>, Message>;
+type ServerSink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+
+/// Topic that Foxglove panels may publish on
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClientPublishTopic {
+    /// Topic the panel publishes on, such as `/cmd_vel` of the Teleop panel
+    pub topic: String,
+    /// Zenoh key the messages are put on, the topic without its leading `/` by default
+    pub key_expr: Option<String>,
+}
+
+impl ClientPublishTopic {
+    pub fn key_expr(&self) -> String {
+        self.key_expr
+            .clone()
+            .unwrap_or_else(|| self.topic.trim_start_matches('/').to_owned())
+    }
+}
+
+/// Zenoh queryable offered to Foxglove as a service, such as for a Service Call panel
+#[derive(Debug, Clone, Deserialize)]
+pub struct FoxgloveService {
+    /// Service name shown in Foxglove
+    pub name: String,
+    /// Key the request is sent to with a zenoh get, its payload is the request of the call
+    pub key_expr: String,
+    /// Type shown in Foxglove, `json` by default
+    pub type_name: Option<String>,
+    /// How long to wait for the queryable to reply, 2000 by default
+    pub timeout_ms: Option<u64>,
+}
+
+impl FoxgloveService {
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_SERVICE_TIMEOUT_MS))
+    }
+}
+
+/// Client operations answered by the gateway, configured in the robot profile
+#[derive(Clone)]
+pub struct FoxgloveGateway {
+    zenoh_session: Arc<Session>,
+    client_publish: Arc<[ClientPublishTopic]>,
+    services: Arc<[FoxgloveService]>,
+    parameter_prefix: Option<Arc<str>>,
+    /// Changes of parameters under [`Self::parameter_prefix`], shared by every client
+    parameter_updates: broadcast::Sender<Parameter>,
+    asset_dir: Option<Arc<Path>>,
+    asset_key_prefix: Option<Arc<str>>,
+    time_source: Option<TimeSource>,
+    /// Messages of panels are refused while the gamepad's emergency stop is engaged
+    estop: EstopLatch,
+}
+
+/// Clock whose time is broadcast to clients
+#[derive(Clone)]
+enum TimeSource {
+    Deck(SharedClock),
+    Robot {
+        key_expr: Arc<str>,
+        clock: RobotClock,
+    },
+}
+
+/// Latest stamp of the robot's clock and when it arrived
+///
+/// Time between stamps is filled in with the Deck's monotonic clock.
+#[derive(Clone, Default)]
+struct RobotClock {
+    latest: Arc<Mutex<Option<(u64, Instant)>>>,
+}
+
+impl RobotClock {
+    fn update(&self, timestamp_nanos: u64) {
+        *self.latest.lock().unwrap() = Some((timestamp_nanos, Instant::now()));
+    }
+
+    /// `None` until the robot's clock was heard
+    fn now_nanos(&self) -> Option<u64> {
+        let (timestamp_nanos, received) = (*self.latest.lock().unwrap())?;
+        Some(timestamp_nanos + received.elapsed().as_nanos() as u64)
+    }
+}
+
+impl FoxgloveGateway {
+    pub fn new(
+        zenoh_session: Arc<Session>,
+        config: &FoxgloveServerConfiguration,
+        clock: SharedClock,
+        estop: EstopLatch,
+    ) -> Self {
+        let (parameter_updates, _) = broadcast::channel(PARAMETER_UPDATE_QUEUE_SIZE);
+        let time_source = match &config.time_key_expr {
+            Some(key_expr) => Some(TimeSource::Robot {
+                key_expr: Arc::from(key_expr.as_str()),
+                clock: RobotClock::default(),
+            }),
+            None => config.broadcast_time.then_some(TimeSource::Deck(clock)),
+        };
+        Self {
+            zenoh_session,
+            client_publish: config.client_publish.clone().into(),
+            services: config.services.clone().into(),
+            parameter_prefix: config.parameter_prefix.as_deref().map(Arc::from),
+            parameter_updates,
+            asset_dir: config.asset_dir.as_deref().map(Arc::from),
+            asset_key_prefix: config.asset_key_prefix.as_deref().map(Arc::from),
+            time_source,
+            estop,
+        }
+    }
+
+    /// Capabilities added to the server's own
+    fn capabilities(&self) -> Vec<&'static str> {
+        let mut capabilities = vec![];
+        if !self.client_publish.is_empty() {
+            capabilities.push("clientPublish");
+        }
+        if !self.services.is_empty() {
+            capabilities.push("services");
+        }
+        if self.parameter_prefix.is_some() {
+            capabilities.extend(["parameters", "parametersSubscribe"]);
+        }
+        if self.asset_dir.is_some() || self.asset_key_prefix.is_some() {
+            capabilities.push("assets");
+        }
+        if self.time_source.is_some() {
+            capabilities.push("time");
+        }
+        capabilities
+    }
+
+    /// Services by id, ids start at 1
+    fn service(&self, service_id: u32) -> Option<&FoxgloveService> {
+        self.services.get(service_id.checked_sub(1)? as usize)
+    }
+
+    fn advertise_services(&self) -> Option<String> {
+        if self.services.is_empty() {
+            return None;
+        }
+        let services: Vec<_> = (1..)
+            .zip(self.services.iter())
+            .map(|(id, service)| AdvertisedService {
+                id,
+                name: &service.name,
+                type_name: service.type_name.as_deref().unwrap_or("json"),
+                request_schema: "",
+                response_schema: "",
+            })
+            .collect();
+        Some(advertise_services(&services))
+    }
+
+    /// Asset from the asset directory, or from the asset queryable if it isn't there
+    async fn load_asset(&self, uri: &str) -> anyhow::Result<Vec<u8>> {
+        let path = asset_path(uri).with_context(|| format!("Unsupported asset URI {uri}"))?;
+        if let Some(asset_dir) = &self.asset_dir {
+            match tokio::fs::read(asset_dir.join(path)).await {
+                Ok(asset) => return Ok(asset),
+                Err(err)
+                    if err.kind() == ErrorKind::NotFound && self.asset_key_prefix.is_some() =>
+                {
+                    debug!(path, "Asset isn't in the asset directory, querying zenoh");
+                }
+                Err(err) => return Err(err).with_context(|| format!("Failed to read {path}")),
+            }
+        }
+        let Some(prefix) = &self.asset_key_prefix else {
+            bail!("No asset directory or asset key prefix");
+        };
+        query_asset(&self.zenoh_session, &format!("{prefix}/{path}")).await
+    }
+
+    /// Time to broadcast, `None` while the robot's clock wasn't heard yet
+    fn server_time(&self) -> Option<u64> {
+        match self.time_source.as_ref()? {
+            TimeSource::Deck(clock) => Some(clock.now_nanos()),
+            TimeSource::Robot { clock, .. } => clock.now_nanos(),
+        }
+    }
+
+    fn supported_encodings(&self) -> Vec<&'static str> {
+        if self.client_publish.is_empty() {
+            vec![]
+        } else {
+            vec![CLIENT_PUBLISH_ENCODING]
+        }
+    }
+}
+
+/// Accept Foxglove clients on `listen` and relay them to the foxglove-ws server on `backend`
+///
+/// Clients stay connected while zenoh reconnects.
+pub fn start_foxglove_gateway(
+    supervisor: &Supervisor,
+    listen: SocketAddr,
+    backend: SocketAddr,
+    gateway: FoxgloveGateway,
+) {
+    if let Some(prefix) = gateway.parameter_prefix.clone() {
+        start_parameter_watch(
+            supervisor,
+            gateway.zenoh_session.clone(),
+            prefix,
+            gateway.parameter_updates.clone(),
+        );
+    }
+    if let Some(TimeSource::Robot { key_expr, clock }) = gateway.time_source.clone() {
+        start_robot_clock_watch(supervisor, gateway.zenoh_session.clone(), key_expr, clock);
+    }
+    let heartbeat = supervisor.health().heartbeat("foxglove_gateway");
+    supervisor.spawn_persistent("foxglove_gateway", move |shutdown| {
+        let heartbeat = heartbeat.clone();
+        let gateway = gateway.clone();
+        async move {
+            let listener = TcpListener::bind(listen)
+                .await
+                .with_context(|| format!("Foxglove gateway can't bind {listen}"))?;
+            let mut connections = JoinSet::new();
+            let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => {
+                        let (stream, peer) = accepted?;
+                        let gateway = gateway.clone();
+                        let shutdown = shutdown.clone();
+                        connections.spawn(async move {
+                            let relayed = relay_connection(gateway, stream, backend, &shutdown);
+                            if let Err(err) = relayed.await {
+                                debug!(%peer, "Foxglove connection closed with error {err:?}");
+                            }
+                        });
+                    }
+                    Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                    _ = heartbeat_interval.tick() => heartbeat.beat(),
+                    _ = shutdown.cancelled() => break,
+                }
+            }
+            // connections say goodbye to their clients on shutdown
+            while connections.join_next().await.is_some() {}
+            Ok(())
+        }
+    });
+}
+
+/// Follow parameter changes for clients that subscribed to parameter updates
+fn start_parameter_watch(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    prefix: Arc<str>,
+    parameter_updates: broadcast::Sender<Parameter>,
+) {
+    let heartbeat = supervisor.health().heartbeat("foxglove_parameters");
+    supervisor.spawn("foxglove_parameters", move |shutdown| {
+        let zenoh_session = zenoh_session.clone();
+        let prefix = prefix.clone();
+        let parameter_updates = parameter_updates.clone();
+        let heartbeat = heartbeat.clone();
+        async move {
+            let subscriber = zenoh_session
+                .declare_subscriber(format!("{prefix}/**"))
+                .res()
+                .await
+                .map_err(ZenohError)?;
+            let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                let sample = tokio::select! {
+                    sample = subscriber.recv_async() => sample?,
+                    _ = heartbeat_interval.tick() => {
+                        heartbeat.beat();
+                        continue;
+                    }
+                    _ = shutdown.cancelled() => break,
+                };
+                if let Some(parameter) = parameter_from_sample(&prefix, sample) {
+                    // no receivers while no client is connected
+                    _ = parameter_updates.send(parameter);
+                }
+            }
+            Ok(())
+        }
+    });
+}
+
+/// Follow the robot's clock topic for the time broadcast to clients
+fn start_robot_clock_watch(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    key_expr: Arc<str>,
+    clock: RobotClock,
+) {
+    let heartbeat = supervisor.health().heartbeat("foxglove_robot_clock");
+    supervisor.spawn("foxglove_robot_clock", move |shutdown| {
+        let zenoh_session = zenoh_session.clone();
+        let key_expr = key_expr.clone();
+        let clock = clock.clone();
+        let heartbeat = heartbeat.clone();
+        async move {
+            let subscriber = zenoh_session
+                .declare_subscriber(key_expr.as_ref())
+                .res()
+                .await
+                .map_err(ZenohError)?;
+            let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                let sample = tokio::select! {
+                    sample = subscriber.recv_async() => sample?,
+                    _ = heartbeat_interval.tick() => {
+                        heartbeat.beat();
+                        continue;
+                    }
+                    _ = shutdown.cancelled() => break,
+                };
+                match robot_clock_nanos(&sample.value.payload.contiguous()) {
+                    Some(timestamp_nanos) => clock.update(timestamp_nanos),
+                    None => debug!(%key_expr, "Robot clock sample without a timestamp"),
+                }
+            }
+            Ok(())
+        }
+    });
+}
+
+/// Accept the Foxglove subprotocol, browsers drop connections that don't confirm it
+fn negotiate_subprotocol(
+    request: &Request,
+    mut response: Response,
+) -> Result<Response, ErrorResponse> {
+    let offered = request
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|protocols| protocols.to_str().ok())
+        .flat_map(|protocols| protocols.split(','))
+        .any(|protocol| protocol.trim() == SUBPROTOCOL);
+    if offered {
+        response.headers_mut().insert(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static(SUBPROTOCOL),
+        );
+    }
+    Ok(response)
+}
+
+async fn relay_connection(
+    gateway: FoxgloveGateway,
+    stream: TcpStream,
+    backend: SocketAddr,
+    shutdown: &CancellationToken,
+) -> anyhow::Result<()> {
+    stream.set_nodelay(true)?;
+    let client = tokio_tungstenite::accept_hdr_async(stream, negotiate_subprotocol)
+        .await
+        .context("Websocket handshake failed")?;
+    let mut request = format!("ws://{backend}").into_client_request()?;
+    request.headers_mut().insert(
+        SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static(SUBPROTOCOL),
+    );
+    let (server, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .context("Failed to connect to foxglove server")?;
+
+    let parameter_updates = gateway.parameter_updates.subscribe();
+    let mut time_interval = tokio::time::interval(TIME_INTERVAL);
+    let (client_sink, mut client_stream) = client.split();
+    let (server_sink, mut server_stream) = server.split();
+    let mut connection = Connection {
+        gateway,
+        client_sink,
+        server_sink,
+        channels: ChannelTracker::default(),
+        client_channels: HashMap::new(),
+        pending_replies: JoinSet::new(),
+        parameter_subscriptions: HashSet::new(),
+        parameter_updates,
+        server_info_sent: false,
+        estop_reported: false,
+    };
+    loop {
+        tokio::select! {
+            message = server_stream.next() => {
+                let Some(message) = message.transpose()? else {
+                    break;
+                };
+                if !connection.relay_to_client(message).await? {
+                    break;
+                }
+            }
+            message = client_stream.next() => {
+                let Some(message) = message.transpose()? else {
+                    break;
+                };
+                if !connection.handle_client_message(message).await? {
+                    break;
+                }
+            }
+            Some(reply) = connection.pending_replies.join_next(),
+                if !connection.pending_replies.is_empty() =>
+            {
+                connection.client_sink.send(reply?).await?;
+            }
+            update = connection.parameter_updates.recv() => match update {
+                Ok(parameter) => connection.send_parameter_update(parameter).await?,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "Foxglove client missed parameter updates");
+                }
+                // the gateway keeps the sender
+                Err(RecvError::Closed) => unreachable!(),
+            },
+            _ = time_interval.tick(), if connection.sends_time() => {
+                connection.send_time().await?;
+            }
+            _ = shutdown.cancelled() => {
+                tokio::time::timeout(CLOSE_TIMEOUT, connection.say_goodbye())
+                    .await
+                    .context("Timed out closing foxglove connection")??;
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One client and its own connection to foxglove-ws
+struct Connection {
+    gateway: FoxgloveGateway,
+    client_sink: ClientSink,
+    server_sink: ServerSink,
+    channels: ChannelTracker,
+    /// Zenoh keys of the channels the client advertised, by client channel id
+    ///
+    /// Messages are put on the session rather than declared publishers, those would be
+    /// lost when zenoh reconnects while the client stays connected.
+    client_channels: HashMap<u32, String>,
+    /// Replies to service calls and parameter requests that wait on zenoh queries
+    pending_replies: JoinSet<Message>,
+    /// Parameters the client wants updates of
+    parameter_subscriptions: HashSet<String>,
+    parameter_updates: broadcast::Receiver<Parameter>,
+    /// The client knows the capabilities, no time is sent before it does
+    server_info_sent: bool,
+    /// The client was told its messages are refused while the emergency stop is engaged
+    estop_reported: bool,
+}
+
+impl Connection {
+    /// Relay a message of the server, false once the connection is closed
+    async fn relay_to_client(&mut self, message: Message) -> anyhow::Result<bool> {
+        let message = match message {
+            // answered by tungstenite on each side
+            Message::Ping(_) | Message::Pong(_) => return Ok(true),
+            Message::Text(text) => {
+                self.channels.observe(&text);
+                let server_info = extend_server_info(
+                    &text,
+                    &self.gateway.capabilities(),
+                    &self.gateway.supported_encodings(),
+                );
+                let Some(server_info) = server_info else {
+                    self.client_sink.send(Message::Text(text)).await?;
+                    return Ok(true);
+                };
+                self.client_sink.send(Message::Text(server_info)).await?;
+                self.server_info_sent = true;
+                // services are advertised right after the server info, like foxglove-ws does
+                // with its channels
+                if let Some(advertise) = self.gateway.advertise_services() {
+                    self.client_sink.send(Message::Text(advertise)).await?;
+                }
+                return Ok(true);
+            }
+            message => message,
+        };
+        let closing = message.is_close();
+        self.client_sink.send(message).await?;
+        Ok(!closing)
+    }
+
+    /// Answer or relay a message of the client, false once the connection is closed
+    async fn handle_client_message(&mut self, message: Message) -> anyhow::Result<bool> {
+        match message {
+            Message::Ping(_) | Message::Pong(_) => {}
+            Message::Text(text) => match serde_json::from_str(&text) {
+                Ok(ClientRequest::Advertise { channels }) => {
+                    for channel in channels {
+                        self.advertise_client_channel(channel).await?;
+                    }
+                }
+                Ok(ClientRequest::Unadvertise { channel_ids }) => {
+                    for channel_id in channel_ids {
+                        self.client_channels.remove(&channel_id);
+                    }
+                }
+                Ok(ClientRequest::GetParameters {
+                    parameter_names,
+                    id,
+                }) if self.gateway.parameter_prefix.is_some() => {
+                    self.get_parameters(parameter_names, id);
+                }
+                Ok(ClientRequest::SetParameters { parameters, id })
+                    if self.gateway.parameter_prefix.is_some() =>
+                {
+                    self.set_parameters(parameters, id).await?;
+                }
+                Ok(ClientRequest::SubscribeParameterUpdates { parameter_names }) => {
+                    self.parameter_subscriptions.extend(parameter_names);
+                }
+                Ok(ClientRequest::UnsubscribeParameterUpdates { parameter_names }) => {
+                    for name in parameter_names {
+                        self.parameter_subscriptions.remove(&name);
+                    }
+                }
+                Ok(ClientRequest::FetchAsset { uri, request_id }) => {
+                    self.fetch_asset(uri, request_id);
+                }
+                // subscriptions are handled by foxglove-ws
+                Ok(_) | Err(_) => {
+                    self.server_sink.send(Message::Text(text)).await?;
+                }
+            },
+            Message::Binary(data) => match ClientBinary::parse(&data) {
+                Some(ClientBinary::MessageData {
+                    channel_id,
+                    payload,
+                }) => self.publish_client_message(channel_id, payload).await?,
+                Some(ClientBinary::ServiceCallRequest {
+                    service_id,
+                    call_id,
+                    encoding,
+                    payload,
+                }) => {
+                    self.call_service(service_id, call_id, encoding, payload)
+                        .await?
+                }
+                None => self.server_sink.send(Message::Binary(data)).await?,
+            },
+            message => {
+                let closing = message.is_close();
+                self.server_sink.send(message).await?;
+                return Ok(!closing);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn advertise_client_channel(&mut self, channel: ClientChannel) -> anyhow::Result<()> {
+        let Some(publish_topic) = self
+            .gateway
+            .client_publish
+            .iter()
+            .find(|publish_topic| publish_topic.topic == channel.topic)
+        else {
+            let message = format!(
+                "Publishing on {} isn't allowed, add it to client_publish",
+                channel.topic
+            );
+            return self.send_status(StatusLevel::Error, &message).await;
+        };
+        if channel.encoding != CLIENT_PUBLISH_ENCODING {
+            let message = format!(
+                "Can't publish {} encoded messages on {}, only {CLIENT_PUBLISH_ENCODING}",
+                channel.encoding, channel.topic
+            );
+            return self.send_status(StatusLevel::Error, &message).await;
+        }
+        let key_expr = publish_topic.key_expr();
+        info!(
+            topic = channel.topic,
+            key_expr, "Foxglove client publishing"
+        );
+        self.client_channels.insert(channel.id, key_expr);
+        Ok(())
+    }
+
+    async fn publish_client_message(
+        &mut self,
+        channel_id: u32,
+        payload: &[u8],
+    ) -> anyhow::Result<()> {
+        let Some(key_expr) = self.client_channels.get(&channel_id).cloned() else {
+            debug!(channel_id, "Message on a channel that wasn't advertised");
+            return Ok(());
+        };
+        if self.gateway.estop.is_engaged() {
+            // panels such as Teleop publish continuously, one status per engagement is enough
+            if !self.estop_reported {
+                self.estop_reported = true;
+                let message = format!("Emergency stop engaged, not publishing on {key_expr}");
+                self.send_status(StatusLevel::Warning, &message).await?;
+            }
+            return Ok(());
+        }
+        self.estop_reported = false;
+        let value =
+            Value::from(payload.to_vec()).encoding(Encoding::Exact(KnownEncoding::TextJson));
+        self.gateway
+            .zenoh_session
+            .put(key_expr, value)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+        Ok(())
+    }
+
+    /// Query the service's key and reply to the client once the queryable answers
+    async fn call_service(
+        &mut self,
+        service_id: u32,
+        call_id: u32,
+        encoding: &str,
+        payload: &[u8],
+    ) -> anyhow::Result<()> {
+        let Some(service) = self.gateway.service(service_id).cloned() else {
+            let failure = service_call_failure(service_id, call_id, "Unknown service");
+            self.client_sink.send(Message::Text(failure)).await?;
+            return Ok(());
+        };
+        let zenoh_session = self.gateway.zenoh_session.clone();
+        let encoding = encoding.to_owned();
+        let request = Value::from(payload.to_vec()).encoding(zenoh_encoding(&encoding));
+        self.pending_replies.spawn(async move {
+            info!(service = service.name, "Foxglove service call");
+            match query_service(&zenoh_session, &service, request).await {
+                Ok(reply) => Message::Binary(service_call_response(
+                    service_id, call_id, &encoding, &reply,
+                )),
+                Err(err) => {
+                    warn!(service = service.name, "Service call failed {err:#}");
+                    Message::Text(service_call_failure(
+                        service_id,
+                        call_id,
+                        &format!("{err:#}"),
+                    ))
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Query the parameters and reply to the client once they are collected
+    fn get_parameters(&mut self, names: Vec<String>, id: Option<String>) {
+        let Some(prefix) = self.gateway.parameter_prefix.clone() else {
+            return;
+        };
+        let zenoh_session = self.gateway.zenoh_session.clone();
+        self.pending_replies.spawn(async move {
+            match query_parameters(&zenoh_session, &prefix, &names).await {
+                Ok(parameters) => Message::Text(parameter_values(&parameters, id.as_deref())),
+                Err(err) => {
+                    warn!("Failed to get parameters {err:#}");
+                    let message = format!("Failed to get parameters: {err:#}");
+                    Message::Text(status(StatusLevel::Error, &message))
+                }
+            }
+        });
+    }
+
+    /// Load the asset and reply to the client once it's read or queried
+    fn fetch_asset(&mut self, uri: String, request_id: u32) {
+        let gateway = self.gateway.clone();
+        self.pending_replies.spawn(async move {
+            let response = match gateway.load_asset(&uri).await {
+                Ok(asset) => {
+                    debug!(uri, bytes = asset.len(), "Foxglove asset fetched");
+                    fetch_asset_response(request_id, Ok(&asset))
+                }
+                Err(err) => {
+                    warn!(uri, "Failed to fetch asset {err:#}");
+                    fetch_asset_response(request_id, Err(&format!("{err:#}")))
+                }
+            };
+            Message::Binary(response)
+        });
+    }
+
+    /// Put the parameters as JSON under the prefix, `null` deletes them
+    async fn set_parameters(
+        &mut self,
+        parameters: Vec<Parameter>,
+        id: Option<String>,
+    ) -> anyhow::Result<()> {
+        let Some(prefix) = self.gateway.parameter_prefix.clone() else {
+            return Ok(());
+        };
+        for parameter in &parameters {
+            info!(name = parameter.name, value = %parameter.value, "Foxglove set parameter");
+            let key_expr = format!("{prefix}/{}", parameter.name);
+            let result = if parameter.value.is_null() {
+                self.gateway
+                    .zenoh_session
+                    .delete(key_expr.as_str())
+                    .res()
+                    .await
+            } else {
+                let value = Value::from(serde_json::to_string(&parameter.value)?)
+                    .encoding(Encoding::Exact(KnownEncoding::TextJson));
+                self.gateway
+                    .zenoh_session
+                    .put(key_expr.as_str(), value)
+                    .res()
+                    .await
+            };
+            if let Err(err) = result {
+                let message = format!("Failed to set parameter {}: {err}", parameter.name);
+                return self.send_status(StatusLevel::Error, &message).await;
+            }
+        }
+        // clients that subscribed hear about the change from the parameter watch
+        if let Some(id) = id {
+            let reply = parameter_values(&parameters, Some(&id));
+            self.client_sink.send(Message::Text(reply)).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_parameter_update(&mut self, parameter: Parameter) -> anyhow::Result<()> {
+        if !self.parameter_subscriptions.contains(&parameter.name) {
+            return Ok(());
+        }
+        let update = parameter_values(&[parameter], None);
+        self.client_sink.send(Message::Text(update)).await?;
+        Ok(())
+    }
+
+    fn sends_time(&self) -> bool {
+        self.server_info_sent && self.gateway.time_source.is_some()
+    }
+
+    async fn send_time(&mut self) -> anyhow::Result<()> {
+        if let Some(timestamp_nanos) = self.gateway.server_time() {
+            let time = server_time(timestamp_nanos);
+            self.client_sink.send(Message::Binary(time)).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_status(&mut self, level: StatusLevel, message: &str) -> anyhow::Result<()> {
+        warn!("Foxglove client request failed: {message}");
+        self.client_sink
+            .send(Message::Text(status(level, message)))
+            .await?;
+        Ok(())
+    }
+
+    /// Unadvertise every channel and close the connection
+    async fn say_goodbye(&mut self) -> anyhow::Result<()> {
+        if let Some(unadvertise) = self.channels.unadvertise_all() {
+            self.client_sink.send(Message::Text(unadvertise)).await?;
+        }
+        self.client_sink
+            .send(Message::Close(Some(CloseFrame {
+                code: CloseCode::Away,
+                reason: "Bridge shutting down".into(),
+            })))
+            .await?;
+        Ok(())
+    }
+}
+
+fn zenoh_encoding(encoding: &str) -> Encoding {
+    match encoding {
+        "json" => Encoding::Exact(KnownEncoding::TextJson),
+        _ => Encoding::Exact(KnownEncoding::AppOctetStream),
+    }
+}
+
+/// Payload of the first reply of the service's queryable
+async fn query_service(
+    zenoh_session: &Session,
+    service: &FoxgloveService,
+    request: Value,
+) -> anyhow::Result<Vec<u8>> {
+    let replies = zenoh_session
+        .get(service.key_expr.as_str())
+        .with_value(request)
+        .timeout(service.timeout())
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let reply = replies
+        .recv_async()
+        .await
+        .map_err(|_| anyhow::anyhow!("No reply from {}", service.key_expr))?;
+    match reply.sample {
+        Ok(sample) => Ok(sample.value.payload.contiguous().into_owned()),
+        Err(error) => bail!(
+            "{} replied with an error: {}",
+            service.key_expr,
+            String::from_utf8_lossy(&error.payload.contiguous())
+        ),
+    }
+}
+
+/// Nanoseconds since the epoch in a robot clock payload
+///
+/// Accepts JSON nanoseconds, an RFC 3339 string, or an object with an RFC 3339 `time` like
+/// the bridge's own messages.
+pub fn robot_clock_nanos(payload: &[u8]) -> Option<u64> {
+    let stamp = match serde_json::from_slice(payload).ok()? {
+        serde_json::Value::Object(mut message) => message.remove("time")?,
+        stamp => stamp,
+    };
+    match stamp {
+        serde_json::Value::Number(nanos) => nanos.as_u64(),
+        serde_json::Value::String(time) => {
+            let time: DateTime<Utc> = time.parse().ok()?;
+            u64::try_from(time.timestamp())
+                .ok()?
+                .checked_mul(1_000_000_000)?
+                .checked_add(time.timestamp_subsec_nanos().into())
+        }
+        _ => None,
+    }
+}
+
+/// Path of a `package://` URI relative to the asset directory
+///
+/// `None` for other URIs and for paths that would leave the asset directory.
+pub fn asset_path(uri: &str) -> Option<&str> {
+    let path = uri.strip_prefix(ASSET_URI_SCHEME)?;
+    let inside = Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    (inside && !path.is_empty()).then_some(path)
+}
+
+/// Payload of the first reply of the asset queryable
+async fn query_asset(zenoh_session: &Session, key_expr: &str) -> anyhow::Result<Vec<u8>> {
+    let replies = zenoh_session
+        .get(key_expr)
+        .timeout(ASSET_QUERY_TIMEOUT)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let reply = replies
+        .recv_async()
+        .await
+        .map_err(|_| anyhow::anyhow!("No reply from {key_expr}"))?;
+    match reply.sample {
+        Ok(sample) => Ok(sample.value.payload.contiguous().into_owned()),
+        Err(error) => bail!(
+            "{key_expr} replied with an error: {}",
+            String::from_utf8_lossy(&error.payload.contiguous())
+        ),
+    }
+}
+
+/// Parameter named after the key below `prefix`, with the JSON payload as its value
+///
+/// Payloads that aren't JSON are shown as strings.
+fn parameter_from_sample(prefix: &str, sample: Sample) -> Option<Parameter> {
+    let name = sample
+        .key_expr
+        .as_str()
+        .strip_prefix(prefix)?
+        .strip_prefix('/')?
+        .to_owned();
+    if sample.kind == SampleKind::Delete {
+        return Some(Parameter {
+            name,
+            value: serde_json::Value::Null,
+        });
+    }
+    let payload = sample.value.payload.contiguous();
+    let value = serde_json::from_slice(&payload)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&payload).into_owned().into());
+    Some(Parameter { name, value })
+}
+
+/// Current values of the named parameters, all parameters under `prefix` if there are none
+async fn query_parameters(
+    zenoh_session: &Session,
+    prefix: &str,
+    names: &[String],
+) -> anyhow::Result<Vec<Parameter>> {
+    let selectors = if names.is_empty() {
+        vec![format!("{prefix}/**")]
+    } else {
+        names
+            .iter()
+            .map(|name| format!("{prefix}/{name}"))
+            .collect()
+    };
+    let mut parameters = vec![];
+    for selector in selectors {
+        let replies = zenoh_session
+            .get(selector.as_str())
+            .timeout(PARAMETER_QUERY_TIMEOUT)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+        while let Ok(reply) = replies.recv_async().await {
+            let Ok(sample) = reply.sample else {
+                continue;
+            };
+            if let Some(parameter) = parameter_from_sample(prefix, sample) {
+                parameters.push(parameter);
+            }
+        }
+    }
+    Ok(parameters)
+}
@@ -0,0 +1,141 @@
+//! Managing Foxglove layouts through the Foxglove API
+//!
+//! Layouts are exported from Studio as JSON files, pushed to the organisation with
+//! `layouts push` and the returned id is set as `foxglove_layout_id` in the robot profile.
+//! Requests are authenticated with an API key that has the layout capabilities.
+
+use std::path::Path;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_FOXGLOVE_API_URL: &str = "https://api.foxglove.dev";
+
+/// Shared with the whole organisation so every operator station can open it
+const LAYOUT_PERMISSION: &str = "ORG_WRITE";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Layout {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Only included when a single layout is requested
+    #[serde(default)]
+    pub data: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct NewLayout<'a> {
+    name: &'a str,
+    data: &'a serde_json::Value,
+    permission: &'a str,
+}
+
+#[derive(Serialize)]
+struct LayoutUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    data: &'a serde_json::Value,
+}
+
+pub struct LayoutClient {
+    http: reqwest::Client,
+    api_url: String,
+    api_key: String,
+}
+
+impl LayoutClient {
+    pub fn new(api_url: &str, api_key: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_url: api_url.trim_end_matches('/').to_owned(),
+            api_key: api_key.to_owned(),
+        }
+    }
+
+    /// Every layout visible to the API key, without their data
+    pub async fn list(&self) -> anyhow::Result<Vec<Layout>> {
+        self.send(self.http.get(self.url("")))
+            .await
+            .context("Failed to list layouts")
+    }
+
+    pub async fn get(&self, id: &str) -> anyhow::Result<Layout> {
+        self.send(
+            self.http
+                .get(self.url(id))
+                .query(&[("includeData", "true")]),
+        )
+        .await
+        .with_context(|| format!("Failed to get layout {id}"))
+    }
+
+    pub async fn create(&self, name: &str, data: &serde_json::Value) -> anyhow::Result<Layout> {
+        let layout = NewLayout {
+            name,
+            data,
+            permission: LAYOUT_PERMISSION,
+        };
+        self.send(self.http.post(self.url("")).json(&layout))
+            .await
+            .with_context(|| format!("Failed to create layout {name:?}"))
+    }
+
+    /// Replace the data of an existing layout, keeping its id so profiles don't change
+    pub async fn update(
+        &self,
+        id: &str,
+        name: Option<&str>,
+        data: &serde_json::Value,
+    ) -> anyhow::Result<Layout> {
+        let update = LayoutUpdate { name, data };
+        self.send(self.http.patch(self.url(id)).json(&update))
+            .await
+            .with_context(|| format!("Failed to update layout {id}"))
+    }
+
+    fn url(&self, id: &str) -> String {
+        if id.is_empty() {
+            format!("{}/v1/layouts", self.api_url)
+        } else {
+            format!("{}/v1/layouts/{id}", self.api_url)
+        }
+    }
+
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> anyhow::Result<T> {
+        let response = request.bearer_auth(&self.api_key).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("Foxglove API returned {status}: {body}");
+        }
+        Ok(response.json().await?)
+    }
+}
+
+/// Read a layout exported from Studio
+pub fn read_layout_file(path: &Path) -> anyhow::Result<serde_json::Value> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_layout(&data).with_context(|| format!("Invalid layout {}", path.display()))
+}
+
+/// Layout data, also accepts a layout as returned by the API and unwraps its `data`
+pub fn parse_layout(data: &str) -> anyhow::Result<serde_json::Value> {
+    let mut layout: serde_json::Value = serde_json::from_str(data)?;
+    if layout.get("id").is_some() {
+        if let Some(data) = layout.get_mut("data") {
+            layout = data.take();
+        }
+    }
+    if !layout.is_object() {
+        anyhow::bail!("Layout has to be a JSON object");
+    }
+    Ok(layout)
+}
@@ -0,0 +1,299 @@
+//! Messages of the Foxglove WebSocket protocol that the gateway reads or sends itself
+//!
+//! Everything else is relayed between clients and foxglove-ws untouched. See
+//! <https://github.com/foxglove/ws-protocol/blob/main/docs/spec.md>.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Websocket subprotocol that Foxglove clients ask for
+pub const SUBPROTOCOL: &str = "foxglove.websocket.v1";
+
+/// JSON messages sent by the server that the gateway follows
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum ServerMessage {
+    Advertise {
+        channels: Vec<AdvertisedChannel>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Unadvertise {
+        channel_ids: Vec<u64>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdvertisedChannel {
+    id: u64,
+}
+
+/// Channels the server advertised to one client
+#[derive(Debug, Default)]
+pub struct ChannelTracker {
+    channel_ids: BTreeSet<u64>,
+}
+
+impl ChannelTracker {
+    /// Follow the `advertise` and `unadvertise` messages of the server
+    pub fn observe(&mut self, server_message: &str) {
+        match serde_json::from_str(server_message) {
+            Ok(ServerMessage::Advertise { channels }) => self
+                .channel_ids
+                .extend(channels.iter().map(|channel| channel.id)),
+            Ok(ServerMessage::Unadvertise { channel_ids }) => {
+                for channel_id in channel_ids {
+                    self.channel_ids.remove(&channel_id);
+                }
+            }
+            Ok(ServerMessage::Other) | Err(_) => {}
+        }
+    }
+
+    pub fn channel_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.channel_ids.iter().copied()
+    }
+
+    /// `unadvertise` of every advertised channel, `None` if there are none
+    pub fn unadvertise_all(&self) -> Option<String> {
+        if self.channel_ids.is_empty() {
+            return None;
+        }
+        let channel_ids: Vec<u64> = self.channel_ids().collect();
+        Some(json!({ "op": "unadvertise", "channelIds": channel_ids }).to_string())
+    }
+}
+
+/// Add the gateway's capabilities to a `serverInfo`, `None` for any other message
+pub fn extend_server_info(
+    server_message: &str,
+    capabilities: &[&str],
+    supported_encodings: &[&str],
+) -> Option<String> {
+    let mut message: Value = serde_json::from_str(server_message).ok()?;
+    if message.get("op")?.as_str()? != "serverInfo" {
+        return None;
+    }
+    let info = message.as_object_mut()?;
+    extend_list(info, "capabilities", capabilities);
+    if !supported_encodings.is_empty() {
+        extend_list(info, "supportedEncodings", supported_encodings);
+    }
+    Some(message.to_string())
+}
+
+fn extend_list(info: &mut serde_json::Map<String, Value>, field: &str, items: &[&str]) {
+    let list = info.entry(field).or_insert_with(|| Value::Array(vec![]));
+    if let Value::Array(list) = list {
+        for item in items {
+            if !list.iter().any(|existing| existing == item) {
+                list.push(Value::from(*item));
+            }
+        }
+    }
+}
+
+/// JSON requests of a client that the gateway answers itself
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum ClientRequest {
+    Advertise {
+        channels: Vec<ClientChannel>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Unadvertise {
+        channel_ids: Vec<u32>,
+    },
+    /// All parameters if `parameter_names` is empty
+    #[serde(rename_all = "camelCase")]
+    GetParameters {
+        parameter_names: Vec<String>,
+        id: Option<String>,
+    },
+    SetParameters {
+        parameters: Vec<Parameter>,
+        id: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    SubscribeParameterUpdates {
+        parameter_names: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    UnsubscribeParameterUpdates {
+        parameter_names: Vec<String>,
+    },
+    /// Files such as the meshes of a URDF, usually with a `package://` URI
+    #[serde(rename_all = "camelCase")]
+    FetchAsset {
+        uri: String,
+        request_id: u32,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Named parameter value, `null` unsets a parameter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    #[serde(default)]
+    pub value: Value,
+}
+
+/// `parameterValues` answering a request with `id`, or an update without one
+pub fn parameter_values(parameters: &[Parameter], id: Option<&str>) -> String {
+    let mut message = json!({ "op": "parameterValues", "parameters": parameters });
+    if let Some(id) = id {
+        message["id"] = Value::from(id);
+    }
+    message.to_string()
+}
+
+/// Channel a client advertises to publish on
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientChannel {
+    pub id: u32,
+    pub topic: String,
+    pub encoding: String,
+    pub schema_name: String,
+}
+
+/// Binary messages of a client that the gateway answers itself
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClientBinary<'a> {
+    MessageData {
+        channel_id: u32,
+        payload: &'a [u8],
+    },
+    ServiceCallRequest {
+        service_id: u32,
+        call_id: u32,
+        encoding: &'a str,
+        payload: &'a [u8],
+    },
+}
+
+impl<'a> ClientBinary<'a> {
+    const MESSAGE_DATA: u8 = 0x01;
+    const SERVICE_CALL_REQUEST: u8 = 0x02;
+
+    /// `None` for unknown opcodes and truncated messages
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let (&opcode, rest) = data.split_first()?;
+        match opcode {
+            Self::MESSAGE_DATA => {
+                let (channel_id, payload) = split_u32(rest)?;
+                Some(Self::MessageData {
+                    channel_id,
+                    payload,
+                })
+            }
+            Self::SERVICE_CALL_REQUEST => {
+                let (service_id, rest) = split_u32(rest)?;
+                let (call_id, rest) = split_u32(rest)?;
+                let (encoding_length, rest) = split_u32(rest)?;
+                if rest.len() < encoding_length as usize {
+                    return None;
+                }
+                let (encoding, payload) = rest.split_at(encoding_length as usize);
+                Some(Self::ServiceCallRequest {
+                    service_id,
+                    call_id,
+                    encoding: std::str::from_utf8(encoding).ok()?,
+                    payload,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Little endian `u32` at the start of `data` and the bytes after it
+fn split_u32(data: &[u8]) -> Option<(u32, &[u8])> {
+    let (value, rest) = data.split_first_chunk::<4>()?;
+    Some((u32::from_le_bytes(*value), rest))
+}
+
+/// Level of a `status` message, shown by Foxglove in its problems panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusLevel {
+    Info = 0,
+    Warning = 1,
+    Error = 2,
+}
+
+pub fn status(level: StatusLevel, message: &str) -> String {
+    json!({ "op": "status", "level": level as u8, "message": message }).to_string()
+}
+
+/// Service offered to clients in `advertiseServices`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdvertisedService<'a> {
+    pub id: u32,
+    pub name: &'a str,
+    #[serde(rename = "type")]
+    pub type_name: &'a str,
+    /// Services take free form requests, Foxglove only shows the schema
+    pub request_schema: &'a str,
+    pub response_schema: &'a str,
+}
+
+pub fn advertise_services(services: &[AdvertisedService]) -> String {
+    json!({ "op": "advertiseServices", "services": services }).to_string()
+}
+
+/// Binary response to a service call
+pub fn service_call_response(
+    service_id: u32,
+    call_id: u32,
+    encoding: &str,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut response = Vec::with_capacity(13 + encoding.len() + payload.len());
+    response.push(0x03);
+    response.extend_from_slice(&service_id.to_le_bytes());
+    response.extend_from_slice(&call_id.to_le_bytes());
+    response.extend_from_slice(&(encoding.len() as u32).to_le_bytes());
+    response.extend_from_slice(encoding.as_bytes());
+    response.extend_from_slice(payload);
+    response
+}
+
+pub fn service_call_failure(service_id: u32, call_id: u32, message: &str) -> String {
+    json!({
+        "op": "serviceCallFailure",
+        "serviceId": service_id,
+        "callId": call_id,
+        "message": message,
+    })
+    .to_string()
+}
+
+/// Binary reply to `fetchAsset` with the asset or why it couldn't be fetched
+pub fn fetch_asset_response(request_id: u32, asset: Result<&[u8], &str>) -> Vec<u8> {
+    let (status, error, data): (u8, &str, &[u8]) = match asset {
+        Ok(data) => (0, "", data),
+        Err(error) => (1, error, &[]),
+    };
+    let mut response = Vec::with_capacity(10 + error.len() + data.len());
+    response.push(0x04);
+    response.extend_from_slice(&request_id.to_le_bytes());
+    response.push(status);
+    response.extend_from_slice(&(error.len() as u32).to_le_bytes());
+    response.extend_from_slice(error.as_bytes());
+    response.extend_from_slice(data);
+    response
+}
+
+/// Binary `time` message with the server's current time
+pub fn server_time(timestamp_nanos: u64) -> Vec<u8> {
+    let mut message = Vec::with_capacity(9);
+    message.push(0x02);
+    message.extend_from_slice(&timestamp_nanos.to_le_bytes());
+    message
+}
@@ -1,55 +1,355 @@
-use anyhow::Context;
+use anyhow::{bail, Context};
 use foxglove_ws::{Channel, FoxgloveWebSocket};
-use prost_reflect::MessageDescriptor;
+use prost::Message;
+use prost_reflect::{DescriptorPool, MessageDescriptor};
 use serde::Deserialize;
 use std::{
-    collections::HashMap,
-    net::SocketAddr,
-    sync::{Arc, OnceLock},
-    time::{SystemTime, UNIX_EPOCH},
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, SystemTime},
 };
-use tracing::{debug, info};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 use zenoh::prelude::r#async::*;
 
-use crate::{error::ErrorWrapper, DESCRIPTOR_POOL};
+use crate::{
+    annotations::Annotation,
+    backoff::Backoff,
+    clock::SharedClock,
+    decimation::{DecimationConfig, Decimator},
+    error::{BridgeError, UnknownProtoType, ZenohError},
+    foxglove,
+    foxglove_clients::{start_client_watch, wait_for_clients, ClientWatch},
+    foxglove_gateway::{
+        start_foxglove_gateway, ClientPublishTopic, FoxgloveGateway, FoxgloveService,
+    },
+    gamepad::EstopLatch,
+    health::{Heartbeat, HEARTBEAT_INTERVAL},
+    json_schemas::JsonSchemas,
+    log_throttle::{LogThrottle, DEFAULT_THROTTLE_WINDOW},
+    proto_descriptors::descriptor_pool,
+    schema_inference::infer_schema,
+    schema_registry::{SchemaRegistry, TopicSchema, SCHEMA_QUERY_SUFFIX},
+    stats::{StatsRegistry, TopicStats},
+    supervisor::Supervisor,
+    tailscale::TailscaleStatus,
+    tls_proxy::free_loopback_address,
+    transform::{output_descriptor, transform_json, transform_proto, PayloadTransform},
+    DESCRIPTOR_POOL,
+};
+
+const MAX_PROTO_TYPE_SUGGESTIONS: usize = 3;
+const MAX_PROTO_TYPE_EDIT_DISTANCE: usize = 3;
+
+/// Look up all configured proto types so that every typo is reported in one pass
+fn resolve_proto_types(
+    subscriptions: &[ProtobufSubscription],
+) -> Result<Vec<MessageDescriptor>, BridgeError> {
+    let descriptor_pool = descriptor_pool();
+    let mut descriptors = vec![];
+    let mut unknown = vec![];
+    for subscription in subscriptions {
+        match descriptor_pool.get_message_by_name(&subscription.proto_type) {
+            Some(descriptor) => descriptors.push(descriptor),
+            None => unknown.push(UnknownProtoType {
+                topic: subscription.topic.clone(),
+                proto_type: subscription.proto_type.clone(),
+                suggestions: close_proto_type_matches(&descriptor_pool, &subscription.proto_type),
+            }),
+        }
+    }
+
+    if !unknown.is_empty() {
+        let packages = descriptor_pool
+            .packages()
+            .map(|package| package.name().to_owned())
+            .collect();
+        return Err(BridgeError::UnknownProtoTypes { unknown, packages });
+    }
+    Ok(descriptors)
+}
+
+/// Known message types with a similar full name or a similar name in another package
+pub fn close_proto_type_matches(descriptor_pool: &DescriptorPool, proto_type: &str) -> Vec<String> {
+    let proto_type = proto_type.to_lowercase();
+    let short_name = proto_type.rsplit('.').next().unwrap_or_default();
+    let mut matches: Vec<(usize, String)> = descriptor_pool
+        .all_messages()
+        .filter_map(|message| {
+            let distance = edit_distance(&proto_type, &message.full_name().to_lowercase())
+                .min(edit_distance(short_name, &message.name().to_lowercase()));
+            (distance <= MAX_PROTO_TYPE_EDIT_DISTANCE)
+                .then(|| (distance, message.full_name().to_owned()))
+        })
+        .collect();
+    matches.sort();
+    matches
+        .into_iter()
+        .take(MAX_PROTO_TYPE_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Levenshtein distance
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The foxglove server doesn't report bind errors so check that the address is free up front
+pub async fn check_bind(host: SocketAddr) -> Result<(), BridgeError> {
+    let listener =
+        tokio::net::TcpListener::bind(host)
+            .await
+            .map_err(|error| BridgeError::Bind {
+                address: host,
+                error,
+            })?;
+    drop(listener);
+    Ok(())
+}
+
+/// Studio link template, `{user}` is replaced with the Foxglove user
+pub const DEFAULT_STUDIO_URL: &str = "https://app.foxglove.dev/{user}/view";
+
+/// Link that opens Foxglove Studio, or a self-hosted Foxglove or Lichtblick, connected to the bridge
+///
+/// ```text
+/// https://app.foxglove.dev/david-weis/view?ds=foxglove-websocket&ds.url=ws://127.0.0.1:8765/&layoutId=ea22e72c-f654-4743-925a-7143a510d390
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoxgloveUrlBuilder {
+    studio_url: String,
+    user: String,
+    ds_url: String,
+    layout_id: Option<String>,
+}
+
+impl FoxgloveUrlBuilder {
+    /// `scheme` is `ws` or `wss` when the server is behind the TLS proxy
+    pub fn new(scheme: &str, host: &str, port: u16) -> Self {
+        Self {
+            studio_url: DEFAULT_STUDIO_URL.to_owned(),
+            user: String::new(),
+            ds_url: format!("{scheme}://{host}:{port}/"),
+            layout_id: None,
+        }
+    }
+
+    pub fn studio_url(mut self, studio_url: &str) -> Self {
+        studio_url.clone_into(&mut self.studio_url);
+        self
+    }
+
+    pub fn user(mut self, user: &str) -> Self {
+        user.clone_into(&mut self.user);
+        self
+    }
+
+    pub fn layout_id(mut self, layout_id: &str) -> Self {
+        self.layout_id = Some(layout_id.to_owned());
+        self
+    }
+
+    pub fn build(&self) -> String {
+        let studio_url = self.studio_url.replace("{user}", &self.user);
+        let separator = if studio_url.contains('?') { '&' } else { '?' };
+        let mut url = format!(
+            "{studio_url}{separator}ds=foxglove-websocket&ds.url={}",
+            self.ds_url
+        );
+        if let Some(layout_id) = &self.layout_id {
+            url.push_str(&format!("&layoutId={layout_id}"));
+        }
+        url
+    }
+}
+
+pub const DEFAULT_FOXGLOVE_PORT: u16 = 8765;
+
+/// Where the Foxglove websocket is bound
+///
+/// Parsed from a socket address or `tailscale` with an optional `:port`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum FoxgloveHost {
+    Address(SocketAddr),
+    /// Our tailscale IPv4 address so other machines on the tailnet can connect
+    Tailscale {
+        port: u16,
+    },
+}
+
+impl FoxgloveHost {
+    /// Bind address and the host name used in the Foxglove link
+    ///
+    /// Tailscale links use the MagicDNS name when it's known.
+    pub fn resolve(
+        &self,
+        tailscale_status: &TailscaleStatus,
+    ) -> anyhow::Result<(SocketAddr, String)> {
+        match self {
+            FoxgloveHost::Address(address) => Ok((*address, address.ip().to_string())),
+            FoxgloveHost::Tailscale { port } => {
+                let address = tailscale_status
+                    .local_ipv4()
+                    .context("No tailscale IPv4 address, is tailscale up?")?;
+                let url_host = tailscale_status
+                    .magic_dns_name()
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| address.to_string());
+                Ok((SocketAddr::new(IpAddr::V4(address), *port), url_host))
+            }
+        }
+    }
+}
+
+impl FromStr for FoxgloveHost {
+    type Err = anyhow::Error;
 
-pub fn create_foxglove_url(user: &str, url: &str, port: &str, layout_id: &str) -> String {
-    // https://app.foxglove.dev/david-weis/view?ds=foxglove-websocket&ds.url=ws://127.0.0.1:8765/&layoutId=ea22e72c-f654-4743-925a-7143a510d390
-    format!("https://app.foxglove.dev/{user}/view?ds=foxglove-websocket&ds.url=ws://{url}:{port}/&layoutId={layout_id}")
+    fn from_str(host: &str) -> Result<Self, Self::Err> {
+        if host == "tailscale" {
+            return Ok(FoxgloveHost::Tailscale {
+                port: DEFAULT_FOXGLOVE_PORT,
+            });
+        }
+        if let Some(port) = host.strip_prefix("tailscale:") {
+            let port = port.parse().context("Invalid tailscale port")?;
+            return Ok(FoxgloveHost::Tailscale { port });
+        }
+        let address = host
+            .parse()
+            .with_context(|| format!("Expected a socket address or tailscale, got {host:?}"))?;
+        Ok(FoxgloveHost::Address(address))
+    }
 }
 
+impl TryFrom<String> for FoxgloveHost {
+    type Error = anyhow::Error;
+
+    fn try_from(host: String) -> Result<Self, Self::Error> {
+        host.parse()
+    }
+}
+
+/// `json_schemas` are the schemas that `json_schema_name` of JSON subscriptions refers to,
+/// messages published from Foxglove panels are refused while `estop` is engaged
+#[allow(clippy::too_many_arguments)]
 pub async fn start_foxglove_bridge(
     config: FoxgloveServerConfiguration,
+    json_schemas: &JsonSchemas,
     host: SocketAddr,
     zenoh_session: Arc<Session>,
-) -> anyhow::Result<()> {
-    // start foxglove server
+    supervisor: &Supervisor,
+    stats: &StatsRegistry,
+    schemas: &SchemaRegistry,
+    clock: SharedClock,
+    estop: EstopLatch,
+) -> Result<(), BridgeError> {
+    let message_descriptors = resolve_proto_types(&config.protobuf_subscriptions)?;
+
+    // fail fast instead of running without a working websocket
+    check_bind(host).await?;
+    info!("Starting foxglove server on {host}");
+    let client_watch = config
+        .has_on_demand_subscriptions()
+        .then(|| start_client_watch(supervisor, host.port()));
+
+    // clients connect to the gateway, foxglove-ws is only reachable through it
+    let backend = free_loopback_address().map_err(BridgeError::Server)?;
+    let gateway = FoxgloveGateway::new(zenoh_session.clone(), &config, clock.clone(), estop);
+    start_foxglove_gateway(supervisor, host, backend, gateway);
     let server = foxglove_ws::FoxgloveWebSocket::new("steam-deck");
-    tokio::spawn({
+    // clients stay connected while zenoh reconnects
+    supervisor.spawn_persistent("foxglove_server", {
         let server = server.clone();
-        async move { server.serve(host).await }
+        let heartbeat = supervisor.health().heartbeat("foxglove_server");
+        move |shutdown| {
+            let server = server.clone();
+            let heartbeat = heartbeat.clone();
+            async move {
+                // the address can be taken while the server was down
+                check_bind(backend).await?;
+                let serve = server.serve(backend);
+                tokio::pin!(serve);
+                let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+                loop {
+                    // dropping the server future stops accepting new clients
+                    tokio::select! {
+                        _ = &mut serve => bail!("Foxglove server on {backend} stopped"),
+                        _ = heartbeat_interval.tick() => heartbeat.beat(),
+                        _ = shutdown.cancelled() => break,
+                    }
+                }
+                Ok(())
+            }
+        }
     });
 
-    for proto_subscription in &config.protobuf_subscriptions {
-        let message_descriptor = DESCRIPTOR_POOL
-            .get_message_by_name(&proto_subscription.proto_type)
-            .context("Failed to find protobuf message descriptor by name")?;
-
+    for (proto_subscription, message_descriptor) in config
+        .protobuf_subscriptions
+        .iter()
+        .zip(message_descriptors)
+    {
         start_proto_subscriber_from_descriptor(
             &proto_subscription.topic,
+            proto_subscription.queue_size,
             zenoh_session.clone(),
             &server,
             &message_descriptor,
+            &proto_subscription.transforms,
+            proto_subscription.latched.unwrap_or(false),
+            proto_subscription.decimation.as_ref(),
+            client_watch
+                .clone()
+                .filter(|_| proto_subscription.on_demand),
+            supervisor,
+            stats,
+            schemas,
+            clock.clone(),
         )
         .await?;
     }
 
     for json_subscription in &config.json_subscriptions {
         info!(?json_subscription, "Starting json subscription");
+        if let Some(sample_count) = json_subscription.infer_schema {
+            start_inferred_json_subscriber(
+                &json_subscription.topic,
+                sample_count,
+                json_subscription.queue_size,
+                zenoh_session.clone(),
+                &server,
+                &json_subscription.type_name,
+                &json_subscription.transforms,
+                json_subscription.latched.unwrap_or(false),
+                supervisor,
+                stats,
+                schemas,
+                clock.clone(),
+            );
+            continue;
+        }
         let json_schema = if let Some(json_schema_name) = &json_subscription.json_schema_name {
-            json_schema_table()
-                .get(json_schema_name)
-                .context("Failed to load json schema")?
+            json_schemas.get(json_schema_name).ok_or_else(|| {
+                BridgeError::invalid_subscription(
+                    &json_subscription.topic,
+                    format!("unknown json schema {json_schema_name:?}"),
+                )
+            })?
         } else {
             GENERIC_JSON_SCHEMA
         };
@@ -58,63 +358,373 @@ pub async fn start_foxglove_bridge(
 
         start_json_subscriber(
             &json_subscription.topic,
+            json_subscription.queue_size,
             zenoh_session.clone(),
             &server,
             &json_subscription.type_name,
             json_schema,
+            &json_subscription.transforms,
             latched,
+            supervisor,
+            stats,
+            schemas,
+            clock.clone(),
         )
         .await?;
     }
 
+    for log_subscription in &config.log_subscriptions {
+        let log_descriptor = DESCRIPTOR_POOL
+            .get_message_by_name(LOG_PROTO_TYPE)
+            .ok_or_else(|| {
+                BridgeError::channel(&log_subscription.topic, "missing foxglove.Log descriptor")
+            })?;
+        info!(topic = log_subscription.topic, "Starting log subscriber");
+        let foxglove_channel = create_publisher_for_protobuf_descriptor(
+            &log_descriptor,
+            &server,
+            &log_subscription.topic,
+            false,
+        )
+        .await?;
+        spawn_forwarder(
+            supervisor,
+            &log_subscription.topic,
+            log_subscription.queue_size,
+            zenoh_session.clone(),
+            foxglove_channel,
+            false,
+            Arc::new(log_payload),
+            None,
+            None,
+            stats.topic(&log_subscription.topic),
+            clock.clone(),
+        );
+    }
+
+    for image_subscription in &config.compressed_image_subscriptions {
+        info!(?image_subscription, "Starting compressed image subscriber");
+        let descriptor = DESCRIPTOR_POOL
+            .get_message_by_name(image_subscription.format.proto_type())
+            .ok_or_else(|| {
+                BridgeError::channel(
+                    &image_subscription.topic,
+                    format!(
+                        "missing {} descriptor",
+                        image_subscription.format.proto_type()
+                    ),
+                )
+            })?;
+        let foxglove_channel = create_publisher_for_protobuf_descriptor(
+            &descriptor,
+            &server,
+            &image_subscription.topic,
+            false,
+        )
+        .await?;
+        let format = image_subscription.format;
+        let frame_id = image_subscription.frame_id.clone();
+        spawn_forwarder(
+            supervisor,
+            &image_subscription.topic,
+            image_subscription.queue_size,
+            zenoh_session.clone(),
+            foxglove_channel,
+            false,
+            Arc::new(move |sample| compressed_image_payload(sample, format, &frame_id)),
+            None,
+            client_watch
+                .clone()
+                .filter(|_| image_subscription.on_demand),
+            stats.topic(&image_subscription.topic),
+            clock.clone(),
+        );
+    }
+
+    if let Some(key_expr) = &config.auto_discover {
+        start_topic_discovery(
+            key_expr,
+            config.topics(),
+            zenoh_session,
+            &server,
+            supervisor,
+            stats,
+            schemas,
+            clock,
+        );
+    }
+
     Ok(())
 }
 
-async fn start_proto_subscriber_from_descriptor(
-    topic: &str,
+const SCHEMA_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Schema a robot reports on `{topic}/__schema__`
+#[derive(Debug)]
+pub enum DiscoveredSchema {
+    /// JSON schema of a JSON topic
+    Json { type_name: String, schema: String },
+    /// Full name of a protobuf message type in the descriptor pool, see [`descriptor_pool`]
+    Protobuf(MessageDescriptor),
+}
+
+impl DiscoveredSchema {
+    /// A JSON object is a JSON schema, anything else is looked up as a protobuf type name
+    pub fn parse(topic: &str, reply: &str) -> Option<Self> {
+        let reply = reply.trim();
+        if reply.starts_with('{') {
+            let schema: serde_json::Value = serde_json::from_str(reply).ok()?;
+            let type_name = schema
+                .get("title")
+                .and_then(|title| title.as_str())
+                .unwrap_or_else(|| topic.rsplit('/').next().unwrap_or(topic))
+                .to_owned();
+            Some(Self::Json {
+                type_name,
+                schema: reply.to_owned(),
+            })
+        } else {
+            descriptor_pool()
+                .get_message_by_name(reply)
+                .map(Self::Protobuf)
+        }
+    }
+}
+
+/// Create foxglove channels for topics under `key_expr` as they show up
+///
+/// Topics without a schema queryable are bridged as generic JSON if their samples are JSON.
+#[allow(clippy::too_many_arguments)]
+fn start_topic_discovery(
+    key_expr: &str,
+    configured_topics: Vec<String>,
     zenoh_session: Arc<Session>,
     foxglove_server: &FoxgloveWebSocket,
-    protobuf_descriptor: &MessageDescriptor,
-) -> anyhow::Result<()> {
-    info!(topic, "Starting proto subscriber");
-    let zenoh_subscriber = zenoh_session
-        .declare_subscriber(topic)
-        .res()
-        .await
-        .map_err(ErrorWrapper::ZenohError)?;
+    supervisor: &Supervisor,
+    stats: &StatsRegistry,
+    schemas: &SchemaRegistry,
+    clock: SharedClock,
+) {
+    info!(key_expr, "Starting topic discovery");
+    // kept across restarts so that topics aren't bridged twice
+    let known_topics = Arc::new(Mutex::new(HashSet::<String>::from_iter(configured_topics)));
+    let discovery = TopicDiscovery {
+        key_expr: key_expr.to_owned(),
+        known_topics,
+        zenoh_session,
+        foxglove_server: foxglove_server.clone(),
+        supervisor: supervisor.clone(),
+        stats: stats.clone(),
+        schemas: schemas.clone(),
+        heartbeat: supervisor.health().heartbeat("topic_discovery"),
+        clock,
+    };
+    supervisor.spawn("topic_discovery", move |shutdown| {
+        discovery.clone().run(shutdown)
+    });
+}
+
+#[derive(Clone)]
+struct TopicDiscovery {
+    key_expr: String,
+    known_topics: Arc<Mutex<HashSet<String>>>,
+    zenoh_session: Arc<Session>,
+    foxglove_server: FoxgloveWebSocket,
+    supervisor: Supervisor,
+    stats: StatsRegistry,
+    schemas: SchemaRegistry,
+    heartbeat: Heartbeat,
+    clock: SharedClock,
+}
 
-    let foxglove_channel =
-        create_publisher_for_protobuf_descriptor(protobuf_descriptor, foxglove_server, topic)
-            .await?;
+impl TopicDiscovery {
+    async fn run(self, shutdown: CancellationToken) -> anyhow::Result<()> {
+        let subscriber = self
+            .zenoh_session
+            .declare_subscriber(&self.key_expr)
+            .with(flume::bounded(DEFAULT_QUEUE_SIZE))
+            .res()
+            .await
+            .map_err(ZenohError)?;
 
-    tokio::spawn({
-        let topic = topic.to_owned();
-        async move {
-            let mut message_counter = 0;
-            loop {
-                let res: anyhow::Result<()> = async {
-                    let sample = zenoh_subscriber.recv_async().await?;
-                    message_counter += 1;
-                    let now = SystemTime::now();
-                    let time_nanos = system_time_to_nanos(&now);
-                    let payload: Vec<u8> = sample.value.try_into()?;
-                    foxglove_channel.send(time_nanos, &payload).await?;
-
-                    if message_counter % 20 == 0 {
-                        debug!(
-                            topic,
-                            message_counter, "{} sent {} messages", topic, message_counter
-                        );
-                    }
-                    Ok(())
+        let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        // schema queries take a while, topics are bridged concurrently and only become known
+        // once bridged so that failed topics are tried again with their next sample
+        let mut bridging = JoinSet::new();
+        let mut pending_topics = HashSet::new();
+        loop {
+            let sample = tokio::select! {
+                sample = subscriber.recv_async() => sample.context("Zenoh subscriber closed")?,
+                Some(topic) = bridging.join_next(), if !bridging.is_empty() => {
+                    pending_topics.remove(&topic?);
+                    continue;
                 }
-                .await;
-                if let Err(err) = res {
-                    tracing::error!(topic, "Error receiving message: {}", err);
+                _ = heartbeat_interval.tick() => {
+                    self.heartbeat.beat();
+                    continue;
                 }
+                _ = shutdown.cancelled() => break,
+            };
+            let topic = sample.key_expr.as_str().to_owned();
+            if topic.ends_with(SCHEMA_QUERY_SUFFIX)
+                || self.known_topics.lock().unwrap().contains(&topic)
+                || !pending_topics.insert(topic.clone())
+            {
+                continue;
             }
+            let discovery = self.clone();
+            bridging.spawn(async move {
+                match discovery.bridge_topic(&topic, sample).await {
+                    Ok(()) => {
+                        discovery.known_topics.lock().unwrap().insert(topic.clone());
+                    }
+                    Err(err) => warn!(topic, "Failed to bridge discovered topic {err:?}"),
+                }
+                topic
+            });
         }
-    });
+        Ok(())
+    }
+
+    async fn bridge_topic(&self, topic: &str, sample: Sample) -> anyhow::Result<()> {
+        let encoding = sample.encoding.clone();
+        let schema = match self.query_schema(topic).await? {
+            Some(schema) => schema,
+            None if is_json_sample(sample) => DiscoveredSchema::Json {
+                type_name: topic.rsplit('/').next().unwrap_or(topic).to_owned(),
+                schema: GENERIC_JSON_SCHEMA.to_owned(),
+            },
+            None => {
+                info!(topic, ?encoding, "Skipping discovered topic without schema");
+                return Ok(());
+            }
+        };
+        info!(topic, ?schema, "Bridging discovered topic");
+
+        match schema {
+            DiscoveredSchema::Json { type_name, schema } => {
+                start_json_subscriber(
+                    topic,
+                    DEFAULT_QUEUE_SIZE,
+                    self.zenoh_session.clone(),
+                    &self.foxglove_server,
+                    &type_name,
+                    &schema,
+                    &[],
+                    false,
+                    &self.supervisor,
+                    &self.stats,
+                    &self.schemas,
+                    self.clock.clone(),
+                )
+                .await?;
+            }
+            DiscoveredSchema::Protobuf(message_descriptor) => {
+                start_proto_subscriber_from_descriptor(
+                    topic,
+                    DEFAULT_QUEUE_SIZE,
+                    self.zenoh_session.clone(),
+                    &self.foxglove_server,
+                    &message_descriptor,
+                    &[],
+                    false,
+                    None,
+                    None,
+                    &self.supervisor,
+                    &self.stats,
+                    &self.schemas,
+                    self.clock.clone(),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn query_schema(&self, topic: &str) -> anyhow::Result<Option<DiscoveredSchema>> {
+        let replies = self
+            .zenoh_session
+            .get(format!("{topic}{SCHEMA_QUERY_SUFFIX}"))
+            .timeout(SCHEMA_QUERY_TIMEOUT)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+        while let Ok(reply) = replies.recv_async().await {
+            let Ok(sample) = reply.sample else {
+                continue;
+            };
+            let Ok(payload) = String::try_from(sample.value) else {
+                continue;
+            };
+            if let Some(schema) = DiscoveredSchema::parse(topic, &payload) {
+                return Ok(Some(schema));
+            }
+            warn!(topic, payload, "Unrecognized schema reply");
+        }
+        Ok(None)
+    }
+}
+
+fn is_json_sample(sample: Sample) -> bool {
+    json_payload(sample)
+        .is_ok_and(|payload| serde_json::from_slice::<serde_json::Value>(&payload).is_ok())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn start_proto_subscriber_from_descriptor(
+    topic: &str,
+    queue_size: usize,
+    zenoh_session: Arc<Session>,
+    foxglove_server: &FoxgloveWebSocket,
+    protobuf_descriptor: &MessageDescriptor,
+    transforms: &[PayloadTransform],
+    latched: bool,
+    decimation: Option<&DecimationConfig>,
+    client_watch: Option<ClientWatch>,
+    supervisor: &Supervisor,
+    stats: &StatsRegistry,
+    schemas: &SchemaRegistry,
+    clock: SharedClock,
+) -> Result<(), BridgeError> {
+    info!(topic, "Starting proto subscriber");
+    let channel_descriptor = output_descriptor(protobuf_descriptor, transforms)
+        .map_err(|err| BridgeError::invalid_subscription(topic, format!("transforms, {err:#}")))?;
+    // keyframes are marked in the robot's message, before transforms
+    let decimator = decimation
+        .map(|decimation| Decimator::new(decimation, protobuf_descriptor))
+        .transpose()
+        .map_err(|err| BridgeError::invalid_subscription(topic, format!("decimation, {err:#}")))?;
+    schemas.register(topic, TopicSchema::Protobuf(channel_descriptor.clone()));
+    let foxglove_channel = create_publisher_for_protobuf_descriptor(
+        &channel_descriptor,
+        foxglove_server,
+        topic,
+        latched,
+    )
+    .await?;
+    let payload_from_sample: PayloadFromSample = if transforms.is_empty() {
+        Arc::new(proto_payload)
+    } else {
+        let protobuf_descriptor = protobuf_descriptor.clone();
+        let transforms = transforms.to_vec();
+        Arc::new(move |sample| {
+            transform_proto(&proto_payload(sample)?, &protobuf_descriptor, &transforms)
+        })
+    };
+
+    spawn_forwarder(
+        supervisor,
+        topic,
+        queue_size,
+        zenoh_session,
+        foxglove_channel,
+        latched,
+        payload_from_sample,
+        decimator,
+        client_watch,
+        stats.topic(topic),
+        clock,
+    );
     Ok(())
 }
 
@@ -124,7 +734,8 @@ async fn create_publisher_for_protobuf_descriptor(
     protobuf_descriptor: &MessageDescriptor,
     foxglove_server: &FoxgloveWebSocket,
     topic: &str,
-) -> anyhow::Result<Channel> {
+    latched: bool,
+) -> Result<Channel, BridgeError> {
     let protobuf_schema_data = protobuf_descriptor.parent_pool().encode_to_vec();
     foxglove_server
         .create_publisher(
@@ -133,27 +744,36 @@ async fn create_publisher_for_protobuf_descriptor(
             protobuf_descriptor.full_name(),
             protobuf_schema_data,
             Some(PROTOBUF_ENCODING),
-            false,
+            latched,
         )
         .await
+        .map_err(|err| BridgeError::channel(topic, format!("{err:#}")))
+}
+
+pub fn proto_payload(sample: Sample) -> anyhow::Result<Vec<u8>> {
+    let payload: Vec<u8> = sample.value.try_into()?;
+    Ok(payload)
 }
 
 const JSON_ENCODING: &str = "json";
 
+#[allow(clippy::too_many_arguments)]
 async fn start_json_subscriber(
     topic: &str,
+    queue_size: usize,
     zenoh_session: Arc<Session>,
     foxglove_server: &FoxgloveWebSocket,
     type_name: &str,
     json_schema: &str,
+    transforms: &[PayloadTransform],
     latched: bool,
-) -> anyhow::Result<()> {
+    supervisor: &Supervisor,
+    stats: &StatsRegistry,
+    schemas: &SchemaRegistry,
+    clock: SharedClock,
+) -> Result<(), BridgeError> {
     info!(topic, "Starting json subscriber");
-    let zenoh_subscriber = zenoh_session
-        .declare_subscriber(topic)
-        .res()
-        .await
-        .map_err(ErrorWrapper::ZenohError)?;
+    schemas.register(topic, TopicSchema::Json(json_schema.to_owned()));
     let foxglove_channel = foxglove_server
         .create_publisher(
             topic,
@@ -163,68 +783,765 @@ async fn start_json_subscriber(
             Some("jsonschema"),
             latched,
         )
-        .await?;
+        .await
+        .map_err(|err| BridgeError::channel(topic, format!("{err:#}")))?;
+    spawn_forwarder(
+        supervisor,
+        topic,
+        queue_size,
+        zenoh_session,
+        foxglove_channel,
+        latched,
+        json_payload_from_sample(transforms),
+        None,
+        None,
+        stats.topic(topic),
+        clock,
+    );
+    Ok(())
+}
+
+fn json_payload_from_sample(transforms: &[PayloadTransform]) -> PayloadFromSample {
+    if transforms.is_empty() {
+        Arc::new(json_payload)
+    } else {
+        let transforms = transforms.to_vec();
+        Arc::new(move |sample| transformed_json_payload(sample, &transforms))
+    }
+}
 
-    tokio::spawn({
-        let topic = topic.to_owned();
+/// How long the first messages of a topic with `infer_schema` are waited for
+const SCHEMA_SAMPLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// [`start_json_subscriber`] advertising a schema inferred from the first `sample_count` messages
+///
+/// The channel is only created once the messages arrived or [`SCHEMA_SAMPLE_TIMEOUT`]
+/// passed, then the sampled messages are forwarded first. Quiet topics get the generic schema.
+#[allow(clippy::too_many_arguments)]
+fn start_inferred_json_subscriber(
+    topic: &str,
+    sample_count: usize,
+    queue_size: usize,
+    zenoh_session: Arc<Session>,
+    foxglove_server: &FoxgloveWebSocket,
+    type_name: &str,
+    transforms: &[PayloadTransform],
+    latched: bool,
+    supervisor: &Supervisor,
+    stats: &StatsRegistry,
+    schemas: &SchemaRegistry,
+    clock: SharedClock,
+) {
+    info!(
+        topic,
+        sample_count, "Sampling json topic to infer its schema"
+    );
+    let task_name = format!("bridge {topic}");
+    let heartbeat = supervisor.health().heartbeat(&task_name);
+    // the channel is advertised once and kept when the supervisor restarts the task
+    let forwarder: Arc<OnceLock<Forwarder>> = Arc::default();
+    let topic = topic.to_owned();
+    let type_name = type_name.to_owned();
+    let payload_from_sample = json_payload_from_sample(transforms);
+    let foxglove_server = foxglove_server.clone();
+    let topic_stats = stats.topic(&topic);
+    let schemas = schemas.clone();
+    supervisor.spawn(&task_name, move |shutdown| {
+        let forwarder = forwarder.clone();
+        let topic = topic.clone();
+        let type_name = type_name.clone();
+        let payload_from_sample = payload_from_sample.clone();
+        let zenoh_session = zenoh_session.clone();
+        let foxglove_server = foxglove_server.clone();
+        let topic_stats = topic_stats.clone();
+        let schemas = schemas.clone();
+        let heartbeat = heartbeat.clone();
+        let clock = clock.clone();
         async move {
-            let mut message_counter = 0;
-            loop {
-                let res: anyhow::Result<()> = async {
-                    let sample = zenoh_subscriber.recv_async().await?;
-                    message_counter += 1;
-                    let now = SystemTime::now();
-                    let time_nanos = system_time_to_nanos(&now);
-
-                    let payload = match &sample.encoding {
-                        Encoding::Exact(KnownEncoding::TextPlain) => {
-                            let payload: String = sample.value.try_into()?;
-                            payload.as_bytes().to_vec()
-                        }
-                        Encoding::Exact(KnownEncoding::TextJson) => {
-                            let payload: String = sample.value.try_into()?;
-                            payload.as_bytes().to_vec()
-                        }
-                        Encoding::Exact(KnownEncoding::AppOctetStream) => {
-                            let payload: Vec<u8> = sample.value.try_into()?;
-                            payload
-                        }
-                        _ => {
-                            tracing::error!(topic, "Unknown encoding: {:?}", sample.encoding);
-                            panic!("Unknown encoding");
-                        }
-                    };
-
-                    foxglove_channel.send(time_nanos, &payload).await?;
-
-                    if message_counter % 20 == 0 {
-                        debug!(
-                            topic,
-                            message_counter, "{} sent {} messages", topic, message_counter
-                        );
-                    }
-                    Ok(())
+            if let Some(forwarder) = forwarder.get() {
+                return forwarder.clone().run(shutdown).await;
+            }
+            let Some(samples) =
+                sample_topic(&zenoh_session, &topic, sample_count, &heartbeat, &shutdown).await?
+            else {
+                return Ok(());
+            };
+            let messages: Vec<serde_json::Value> = samples
+                .iter()
+                .filter_map(|sample| payload_from_sample(sample.clone()).ok())
+                .filter_map(|payload| serde_json::from_slice(&payload).ok())
+                .collect();
+            let json_schema = match infer_schema(&type_name, &messages) {
+                Some(schema) => {
+                    info!(topic, messages = messages.len(), "Inferred json schema");
+                    schema.to_string()
+                }
+                None => {
+                    warn!(
+                        topic,
+                        "No messages to infer a schema from, using the generic one"
+                    );
+                    GENERIC_JSON_SCHEMA.to_owned()
                 }
-                .await;
-                if let Err(err) = res {
-                    tracing::error!(topic, "Error receiving message: {}", err);
+            };
+            schemas.register(&topic, TopicSchema::Json(json_schema.clone()));
+            let foxglove_channel = foxglove_server
+                .create_publisher(
+                    &topic,
+                    JSON_ENCODING,
+                    &type_name,
+                    &json_schema,
+                    Some("jsonschema"),
+                    latched,
+                )
+                .await?;
+            let forwarder = forwarder.get_or_init(|| Forwarder {
+                topic: topic.clone(),
+                queue_size,
+                zenoh_session,
+                foxglove_channel: Arc::new(foxglove_channel),
+                latched,
+                payload_from_sample,
+                decimator: None,
+                client_watch: None,
+                topic_stats,
+                heartbeat,
+                clock,
+            });
+            for sample in samples {
+                forwarder.topic_stats.record_received();
+                match forwarder.forward(sample).await {
+                    Ok(bytes) => forwarder.topic_stats.record_forwarded(bytes),
+                    Err(_) => forwarder.topic_stats.record_dropped(),
                 }
             }
+            forwarder.clone().run(shutdown).await
         }
     });
-    Ok(())
 }
 
-#[derive(Debug, Deserialize)]
+/// Up to `sample_count` samples of `topic`, fewer if [`SCHEMA_SAMPLE_TIMEOUT`] passes first
+///
+/// `None` if `shutdown` was cancelled.
+async fn sample_topic(
+    zenoh_session: &Session,
+    topic: &str,
+    sample_count: usize,
+    heartbeat: &Heartbeat,
+    shutdown: &CancellationToken,
+) -> anyhow::Result<Option<Vec<Sample>>> {
+    let subscriber = zenoh_session
+        .declare_subscriber(topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let mut samples = vec![];
+    let timeout = tokio::time::sleep(SCHEMA_SAMPLE_TIMEOUT);
+    tokio::pin!(timeout);
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    while samples.len() < sample_count {
+        tokio::select! {
+            sample = subscriber.recv_async() => samples.push(sample.context("Zenoh subscriber closed")?),
+            _ = heartbeat_interval.tick() => heartbeat.beat(),
+            _ = &mut timeout => break,
+            _ = shutdown.cancelled() => return Ok(None),
+        }
+    }
+    Ok(Some(samples))
+}
+
+pub fn json_payload(sample: Sample) -> anyhow::Result<Vec<u8>> {
+    let payload = match &sample.encoding {
+        Encoding::Exact(KnownEncoding::TextPlain) => {
+            let payload: String = sample.value.try_into()?;
+            payload.as_bytes().to_vec()
+        }
+        Encoding::Exact(KnownEncoding::TextJson) => {
+            let payload: String = sample.value.try_into()?;
+            payload.as_bytes().to_vec()
+        }
+        Encoding::Exact(KnownEncoding::AppOctetStream) => {
+            let payload: Vec<u8> = sample.value.try_into()?;
+            payload
+        }
+        _ => anyhow::bail!("Unknown encoding: {:?}", sample.encoding),
+    };
+    Ok(payload)
+}
+
+/// [`json_payload`] with `transforms` applied
+pub fn transformed_json_payload(
+    sample: Sample,
+    transforms: &[PayloadTransform],
+) -> anyhow::Result<Vec<u8>> {
+    let message = serde_json::from_slice(&json_payload(sample)?)?;
+    Ok(serde_json::to_vec(&transform_json(message, transforms)?)?)
+}
+
+/// Encoding of raw camera payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+    /// Annex B, every sample has to hold exactly one frame
+    H264,
+    H265,
+}
+
+impl ImageFormat {
+    /// `format` field of the foxglove message
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+            ImageFormat::H264 => "h264",
+            ImageFormat::H265 => "h265",
+        }
+    }
+
+    pub fn is_video(&self) -> bool {
+        matches!(self, ImageFormat::H264 | ImageFormat::H265)
+    }
+
+    pub fn proto_type(&self) -> &'static str {
+        if self.is_video() {
+            "foxglove.CompressedVideo"
+        } else {
+            "foxglove.CompressedImage"
+        }
+    }
+}
+
+/// Wrap a raw camera frame in `foxglove.CompressedImage` or `foxglove.CompressedVideo`
+///
+/// The frame is stamped with the zenoh timestamp of the sample if it has one.
+pub fn compressed_image_payload(
+    sample: Sample,
+    format: ImageFormat,
+    frame_id: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let timestamp = sample
+        .timestamp
+        .map(|timestamp| timestamp.get_time().to_system_time())
+        .unwrap_or_else(SystemTime::now)
+        .into();
+    let data: Vec<u8> = sample.value.try_into()?;
+    let payload = if format.is_video() {
+        foxglove::CompressedVideo {
+            timestamp: Some(timestamp),
+            frame_id: frame_id.to_owned(),
+            data,
+            format: format.name().to_owned(),
+        }
+        .encode_to_vec()
+    } else {
+        foxglove::CompressedImage {
+            timestamp: Some(timestamp),
+            frame_id: frame_id.to_owned(),
+            data,
+            format: format.name().to_owned(),
+        }
+        .encode_to_vec()
+    };
+    Ok(payload)
+}
+
+const LOG_PROTO_TYPE: &str = "foxglove.Log";
+
+/// Encode a robot log line or an operator [`Annotation`] as `foxglove.Log`
+///
+/// Log lines are named after the topic they came from.
+pub fn log_payload(sample: Sample) -> anyhow::Result<Vec<u8>> {
+    let name = sample.key_expr.to_string();
+    let fallback_time = sample
+        .timestamp
+        .map(|timestamp| timestamp.get_time().to_system_time())
+        .unwrap_or_else(SystemTime::now);
+    let line = String::from_utf8(json_payload(sample)?)?;
+    if let Ok(annotation) = serde_json::from_str::<Annotation>(&line) {
+        return Ok(annotation.to_log().encode_to_vec());
+    }
+    Ok(parse_log_line(&line, &name, fallback_time).encode_to_vec())
+}
+
+/// Parse a JSON event from `tracing-subscriber` or a plain text log line
+///
+/// Plain text lines are searched for a level such as `INFO` in their first few words.
+pub fn parse_log_line(line: &str, name: &str, fallback_time: SystemTime) -> foxglove::Log {
+    let line = line.trim_end();
+    if let Ok(event) = serde_json::from_str::<TracingEvent>(line) {
+        let mut message = event
+            .fields
+            .get("message")
+            .map(json_field_to_string)
+            .unwrap_or_default();
+        for (key, value) in event.fields.iter().filter(|(key, _)| *key != "message") {
+            message.push_str(&format!(" {key}={}", json_field_to_string(value)));
+        }
+        let time = event
+            .timestamp
+            .map(SystemTime::from)
+            .unwrap_or(fallback_time);
+        return foxglove::Log {
+            timestamp: Some(time.into()),
+            level: event.level.as_deref().map(log_level).unwrap_or_default() as i32,
+            message,
+            name: event.target.unwrap_or_else(|| name.to_owned()),
+            file: event.filename.unwrap_or_default(),
+            line: event.line_number.unwrap_or_default(),
+        };
+    }
+
+    let level = line
+        .split_whitespace()
+        .take(3)
+        .map(log_level)
+        .find(|level| *level != foxglove::log::Level::Unknown)
+        .unwrap_or(foxglove::log::Level::Info);
+    foxglove::Log {
+        timestamp: Some(fallback_time.into()),
+        level: level as i32,
+        message: line.to_owned(),
+        name: name.to_owned(),
+        file: String::new(),
+        line: 0,
+    }
+}
+
+/// Event written by `tracing_subscriber::fmt().json()`
+#[derive(Deserialize)]
+struct TracingEvent {
+    timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    level: Option<String>,
+    #[serde(default)]
+    fields: serde_json::Map<String, serde_json::Value>,
+    target: Option<String>,
+    filename: Option<String>,
+    line_number: Option<u32>,
+}
+
+fn json_field_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn log_level(level: &str) -> foxglove::log::Level {
+    match level
+        .trim_matches(|c: char| !c.is_ascii_alphabetic())
+        .to_ascii_uppercase()
+        .as_str()
+    {
+        "TRACE" | "DEBUG" => foxglove::log::Level::Debug,
+        "INFO" => foxglove::log::Level::Info,
+        "WARN" | "WARNING" => foxglove::log::Level::Warning,
+        "ERROR" => foxglove::log::Level::Error,
+        "FATAL" | "CRITICAL" => foxglove::log::Level::Fatal,
+        _ => foxglove::log::Level::Unknown,
+    }
+}
+
+type PayloadFromSample = Arc<dyn Fn(Sample) -> anyhow::Result<Vec<u8>> + Send + Sync>;
+
+const INITIAL_FORWARD_ERROR_DELAY: Duration = Duration::from_millis(50);
+const MAX_FORWARD_ERROR_DELAY: Duration = Duration::from_secs(5);
+const MAX_CONSECUTIVE_FORWARD_ERRORS: u32 = 10;
+const LATCHED_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_forwarder(
+    supervisor: &Supervisor,
+    topic: &str,
+    queue_size: usize,
+    zenoh_session: Arc<Session>,
+    foxglove_channel: Channel,
+    latched: bool,
+    payload_from_sample: PayloadFromSample,
+    decimator: Option<Decimator>,
+    client_watch: Option<ClientWatch>,
+    topic_stats: Arc<TopicStats>,
+    clock: SharedClock,
+) {
+    let task_name = format!("bridge {topic}");
+    let forwarder = Forwarder {
+        topic: topic.to_owned(),
+        queue_size,
+        zenoh_session,
+        foxglove_channel: Arc::new(foxglove_channel),
+        latched,
+        payload_from_sample,
+        decimator,
+        client_watch,
+        topic_stats,
+        heartbeat: supervisor.health().heartbeat(&task_name),
+        clock,
+    };
+    supervisor.spawn(&task_name, move |shutdown| forwarder.clone().run(shutdown));
+}
+
+/// Forwards samples of one zenoh topic to a foxglove channel
+#[derive(Clone)]
+struct Forwarder {
+    topic: String,
+    queue_size: usize,
+    zenoh_session: Arc<Session>,
+    foxglove_channel: Arc<Channel>,
+    /// Query the last sample on start so the channel holds one before the next publish
+    latched: bool,
+    payload_from_sample: PayloadFromSample,
+    /// Rate limit, restarted forwarders start with a fresh copy
+    decimator: Option<Decimator>,
+    /// Only subscribed while a Foxglove client is attached
+    client_watch: Option<ClientWatch>,
+    topic_stats: Arc<TopicStats>,
+    heartbeat: Heartbeat,
+    clock: SharedClock,
+}
+
+impl Forwarder {
+    /// Forward until `shutdown`, with a client watch only while a Foxglove client is attached
+    async fn run(self, shutdown: CancellationToken) -> anyhow::Result<()> {
+        let Some(mut client_watch) = self.client_watch.clone() else {
+            return self.forward_until(&shutdown).await;
+        };
+        let topic = self.topic.as_str();
+        loop {
+            if !self.idle_until_client(&mut client_watch, &shutdown).await? {
+                return Ok(());
+            }
+            info!(topic, "Foxglove client attached, subscribing");
+            // undeclares the subscriber without stopping the task
+            let paused = shutdown.child_token();
+            let forwarding = self.forward_until(&paused);
+            tokio::pin!(forwarding);
+            tokio::select! {
+                result = &mut forwarding => return result,
+                detached = wait_for_clients(&mut client_watch, false) => {
+                    detached?;
+                    paused.cancel();
+                    forwarding.await?;
+                    info!(topic, "No Foxglove clients, unsubscribed");
+                }
+            }
+        }
+    }
+
+    /// Wait until a Foxglove client is attached, false if `shutdown` was cancelled first
+    async fn idle_until_client(
+        &self,
+        client_watch: &mut ClientWatch,
+        shutdown: &CancellationToken,
+    ) -> anyhow::Result<bool> {
+        let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let attached = wait_for_clients(client_watch, true);
+        tokio::pin!(attached);
+        loop {
+            tokio::select! {
+                attached = &mut attached => {
+                    attached?;
+                    return Ok(true);
+                }
+                _ = heartbeat_interval.tick() => self.heartbeat.beat(),
+                _ = shutdown.cancelled() => return Ok(false),
+            }
+        }
+    }
+
+    /// Forward zenoh samples to the foxglove channel until `shutdown` is cancelled
+    ///
+    /// Samples already queued in the subscriber are still forwarded on shutdown.
+    async fn forward_until(&self, shutdown: &CancellationToken) -> anyhow::Result<()> {
+        let topic = self.topic.as_str();
+        let (sample_sender, samples) = flume::bounded(self.queue_size);
+        let _zenoh_subscriber = self
+            .zenoh_session
+            .declare_subscriber(topic)
+            // stamped on arrival so that the latency includes the time spent in the queue
+            .callback(move |sample| {
+                let _ = sample_sender.send((std::time::Instant::now(), sample));
+            })
+            .res()
+            .await
+            .map_err(ZenohError)?;
+        let _subscribed = self.topic_stats.subscribed();
+        // queried after subscribing so that nothing published in between is missed
+        if self.latched {
+            self.forward_latest().await;
+        }
+
+        let mut consecutive_errors = 0;
+        let mut error_backoff = Backoff::new(INITIAL_FORWARD_ERROR_DELAY, MAX_FORWARD_ERROR_DELAY);
+        let mut error_log = LogThrottle::new(topic, DEFAULT_THROTTLE_WINDOW);
+        let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut decimator = self.decimator.clone();
+        loop {
+            let (received, sample) = tokio::select! {
+                sample = samples.recv_async() => sample.context("Zenoh subscriber closed")?,
+                _ = heartbeat_interval.tick() => {
+                    self.heartbeat.beat();
+                    error_log.flush();
+                    continue;
+                }
+                _ = shutdown.cancelled() => break,
+            };
+            self.topic_stats.record_received();
+            // count the sample that was just taken off the queue
+            self.topic_stats
+                .record_queue_depth(samples.len() + 1, self.queue_size);
+            if let Some(decimator) = &mut decimator {
+                if !decimator.keep(&sample.value.payload.contiguous(), received) {
+                    self.topic_stats.record_decimated();
+                    continue;
+                }
+            }
+            match self.forward(sample).await {
+                Ok(bytes) => {
+                    self.topic_stats.record_forwarded(bytes);
+                    self.topic_stats.record_latency(received.elapsed());
+                    consecutive_errors = 0;
+                    error_backoff.reset();
+                }
+                Err(err) => {
+                    self.topic_stats.record_dropped();
+                    consecutive_errors += 1;
+                    error_log.error(&format!("Error receiving message: {err}"));
+                    if consecutive_errors >= MAX_CONSECUTIVE_FORWARD_ERRORS {
+                        // let the supervisor recreate the subscriber
+                        return Err(err.context(format!(
+                            "Forwarding {topic} failed {consecutive_errors} times in a row"
+                        )));
+                    }
+                    tokio::select! {
+                        _ = tokio::time::sleep(error_backoff.next_delay()) => {}
+                        _ = shutdown.cancelled() => break,
+                    }
+                }
+            }
+        }
+
+        let mut drained = 0;
+        while let Ok((_, sample)) = samples.try_recv() {
+            self.topic_stats.record_received();
+            let time_nanos = self.clock.now_nanos();
+            match (self.payload_from_sample)(sample) {
+                Ok(payload) => {
+                    if let Err(err) = self.foxglove_channel.send(time_nanos, &payload).await {
+                        self.topic_stats.record_dropped();
+                        tracing::error!(topic, "Error sending message during shutdown: {}", err);
+                        break;
+                    }
+                    self.topic_stats.record_forwarded(payload.len());
+                    drained += 1;
+                }
+                Err(err) => {
+                    self.topic_stats.record_dropped();
+                    error_log.error(&format!("Error receiving message: {err}"));
+                }
+            }
+        }
+        error_log.flush();
+        debug!(topic, drained, "Subscriber stopped");
+        Ok(())
+    }
+
+    async fn forward(&self, sample: Sample) -> anyhow::Result<usize> {
+        let time_nanos = self.clock.now_nanos();
+        let payload = (self.payload_from_sample)(sample)?;
+        self.foxglove_channel.send(time_nanos, &payload).await?;
+        Ok(payload.len())
+    }
+
+    /// Forward the last sample of a latched topic from a zenoh storage or publication cache
+    ///
+    /// Robots without either don't reply and the channel fills with the next publish.
+    async fn forward_latest(&self) {
+        let topic = self.topic.as_str();
+        let replies = match self
+            .zenoh_session
+            .get(topic)
+            .timeout(LATCHED_QUERY_TIMEOUT)
+            .res()
+            .await
+        {
+            Ok(replies) => replies,
+            Err(err) => {
+                warn!(topic, "Failed to query latched topic {err}");
+                return;
+            }
+        };
+        while let Ok(reply) = replies.recv_async().await {
+            let Ok(sample) = reply.sample else {
+                continue;
+            };
+            self.topic_stats.record_received();
+            match self.forward(sample).await {
+                Ok(bytes) => self.topic_stats.record_forwarded(bytes),
+                Err(err) => {
+                    self.topic_stats.record_dropped();
+                    warn!(topic, "Failed to forward latched sample {err}");
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
 pub struct FoxgloveServerConfiguration {
+    /// `.proto` files or encoded file descriptor sets with message types for `proto_type`
+    #[serde(default)]
+    pub descriptor_files: Vec<PathBuf>,
+    #[serde(default)]
     pub protobuf_subscriptions: Vec<ProtobufSubscription>,
+    #[serde(default)]
     pub json_subscriptions: Vec<JsonSubscription>,
+    /// Key expression such as `hopper/**` whose topics are bridged as they are discovered
+    #[serde(default)]
+    pub auto_discover: Option<String>,
+    /// Robot log topics shown in the Foxglove Log panel
+    #[serde(default)]
+    pub log_subscriptions: Vec<LogSubscription>,
+    /// Camera topics with raw JPEG, PNG, WebP or H.264/H.265 frames
+    #[serde(default)]
+    pub compressed_image_subscriptions: Vec<CompressedImageSubscription>,
+    /// Topics Foxglove panels such as Teleop may publish JSON messages on
+    #[serde(default)]
+    pub client_publish: Vec<ClientPublishTopic>,
+    /// Zenoh queryables that Foxglove panels can call as services
+    #[serde(default)]
+    pub services: Vec<FoxgloveService>,
+    /// Zenoh key prefix such as `hopper/parameters` whose keys show up in the Parameters panel
+    #[serde(default)]
+    pub parameter_prefix: Option<String>,
+    /// Directory `package://` URIs of URDFs are served from, `package://hopper/base.stl` is
+    /// `{asset_dir}/hopper/base.stl`
+    #[serde(default)]
+    pub asset_dir: Option<PathBuf>,
+    /// Zenoh key prefix queried for assets that aren't in `asset_dir`, such as `hopper/assets`
+    #[serde(default)]
+    pub asset_key_prefix: Option<String>,
+    /// Send clients the Deck's time so plots line up with the bridge's stamps
+    #[serde(default)]
+    pub broadcast_time: bool,
+    /// Zenoh key of a robot clock whose time is sent instead of the Deck's, implies
+    /// `broadcast_time`
+    #[serde(default)]
+    pub time_key_expr: Option<String>,
+}
+
+impl FoxgloveServerConfiguration {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let transforms = self
+            .protobuf_subscriptions
+            .iter()
+            .map(|subscription| (&subscription.topic, &subscription.transforms))
+            .chain(
+                self.json_subscriptions
+                    .iter()
+                    .map(|subscription| (&subscription.topic, &subscription.transforms)),
+            );
+        for (topic, transforms) in transforms {
+            for transform in transforms {
+                transform
+                    .validate()
+                    .with_context(|| format!("Invalid transform for {topic}"))?;
+            }
+        }
+        for subscription in &self.json_subscriptions {
+            match (subscription.infer_schema, &subscription.json_schema_name) {
+                (Some(0), _) => {
+                    bail!(
+                        "infer_schema of {} needs at least one message",
+                        subscription.topic
+                    )
+                }
+                (Some(_), Some(_)) => bail!(
+                    "Use either infer_schema or json_schema_name for {}",
+                    subscription.topic
+                ),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Every zenoh topic that is bridged
+    pub fn topics(&self) -> Vec<String> {
+        self.protobuf_subscriptions
+            .iter()
+            .map(|subscription| subscription.topic.clone())
+            .chain(
+                self.json_subscriptions
+                    .iter()
+                    .map(|subscription| subscription.topic.clone()),
+            )
+            .chain(
+                self.log_subscriptions
+                    .iter()
+                    .map(|subscription| subscription.topic.clone()),
+            )
+            .chain(
+                self.compressed_image_subscriptions
+                    .iter()
+                    .map(|subscription| subscription.topic.clone()),
+            )
+            .collect()
+    }
+
+    /// Bridged topics that are subscribed without a Foxglove client attached
+    ///
+    /// `on_demand` topics go quiet when nobody watches, so they don't tell if the robot is alive.
+    pub fn telemetry_topics(&self) -> Vec<String> {
+        let on_demand: HashSet<&str> = self
+            .protobuf_subscriptions
+            .iter()
+            .filter(|subscription| subscription.on_demand)
+            .map(|subscription| subscription.topic.as_str())
+            .chain(
+                self.compressed_image_subscriptions
+                    .iter()
+                    .filter(|subscription| subscription.on_demand)
+                    .map(|subscription| subscription.topic.as_str()),
+            )
+            .collect();
+        self.topics()
+            .into_iter()
+            .filter(|topic| !on_demand.contains(topic.as_str()))
+            .collect()
+    }
+
+    fn has_on_demand_subscriptions(&self) -> bool {
+        self.protobuf_subscriptions
+            .iter()
+            .any(|subscription| subscription.on_demand)
+            || self
+                .compressed_image_subscriptions
+                .iter()
+                .any(|subscription| subscription.on_demand)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ProtobufSubscription {
     pub topic: String,
     pub proto_type: String,
+    /// Late joining clients get the last message, for TF, robot descriptions and poses
+    pub latched: Option<bool>,
+    /// Applied in order before forwarding, `extract` changes the channel's message type
+    #[serde(default)]
+    pub transforms: Vec<PayloadTransform>,
+    /// Samples buffered between zenoh and the foxglove sender
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    /// Rate limit for video and point clouds that only drops messages between keyframes
+    #[serde(default)]
+    pub decimation: Option<DecimationConfig>,
+    /// Only subscribe on zenoh while a Foxglove client is attached
+    #[serde(default)]
+    pub on_demand: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -232,200 +1549,49 @@ pub struct JsonSubscription {
     pub topic: String,
     pub type_name: String,
     pub json_schema_name: Option<String>,
+    /// Advertise a schema inferred from this many messages instead of the generic one
+    #[serde(default)]
+    pub infer_schema: Option<usize>,
     pub latched: Option<bool>,
+    /// Applied in order before forwarding
+    #[serde(default)]
+    pub transforms: Vec<PayloadTransform>,
+    /// Samples buffered between zenoh and the foxglove sender
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
 }
 
-pub fn system_time_to_nanos(d: &SystemTime) -> u64 {
-    let ns = d.duration_since(UNIX_EPOCH).unwrap().as_nanos();
-    assert!(ns <= u64::MAX as u128);
-    ns as u64
+/// Plain text or JSON `tracing` log lines republished as `foxglove.Log`
+#[derive(Debug, Deserialize)]
+pub struct LogSubscription {
+    pub topic: String,
+    /// Samples buffered between zenoh and the foxglove sender
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
 }
 
-fn json_schema_table() -> &'static HashMap<String, String> {
-    static INSTANCE: OnceLock<HashMap<String, String>> = OnceLock::new();
-    INSTANCE.get_or_init(|| {
-        let mut m = HashMap::new();
-        m.insert(
-            "GENERIC_JSON_SCHEMA".to_owned(),
-            GENERIC_JSON_SCHEMA.to_owned(),
-        );
-        m.insert(
-            "IKEA_DIMMER_JSON_SCHEMA".to_owned(),
-            IKEA_DIMMER_JSON_SCHEMA.to_owned(),
-        );
-        m.insert(
-            "MOTION_SENSOR_JSON_SCHEMA".to_owned(),
-            MOTION_SENSOR_JSON_SCHEMA.to_owned(),
-        );
-        m.insert(
-            "CONTACT_SENSOR_JSON_SCHEMA".to_owned(),
-            CONTACT_SENSOR_JSON_SCHEMA.to_owned(),
-        );
-        m.insert(
-            "CLIMATE_SENSOR_JSON_SCHEMA".to_owned(),
-            CLIMATE_SENSOR_JSON_SCHEMA.to_owned(),
-        );
-        m.insert(
-            "VOICE_PROBABILITY_JSON_SCHEMA".to_owned(),
-            VOICE_PROBABILITY_JSON_SCHEMA.to_owned(),
-        );
-        m
-    })
-}
-
-#[allow(dead_code)]
-const GENERIC_JSON_SCHEMA: &str = r#"
-{
-"title": "GenericJsonSchema",
-"description": "Generic JSON Schema",
-"type": "object",
-"properties": {}
-}
-"#;
-
-const IKEA_DIMMER_JSON_SCHEMA: &str = r#"
-{
-    "$schema": "http://json-schema.org/draft-04/schema#",
-    "type": "object",
-    "properties": {
-      "action": {
-        "type": "string"
-      },
-      "battery": {
-        "type": "integer"
-      },
-      "brightness": {
-        "type": "integer"
-      },
-      "linkquality": {
-        "type": "integer"
-      }
-    },
-    "required": [
-      "action",
-      "battery",
-      "brightness",
-      "linkquality"
-    ]
-}
-"#;
-
-const MOTION_SENSOR_JSON_SCHEMA: &str = r#"
-{
-    "$schema": "http://json-schema.org/draft-04/schema#",
-    "type": "object",
-    "properties": {
-      "battery": {
-        "type": "integer"
-      },
-      "battery_low": {
-        "type": "boolean"
-      },
-      "linkquality": {
-        "type": "integer"
-      },
-      "occupancy": {
-        "type": "boolean"
-      },
-      "tamper": {
-        "type": "boolean"
-      },
-      "voltage": {
-        "type": "integer"
-      }
-    },
-    "required": [
-      "battery",
-      "battery_low",
-      "linkquality",
-      "occupancy",
-      "tamper",
-      "voltage"
-    ]
-  }
-"#;
-
-const CONTACT_SENSOR_JSON_SCHEMA: &str = r#"
-{
-"$schema": "http://json-schema.org/draft-04/schema#",
-"type": "object",
-"properties": {
-    "battery": {
-    "type": "integer"
-    },
-    "battery_low": {
-    "type": "boolean"
-    },
-    "contact": {
-    "type": "boolean"
-    },
-    "linkquality": {
-    "type": "integer"
-    },
-    "tamper": {
-    "type": "boolean"
-    },
-    "voltage": {
-    "type": "integer"
-    }
-},
-"required": [
-    "battery",
-    "battery_low",
-    "contact",
-    "linkquality",
-    "tamper",
-    "voltage"
-]
-}
-"#;
-
-const CLIMATE_SENSOR_JSON_SCHEMA: &str = r#"
-{
-"$schema": "http://json-schema.org/draft-04/schema#",
-"type": "object",
-"properties": {
-    "battery": {
-    "type": "integer"
-    },
-    "humidity": {
-    "type": "number"
-    },
-    "linkquality": {
-    "type": "integer"
-    },
-    "temperature": {
-    "type": "number"
-    },
-    "voltage": {
-    "type": "integer"
-    }
-},
-"required": [
-    "battery",
-    "humidity",
-    "linkquality",
-    "temperature",
-    "voltage"
-]
-}
-"#;
-
-const VOICE_PROBABILITY_JSON_SCHEMA: &str = r#"
-{
-    "$schema": "http://json-schema.org/draft-04/schema#",
-    "type": "object",
-    "properties": {
-      "probability": {
-        "type": "number"
-      },
-      "timestamp": {
-        "type": "string"
-      }
-    },
-    "required": [
-      "probability",
-      "timestamp"
-    ]
-  }
-"#;
+/// Raw camera frames republished as `foxglove.CompressedImage` or `foxglove.CompressedVideo`
+#[derive(Debug, Deserialize)]
+pub struct CompressedImageSubscription {
+    pub topic: String,
+    pub format: ImageFormat,
+    /// Frame of the camera's optical center
+    #[serde(default)]
+    pub frame_id: String,
+    /// Samples buffered between zenoh and the foxglove sender
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    /// Only subscribe on zenoh while a Foxglove client is attached
+    #[serde(default)]
+    pub on_demand: bool,
+}
+
+/// Same as the zenoh default, keep it small for control topics and larger for images
+pub const DEFAULT_QUEUE_SIZE: usize = 256;
+
+fn default_queue_size() -> usize {
+    DEFAULT_QUEUE_SIZE
+}
+
+/// Empty object schema for JSON topics whose fields aren't known
+const GENERIC_JSON_SCHEMA: &str = include_str!("../config/schemas/GENERIC_JSON_SCHEMA.json");
@@ -1,163 +1,1669 @@
-use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use gilrs::GilrsBuilder;
+use prost::Message;
 use schemars::schema_for;
+use serde::Serialize;
+use tokio::{sync::mpsc, time::MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
 use tracing::*;
-use zenoh::prelude::r#async::*;
+use zenoh::{
+    prelude::r#async::*,
+    publication::{CongestionControl, Priority, Publisher},
+};
 
 use crate::{
-    error::ErrorWrapper,
-    messages::{Button, InputMessage},
+    annotations::{Annotation, ANNOTATION_TOPIC, DEFAULT_ANNOTATION_NOTE},
+    clock::{Clock, SharedClock},
+    config::{AxisMapping, ButtonCombos, ButtonMapping, GamepadRoles, GamepadSelector},
+    deadman::DeadmanConfig,
+    error::{GamepadError, ZenohError},
+    gamepad_battery::{
+        low_battery_alert, low_battery_rumble, BatteryMonitor, DEFAULT_LOW_BATTERY_PERCENT,
+        GAMEPAD_ALERT_TOPIC,
+    },
+    gamepad_events::{AxisThresholds, EventDetector},
+    health::{Heartbeat, HEARTBEAT_INTERVAL},
+    input_pipeline::{AxisSmoothing, InputPipeline},
+    input_script::{InputScript, ScriptMessage, ScriptSource},
+    log_throttle::{LogThrottle, DEFAULT_THROTTLE_WINDOW},
+    macros::{MacroConfig, MacroEngine},
+    messages::{
+        Axis, Button, ButtonCounterPolicy, ComboMessage, EstopMessage, GamepadEncoding,
+        GamepadEventMessage, GamepadMessage, InputMessage, PowerState, PublishMode, RumbleCommand,
+        WatchdogMessage, WatchdogReason,
+    },
+    mixer::{mix_twist, TwistMixerConfig},
+    modifiers::ModifierConfig,
+    remote_control,
+    robot_registry::RobotRegistry,
+    ros2::{joy_message, serialize_joy, Ros2JoyConfig},
+    schema_registry::{SchemaRegistry, TopicSchema},
+    shared_memory::{maybe_share, SharedMemoryPayloads},
+    stats::GamepadStats,
+    supervisor::Supervisor,
+    DESCRIPTOR_POOL,
 };
 
-pub async fn start_schema_queryable(
+/// Drive gamepad rumble from commands published on `<pub_topic>/feedback`
+pub fn start_feedback_subscriber(
+    supervisor: &Supervisor,
     zenoh_session: Arc<Session>,
     pub_topic: &str,
-) -> anyhow::Result<()> {
-    let schema_topic = format!("{}/__schema__", pub_topic);
+    input_backend: Arc<dyn InputBackend>,
+) {
+    let feedback_topic = format!("{}/feedback", pub_topic);
+    let heartbeat = supervisor.health().heartbeat("gamepad_feedback");
+    supervisor.spawn("gamepad_feedback", move |shutdown| {
+        run_feedback_subscriber(
+            zenoh_session.clone(),
+            feedback_topic.clone(),
+            input_backend.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
 
-    let queryable = zenoh_session
-        .declare_queryable(&schema_topic)
+async fn run_feedback_subscriber(
+    zenoh_session: Arc<Session>,
+    feedback_topic: String,
+    input_backend: Arc<dyn InputBackend>,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let subscriber = zenoh_session
+        .declare_subscriber(&feedback_topic)
         .res()
         .await
-        .map_err(ErrorWrapper::ZenohError)?;
+        .map_err(ZenohError)?;
+    info!("Listening for rumble commands on {feedback_topic}");
 
-    tokio::spawn(async move {
-        while let Ok(query) = queryable.recv_async().await {
-            let schema = schema_for!(InputMessage);
-            if let Ok(schema) = serde_json::to_string(&schema) {
-                if let Ok(key_expr) = KeyExpr::<'static>::from_str(&schema_topic) {
-                    let reply = Ok(Sample::new(key_expr, schema));
-                    _ = query.reply(reply).res().await;
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        let sample = tokio::select! {
+            sample = subscriber.recv_async() => sample?,
+            _ = heartbeat_interval.tick() => {
+                heartbeat.beat();
+                continue;
+            }
+            _ = shutdown.cancelled() => break,
+        };
+        let payload: String = match sample.value.try_into() {
+            Ok(payload) => payload,
+            Err(err) => {
+                warn!("Failed to read rumble command {err:?}");
+                continue;
+            }
+        };
+        match serde_json::from_str::<RumbleCommand>(&payload) {
+            Ok(command) => {
+                if let Err(err) = input_backend.rumble(command) {
+                    warn!("Failed to rumble {err:?}");
                 }
             }
+            Err(err) => warn!("Invalid rumble command {err}"),
         }
-    });
-
+    }
     Ok(())
 }
 
-pub async fn start_gamepad_reader(
+#[derive(Debug, Clone)]
+pub struct GamepadReaderConfig {
+    pub pub_topic: String,
+    pub sleep_ms: u64,
+    pub button_counter_policy: ButtonCounterPolicy,
+    /// Largest value button event counters reach, unbounded if not set
+    pub button_counter_limit: Option<usize>,
+    pub encoding: GamepadEncoding,
+    /// Engages the emergency stop
+    pub estop_button: Button,
+    /// Re-arms the emergency stop after it was engaged
+    pub estop_rearm_button: Button,
+    /// Shared with the control panel, which can engage and re-arm it too
+    pub estop: EstopLatch,
+    /// Shaping applied to axis values, axes without an entry are published raw
+    pub axis_mapping: AxisMapping,
+    /// Renamed buttons, applied before anything else looks at button events
+    pub button_mapping: ButtonMapping,
+    /// Smoothing applied to mapped axis values before they are published
+    pub axis_smoothing: AxisSmoothing,
+    /// Axes that publish events on `<pub_topic>/events`, buttons always do
+    pub axis_event_thresholds: AxisThresholds,
+    /// Gamepads are reported disconnected with neutral input if no input events arrive for this long
+    pub watchdog_timeout: Duration,
+    pub publish_mode: PublishMode,
+    /// Longest time without a message in [`PublishMode::Hybrid`]
+    pub keepalive_interval: Duration,
+    /// Holding select and pressing left or right on the d-pad switches between these robots
+    pub robot_registry: Option<RobotRegistry>,
+    /// Publish under the key prefix of the active robot of [`Self::robot_registry`]
+    ///
+    /// Always the case for robots connected at once.
+    pub robot_namespaces: bool,
+    /// Switches to the next robot on its own, for toggling between robots connected at once
+    pub target_button: Option<Button>,
+    /// Applied to the gamepad and emergency stop publishers
+    pub qos: PublisherQos,
+    /// Gamepads additionally published alone on `<pub_topic>/<role>`
+    pub gamepad_roles: GamepadRoles,
+    /// Velocity commands published along with every gamepad message
+    pub twist: Option<TwistMixerConfig>,
+    /// Button chords published on `<pub_topic>/combos`
+    pub combos: ButtonCombos,
+    /// `sensor_msgs/Joy` published along with every gamepad message
+    pub ros2_joy: Option<Ros2JoyConfig>,
+    /// Buttons that record and replay input
+    pub macros: Option<MacroConfig>,
+    /// Script run on every input event and publish tick
+    pub script: Option<ScriptSource>,
+    /// Motion axes are zeroed unless this button is held
+    pub deadman: Option<DeadmanConfig>,
+    /// Speed modes that scale the axes while their button is held
+    pub modifiers: Option<ModifierConfig>,
+    /// Gamepad messages are written to shared memory for subscribers on the same machine
+    pub shared_memory: Option<Arc<SharedMemoryPayloads>>,
+    /// Records an annotation on `remote-control/annotations`
+    pub annotation_button: Option<Button>,
+    /// Note of annotations recorded with [`Self::annotation_button`]
+    pub annotation_note: String,
+    /// Battery level below which a gamepad raises a warning, 0 turns warnings off
+    pub low_battery_percent: u8,
+    /// Also rumble a gamepad when it runs low on battery
+    pub low_battery_rumble: bool,
+}
+
+/// Zenoh QoS so control input isn't stuck behind bulk telemetry under congestion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PublisherQos {
+    pub priority: PublisherPriority,
+    pub congestion_control: CongestionControlMode,
+    /// Send without batching
+    pub express: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum PublisherPriority {
+    RealTime,
+    InteractiveHigh,
+    InteractiveLow,
+    DataHigh,
+    #[default]
+    Data,
+    DataLow,
+    Background,
+}
+
+impl From<PublisherPriority> for Priority {
+    fn from(priority: PublisherPriority) -> Self {
+        match priority {
+            PublisherPriority::RealTime => Priority::RealTime,
+            PublisherPriority::InteractiveHigh => Priority::InteractiveHigh,
+            PublisherPriority::InteractiveLow => Priority::InteractiveLow,
+            PublisherPriority::DataHigh => Priority::DataHigh,
+            PublisherPriority::Data => Priority::Data,
+            PublisherPriority::DataLow => Priority::DataLow,
+            PublisherPriority::Background => Priority::Background,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum CongestionControlMode {
+    /// Drop messages when the link is congested
+    #[default]
+    Drop,
+    /// Wait until the message can be sent
+    Block,
+}
+
+impl From<CongestionControlMode> for CongestionControl {
+    fn from(congestion_control: CongestionControlMode) -> Self {
+        match congestion_control {
+            CongestionControlMode::Drop => CongestionControl::Drop,
+            CongestionControlMode::Block => CongestionControl::Block,
+        }
+    }
+}
+
+impl Default for GamepadReaderConfig {
+    fn default() -> Self {
+        Self {
+            pub_topic: String::from("remote-control/gamepad"),
+            sleep_ms: 50,
+            button_counter_policy: ButtonCounterPolicy::default(),
+            button_counter_limit: None,
+            encoding: GamepadEncoding::default(),
+            estop_button: Button::Mode,
+            estop_rearm_button: Button::Start,
+            estop: EstopLatch::default(),
+            axis_mapping: AxisMapping::new(),
+            button_mapping: ButtonMapping::new(),
+            axis_smoothing: AxisSmoothing::new(),
+            axis_event_thresholds: AxisThresholds::new(),
+            watchdog_timeout: DEFAULT_WATCHDOG_TIMEOUT,
+            publish_mode: PublishMode::default(),
+            keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+            robot_registry: None,
+            robot_namespaces: false,
+            target_button: None,
+            qos: PublisherQos::default(),
+            gamepad_roles: GamepadRoles::new(),
+            twist: None,
+            ros2_joy: None,
+            macros: None,
+            combos: ButtonCombos::new(),
+            script: None,
+            deadman: None,
+            modifiers: None,
+            shared_memory: None,
+            annotation_button: None,
+            annotation_note: DEFAULT_ANNOTATION_NOTE.to_owned(),
+            low_battery_percent: DEFAULT_LOW_BATTERY_PERCENT,
+            low_battery_rumble: false,
+        }
+    }
+}
+
+impl GamepadReaderConfig {
+    /// How often input backends report gamepad status
+    ///
+    /// At most half the watchdog timeout, otherwise a publish interval as long as the
+    /// timeout would trip the watchdog between two status reports of an idle gamepad.
+    pub fn status_interval(&self) -> Duration {
+        Duration::from_millis(self.sleep_ms).min(self.watchdog_timeout / 2)
+    }
+
+    /// Schemas of every topic the gamepad reader publishes on
+    pub fn register_schemas(&self, schemas: &SchemaRegistry) -> anyhow::Result<()> {
+        let input_schema = match self.encoding {
+            GamepadEncoding::Protobuf => {
+                TopicSchema::Protobuf(message_descriptor("remote_control.InputMessage")?)
+            }
+            // CBOR and MessagePack have the same structure as JSON
+            _ => TopicSchema::Json(serde_json::to_string(&schema_for!(InputMessage))?),
+        };
+        let combo_schema = TopicSchema::Json(serde_json::to_string(&schema_for!(ComboMessage))?);
+        let event_schema =
+            TopicSchema::Json(serde_json::to_string(&schema_for!(GamepadEventMessage))?);
+        for keys in self.target_keys() {
+            for role_key in &keys.roles {
+                schemas.register(role_key, input_schema.clone());
+            }
+            schemas.register(&keys.gamepad, input_schema.clone());
+            schemas.register(&keys.combos, combo_schema.clone());
+            schemas.register(&keys.events, event_schema.clone());
+            if let Some(twist_key) = &keys.twist {
+                schemas.register(
+                    twist_key,
+                    TopicSchema::Protobuf(message_descriptor("remote_control.Twist")?),
+                );
+            }
+        }
+        schemas.register(
+            ESTOP_TOPIC,
+            TopicSchema::Json(serde_json::to_string(&schema_for!(EstopMessage))?),
+        );
+        schemas.register(
+            WATCHDOG_TOPIC,
+            TopicSchema::Json(serde_json::to_string(&schema_for!(WatchdogMessage))?),
+        );
+        schemas.register(
+            ANNOTATION_TOPIC,
+            TopicSchema::Json(serde_json::to_string(&schema_for!(Annotation))?),
+        );
+        Ok(())
+    }
+
+    /// Keys of every robot gamepad input is sent to
+    ///
+    /// With robot namespaces every robot gets its own copy of every topic under its key
+    /// prefix, otherwise everything is published on the keys as configured.
+    fn target_keys(&self) -> Vec<TargetKeys> {
+        let robots: Vec<(Option<String>, Option<String>)> = match &self.robot_registry {
+            Some(registry) if self.robot_namespaces || registry.is_simultaneous() => registry
+                .targets()
+                .iter()
+                .map(|target| {
+                    (
+                        Some(target.name.clone()),
+                        Some(target.key_prefix().to_owned()),
+                    )
+                })
+                .collect(),
+            _ => vec![(None, None)],
+        };
+        robots
+            .into_iter()
+            .map(|(robot, prefix)| {
+                let key = |key: &str| match &prefix {
+                    Some(prefix) => format!("{prefix}/{key}"),
+                    None => key.to_owned(),
+                };
+                TargetKeys {
+                    robot,
+                    gamepad: key(&self.pub_topic),
+                    roles: self
+                        .gamepad_roles
+                        .keys()
+                        .map(|role| key(&role_topic(&self.pub_topic, role)))
+                        .collect(),
+                    combos: key(&role_topic(&self.pub_topic, "combos")),
+                    events: key(&role_topic(&self.pub_topic, "events")),
+                    twist: self.twist.as_ref().map(|twist| key(&twist.topic)),
+                    joy: self.ros2_joy.as_ref().map(|joy| key(&joy.key_expr)),
+                }
+            })
+            .collect()
+    }
+
+    fn serialize(
+        &self,
+        buffer: &mut Vec<u8>,
+        message: &InputMessage,
+    ) -> Result<Value, GamepadError> {
+        serialize_input(self.encoding, buffer, message)
+    }
+
+    fn increment_counter(&self, counter: &mut usize) {
+        let limit = self.button_counter_limit.unwrap_or(usize::MAX);
+        self.button_counter_policy.increment(counter, limit);
+    }
+}
+
+pub fn start_gamepad_reader(
+    supervisor: &Supervisor,
     zenoh_session: Arc<Session>,
-    pub_topic: &str,
-    sleep_ms: u64,
-) -> anyhow::Result<()> {
-    tokio::spawn({
+    config: GamepadReaderConfig,
+    stats: Arc<GamepadStats>,
+    clock: SharedClock,
+    input_backend: Arc<dyn InputBackend>,
+) {
+    let heartbeat = supervisor.health().heartbeat("gamepad_reader");
+    // shared between restarts so that a restarted reader doesn't release the emergency stop
+    let estop = config.estop.clone();
+    supervisor.spawn("gamepad_reader", move |shutdown| {
         let zenoh_session = zenoh_session.clone();
-        let pub_topic = pub_topic.to_owned();
+        let estop = estop.clone();
+        let config = config.clone();
+        let stats = stats.clone();
+        let clock = clock.clone();
+        let input_backend = input_backend.clone();
+        let heartbeat = heartbeat.clone();
         async move {
-            while let Err(err) =
-                run_gamepad_reader(zenoh_session.clone(), &pub_topic, sleep_ms).await
-            {
-                error!("Gamepad reader failed with {err:?}");
-            }
+            run_gamepad_reader(
+                zenoh_session,
+                &config,
+                &stats,
+                clock.as_ref(),
+                input_backend.as_ref(),
+                &estop,
+                &heartbeat,
+                &shutdown,
+            )
+            .await?;
+            Ok(())
         }
     });
-    Ok(())
 }
 
+/// Publish gamepad state until `shutdown` is cancelled
+///
+/// A final message with all buttons released and all axes centered is published
+/// on shutdown so that the robot doesn't keep executing the last command.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_gamepad_reader(
     zenoh_session: Arc<Session>,
-    pub_topic: &str,
-    sleep_ms: u64,
-) -> anyhow::Result<()> {
-    let gamepad_publisher = zenoh_session
-        .declare_publisher(pub_topic.to_owned())
+    config: &GamepadReaderConfig,
+    stats: &GamepadStats,
+    clock: &dyn Clock,
+    input_backend: &dyn InputBackend,
+    estop: &EstopLatch,
+    heartbeat: &Heartbeat,
+    shutdown: &CancellationToken,
+) -> Result<(), GamepadError> {
+    let mut targets = vec![];
+    for keys in config.target_keys() {
+        targets.push(TargetPublishers::declare(&zenoh_session, config, keys).await?);
+    }
+    let estop_publisher = control_publisher(&zenoh_session, ESTOP_TOPIC, config.qos).await?;
+    let watchdog_publisher = zenoh_session
+        .declare_publisher(WATCHDOG_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let annotation_publisher = zenoh_session
+        .declare_publisher(ANNOTATION_TOPIC)
         .res()
         .await
-        .map_err(ErrorWrapper::ZenohError)?;
+        .map_err(ZenohError)?;
+    let alert_publisher = zenoh_session
+        .declare_publisher(GAMEPAD_ALERT_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let mut battery = BatteryMonitor::new(config.low_battery_percent);
+    let mut combos = ComboDetector::new(config.combos.clone());
+    let mut events = EventDetector::new(config.axis_event_thresholds.clone());
+    let mut button_timer = ButtonTimer::default();
+    let mut input_pipeline = InputPipeline::new(config.axis_smoothing.clone());
+    let mut macros = config.macros.map(MacroEngine::new);
+    // loaded here so a restarted reader starts with fresh script state
+    let mut script = config
+        .script
+        .as_ref()
+        .map(InputScript::load)
+        .transpose()
+        .map_err(|err| GamepadError::Script(format!("{err:#}")))?;
+    let mut script_errors = LogThrottle::new("input_script", DEFAULT_THROTTLE_WINDOW);
+    let share = |value| maybe_share(config.shared_memory.as_deref(), value);
 
     info!("Starting gamepad reader");
 
-    // gamepad
-    let mut gilrs = GilrsBuilder::new()
-        .with_default_filters(true)
-        .build()
-        .expect("Failed to get gilrs handle");
-
-    info!("{} gamepad(s) found", gilrs.gamepads().count());
-    for (_id, gamepad) in gilrs.gamepads() {
-        info!("{} is {:?}", gamepad.name(), gamepad.power_info());
-    }
+    let (input_sender, mut input_events) = mpsc::channel(INPUT_EVENT_QUEUE_SIZE);
+    input_backend
+        .start(input_sender, config.status_interval(), shutdown.clone())
+        .map_err(|err| {
+            err.downcast::<GamepadError>()
+                .unwrap_or_else(|err| GamepadError::Backend(format!("{err:#}")))
+        })?;
 
     let mut message_data = InputMessage {
         gamepads: HashMap::new(),
-        time: std::time::SystemTime::now().into(),
+        time: clock.now().into(),
+        button_counter_policy: config.button_counter_policy,
+        estop_engaged: estop.is_engaged(),
     };
 
+    let mut message_buffer = Vec::with_capacity(MESSAGE_BUFFER_CAPACITY);
+
+    let mut publish_interval = tokio::time::interval(Duration::from_millis(config.sleep_ms));
+    publish_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    // input backends report gamepad status periodically so silence means input was lost
+    let mut last_input_event = tokio::time::Instant::now();
+    let mut input_stalled = false;
+
+    let mut last_published: Option<InputMessage> = None;
+    let mut last_target = active_target(&targets, config).keys.robot.clone();
+    let mut last_publish_time = tokio::time::Instant::now();
+
     loop {
-        let loop_start = tokio::time::Instant::now();
-        while let Some(gilrs_event) = gilrs.next_event() {
-            let gamepad_id: usize = gilrs_event.id.into();
-            let gamepad_data = message_data.gamepads.entry(gamepad_id).or_default();
-
-            gamepad_data.last_event_time = std::time::SystemTime::now().into();
-            match gilrs_event.event {
-                gilrs::EventType::ButtonPressed(button, _) => {
-                    *gamepad_data
-                        .button_down_event_counter
-                        .entry(button.into())
-                        .or_default() += 1;
-                }
-                gilrs::EventType::ButtonReleased(button, _) => {
-                    *gamepad_data
-                        .button_up_event_counter
-                        .entry(button.into())
-                        .or_default() += 1;
-                }
-                gilrs::EventType::AxisChanged(axis, value, _) => {
-                    gamepad_data.axis_state.insert(axis.into(), value);
-                }
-                gilrs::EventType::Connected => {
-                    gamepad_data.connected = true;
-                    info!("Gamepad {} - {} connected", gamepad_id, gamepad_data.name)
-                }
-                gilrs::EventType::Disconnected => {
-                    gamepad_data.connected = false;
+        tokio::select! {
+            input_event = input_events.recv() => {
+                let input_event = input_event.ok_or(GamepadError::BackendStopped)?;
+                let input_event = remap_buttons(input_event, &config.button_mapping);
+                stats.record_input_event();
+                last_input_event = tokio::time::Instant::now();
+                if input_stalled {
+                    input_stalled = false;
+                    info!("Gamepad input resumed");
+                }
+                if let InputEvent::Disconnected { gamepad_id } = &input_event {
+                    let watchdog_message = WatchdogMessage {
+                        reason: WatchdogReason::GamepadDisconnected,
+                        gamepad_id: Some(*gamepad_id),
+                        time: clock.now().into(),
+                    };
+                    watchdog_publisher
+                        .put(serde_json::to_string(&watchdog_message)?)
+                        .res()
+                        .await
+                        .map_err(ZenohError)?;
+                }
+                if let Some(engaged) = estop.update(&input_event, config) {
+                    if engaged {
+                        warn!("Emergency stop engaged");
+                    } else {
+                        info!("Emergency stop re-armed");
+                    }
+                    let estop_message = EstopMessage {
+                        engaged,
+                        time: clock.now().into(),
+                    };
+                    estop_publisher
+                        .put(serde_json::to_string(&estop_message)?)
+                        .res()
+                        .await
+                        .map_err(ZenohError)?;
+                }
+                if let Some(annotation) = annotation_for_event(&input_event, config, clock) {
+                    info!(note = annotation.note, "Annotation recorded");
+                    annotation_publisher
+                        .put(serde_json::to_string(&annotation)?)
+                        .res()
+                        .await
+                        .map_err(ZenohError)?;
+                }
+                if let Some(robot_registry) = &config.robot_registry {
+                    switch_robot_on_combo(
+                        robot_registry,
+                        config.target_button,
+                        &message_data,
+                        &input_event,
+                    );
+                }
+                apply_input_event(&mut message_data, &input_event, config, clock.now().into());
+                let double_press = button_timer.update(&input_event, clock.now().into());
+                if let Some((gamepad_id, button)) = double_press {
+                    if let Some(gamepad_data) = message_data.gamepads.get_mut(&gamepad_id) {
+                        config.increment_counter(
+                            gamepad_data
+                                .button_double_press_counter
+                                .entry(button)
+                                .or_default(),
+                        );
+                    }
+                }
+                message_data.estop_engaged = estop.is_engaged();
+                if message_data.estop_engaged {
+                    message_data
+                        .gamepads
+                        .values_mut()
+                        .for_each(GamepadMessage::center_axes);
+                }
+                let target = active_target(&targets, config);
+                // published right away instead of waiting for the next publish tick
+                for (gamepad_id, event) in events.process(&input_event, &message_data) {
+                    let event_message = GamepadEventMessage {
+                        gamepad_id,
+                        event,
+                        time: clock.now().into(),
+                    };
+                    target
+                        .events
+                        .put(serialize_json(&mut message_buffer, &event_message)?)
+                        .res()
+                        .await
+                        .map_err(ZenohError)?;
+                }
+                publish_combos(&target.combos, &mut combos, &message_data, clock).await?;
+                if let Some(script) = &mut script {
+                    match script.on_event(&input_event, &message_data) {
+                        Ok(messages) if estop.is_engaged() => drop_script_messages(messages),
+                        Ok(messages) => publish_script_messages(&zenoh_session, messages).await?,
+                        Err(err) => script_errors.error(&format!("{err:#}")),
+                    }
+                }
+            }
+            _ = publish_interval.tick() => {
+                heartbeat.beat();
+                let target = active_target(&targets, config);
+                // held combos complete without new input events
+                publish_combos(&target.combos, &mut combos, &message_data, clock).await?;
+                if !input_stalled && last_input_event.elapsed() >= config.watchdog_timeout {
+                    input_stalled = true;
                     warn!(
-                        "Gamepad {} - {} disconnected",
-                        gamepad_id, gamepad_data.name
-                    )
+                        "No gamepad input for {:?}, publishing neutral message",
+                        last_input_event.elapsed()
+                    );
+                    for gamepad_data in message_data.gamepads.values_mut() {
+                        gamepad_data.clear_input_state();
+                        gamepad_data.connected = false;
+                    }
+                    button_timer.clear();
+                    let watchdog_message = WatchdogMessage {
+                        reason: WatchdogReason::InputStalled,
+                        gamepad_id: None,
+                        time: clock.now().into(),
+                    };
+                    watchdog_publisher
+                        .put(serde_json::to_string(&watchdog_message)?)
+                        .res()
+                        .await
+                        .map_err(ZenohError)?;
+                }
+                for gamepad_id in battery.update(&message_data) {
+                    let alert = low_battery_alert(gamepad_id, &message_data.gamepads[&gamepad_id]);
+                    warn!("{alert}");
+                    alert_publisher
+                        .put(format!("WARN {alert}"))
+                        .res()
+                        .await
+                        .map_err(ZenohError)?;
+                    if config.low_battery_rumble {
+                        if let Err(err) = input_backend.rumble(low_battery_rumble(gamepad_id)) {
+                            warn!("Failed to rumble {err:?}");
+                        }
+                    }
+                }
+                // the control panel changes the latch without an input event
+                if estop.is_engaged() && !message_data.estop_engaged {
+                    message_data
+                        .gamepads
+                        .values_mut()
+                        .for_each(GamepadMessage::center_axes);
+                }
+                message_data.estop_engaged = estop.is_engaged();
+                // message_data holds the targets, smoothed axes only exist in the published message
+                let mut published = input_pipeline.process(&message_data, Instant::now());
+                button_timer.apply_hold_durations(&mut published, clock.now().into());
+                if let Some(replayed) = macros
+                    .as_mut()
+                    .and_then(|macros| macros.process(&published, Instant::now()))
+                {
+                    published = replayed;
+                }
+                if let Some(script) = &mut script {
+                    match script.on_tick(&mut published) {
+                        Ok(messages) if estop.is_engaged() || input_stalled => {
+                            drop_script_messages(messages)
+                        }
+                        Ok(messages) => publish_script_messages(&zenoh_session, messages).await?,
+                        Err(err) => script_errors.error(&format!("{err:#}")),
+                    }
+                    // the script may have changed the returned state, the latch still holds it
+                    published.estop_engaged = message_data.estop_engaged;
+                    if published.estop_engaged {
+                        published
+                            .gamepads
+                            .values_mut()
+                            .for_each(GamepadMessage::center_axes);
+                    }
+                }
+                if let Some(modifiers) = &config.modifiers {
+                    modifiers.apply(&message_data, &mut published);
+                }
+                // last so nothing above can produce motion without the switch held
+                if let Some(deadman) = &config.deadman {
+                    deadman.apply(&message_data, &mut published);
+                }
+                let switched = last_target != target.keys.robot;
+                if switched {
+                    // robots are only told apart with namespaces, so this has a name
+                    let previous = targets
+                        .iter()
+                        .find(|previous| previous.keys.robot == last_target);
+                    if let Some(previous) = previous {
+                        info!("Releasing gamepad input on {}", previous.keys.gamepad);
+                        publish_neutral(
+                            &zenoh_session,
+                            config,
+                            &previous.keys,
+                            &published,
+                            &mut message_buffer,
+                        )
+                        .await?;
+                    }
+                    last_target.clone_from(&target.keys.robot);
+                }
+                let changed = switched
+                    || !last_published
+                        .as_ref()
+                        .is_some_and(|last| last.same_state(&published));
+                let publish = match config.publish_mode {
+                    PublishMode::Periodic => true,
+                    PublishMode::OnChange => changed,
+                    PublishMode::Hybrid => {
+                        changed || last_publish_time.elapsed() >= config.keepalive_interval
+                    }
+                };
+                if !publish {
+                    continue;
+                }
+                let publish_start = Instant::now();
+                published.time = clock.now().into();
+                target
+                    .gamepad
+                    .put(share(config.serialize(&mut message_buffer, &published)?))
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+                for (selector, role_publisher) in &target.roles {
+                    let role_message = role_message(&published, selector);
+                    role_publisher
+                        .put(share(config.serialize(&mut message_buffer, &role_message)?))
+                        .res()
+                        .await
+                        .map_err(ZenohError)?;
+                }
+                if let (Some(twist), Some(twist_publisher)) = (&config.twist, &target.twist) {
+                    let twist = mix_twist(&published, twist);
+                    let payload = share(serialize_twist(&mut message_buffer, &twist)?);
+                    twist_publisher
+                        .put(payload)
+                        .res()
+                        .await
+                        .map_err(ZenohError)?;
+                }
+                if let (Some(joy), Some(joy_publisher)) = (&config.ros2_joy, &target.joy) {
+                    let message = joy_message(&published, joy);
+                    let payload = serialize_joy(&mut message_buffer, &message, joy.encoding)?;
+                    joy_publisher
+                        .put(share(payload))
+                        .res()
+                        .await
+                        .map_err(ZenohError)?;
+                }
+                stats.record_publish(publish_start.elapsed());
+                stats.record_message(&published);
+                last_publish_time = tokio::time::Instant::now();
+                if config.button_counter_policy == ButtonCounterPolicy::ResetOnPublish {
+                    for gamepad_data in message_data
+                        .gamepads
+                        .values_mut()
+                        .chain(published.gamepads.values_mut())
+                    {
+                        gamepad_data.clear_event_counters();
+                    }
+                }
+                // compared after resetting counters so that the reset alone isn't a change
+                if config.publish_mode != PublishMode::Periodic {
+                    last_published = Some(published);
                 }
-                _ => {}
             }
+            _ = shutdown.cancelled() => break,
         }
+    }
 
-        let mut known_ids = vec![];
+    info!("Publishing neutral gamepad message before shutdown");
+    message_data.time = clock.now().into();
+    for target in &targets {
+        publish_neutral(
+            &zenoh_session,
+            config,
+            &target.keys,
+            &message_data,
+            &mut message_buffer,
+        )
+        .await?;
+    }
 
-        for (gamepad_id, gamepad) in gilrs.gamepads() {
-            let gamepad_id: usize = gamepad_id.into();
-            known_ids.push(gamepad_id);
-            let gamepad_data = message_data.gamepads.entry(gamepad_id).or_default();
+    Ok(())
+}
 
-            gamepad_data.connected = gamepad.is_connected();
-            gamepad_data.name = gamepad.name().to_string();
+/// `publish` can name any key, including the robot's own command topics, so nothing a
+/// script sends gets out while the emergency stop is engaged or the watchdog tripped
+fn drop_script_messages(messages: Vec<ScriptMessage>) {
+    if !messages.is_empty() {
+        debug!(
+            "Dropped {} script messages while input is held",
+            messages.len()
+        );
+    }
+}
 
-            if gamepad.is_connected() {
-                for button in Button::all_gilrs_buttons() {
-                    gamepad_data
-                        .button_down
-                        .insert(Button::from(*button), gamepad.is_pressed(*button));
-                }
+async fn publish_script_messages(
+    zenoh_session: &Session,
+    messages: Vec<ScriptMessage>,
+) -> Result<(), GamepadError> {
+    for message in messages {
+        zenoh_session
+            .put(&message.topic, serde_json::to_string(&message.payload)?)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+    }
+    Ok(())
+}
 
-                // should we also get stick values here or use events?
-                // let x = gamepad.value(gilrs::Axis::LeftStickY);
-                // let x = if x.abs() > 0.2 { x } else { 0.0 };
-            }
+/// Keys of everything the gamepad reader sends to one robot
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TargetKeys {
+    /// Robot the keys belong to if robots have their own namespace
+    robot: Option<String>,
+    gamepad: String,
+    /// In the order of [`GamepadReaderConfig::gamepad_roles`]
+    roles: Vec<String>,
+    combos: String,
+    events: String,
+    twist: Option<String>,
+    joy: Option<String>,
+}
+
+/// Publishers for the keys of one robot
+struct TargetPublishers {
+    keys: TargetKeys,
+    gamepad: Publisher<'static>,
+    roles: Vec<(GamepadSelector, Publisher<'static>)>,
+    combos: Publisher<'static>,
+    events: Publisher<'static>,
+    twist: Option<Publisher<'static>>,
+    joy: Option<Publisher<'static>>,
+}
+
+impl TargetPublishers {
+    async fn declare(
+        zenoh_session: &Arc<Session>,
+        config: &GamepadReaderConfig,
+        keys: TargetKeys,
+    ) -> Result<Self, ZenohError> {
+        let mut roles = vec![];
+        for (selector, role_key) in config.gamepad_roles.values().zip(&keys.roles) {
+            roles.push((
+                selector.clone(),
+                control_publisher(zenoh_session, role_key, config.qos).await?,
+            ));
+        }
+        let twist = match &keys.twist {
+            Some(twist_key) => Some(control_publisher(zenoh_session, twist_key, config.qos).await?),
+            None => None,
+        };
+        let joy = match &keys.joy {
+            Some(joy_key) => Some(control_publisher(zenoh_session, joy_key, config.qos).await?),
+            None => None,
+        };
+        Ok(Self {
+            gamepad: control_publisher(zenoh_session, &keys.gamepad, config.qos).await?,
+            combos: control_publisher(zenoh_session, &keys.combos, config.qos).await?,
+            events: control_publisher(zenoh_session, &keys.events, config.qos).await?,
+            roles,
+            twist,
+            joy,
+            keys,
+        })
+    }
+}
+
+async fn control_publisher(
+    zenoh_session: &Arc<Session>,
+    key_expr: &str,
+    qos: PublisherQos,
+) -> Result<Publisher<'static>, ZenohError> {
+    zenoh_session
+        .declare_publisher(key_expr.to_owned())
+        .priority(qos.priority.into())
+        .congestion_control(qos.congestion_control.into())
+        .express(qos.express)
+        .res()
+        .await
+        .map_err(ZenohError)
+}
+
+/// Publishers of the active robot, the only ones without robot namespaces
+fn active_target<'a>(
+    targets: &'a [TargetPublishers],
+    config: &GamepadReaderConfig,
+) -> &'a TargetPublishers {
+    let active = config
+        .robot_registry
+        .as_ref()
+        .map(|registry| registry.active().name);
+    targets
+        .iter()
+        .find(|target| target.keys.robot.is_some() && target.keys.robot == active)
+        .unwrap_or(&targets[0])
+}
+
+/// Every topic of one robot with all buttons released and all axes centered
+///
+/// Blocking so that the last command isn't dropped even if the publishers drop under congestion.
+async fn publish_neutral(
+    zenoh_session: &Session,
+    config: &GamepadReaderConfig,
+    keys: &TargetKeys,
+    message: &InputMessage,
+    buffer: &mut Vec<u8>,
+) -> Result<(), GamepadError> {
+    let share = |value| maybe_share(config.shared_memory.as_deref(), value);
+    let mut message = message.clone();
+    for gamepad_data in message.gamepads.values_mut() {
+        gamepad_data.clear_input_state();
+    }
+    if let Some(deadman) = &config.deadman {
+        let live = message.clone();
+        deadman.apply(&live, &mut message);
+    }
+    zenoh_session
+        .put(&keys.gamepad, share(config.serialize(buffer, &message)?))
+        .priority(config.qos.priority.into())
+        .congestion_control(CongestionControl::Block)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    for (selector, role_key) in config.gamepad_roles.values().zip(&keys.roles) {
+        let role_message = role_message(&message, selector);
+        zenoh_session
+            .put(role_key, share(config.serialize(buffer, &role_message)?))
+            .priority(config.qos.priority.into())
+            .congestion_control(CongestionControl::Block)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+    }
+    if let (Some(twist), Some(twist_key)) = (&config.twist, &keys.twist) {
+        zenoh_session
+            .put(
+                twist_key,
+                share(serialize_twist(buffer, &mix_twist(&message, twist))?),
+            )
+            .priority(config.qos.priority.into())
+            .congestion_control(CongestionControl::Block)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+    }
+    if let (Some(joy), Some(joy_key)) = (&config.ros2_joy, &keys.joy) {
+        zenoh_session
+            .put(
+                joy_key,
+                share(serialize_joy(
+                    buffer,
+                    &joy_message(&message, joy),
+                    joy.encoding,
+                )?),
+            )
+            .priority(config.qos.priority.into())
+            .congestion_control(CongestionControl::Block)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+    }
+    Ok(())
+}
+
+fn role_topic(pub_topic: &str, role: &str) -> String {
+    format!("{pub_topic}/{role}")
+}
+
+fn message_descriptor(name: &str) -> anyhow::Result<prost_reflect::MessageDescriptor> {
+    DESCRIPTOR_POOL
+        .get_message_by_name(name)
+        .with_context(|| format!("Missing {name} descriptor"))
+}
+
+/// `message` with only the gamepad filling a role
+///
+/// Connected gamepads are preferred over ones that were unplugged, then the lowest id wins.
+/// The message has no gamepads if none match.
+pub fn role_message(message: &InputMessage, selector: &GamepadSelector) -> InputMessage {
+    let gamepad = message
+        .gamepads
+        .iter()
+        .filter(|(gamepad_id, gamepad)| selector.matches(**gamepad_id, &gamepad.name))
+        .min_by_key(|(gamepad_id, gamepad)| (!gamepad.connected, **gamepad_id));
+    InputMessage {
+        gamepads: gamepad
+            .map(|(gamepad_id, gamepad)| (*gamepad_id, gamepad.clone()))
+            .into_iter()
+            .collect(),
+        ..message.clone()
+    }
+}
+
+pub const ESTOP_TOPIC: &str = "remote-control/estop";
+const WATCHDOG_TOPIC: &str = "remote-control/watchdog";
+
+pub const DEFAULT_WATCHDOG_TIMEOUT: Duration = Duration::from_millis(500);
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+/// A press this soon after the previous press of the same button is a double press
+pub const DOUBLE_PRESS_WINDOW: Duration = Duration::from_millis(300);
+
+/// Emergency stop state, stays engaged until explicitly re-armed
+#[derive(Debug, Clone, Default)]
+pub struct EstopLatch {
+    engaged: Arc<AtomicBool>,
+}
+
+impl EstopLatch {
+    pub fn is_engaged(&self) -> bool {
+        self.engaged.load(Ordering::Relaxed)
+    }
+
+    /// Engage or re-arm, returns whether the state changed
+    pub fn set(&self, engaged: bool) -> bool {
+        self.engaged.swap(engaged, Ordering::Relaxed) != engaged
+    }
+
+    /// Returns the new state if `input_event` engaged or re-armed the emergency stop
+    fn update(&self, input_event: &InputEvent, config: &GamepadReaderConfig) -> Option<bool> {
+        let InputEvent::ButtonPressed { button, .. } = input_event else {
+            return None;
+        };
+        let engaged = if *button == config.estop_button {
+            true
+        } else if *button == config.estop_rearm_button {
+            false
+        } else {
+            return None;
+        };
+        let previous = self.engaged.swap(engaged, Ordering::Relaxed);
+        (previous != engaged).then_some(engaged)
+    }
+}
+
+/// Tracks how long every configured combo has been held on each gamepad
+#[derive(Debug, Default)]
+pub struct ComboDetector {
+    combos: ButtonCombos,
+    /// When all buttons of a combo went down and whether it fired since
+    held: HashMap<(usize, String), (Instant, bool)>,
+}
+
+impl ComboDetector {
+    pub fn new(combos: ButtonCombos) -> Self {
+        Self {
+            combos,
+            held: HashMap::new(),
         }
+    }
 
-        // remove gamepads that are no longer connected
-        message_data
-            .gamepads
-            .retain(|gamepad_id, _| known_ids.contains(gamepad_id));
+    /// Gamepad ids and names of the combos that completed since the last update
+    ///
+    /// A combo fires once and again only after one of its buttons was released.
+    pub fn update(&mut self, message: &InputMessage, now: Instant) -> Vec<(usize, String)> {
+        let mut fired = vec![];
+        for (gamepad_id, gamepad) in &message.gamepads {
+            for (name, combo) in &self.combos {
+                let key = (*gamepad_id, name.clone());
+                let all_held = combo
+                    .buttons
+                    .iter()
+                    .all(|button| gamepad.button_down.get(button).copied().unwrap_or_default());
+                if !all_held {
+                    self.held.remove(&key);
+                    continue;
+                }
+                let (held_since, done) = self.held.entry(key).or_insert((now, false));
+                if !*done && now.duration_since(*held_since) >= Duration::from_millis(combo.hold_ms)
+                {
+                    *done = true;
+                    fired.push((*gamepad_id, name.clone()));
+                }
+            }
+        }
+        // forget gamepads that went away
+        self.held
+            .retain(|(gamepad_id, _), _| message.gamepads.contains_key(gamepad_id));
+        fired
+    }
+}
 
-        message_data.time = std::time::SystemTime::now().into();
-        let json = serde_json::to_string(&message_data)?;
-        gamepad_publisher
-            .put(json)
+async fn publish_combos(
+    combo_publisher: &Publisher<'_>,
+    combos: &mut ComboDetector,
+    message_data: &InputMessage,
+    clock: &dyn Clock,
+) -> Result<(), GamepadError> {
+    for (gamepad_id, combo) in combos.update(message_data, Instant::now()) {
+        info!("Combo {combo:?} on gamepad {gamepad_id}");
+        let combo_message = ComboMessage {
+            combo,
+            gamepad_id,
+            time: clock.now().into(),
+        };
+        combo_publisher
+            .put(serde_json::to_string(&combo_message)?)
             .res()
             .await
-            .map_err(ErrorWrapper::ZenohError)?;
-        tokio::time::sleep_until(loop_start + Duration::from_millis(sleep_ms)).await;
+            .map_err(ZenohError)?;
+    }
+    Ok(())
+}
+
+const INPUT_EVENT_QUEUE_SIZE: usize = 1024;
+const MESSAGE_BUFFER_CAPACITY: usize = 4096;
+
+/// Hand the serialized bytes to zenoh without copying them
+///
+/// Zenoh keeps the bytes, so `buffer` is replaced with an empty one of the same capacity
+/// and the next message is serialized without growing it.
+pub fn take_payload(buffer: &mut Vec<u8>) -> Vec<u8> {
+    let capacity = buffer.capacity();
+    std::mem::replace(buffer, Vec::with_capacity(capacity))
+}
+
+/// Serialize into a buffer that is already large enough so that serializing doesn't reallocate
+pub fn serialize_json<T: Serialize>(
+    buffer: &mut Vec<u8>,
+    message: &T,
+) -> Result<Value, GamepadError> {
+    buffer.clear();
+    serde_json::to_writer(&mut *buffer, message)
+        .map_err(|err| GamepadError::encoding("json", err))?;
+    // keep the text/plain encoding that publishing a String used to produce
+    Ok(Value::from(take_payload(buffer)).encoding(Encoding::Exact(KnownEncoding::TextPlain)))
+}
+
+/// Protobuf counterpart of [`serialize_json`]
+pub fn serialize_protobuf(
+    buffer: &mut Vec<u8>,
+    message: &InputMessage,
+) -> Result<Value, GamepadError> {
+    buffer.clear();
+    remote_control::InputMessage::from(message)
+        .encode(buffer)
+        .map_err(|err| GamepadError::encoding("protobuf", err))?;
+    Ok(Value::from(take_payload(buffer)).encoding(Encoding::Exact(KnownEncoding::AppOctetStream)))
+}
+
+/// CBOR counterpart of [`serialize_json`]
+pub fn serialize_cbor<T: Serialize>(
+    buffer: &mut Vec<u8>,
+    message: &T,
+) -> Result<Value, GamepadError> {
+    buffer.clear();
+    ciborium::into_writer(message, &mut *buffer)
+        .map_err(|err| GamepadError::encoding("cbor", err))?;
+    Ok(Value::from(take_payload(buffer)).encoding(Encoding::from("application/cbor")))
+}
+
+/// MessagePack counterpart of [`serialize_json`]
+///
+/// Structs are written as maps so that field names match the JSON messages.
+pub fn serialize_msgpack<T: Serialize>(
+    buffer: &mut Vec<u8>,
+    message: &T,
+) -> Result<Value, GamepadError> {
+    buffer.clear();
+    rmp_serde::encode::write_named(buffer, message)
+        .map_err(|err| GamepadError::encoding("msgpack", err))?;
+    Ok(Value::from(take_payload(buffer)).encoding(Encoding::from("application/msgpack")))
+}
+
+/// Gamepad message in the wire format the robot expects
+pub fn serialize_input(
+    encoding: GamepadEncoding,
+    buffer: &mut Vec<u8>,
+    message: &InputMessage,
+) -> Result<Value, GamepadError> {
+    match encoding {
+        GamepadEncoding::Json => serialize_json(buffer, message),
+        GamepadEncoding::Protobuf => serialize_protobuf(buffer, message),
+        GamepadEncoding::Cbor => serialize_cbor(buffer, message),
+        GamepadEncoding::Msgpack => serialize_msgpack(buffer, message),
+    }
+}
+
+/// Velocity command from [`mix_twist`] as protobuf
+pub fn serialize_twist(
+    buffer: &mut Vec<u8>,
+    twist: &remote_control::Twist,
+) -> Result<Value, GamepadError> {
+    buffer.clear();
+    twist
+        .encode(buffer)
+        .map_err(|err| GamepadError::encoding("twist", err))?;
+    Ok(Value::from(take_payload(buffer)).encoding(Encoding::Exact(KnownEncoding::AppOctetStream)))
+}
+
+/// Source of input events for the gamepad reader
+///
+/// Implemented by gilrs for real hardware and by scripted sources in tests.
+pub trait InputBackend: Send + Sync {
+    /// Start sending events to `input_sender` until `shutdown` is cancelled
+    ///
+    /// Called again every time the gamepad reader is restarted.
+    fn start(
+        &self,
+        input_sender: mpsc::Sender<InputEvent>,
+        status_interval: Duration,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()>;
+
+    /// Play a rumble effect, backends without force feedback ignore it
+    fn rumble(&self, _command: RumbleCommand) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads gamepads through gilrs
+#[derive(Debug, Default, Clone)]
+pub struct GilrsBackend {
+    /// Rumble commands for the currently running gilrs thread
+    rumble_sender: Arc<Mutex<Option<std::sync::mpsc::Sender<RumbleCommand>>>>,
+}
+
+impl InputBackend for GilrsBackend {
+    fn start(
+        &self,
+        input_sender: mpsc::Sender<InputEvent>,
+        status_interval: Duration,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let (rumble_sender, rumble_receiver) = std::sync::mpsc::channel();
+        *self.rumble_sender.lock().unwrap() = Some(rumble_sender);
+        spawn_gilrs_thread(input_sender, rumble_receiver, status_interval, shutdown)
+    }
+
+    fn rumble(&self, command: RumbleCommand) -> anyhow::Result<()> {
+        let rumble_sender = self.rumble_sender.lock().unwrap();
+        let rumble_sender = rumble_sender.as_ref().ok_or(GamepadError::BackendStopped)?;
+        rumble_sender
+            .send(command)
+            .map_err(|_| GamepadError::BackendStopped)?;
+        Ok(())
+    }
+}
+
+/// Input read from an input backend
+#[derive(Debug, Clone)]
+pub enum InputEvent {
+    ButtonPressed {
+        gamepad_id: usize,
+        button: Button,
+    },
+    ButtonReleased {
+        gamepad_id: usize,
+        button: Button,
+    },
+    AxisChanged {
+        gamepad_id: usize,
+        axis: Axis,
+        value: f32,
+    },
+    Connected {
+        gamepad_id: usize,
+    },
+    Disconnected {
+        gamepad_id: usize,
+    },
+    /// Status of every gamepad gilrs knows about
+    Gamepads(Vec<GamepadStatus>),
+}
+
+#[derive(Debug, Clone)]
+pub struct GamepadStatus {
+    pub gamepad_id: usize,
+    pub name: String,
+    pub connected: bool,
+    pub button_down: Vec<(Button, bool)>,
+    pub power_state: PowerState,
+    pub battery_percent: Option<u8>,
+}
+
+/// gilrs polling is blocking so it runs on its own thread instead of the async runtime
+///
+/// The thread stops once `shutdown` is cancelled or the receiving side is dropped.
+fn spawn_gilrs_thread(
+    input_sender: mpsc::Sender<InputEvent>,
+    rumble_receiver: std::sync::mpsc::Receiver<RumbleCommand>,
+    status_interval: Duration,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name(String::from("gilrs"))
+        .spawn(move || {
+            let mut gilrs = match GilrsBuilder::new().with_default_filters(true).build() {
+                Ok(gilrs) => gilrs,
+                Err(err) => {
+                    error!("Failed to get gilrs handle {err:?}");
+                    return;
+                }
+            };
+
+            info!("{} gamepad(s) found", gilrs.gamepads().count());
+            for (_id, gamepad) in gilrs.gamepads() {
+                info!("{} is {:?}", gamepad.name(), gamepad.power_info());
+            }
+
+            // effects stop playing when dropped
+            let mut _rumble_effect = None;
+            let mut last_status = Instant::now();
+            while !shutdown.is_cancelled() {
+                while let Ok(command) = rumble_receiver.try_recv() {
+                    match play_rumble(&mut gilrs, &command) {
+                        Ok(effect) => _rumble_effect = effect,
+                        Err(err) => warn!("Failed to play rumble effect {err:?}"),
+                    }
+                }
+
+                // block until the next event but wake up in time for the next status update
+                let timeout = status_interval.saturating_sub(last_status.elapsed());
+                if let Some(gilrs_event) = gilrs.next_event_blocking(Some(timeout)) {
+                    if let Some(input_event) = InputEvent::from_gilrs(gilrs_event) {
+                        if input_sender.blocking_send(input_event).is_err() {
+                            break;
+                        }
+                    }
+                }
+
+                if last_status.elapsed() >= status_interval {
+                    last_status = std::time::Instant::now();
+                    let statuses = gilrs
+                        .gamepads()
+                        .map(|(gamepad_id, gamepad)| GamepadStatus::from_gilrs(gamepad_id, gamepad))
+                        .collect();
+                    if input_sender
+                        .blocking_send(InputEvent::Gamepads(statuses))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+            debug!("Gilrs thread stopped");
+        })
+        .map_err(|err| GamepadError::Backend(format!("Failed to start gilrs thread {err}")))?;
+    Ok(())
+}
+
+/// Returns the effect that has to be kept alive while it plays, none if no gamepad supports it
+fn play_rumble(
+    gilrs: &mut gilrs::Gilrs,
+    command: &RumbleCommand,
+) -> anyhow::Result<Option<gilrs::ff::Effect>> {
+    use gilrs::ff::{BaseEffect, BaseEffectType, EffectBuilder, Repeat, Replay, Ticks};
+
+    let gamepad_ids: Vec<gilrs::GamepadId> = gilrs
+        .gamepads()
+        .filter(|(_, gamepad)| gamepad.is_ff_supported())
+        .map(|(gamepad_id, _)| gamepad_id)
+        .filter(|gamepad_id| {
+            command
+                .gamepad_id
+                .map_or(true, |wanted| usize::from(*gamepad_id) == wanted)
+        })
+        .collect();
+    if gamepad_ids.is_empty() {
+        debug!("No force feedback capable gamepad for rumble command");
+        return Ok(None);
+    }
+
+    let duration = Ticks::from_ms(command.duration_ms.min(u32::MAX as u64) as u32);
+    let magnitude = |value: f32| (value.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+    let scheduling = Replay {
+        play_for: duration,
+        ..Default::default()
+    };
+    let effect = EffectBuilder::new()
+        .add_effect(BaseEffect {
+            kind: BaseEffectType::Strong {
+                magnitude: magnitude(command.strong),
+            },
+            scheduling,
+            envelope: Default::default(),
+        })
+        .add_effect(BaseEffect {
+            kind: BaseEffectType::Weak {
+                magnitude: magnitude(command.weak),
+            },
+            scheduling,
+            envelope: Default::default(),
+        })
+        .repeat(Repeat::For(duration))
+        .gamepads(&gamepad_ids)
+        .finish(gilrs)?;
+    effect.play()?;
+    Ok(Some(effect))
+}
+
+impl InputEvent {
+    fn from_gilrs(gilrs_event: gilrs::Event) -> Option<Self> {
+        let gamepad_id: usize = gilrs_event.id.into();
+        let input_event = match gilrs_event.event {
+            gilrs::EventType::ButtonPressed(button, code) => InputEvent::ButtonPressed {
+                gamepad_id,
+                button: Button::from_gilrs(button, code),
+            },
+            gilrs::EventType::ButtonReleased(button, code) => InputEvent::ButtonReleased {
+                gamepad_id,
+                button: Button::from_gilrs(button, code),
+            },
+            gilrs::EventType::AxisChanged(axis, value, code) => InputEvent::AxisChanged {
+                gamepad_id,
+                axis: Axis::from_gilrs(axis, code),
+                value,
+            },
+            gilrs::EventType::Connected => InputEvent::Connected { gamepad_id },
+            gilrs::EventType::Disconnected => InputEvent::Disconnected { gamepad_id },
+            _ => return None,
+        };
+        Some(input_event)
+    }
+}
+
+impl GamepadStatus {
+    fn from_gilrs(gamepad_id: gilrs::GamepadId, gamepad: gilrs::Gamepad) -> Self {
+        let connected = gamepad.is_connected();
+        let button_down = if connected {
+            Button::all_gilrs_buttons()
+                .iter()
+                .map(|button| (Button::from(*button), gamepad.is_pressed(*button)))
+                .collect()
+        } else {
+            vec![]
+        };
+        // should we also get stick values here or use events?
+        // let x = gamepad.value(gilrs::Axis::LeftStickY);
+        // let x = if x.abs() > 0.2 { x } else { 0.0 };
+        let (power_state, battery_percent) = match gamepad.power_info() {
+            gilrs::PowerInfo::Unknown => (PowerState::Unknown, None),
+            gilrs::PowerInfo::Wired => (PowerState::Wired, None),
+            gilrs::PowerInfo::Discharging(percent) => (PowerState::Discharging, Some(percent)),
+            gilrs::PowerInfo::Charging(percent) => (PowerState::Charging, Some(percent)),
+            gilrs::PowerInfo::Charged => (PowerState::Charged, Some(100)),
+        };
+        Self {
+            gamepad_id: gamepad_id.into(),
+            name: gamepad.name().to_string(),
+            connected,
+            button_down,
+            power_state,
+            battery_percent,
+        }
+    }
+}
+
+pub fn remap_buttons(input_event: InputEvent, button_mapping: &ButtonMapping) -> InputEvent {
+    let remap = |button: Button| button_mapping.get(&button).copied().unwrap_or(button);
+    match input_event {
+        InputEvent::ButtonPressed { gamepad_id, button } => InputEvent::ButtonPressed {
+            gamepad_id,
+            button: remap(button),
+        },
+        InputEvent::ButtonReleased { gamepad_id, button } => InputEvent::ButtonReleased {
+            gamepad_id,
+            button: remap(button),
+        },
+        InputEvent::Gamepads(mut statuses) => {
+            for status in &mut statuses {
+                for (button, _) in &mut status.button_down {
+                    *button = remap(*button);
+                }
+            }
+            InputEvent::Gamepads(statuses)
+        }
+        input_event => input_event,
+    }
+}
+
+/// Select + d-pad left/right cycles through robots, so does the target button
+fn switch_robot_on_combo(
+    robot_registry: &RobotRegistry,
+    target_button: Option<Button>,
+    message_data: &InputMessage,
+    input_event: &InputEvent,
+) {
+    let InputEvent::ButtonPressed { gamepad_id, button } = input_event else {
+        return;
+    };
+    if Some(*button) == target_button {
+        robot_registry.select_next(1);
+        return;
+    }
+    let select_held = message_data
+        .gamepads
+        .get(gamepad_id)
+        .is_some_and(|gamepad| {
+            gamepad
+                .button_down
+                .get(&Button::Select)
+                .copied()
+                .unwrap_or_default()
+        });
+    if !select_held {
+        return;
+    }
+    match button {
+        Button::DPadRight => robot_registry.select_next(1),
+        Button::DPadLeft => robot_registry.select_next(-1),
+        _ => {}
+    }
+}
+
+/// Annotation recorded by pressing the annotation button
+fn annotation_for_event(
+    input_event: &InputEvent,
+    config: &GamepadReaderConfig,
+    clock: &dyn Clock,
+) -> Option<Annotation> {
+    let InputEvent::ButtonPressed { button, .. } = input_event else {
+        return None;
+    };
+    if config.annotation_button != Some(*button) {
+        return None;
+    }
+    Some(Annotation {
+        time: clock.now().into(),
+        note: config.annotation_note.clone(),
+        robot: config
+            .robot_registry
+            .as_ref()
+            .map(|registry| registry.active().name),
+    })
+}
+
+/// Press times behind `button_hold_ms` and `button_double_press_counter`
+#[derive(Debug, Default)]
+pub struct ButtonTimer {
+    pressed_at: HashMap<(usize, Button), DateTime<Utc>>,
+    /// Presses that become a double press if the button is pressed again in time
+    first_press: HashMap<(usize, Button), DateTime<Utc>>,
+}
+
+impl ButtonTimer {
+    /// Track `input_event`, returns the button if it completed a double press
+    ///
+    /// A third quick press starts a new double press instead of completing another one.
+    pub fn update(
+        &mut self,
+        input_event: &InputEvent,
+        now: DateTime<Utc>,
+    ) -> Option<(usize, Button)> {
+        match input_event {
+            InputEvent::ButtonPressed { gamepad_id, button } => {
+                let key = (*gamepad_id, *button);
+                self.pressed_at.insert(key, now);
+                let double_press = self.first_press.remove(&key).is_some_and(|first| {
+                    (now - first)
+                        .to_std()
+                        .is_ok_and(|elapsed| elapsed <= DOUBLE_PRESS_WINDOW)
+                });
+                if double_press {
+                    return Some(key);
+                }
+                self.first_press.insert(key, now);
+            }
+            InputEvent::ButtonReleased { gamepad_id, button } => {
+                self.pressed_at.remove(&(*gamepad_id, *button));
+            }
+            InputEvent::Disconnected { gamepad_id } => {
+                self.pressed_at.retain(|(id, _), _| id != gamepad_id);
+                self.first_press.retain(|(id, _), _| id != gamepad_id);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Forget all presses, for when input was lost
+    pub fn clear(&mut self) {
+        self.pressed_at.clear();
+        self.first_press.clear();
+    }
+
+    /// Set `button_hold_ms` of every gamepad in `message` to the time since its buttons were pressed
+    pub fn apply_hold_durations(&self, message: &mut InputMessage, now: DateTime<Utc>) {
+        for (gamepad_id, gamepad_data) in message.gamepads.iter_mut() {
+            gamepad_data.button_hold_ms = self
+                .pressed_at
+                .iter()
+                .filter(|((id, _), _)| id == gamepad_id)
+                .map(|((_, button), pressed_at)| {
+                    let held = (now - *pressed_at).num_milliseconds().max(0) as u64;
+                    (*button, held)
+                })
+                .collect();
+        }
+    }
+}
+
+fn apply_input_event(
+    message_data: &mut InputMessage,
+    input_event: &InputEvent,
+    config: &GamepadReaderConfig,
+    now: DateTime<Utc>,
+) {
+    let gamepad_id = match input_event {
+        InputEvent::ButtonPressed { gamepad_id, .. }
+        | InputEvent::ButtonReleased { gamepad_id, .. }
+        | InputEvent::AxisChanged { gamepad_id, .. }
+        | InputEvent::Connected { gamepad_id }
+        | InputEvent::Disconnected { gamepad_id } => *gamepad_id,
+        InputEvent::Gamepads(statuses) => {
+            for status in statuses {
+                let gamepad_data = message_data.gamepads.entry(status.gamepad_id).or_default();
+                gamepad_data.connected = status.connected;
+                gamepad_data.name.clone_from(&status.name);
+                gamepad_data.power_state = status.power_state;
+                gamepad_data.battery_percent = status.battery_percent;
+                for (button, pressed) in &status.button_down {
+                    gamepad_data.button_down.insert(*button, *pressed);
+                }
+                if !status.connected {
+                    gamepad_data.clear_input_state();
+                }
+            }
+
+            // remove gamepads that are no longer connected
+            message_data.gamepads.retain(|gamepad_id, _| {
+                statuses
+                    .iter()
+                    .any(|status| status.gamepad_id == *gamepad_id)
+            });
+            return;
+        }
+    };
+
+    let gamepad_data = message_data.gamepads.entry(gamepad_id).or_default();
+    gamepad_data.last_event_time = now;
+    match input_event {
+        InputEvent::ButtonPressed { button, .. } => {
+            config.increment_counter(
+                gamepad_data
+                    .button_down_event_counter
+                    .entry(*button)
+                    .or_default(),
+            );
+        }
+        InputEvent::ButtonReleased { button, .. } => {
+            config.increment_counter(
+                gamepad_data
+                    .button_up_event_counter
+                    .entry(*button)
+                    .or_default(),
+            );
+        }
+        InputEvent::AxisChanged { axis, value, .. } => {
+            let value = config
+                .axis_mapping
+                .get(axis)
+                .map_or(*value, |curve| curve.apply(*value));
+            gamepad_data.axis_state.insert(*axis, value);
+        }
+        InputEvent::Connected { .. } => {
+            gamepad_data.connected = true;
+            info!("Gamepad {} - {} connected", gamepad_id, gamepad_data.name)
+        }
+        InputEvent::Disconnected { .. } => {
+            gamepad_data.connected = false;
+            gamepad_data.clear_input_state();
+            warn!(
+                "Gamepad {} - {} disconnected",
+                gamepad_id, gamepad_data.name
+            )
+        }
+        InputEvent::Gamepads(_) => {}
     }
 }
@@ -0,0 +1,78 @@
+//! Warnings for gamepads running low on battery
+//!
+//! Controllers dying mid-drive stop the robot through the watchdog, but by then the
+//! operator has lost control. A gamepad that drops below the threshold while discharging
+//! raises one warning on [`GAMEPAD_ALERT_TOPIC`], bridged to Foxglove as `foxglove.Log`,
+//! and optionally a rumble pulse. It is warned again once it was charged or reconnected.
+
+use std::collections::HashSet;
+
+use crate::messages::{GamepadMessage, InputMessage, PowerState, RumbleCommand};
+
+/// Plain text warnings about the operator's gamepads, prefixed with `WARN` for the log bridge
+pub const GAMEPAD_ALERT_TOPIC: &str = "remote-control/gamepad_alerts";
+
+pub const DEFAULT_LOW_BATTERY_PERCENT: u8 = 20;
+
+const LOW_BATTERY_RUMBLE_MS: u64 = 400;
+
+#[derive(Debug, Default)]
+pub struct BatteryMonitor {
+    threshold_percent: u8,
+    warned: HashSet<usize>,
+}
+
+impl BatteryMonitor {
+    /// Warn about gamepads below `threshold_percent`, 0 never warns
+    pub fn new(threshold_percent: u8) -> Self {
+        Self {
+            threshold_percent,
+            warned: HashSet::new(),
+        }
+    }
+
+    /// Gamepads in `message` that dropped below the threshold since the last call
+    pub fn update(&mut self, message: &InputMessage) -> Vec<usize> {
+        // still low after a warning doesn't warn again until the gamepad was charged
+        self.warned.retain(|gamepad_id| {
+            message
+                .gamepads
+                .get(gamepad_id)
+                .is_some_and(|gamepad| gamepad.power_state == PowerState::Discharging)
+        });
+        let mut low: Vec<usize> = message
+            .gamepads
+            .iter()
+            .filter(|(_, gamepad)| gamepad.connected && self.is_low(gamepad))
+            .map(|(gamepad_id, _)| *gamepad_id)
+            .filter(|gamepad_id| self.warned.insert(*gamepad_id))
+            .collect();
+        low.sort_unstable();
+        low
+    }
+
+    fn is_low(&self, gamepad: &GamepadMessage) -> bool {
+        gamepad.power_state == PowerState::Discharging
+            && gamepad
+                .battery_percent
+                .is_some_and(|percent| percent < self.threshold_percent)
+    }
+}
+
+pub fn low_battery_alert(gamepad_id: usize, gamepad: &GamepadMessage) -> String {
+    format!(
+        "Gamepad {gamepad_id} - {} battery low at {}%",
+        gamepad.name,
+        gamepad.battery_percent.unwrap_or_default()
+    )
+}
+
+/// Short pulse so the operator notices without looking at a screen
+pub fn low_battery_rumble(gamepad_id: usize) -> RumbleCommand {
+    RumbleCommand {
+        gamepad_id: Some(gamepad_id),
+        strong: 0.0,
+        weak: 0.8,
+        duration_ms: LOW_BATTERY_RUMBLE_MS,
+    }
+}
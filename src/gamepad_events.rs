@@ -0,0 +1,89 @@
+//! Discrete button and axis events published as they happen
+//!
+//! The sampled [`InputMessage`] only changes every publish interval and button presses
+//! show up as counters. Events are detected on every input event instead, so robots can
+//! react to a press without waiting for the next sample.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    gamepad::InputEvent,
+    messages::{Axis, GamepadEvent, InputMessage},
+};
+
+/// Absolute mapped axis value at which an axis counts as engaged
+pub type AxisThresholds = BTreeMap<Axis, f32>;
+
+pub fn validate_thresholds(thresholds: &AxisThresholds) -> anyhow::Result<()> {
+    for (axis, threshold) in thresholds {
+        if !(*threshold > 0.0 && *threshold <= 1.0) {
+            anyhow::bail!("Threshold of {axis:?} must be above 0.0 and at most 1.0");
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct EventDetector {
+    thresholds: AxisThresholds,
+    engaged: HashSet<(usize, Axis)>,
+}
+
+impl EventDetector {
+    pub fn new(thresholds: AxisThresholds) -> Self {
+        Self {
+            thresholds,
+            engaged: HashSet::new(),
+        }
+    }
+
+    /// Events caused by `input_event`, `message` is the state after it was applied
+    ///
+    /// Axes are checked against the message rather than the raw event so that mapping,
+    /// the emergency stop and disconnects release them too.
+    pub fn process(
+        &mut self,
+        input_event: &InputEvent,
+        message: &InputMessage,
+    ) -> Vec<(usize, GamepadEvent)> {
+        let mut events = vec![];
+        match input_event {
+            InputEvent::ButtonPressed { gamepad_id, button } => {
+                events.push((*gamepad_id, GamepadEvent::ButtonDown { button: *button }));
+            }
+            InputEvent::ButtonReleased { gamepad_id, button } => {
+                events.push((*gamepad_id, GamepadEvent::ButtonUp { button: *button }));
+            }
+            _ => {}
+        }
+
+        for (axis, threshold) in &self.thresholds {
+            let mut gamepad_ids: Vec<usize> = message.gamepads.keys().copied().collect();
+            gamepad_ids.extend(
+                self.engaged
+                    .iter()
+                    .filter(|(_, engaged_axis)| engaged_axis == axis)
+                    .map(|(gamepad_id, _)| *gamepad_id),
+            );
+            gamepad_ids.sort_unstable();
+            gamepad_ids.dedup();
+            for gamepad_id in gamepad_ids {
+                let value = message
+                    .gamepads
+                    .get(&gamepad_id)
+                    .and_then(|gamepad| gamepad.axis_state.get(axis).copied())
+                    .unwrap_or_default();
+                let engaged = value.abs() >= *threshold;
+                if engaged && self.engaged.insert((gamepad_id, *axis)) {
+                    events.push((gamepad_id, GamepadEvent::AxisEngaged { axis: *axis, value }));
+                } else if !engaged && self.engaged.remove(&(gamepad_id, *axis)) {
+                    events.push((
+                        gamepad_id,
+                        GamepadEvent::AxisReleased { axis: *axis, value },
+                    ));
+                }
+            }
+        }
+        events
+    }
+}
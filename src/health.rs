@@ -0,0 +1,170 @@
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{error::ZenohError, supervisor::Supervisor};
+
+const HEALTH_TOPIC: &str = "remote-control/health";
+const HEALTH_PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+/// Interval at which idle tasks should still report a heartbeat
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// Running subsystems without a heartbeat for this long are reported as stale
+const STALE_AFTER: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SubsystemState {
+    Starting,
+    Running,
+    Stale,
+    Restarting,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemHealth {
+    pub state: SubsystemState,
+    pub last_seen: DateTime<Utc>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthMessage {
+    pub healthy: bool,
+    pub subsystems: BTreeMap<String, SubsystemHealth>,
+    pub time: DateTime<Utc>,
+}
+
+/// Central registry that long running tasks report their heartbeat to
+#[derive(Debug, Clone, Default)]
+pub struct HealthRegistry {
+    subsystems: Arc<Mutex<BTreeMap<String, SubsystemHealth>>>,
+}
+
+impl HealthRegistry {
+    /// Handle for reporting heartbeats of a single subsystem
+    pub fn heartbeat(&self, name: &str) -> Heartbeat {
+        Heartbeat {
+            name: name.to_owned(),
+            registry: self.clone(),
+        }
+    }
+
+    pub fn set_state(&self, name: &str, state: SubsystemState, message: Option<String>) {
+        let mut subsystems = self.subsystems.lock().unwrap();
+        subsystems.insert(
+            name.to_owned(),
+            SubsystemHealth {
+                state,
+                last_seen: Utc::now(),
+                message,
+            },
+        );
+    }
+
+    fn beat(&self, name: &str) {
+        let mut subsystems = self.subsystems.lock().unwrap();
+        let entry = subsystems
+            .entry(name.to_owned())
+            .or_insert_with(|| SubsystemHealth {
+                state: SubsystemState::Running,
+                last_seen: Utc::now(),
+                message: None,
+            });
+        entry.state = SubsystemState::Running;
+        entry.last_seen = Utc::now();
+        entry.message = None;
+    }
+
+    pub fn snapshot(&self) -> HealthMessage {
+        let now = Utc::now();
+        let mut subsystems = self.subsystems.lock().unwrap().clone();
+        for health in subsystems.values_mut() {
+            let silent_for = (now - health.last_seen).to_std().unwrap_or_default();
+            if health.state == SubsystemState::Running && silent_for > STALE_AFTER {
+                health.state = SubsystemState::Stale;
+            }
+        }
+        let healthy = subsystems
+            .values()
+            .all(|health| health.state == SubsystemState::Running);
+        HealthMessage {
+            healthy,
+            subsystems,
+            time: now,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    name: String,
+    registry: HealthRegistry,
+}
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        self.registry.beat(&self.name);
+    }
+}
+
+/// Periodically publish the aggregated health and answer health queries
+pub fn start_health_publisher(supervisor: &Supervisor, zenoh_session: Arc<Session>) {
+    let registry = supervisor.health();
+    supervisor.spawn("health_publisher", move |shutdown| {
+        run_health_publisher(zenoh_session.clone(), registry.clone(), shutdown)
+    });
+}
+
+async fn run_health_publisher(
+    zenoh_session: Arc<Session>,
+    registry: HealthRegistry,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let publisher = zenoh_session
+        .declare_publisher(HEALTH_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let queryable = zenoh_session
+        .declare_queryable(HEALTH_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let heartbeat = registry.heartbeat("health_publisher");
+
+    let mut interval = tokio::time::interval(HEALTH_PUBLISH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                heartbeat.beat();
+                let json = serde_json::to_string(&registry.snapshot())?;
+                publisher
+                    .put(json)
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            query = queryable.recv_async() => {
+                let query = query?;
+                let json = serde_json::to_string(&registry.snapshot())?;
+                let key_expr = KeyExpr::<'static>::from_str(HEALTH_TOPIC)
+                    .map_err(ZenohError)?;
+                if let Err(err) = query.reply(Ok(Sample::new(key_expr, json))).res().await {
+                    warn!("Failed to reply to health query {err:?}");
+                }
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,223 @@
+//! Steam Deck gyro and accelerometer read through the industrial-io sysfs interface
+//!
+//! The Deck exposes its BMI260 IMU as an iio device with raw channel values and scales
+//! under `/sys/bus/iio/devices/iio:deviceN`.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    error::ZenohError,
+    health::Heartbeat,
+    shared_memory::{maybe_share, SharedMemoryPayloads},
+    supervisor::Supervisor,
+};
+
+const IIO_DEVICE_DIR: &str = "/sys/bus/iio/devices";
+pub const IMU_PUBLISH_INTERVAL: Duration = Duration::from_millis(20);
+/// Weight of the integrated gyro rate in the complementary filter, the rest comes from gravity
+const GYRO_FILTER_WEIGHT: f64 = 0.98;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Vector3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// Published on `{gamepad_topic}/imu`
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct ImuMessage {
+    /// rad/s
+    pub angular_velocity: Vector3,
+    /// m/s^2
+    pub linear_acceleration: Vector3,
+    /// Tilt around the x axis in radians
+    pub roll: f64,
+    /// Tilt around the y axis in radians
+    pub pitch: f64,
+    pub time: DateTime<Utc>,
+}
+
+/// Accelerometer and gyro channels of an iio device
+#[derive(Debug, Clone)]
+pub struct IioImu {
+    device: PathBuf,
+    accel_scale: f64,
+    anglvel_scale: f64,
+}
+
+impl IioImu {
+    /// First iio device that has both accelerometer and gyro channels
+    pub fn find() -> anyhow::Result<Self> {
+        let mut devices: Vec<PathBuf> = std::fs::read_dir(IIO_DEVICE_DIR)
+            .with_context(|| format!("Failed to read {IIO_DEVICE_DIR}"))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect();
+        devices.sort();
+        let device = devices
+            .into_iter()
+            .find(|device| {
+                device.join("in_accel_x_raw").exists() && device.join("in_anglvel_x_raw").exists()
+            })
+            .context("No iio device with accelerometer and gyro found")?;
+        Self::open(&device)
+    }
+
+    pub fn open(device: &Path) -> anyhow::Result<Self> {
+        let imu = Self {
+            device: device.to_owned(),
+            accel_scale: read_value(&device.join("in_accel_scale"))?,
+            anglvel_scale: read_value(&device.join("in_anglvel_scale"))?,
+        };
+        info!("Using IMU {}", device.display());
+        Ok(imu)
+    }
+
+    /// Angular velocity and linear acceleration
+    pub fn read(&self) -> anyhow::Result<(Vector3, Vector3)> {
+        let angular_velocity = self.read_channel("anglvel", self.anglvel_scale)?;
+        let linear_acceleration = self.read_channel("accel", self.accel_scale)?;
+        Ok((angular_velocity, linear_acceleration))
+    }
+
+    fn read_channel(&self, channel: &str, scale: f64) -> anyhow::Result<Vector3> {
+        let read_axis = |axis: &str| -> anyhow::Result<f64> {
+            let path = self.device.join(format!("in_{channel}_{axis}_raw"));
+            Ok(read_value(&path)? * scale)
+        };
+        Ok(Vector3 {
+            x: read_axis("x")?,
+            y: read_axis("y")?,
+            z: read_axis("z")?,
+        })
+    }
+}
+
+fn read_value(path: &Path) -> anyhow::Result<f64> {
+    let value = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid value {:?} in {}", value.trim(), path.display()))
+}
+
+/// Roll and pitch from the gyro rate corrected by the direction of gravity
+#[derive(Debug, Clone, Default)]
+pub struct TiltFilter {
+    roll: f64,
+    pitch: f64,
+    initialized: bool,
+}
+
+impl TiltFilter {
+    pub fn update(
+        &mut self,
+        angular_velocity: Vector3,
+        linear_acceleration: Vector3,
+        elapsed: Duration,
+    ) -> (f64, f64) {
+        let Vector3 { x, y, z } = linear_acceleration;
+        let accel_roll = y.atan2(z);
+        let accel_pitch = (-x).atan2((y * y + z * z).sqrt());
+        if self.initialized {
+            let dt = elapsed.as_secs_f64();
+            self.roll = GYRO_FILTER_WEIGHT * (self.roll + angular_velocity.x * dt)
+                + (1.0 - GYRO_FILTER_WEIGHT) * accel_roll;
+            self.pitch = GYRO_FILTER_WEIGHT * (self.pitch + angular_velocity.y * dt)
+                + (1.0 - GYRO_FILTER_WEIGHT) * accel_pitch;
+        } else {
+            self.roll = accel_roll;
+            self.pitch = accel_pitch;
+            self.initialized = true;
+        }
+        (self.roll, self.pitch)
+    }
+}
+
+pub fn start_imu_publisher(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    topic: String,
+    clock: SharedClock,
+    shared_memory: Option<Arc<SharedMemoryPayloads>>,
+) {
+    let heartbeat = supervisor.health().heartbeat("imu_publisher");
+    supervisor.spawn("imu_publisher", move |shutdown| {
+        run_imu_publisher(
+            zenoh_session.clone(),
+            topic.clone(),
+            clock.clone(),
+            shared_memory.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_imu_publisher(
+    zenoh_session: Arc<Session>,
+    topic: String,
+    clock: SharedClock,
+    shared_memory: Option<Arc<SharedMemoryPayloads>>,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let imu = tokio::task::spawn_blocking(IioImu::find).await??;
+    let publisher = zenoh_session
+        .declare_publisher(topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut filter = TiltFilter::default();
+    let mut last_read = tokio::time::Instant::now();
+    let mut interval = tokio::time::interval(IMU_PUBLISH_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                heartbeat.beat();
+                // sysfs reads go through i2c and can take a while
+                let reader = imu.clone();
+                let (angular_velocity, linear_acceleration) =
+                    tokio::task::spawn_blocking(move || reader.read()).await??;
+                let (roll, pitch) =
+                    filter.update(angular_velocity, linear_acceleration, last_read.elapsed());
+                last_read = tokio::time::Instant::now();
+                let message = ImuMessage {
+                    angular_velocity,
+                    linear_acceleration,
+                    roll,
+                    pitch,
+                    time: clock.now().into(),
+                };
+                publisher
+                    .put(maybe_share(
+                        shared_memory.as_deref(),
+                        Value::from(serde_json::to_string(&message)?),
+                    ))
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Per-axis smoothing between the raw gamepad state and what is published
+//!
+//! Axis events only update the target value, the published value follows it on every
+//! publish tick through an exponential filter and an optional slew-rate limit. Axis
+//! mapping is applied before, so the filters see shaped values. The emergency stop and
+//! disconnected gamepads bypass smoothing so the axes drop to zero immediately.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use crate::messages::{Axis, InputMessage};
+
+/// `axis_smoothing` section of a robot profile
+pub type AxisSmoothing = BTreeMap<Axis, AxisFilter>;
+
+/// Low-pass filter and slew-rate limit for a single axis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AxisFilter {
+    /// Time constant of the exponential filter, 0 disables it
+    pub time_constant_ms: u64,
+    /// Largest change per second, a full stick sweep from -1.0 to 1.0 is 2.0
+    pub max_rate: Option<f32>,
+}
+
+impl AxisFilter {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(max_rate) = self.max_rate {
+            if !(max_rate.is_finite() && max_rate > 0.0) {
+                anyhow::bail!("max_rate must be a positive number, got {max_rate}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Value `elapsed` after `previous` while moving towards `target`
+    pub fn step(&self, previous: f32, target: f32, elapsed: Duration) -> f32 {
+        let elapsed = elapsed.as_secs_f32();
+        let filtered = if self.time_constant_ms == 0 {
+            target
+        } else {
+            let time_constant = self.time_constant_ms as f32 / 1000.0;
+            let alpha = 1.0 - (-elapsed / time_constant).exp();
+            previous + alpha * (target - previous)
+        };
+        match self.max_rate {
+            Some(max_rate) => {
+                let max_step = max_rate * elapsed;
+                previous + (filtered - previous).clamp(-max_step, max_step)
+            }
+            None => filtered,
+        }
+    }
+}
+
+/// Smoothed axis state across publish ticks
+#[derive(Debug, Default)]
+pub struct InputPipeline {
+    smoothing: AxisSmoothing,
+    /// Last published value per gamepad and axis
+    filtered: HashMap<(usize, Axis), f32>,
+    last_update: Option<Instant>,
+}
+
+impl InputPipeline {
+    pub fn new(smoothing: AxisSmoothing) -> Self {
+        Self {
+            smoothing,
+            ..Default::default()
+        }
+    }
+
+    /// `message` with smoothed axes as of `now`
+    pub fn process(&mut self, message: &InputMessage, now: Instant) -> InputMessage {
+        let mut output = message.clone();
+        if self.smoothing.is_empty() {
+            return output;
+        }
+        let elapsed = self.last_update.map_or(Duration::ZERO, |last_update| {
+            now.duration_since(last_update)
+        });
+        self.last_update = Some(now);
+        self.filtered
+            .retain(|(gamepad_id, _), _| message.gamepads.contains_key(gamepad_id));
+
+        for (gamepad_id, gamepad) in output.gamepads.iter_mut() {
+            let bypass = message.estop_engaged || !gamepad.connected;
+            for (axis, value) in gamepad.axis_state.iter_mut() {
+                let Some(filter) = self.smoothing.get(axis) else {
+                    continue;
+                };
+                // sticks rest at zero before their first event
+                let previous = self.filtered.entry((*gamepad_id, *axis)).or_insert(0.0);
+                if !bypass {
+                    *value = filter.step(*previous, *value, elapsed);
+                }
+                *previous = *value;
+            }
+        }
+        output
+    }
+}
@@ -0,0 +1,189 @@
+//! Robot specific input logic in Rhai scripts
+//!
+//! A profile's `script` can define any of these functions:
+//!
+//! ```text
+//! // every raw input event, such as #{type: "button_down", gamepad_id: 0, button: "DPadUp"}
+//! fn on_event(event, state) { }
+//! // every publish tick, returning a state replaces the published message
+//! fn on_tick(state) { state }
+//! ```
+//!
+//! `state` is the [`InputMessage`] as it is published in JSON. `publish(topic, value)`
+//! sends `value` as JSON on any zenoh key and `this` is a map kept between calls, for
+//! things like the selected gait or speed mode.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{gamepad::InputEvent, messages::InputMessage};
+
+/// Limits a single call so a runaway loop can't stall the gamepad reader
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// `script` section of a robot profile
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ScriptSource {
+    Path(PathBuf),
+    Source(String),
+}
+
+impl ScriptSource {
+    pub fn read(&self) -> anyhow::Result<String> {
+        match self {
+            ScriptSource::Path(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read script {}", path.display())),
+            ScriptSource::Source(source) => Ok(source.clone()),
+        }
+    }
+
+    /// Compiles the script so syntax errors are reported when the profile is loaded
+    pub fn validate(&self) -> anyhow::Result<()> {
+        InputScript::compile(&self.read()?)?;
+        Ok(())
+    }
+}
+
+/// Message sent by a script with `publish`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptMessage {
+    pub topic: String,
+    pub payload: serde_json::Value,
+}
+
+pub struct InputScript {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    /// Bound to `this`
+    state: Dynamic,
+    outbox: Arc<Mutex<Vec<ScriptMessage>>>,
+}
+
+impl InputScript {
+    pub fn compile(source: &str) -> anyhow::Result<Self> {
+        let outbox = Arc::new(Mutex::new(vec![]));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.register_fn("publish", {
+            let outbox = outbox.clone();
+            move |topic: &str, value: Dynamic| -> Result<(), Box<rhai::EvalAltResult>> {
+                let payload: serde_json::Value = rhai::serde::from_dynamic(&value)?;
+                outbox.lock().unwrap().push(ScriptMessage {
+                    topic: topic.to_owned(),
+                    payload,
+                });
+                Ok(())
+            }
+        });
+        let ast = engine.compile(source).context("Invalid input script")?;
+        Ok(Self {
+            engine,
+            ast,
+            scope: Scope::new(),
+            state: Dynamic::from_map(Map::new()),
+            outbox,
+        })
+    }
+
+    pub fn load(source: &ScriptSource) -> anyhow::Result<Self> {
+        Self::compile(&source.read()?)
+    }
+
+    /// Messages published while the script handled `input_event`
+    ///
+    /// `message` is the state after the event was applied.
+    pub fn on_event(
+        &mut self,
+        input_event: &InputEvent,
+        message: &InputMessage,
+    ) -> anyhow::Result<Vec<ScriptMessage>> {
+        if !self.has_function("on_event", 2) {
+            return Ok(vec![]);
+        }
+        let Some(event) = script_event(input_event) else {
+            return Ok(vec![]);
+        };
+        let args = (rhai::serde::to_dynamic(event)?, to_script_state(message)?);
+        self.call("on_event", args)?;
+        Ok(self.take_outbox())
+    }
+
+    /// Let the script change the message about to be published
+    pub fn on_tick(&mut self, message: &mut InputMessage) -> anyhow::Result<Vec<ScriptMessage>> {
+        if !self.has_function("on_tick", 1) {
+            return Ok(vec![]);
+        }
+        let result = self.call("on_tick", (to_script_state(message)?,))?;
+        if !result.is_unit() {
+            let state: serde_json::Value = rhai::serde::from_dynamic(&result)?;
+            *message =
+                serde_json::from_value(state).context("on_tick returned an invalid state")?;
+        }
+        Ok(self.take_outbox())
+    }
+
+    fn has_function(&self, name: &str, params: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|function| function.name == name && function.params.len() == params)
+    }
+
+    fn call(&mut self, name: &str, args: impl rhai::FuncArgs) -> anyhow::Result<Dynamic> {
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        let result =
+            self.engine
+                .call_fn_with_options(options, &mut self.scope, &self.ast, name, args);
+        // messages of a failed call are dropped with it
+        result.map_err(|err| {
+            self.outbox.lock().unwrap().clear();
+            anyhow::anyhow!("{name} failed: {err}")
+        })
+    }
+
+    fn take_outbox(&self) -> Vec<ScriptMessage> {
+        std::mem::take(&mut *self.outbox.lock().unwrap())
+    }
+}
+
+/// State as published in JSON, gamepad ids become string keys
+fn to_script_state(message: &InputMessage) -> anyhow::Result<Dynamic> {
+    Ok(rhai::serde::to_dynamic(serde_json::to_value(message)?)?)
+}
+
+fn script_event(input_event: &InputEvent) -> Option<serde_json::Value> {
+    let event = match input_event {
+        InputEvent::ButtonPressed { gamepad_id, button } => {
+            json!({ "type": "button_down", "gamepad_id": gamepad_id, "button": button })
+        }
+        InputEvent::ButtonReleased { gamepad_id, button } => {
+            json!({ "type": "button_up", "gamepad_id": gamepad_id, "button": button })
+        }
+        InputEvent::AxisChanged {
+            gamepad_id,
+            axis,
+            value,
+        } => {
+            json!({ "type": "axis", "gamepad_id": gamepad_id, "axis": axis, "value": value })
+        }
+        InputEvent::Connected { gamepad_id } => {
+            json!({ "type": "connected", "gamepad_id": gamepad_id })
+        }
+        InputEvent::Disconnected { gamepad_id } => {
+            json!({ "type": "disconnected", "gamepad_id": gamepad_id })
+        }
+        // periodic status reports aren't input
+        InputEvent::Gamepads(_) => return None,
+    };
+    Some(event)
+}
@@ -0,0 +1,97 @@
+//! Named JSON schemas that `json_schema_name` of a JSON subscription refers to
+//!
+//! Schemas are named after their file without the `.json` extension. Files in the
+//! schema directory replace built-in schemas with the same name.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use tracing::*;
+
+use crate::error::ConfigError;
+
+const BUILTIN_SCHEMAS: &[(&str, &str)] = &[
+    (
+        "CLIMATE_SENSOR_JSON_SCHEMA",
+        include_str!("../config/schemas/CLIMATE_SENSOR_JSON_SCHEMA.json"),
+    ),
+    (
+        "CONTACT_SENSOR_JSON_SCHEMA",
+        include_str!("../config/schemas/CONTACT_SENSOR_JSON_SCHEMA.json"),
+    ),
+    (
+        "GENERIC_JSON_SCHEMA",
+        include_str!("../config/schemas/GENERIC_JSON_SCHEMA.json"),
+    ),
+    (
+        "IKEA_DIMMER_JSON_SCHEMA",
+        include_str!("../config/schemas/IKEA_DIMMER_JSON_SCHEMA.json"),
+    ),
+    (
+        "MOTION_SENSOR_JSON_SCHEMA",
+        include_str!("../config/schemas/MOTION_SENSOR_JSON_SCHEMA.json"),
+    ),
+    (
+        "VOICE_PROBABILITY_JSON_SCHEMA",
+        include_str!("../config/schemas/VOICE_PROBABILITY_JSON_SCHEMA.json"),
+    ),
+];
+
+/// Schema name mapped to the schema
+pub type JsonSchemas = BTreeMap<String, String>;
+
+/// Built-in schemas plus all `*.json` schemas in `schema_dir`
+pub fn load_json_schemas(schema_dir: Option<&Path>) -> Result<JsonSchemas, ConfigError> {
+    let mut schemas: JsonSchemas = BUILTIN_SCHEMAS
+        .iter()
+        .map(|(name, schema)| ((*name).to_owned(), (*schema).to_owned()))
+        .collect();
+
+    if let Some(schema_dir) = schema_dir {
+        for path in schema_files(schema_dir)? {
+            let name = path
+                .file_stem()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| ConfigError::Invalid {
+                    name: format!("schema {}", path.display()),
+                    reason: "file name isn't UTF-8".to_owned(),
+                })?
+                .to_owned();
+            let schema = std::fs::read_to_string(&path).map_err(|error| ConfigError::Read {
+                path: path.clone(),
+                error,
+            })?;
+            // Foxglove silently drops channels with broken schemas
+            serde_json::from_str::<serde_json::Value>(&schema).map_err(|err| {
+                ConfigError::Invalid {
+                    name: format!("schema {}", path.display()),
+                    reason: err.to_string(),
+                }
+            })?;
+            debug!("Loaded json schema {name} from {}", path.display());
+            schemas.insert(name, schema);
+        }
+    }
+    Ok(schemas)
+}
+
+fn schema_files(schema_dir: &Path) -> Result<Vec<PathBuf>, ConfigError> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(schema_dir)
+        .map_err(|error| ConfigError::Read {
+            path: schema_dir.to_owned(),
+            error,
+        })?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,290 @@
+//! Driving the robot from a laptop keyboard when no gamepad is available
+//!
+//! The terminal is put into raw mode and keys are mapped onto a virtual gamepad:
+//!
+//! | Keys            | Gamepad                  |
+//! |-----------------|--------------------------|
+//! | W/S, Up/Down    | LeftStickY               |
+//! | A/D, Left/Right | LeftStickX               |
+//! | Q/E             | RightStickX              |
+//! | Space           | South                    |
+//! | Tab             | Select                   |
+//! | Enter           | Start (re-arm)           |
+//! | Esc             | Mode (emergency stop)    |
+//! | Ctrl-C          | quit                     |
+//!
+//! Most terminals don't report key releases. When the terminal doesn't support the
+//! keyboard enhancement protocol a key counts as held until its auto repeat stops.
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    terminal,
+};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use crate::{
+    gamepad::{GamepadStatus, InputBackend, InputEvent},
+    messages::{Axis, Button, PowerState},
+};
+
+const KEYBOARD_GAMEPAD_ID: usize = 0;
+const KEYBOARD_GAMEPAD_NAME: &str = "Keyboard";
+/// Longer than the usual delay before a held key starts repeating
+pub const KEY_HOLD_TIMEOUT: Duration = Duration::from_millis(600);
+const KEYBOARD_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// What a key controls on the virtual gamepad
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyTarget {
+    Axis { axis: Axis, positive: bool },
+    Button(Button),
+}
+
+pub fn key_target(code: KeyCode) -> Option<KeyTarget> {
+    let axis = |axis, positive| Some(KeyTarget::Axis { axis, positive });
+    match code {
+        KeyCode::Char(c) => match c.to_ascii_lowercase() {
+            'w' => axis(Axis::LeftStickY, true),
+            's' => axis(Axis::LeftStickY, false),
+            'a' => axis(Axis::LeftStickX, false),
+            'd' => axis(Axis::LeftStickX, true),
+            'q' => axis(Axis::RightStickX, false),
+            'e' => axis(Axis::RightStickX, true),
+            ' ' => Some(KeyTarget::Button(Button::South)),
+            _ => None,
+        },
+        KeyCode::Up => axis(Axis::LeftStickY, true),
+        KeyCode::Down => axis(Axis::LeftStickY, false),
+        KeyCode::Left => axis(Axis::LeftStickX, false),
+        KeyCode::Right => axis(Axis::LeftStickX, true),
+        KeyCode::Tab => Some(KeyTarget::Button(Button::Select)),
+        KeyCode::Enter => Some(KeyTarget::Button(Button::Start)),
+        KeyCode::Esc => Some(KeyTarget::Button(Button::Mode)),
+        _ => None,
+    }
+}
+
+/// Keys currently held and when they were last seen
+#[derive(Debug, Clone, Default)]
+pub struct KeyboardState {
+    held: HashMap<KeyTarget, Instant>,
+}
+
+impl KeyboardState {
+    pub fn press(&mut self, target: KeyTarget, now: Instant) {
+        self.held.insert(target, now);
+    }
+
+    pub fn release(&mut self, target: KeyTarget) {
+        self.held.remove(&target);
+    }
+
+    /// Release keys that haven't repeated within `hold_timeout`
+    pub fn release_stale(&mut self, now: Instant, hold_timeout: Duration) {
+        self.held
+            .retain(|_, last_seen| now.duration_since(*last_seen) < hold_timeout);
+    }
+
+    pub fn release_all(&mut self) {
+        self.held.clear();
+    }
+
+    /// Opposite keys held together cancel out
+    pub fn axis(&self, axis: Axis) -> f32 {
+        let held = |positive| self.held.contains_key(&KeyTarget::Axis { axis, positive });
+        match (held(true), held(false)) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        }
+    }
+
+    pub fn pressed(&self, button: Button) -> bool {
+        self.held.contains_key(&KeyTarget::Button(button))
+    }
+}
+
+/// Input backend reading the keyboard of the terminal the remote runs in
+///
+/// Ctrl-C doesn't raise a signal in raw mode so it cancels `quit` instead.
+#[derive(Debug, Clone)]
+pub struct KeyboardInput {
+    quit: CancellationToken,
+}
+
+impl KeyboardInput {
+    pub fn new(quit: CancellationToken) -> Self {
+        Self { quit }
+    }
+}
+
+impl InputBackend for KeyboardInput {
+    fn start(
+        &self,
+        input_sender: mpsc::Sender<InputEvent>,
+        status_interval: Duration,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let quit = self.quit.clone();
+        std::thread::Builder::new()
+            .name(String::from("keyboard"))
+            .spawn(move || {
+                let raw_mode = match RawMode::enable() {
+                    Ok(raw_mode) => raw_mode,
+                    Err(err) => {
+                        error!("Failed to put terminal into raw mode {err:?}");
+                        return;
+                    }
+                };
+                let result = read_keyboard(
+                    &input_sender,
+                    status_interval,
+                    raw_mode.reports_releases,
+                    &quit,
+                    &shutdown,
+                );
+                drop(raw_mode);
+                if let Err(err) = result {
+                    debug!("Keyboard reader stopped {err:?}");
+                }
+            })?;
+        Ok(())
+    }
+}
+
+/// Restores the terminal when dropped
+struct RawMode {
+    reports_releases: bool,
+}
+
+impl RawMode {
+    fn enable() -> anyhow::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false)
+            && crossterm::execute!(
+                std::io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )
+            .is_ok();
+        if !reports_releases {
+            warn!("Terminal doesn't report key releases, keys are released when auto repeat stops");
+        }
+        Ok(Self { reports_releases })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if self.reports_releases {
+            _ = crossterm::execute!(std::io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        _ = terminal::disable_raw_mode();
+        _ = std::io::stdout().flush();
+    }
+}
+
+fn read_keyboard(
+    input_sender: &mpsc::Sender<InputEvent>,
+    status_interval: Duration,
+    reports_releases: bool,
+    quit: &CancellationToken,
+    shutdown: &CancellationToken,
+) -> anyhow::Result<()> {
+    info!("Reading keyboard input, WASD/arrows to drive, Esc for emergency stop, Ctrl-C to quit");
+    input_sender.blocking_send(InputEvent::Connected {
+        gamepad_id: KEYBOARD_GAMEPAD_ID,
+    })?;
+
+    let mut state = KeyboardState::default();
+    let mut sent_axes: HashMap<Axis, f32> = HashMap::new();
+    let mut sent_buttons: HashMap<Button, bool> = HashMap::new();
+    let mut last_status: Option<Instant> = None;
+    while !shutdown.is_cancelled() {
+        if crossterm::event::poll(KEYBOARD_POLL_INTERVAL)? {
+            match crossterm::event::read()? {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                }) => {
+                    info!("Ctrl-C pressed on keyboard input");
+                    quit.cancel();
+                    break;
+                }
+                Event::Key(event) => {
+                    if let Some(target) = key_target(event.code) {
+                        match event.kind {
+                            KeyEventKind::Press | KeyEventKind::Repeat => {
+                                state.press(target, Instant::now())
+                            }
+                            KeyEventKind::Release => state.release(target),
+                        }
+                    }
+                }
+                Event::FocusLost => state.release_all(),
+                _ => {}
+            }
+        }
+        let now = Instant::now();
+        if !reports_releases {
+            state.release_stale(now, KEY_HOLD_TIMEOUT);
+        }
+
+        for axis in [Axis::LeftStickX, Axis::LeftStickY, Axis::RightStickX] {
+            let value = state.axis(axis);
+            if sent_axes.insert(axis, value) != Some(value) {
+                input_sender.blocking_send(InputEvent::AxisChanged {
+                    gamepad_id: KEYBOARD_GAMEPAD_ID,
+                    axis,
+                    value,
+                })?;
+            }
+        }
+        for button in [Button::South, Button::Select, Button::Start, Button::Mode] {
+            let pressed = state.pressed(button);
+            if sent_buttons.insert(button, pressed).unwrap_or(false) != pressed {
+                let event = if pressed {
+                    InputEvent::ButtonPressed {
+                        gamepad_id: KEYBOARD_GAMEPAD_ID,
+                        button,
+                    }
+                } else {
+                    InputEvent::ButtonReleased {
+                        gamepad_id: KEYBOARD_GAMEPAD_ID,
+                        button,
+                    }
+                };
+                input_sender.blocking_send(event)?;
+            }
+        }
+
+        if !last_status.is_some_and(|last| now.duration_since(last) < status_interval) {
+            last_status = Some(now);
+            let status = GamepadStatus {
+                gamepad_id: KEYBOARD_GAMEPAD_ID,
+                name: KEYBOARD_GAMEPAD_NAME.to_owned(),
+                connected: true,
+                button_down: Button::all_gilrs_buttons()
+                    .iter()
+                    .map(|button| Button::from(*button))
+                    .map(|button| (button, state.pressed(button)))
+                    .collect(),
+                power_state: PowerState::Wired,
+                battery_percent: None,
+            };
+            input_sender.blocking_send(InputEvent::Gamepads(vec![status]))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,182 @@
+//! Gamepad input and Foxglove bridge for remote controlling robots over zenoh
+//!
+//! The `deck-robot-remote` binary is a thin wrapper around this crate. Other tools can
+//! embed the same pieces:
+//!
+//! - [`gamepad`] reads gamepads through an [`InputBackend`] and publishes [`InputMessage`]s
+//! - [`foxglove_server`] bridges zenoh topics to a Foxglove websocket
+//! - [`tailscale`] discovers robots on the tailnet and adds them as zenoh endpoints
+//! - [`supervisor`] runs all of the above as restartable tasks
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use deck_robot_remote::{
+//!     clock::SystemClock, start_gamepad_reader, stats::StatsRegistry, GamepadReaderConfig,
+//!     GilrsBackend, Supervisor,
+//! };
+//! use tokio_util::sync::CancellationToken;
+//! use zenoh::prelude::r#async::*;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let session = zenoh::open(Config::default()).res().await.unwrap().into_arc();
+//! let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+//! let stats = StatsRegistry::default();
+//! start_gamepad_reader(
+//!     &supervisor,
+//!     session,
+//!     GamepadReaderConfig {
+//!         pub_topic: "remote-control/gamepad".to_owned(),
+//!         ..Default::default()
+//!     },
+//!     stats.gamepad(),
+//!     Arc::new(SystemClock),
+//!     Arc::new(GilrsBackend::default()),
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+/// Timestamped operator notes in a session log and Foxglove
+pub mod annotations;
+/// Operator intercom over zenoh
+pub mod audio;
+/// Randomized exponential backoff
+pub mod backoff;
+/// Wall clock abstraction so time can be mocked
+pub mod clock;
+/// Robot profiles
+pub mod config;
+/// Connection state of the link to the active robot
+pub mod connection;
+/// Web control panel served next to the Foxglove socket
+pub mod control_panel;
+/// Crash reports published from the panic hook
+pub mod crash_report;
+/// Hold-to-enable switch for motion input
+pub mod deadman;
+/// Keyframe aware rate limiting of bridged protobuf topics
+pub mod decimation;
+/// Checks behind the `doctor` subcommand
+pub mod doctor;
+/// Errors of the remote's subsystems
+pub mod error;
+/// Failing over between the tailscale peers of one robot
+pub mod failover;
+/// Foxglove clients attached to the bridge
+pub mod foxglove_clients;
+/// Foxglove protocol features on top of foxglove-ws
+pub mod foxglove_gateway;
+/// Pushing and pulling Foxglove layouts through the Foxglove API
+pub mod foxglove_layouts;
+/// Foxglove WebSocket protocol messages handled by the gateway
+pub mod foxglove_protocol;
+/// Bridge from zenoh topics to a Foxglove websocket server
+pub mod foxglove_server;
+/// Gamepad reader publishing [`InputMessage`]s
+pub mod gamepad;
+/// Warnings for gamepads running low on battery
+pub mod gamepad_battery;
+/// Button and axis threshold events published as they happen
+pub mod gamepad_events;
+/// Subsystem heartbeats and the aggregated health topic
+pub mod health;
+/// Steam Deck IMU publisher
+pub mod imu;
+/// Axis smoothing between raw input and the published message
+pub mod input_pipeline;
+/// Rhai scripts for robot specific input logic
+pub mod input_script;
+/// Named JSON schemas for JSON subscriptions
+pub mod json_schemas;
+/// Keyboard teleop input backend
+pub mod keyboard_input;
+/// Round trip time monitor
+pub mod link_stats;
+/// Rate limiting for repeated error logs
+pub mod log_throttle;
+/// Recording and replaying gamepad input
+pub mod macros;
+/// Messages published by the remote
+pub mod messages;
+/// Velocity commands mixed from stick input
+pub mod mixer;
+/// Named speed modes selected by holding a button
+pub mod modifiers;
+/// MQTT to zenoh bridge
+pub mod mqtt_bridge;
+/// Battery, temperature and WiFi signal of the Deck
+pub mod operator_station;
+/// Input device permission checks
+pub mod permissions;
+/// Protobuf message types loaded at runtime
+pub mod proto_descriptors;
+/// Reconnecting after the zenoh link to the robot was lost
+pub mod reconnect;
+/// Replaying recorded sessions onto zenoh
+pub mod replay;
+/// Switching the active robot at runtime
+pub mod robot_registry;
+/// Gamepad state as a ROS 2 `sensor_msgs/Joy`
+pub mod ros2;
+/// JSON schemas inferred from sampled messages
+pub mod schema_inference;
+/// Schemas of published and bridged topics served on `<topic>/__schema__`
+pub mod schema_registry;
+/// Startup check that bridged topics have publishers
+pub mod self_test;
+/// Zenoh shared memory for subscribers on the same machine
+pub mod shared_memory;
+/// Scripted input backend
+pub mod sim_input;
+/// Live counters and latency histograms
+pub mod stats;
+/// Restarting task supervisor
+pub mod supervisor;
+/// Tailscale status parsing and zenoh endpoint discovery
+pub mod tailscale;
+/// wss termination for the Foxglove server
+pub mod tls_proxy;
+/// Steam Deck trackpad publisher
+pub mod touchpad;
+/// Payload transforms applied before forwarding to Foxglove
+pub mod transform;
+/// Live terminal dashboard for `--tui`
+pub mod tui;
+/// User/password and TLS certificate authentication of the zenoh link
+pub mod zenoh_auth;
+
+pub use foxglove_server::{start_foxglove_bridge, FoxgloveServerConfiguration};
+pub use gamepad::{start_gamepad_reader, GamepadReaderConfig, GilrsBackend, InputBackend};
+pub use messages::{
+    Axis, Button, ButtonCounterPolicy, EstopMessage, GamepadEncoding, GamepadMessage, InputMessage,
+    RumbleCommand, TouchpadMessage, WatchdogMessage,
+};
+pub use supervisor::Supervisor;
+pub use tailscale::{add_tailscale_endpoints, TailscaleStatus};
+
+use once_cell::sync::Lazy;
+use prost_reflect::DescriptorPool;
+
+static FILE_DESCRIPTOR_SET: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+
+pub static DESCRIPTOR_POOL: Lazy<DescriptorPool> = Lazy::new(|| {
+    DescriptorPool::decode(FILE_DESCRIPTOR_SET).expect("Failed to load file descriptor set")
+});
+
+/// protobuf
+pub mod foxglove {
+    #![allow(non_snake_case)]
+    include!(concat!(env!("OUT_DIR"), "/foxglove.rs"));
+}
+
+pub mod hopper {
+    #![allow(non_snake_case)]
+    include!(concat!(env!("OUT_DIR"), "/hopper.rs"));
+}
+
+pub mod remote_control {
+    #![allow(non_snake_case)]
+    include!(concat!(env!("OUT_DIR"), "/remote_control.rs"));
+}
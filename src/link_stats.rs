@@ -0,0 +1,154 @@
+//! Round trip time to the robot measured with queries to an echo queryable on the robot
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    error::ZenohError,
+    health::Heartbeat,
+    supervisor::Supervisor,
+};
+
+pub const LINK_STATS_TOPIC: &str = "remote-control/link_stats";
+pub const DEFAULT_ECHO_TOPIC: &str = "remote-control/echo";
+
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// Pings without a reply in this time count as lost
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+/// Number of recent pings the statistics are computed over
+const PING_WINDOW: usize = 20;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LinkStatsMessage {
+    /// Round trip time of the latest ping, not set if it was lost
+    pub rtt_ms: Option<f64>,
+    pub rtt_avg_ms: Option<f64>,
+    pub rtt_max_ms: Option<f64>,
+    /// Fraction of lost pings in the window
+    pub loss_ratio: f64,
+    pub window: usize,
+    pub time: DateTime<Utc>,
+}
+
+/// Results of the most recent pings
+#[derive(Debug, Clone, Default)]
+pub struct PingWindow {
+    pings: VecDeque<Option<Duration>>,
+}
+
+impl PingWindow {
+    /// Record a round trip time or `None` for a lost ping
+    pub fn record(&mut self, rtt: Option<Duration>) {
+        if self.pings.len() == PING_WINDOW {
+            self.pings.pop_front();
+        }
+        self.pings.push_back(rtt);
+    }
+
+    pub fn message(&self, time: DateTime<Utc>) -> LinkStatsMessage {
+        let replies: Vec<f64> = self
+            .pings
+            .iter()
+            .flatten()
+            .map(|rtt| rtt.as_secs_f64() * 1000.0)
+            .collect();
+        let lost = self.pings.len() - replies.len();
+        LinkStatsMessage {
+            rtt_ms: self
+                .pings
+                .back()
+                .copied()
+                .flatten()
+                .map(|rtt| rtt.as_secs_f64() * 1000.0),
+            rtt_avg_ms: (!replies.is_empty())
+                .then(|| replies.iter().sum::<f64>() / replies.len() as f64),
+            rtt_max_ms: replies.iter().copied().reduce(f64::max),
+            loss_ratio: if self.pings.is_empty() {
+                0.0
+            } else {
+                lost as f64 / self.pings.len() as f64
+            },
+            window: self.pings.len(),
+            time,
+        }
+    }
+}
+
+pub fn start_link_monitor(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    echo_topic: String,
+    clock: SharedClock,
+) {
+    let heartbeat = supervisor.health().heartbeat("link_monitor");
+    supervisor.spawn("link_monitor", move |shutdown| {
+        run_link_monitor(
+            zenoh_session.clone(),
+            echo_topic.clone(),
+            clock.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_link_monitor(
+    zenoh_session: Arc<Session>,
+    echo_topic: String,
+    clock: SharedClock,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let publisher = zenoh_session
+        .declare_publisher(LINK_STATS_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut window = PingWindow::default();
+    let mut interval = tokio::time::interval(PING_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                heartbeat.beat();
+                let rtt = tokio::select! {
+                    rtt = ping(&zenoh_session, &echo_topic) => rtt?,
+                    _ = shutdown.cancelled() => break,
+                };
+                window.record(rtt);
+                let message = window.message(clock.now().into());
+                publisher
+                    .put(serde_json::to_string(&message)?)
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
+
+/// Round trip time of a query to `echo_topic` or `None` if nothing replied in time
+async fn ping(zenoh_session: &Session, echo_topic: &str) -> anyhow::Result<Option<Duration>> {
+    let sent = Instant::now();
+    let replies = zenoh_session
+        .get(echo_topic)
+        .timeout(PING_TIMEOUT)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    while let Ok(reply) = replies.recv_async().await {
+        if reply.sample.is_ok() {
+            return Ok(Some(sent.elapsed()));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tracing::*;
+
+pub const DEFAULT_THROTTLE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Logs repeated identical errors once per window and summarizes the repeats
+///
+/// `source` is attached to every log line, usually the topic the errors belong to.
+#[derive(Debug)]
+pub struct LogThrottle {
+    source: String,
+    window: Duration,
+    entries: HashMap<String, ThrottleEntry>,
+}
+
+#[derive(Debug)]
+struct ThrottleEntry {
+    window_start: Instant,
+    repeated: u64,
+}
+
+impl LogThrottle {
+    pub fn new(source: &str, window: Duration) -> Self {
+        Self {
+            source: source.to_owned(),
+            window,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn error(&mut self, message: &str) {
+        let now = Instant::now();
+        if let Some(entry) = self.entries.get_mut(message) {
+            if now.duration_since(entry.window_start) < self.window {
+                entry.repeated += 1;
+                return;
+            }
+            Self::log_summary(&self.source, self.window, message, entry.repeated);
+            entry.window_start = now;
+            entry.repeated = 0;
+        } else {
+            self.entries.insert(
+                message.to_owned(),
+                ThrottleEntry {
+                    window_start: now,
+                    repeated: 0,
+                },
+            );
+        }
+        error!(source = %self.source, "{}", message);
+    }
+
+    /// Summarize and forget errors whose window ended
+    ///
+    /// Should be called periodically so that repeats are reported even if the error stops.
+    pub fn flush(&mut self) {
+        let now = Instant::now();
+        let source = &self.source;
+        let window = self.window;
+        self.entries.retain(|message, entry| {
+            if now.duration_since(entry.window_start) < window {
+                return true;
+            }
+            Self::log_summary(source, window, message, entry.repeated);
+            false
+        });
+    }
+
+    fn log_summary(source: &str, window: Duration, message: &str, repeated: u64) {
+        if repeated > 0 {
+            error!(
+                source,
+                repeated, "Error repeated {} times in the last {:?}: {}", repeated, window, message
+            );
+        }
+    }
+}
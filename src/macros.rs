@@ -0,0 +1,131 @@
+//! Recording and replaying gamepad input for repeatable maneuvers
+//!
+//! Holding the record button records every published message. Pressing the play button
+//! publishes the recording with its original timing instead of live input, pressing it
+//! again stops early. The emergency stop aborts playback.
+
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tracing::*;
+
+use crate::messages::{Button, InputMessage};
+
+/// `macros` section of a robot profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacroConfig {
+    pub record_button: Button,
+    pub play_button: Button,
+}
+
+impl MacroConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.record_button == self.play_button {
+            anyhow::bail!("record_button and play_button must differ");
+        }
+        Ok(())
+    }
+}
+
+/// Messages with the time since recording started
+type Recording = Vec<(Duration, InputMessage)>;
+
+#[derive(Debug)]
+pub struct MacroEngine {
+    config: MacroConfig,
+    recording: Option<(Instant, Recording)>,
+    recorded: Recording,
+    playback: Option<Instant>,
+    play_was_pressed: bool,
+}
+
+fn pressed(message: &InputMessage, button: Button) -> bool {
+    message
+        .gamepads
+        .values()
+        .any(|gamepad| gamepad.button_down.get(&button) == Some(&true))
+}
+
+impl MacroEngine {
+    pub fn new(config: MacroConfig) -> Self {
+        Self {
+            config,
+            recording: None,
+            recorded: Recording::new(),
+            playback: None,
+            play_was_pressed: false,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Length of the stored recording
+    pub fn duration(&self) -> Duration {
+        self.recorded
+            .last()
+            .map_or(Duration::ZERO, |(offset, _)| *offset)
+    }
+
+    /// Message to publish instead of `live`, if a recording is playing
+    pub fn process(&mut self, live: &InputMessage, now: Instant) -> Option<InputMessage> {
+        let play_pressed = pressed(live, self.config.play_button);
+        let play_clicked = play_pressed && !self.play_was_pressed;
+        self.play_was_pressed = play_pressed;
+
+        if live.estop_engaged {
+            if self.playback.take().is_some() {
+                warn!("Macro playback aborted by emergency stop");
+            }
+            return None;
+        }
+
+        if pressed(live, self.config.record_button) && self.playback.is_none() {
+            let (started, recording) = self.recording.get_or_insert_with(|| {
+                info!("Recording macro");
+                (now, Recording::new())
+            });
+            let mut message = live.clone();
+            // the recording would otherwise hold the record button the whole time
+            for gamepad in message.gamepads.values_mut() {
+                gamepad.button_down.remove(&self.config.record_button);
+            }
+            recording.push((now.duration_since(*started), message));
+            return None;
+        }
+        if let Some((_, recording)) = self.recording.take() {
+            self.recorded = recording;
+            info!("Recorded macro of {:?}", self.duration());
+        }
+
+        if play_clicked {
+            if self.playback.take().is_some() {
+                info!("Macro playback stopped");
+            } else if self.recorded.is_empty() {
+                warn!("No macro recorded yet");
+            } else {
+                info!("Playing macro of {:?}", self.duration());
+                self.playback = Some(now);
+            }
+        }
+
+        let started = self.playback?;
+        let elapsed = now.duration_since(started);
+        if elapsed > self.duration() {
+            info!("Macro playback finished");
+            self.playback = None;
+            return None;
+        }
+        self.recorded
+            .iter()
+            .take_while(|(offset, _)| *offset <= elapsed)
+            .last()
+            .map(|(_, message)| message.clone())
+    }
+}
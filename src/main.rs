@@ -1,49 +1,125 @@
-mod config;
-mod error;
-mod foxglove_server;
-mod gamepad;
-mod messages;
-mod tailscale;
-
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use tokio::{
     io::{self, AsyncBufReadExt},
-    process::Command,
+    process::{Child, Command},
 };
+use tokio_util::sync::CancellationToken;
 
 use anyhow::Context;
-use clap::{Parser, ValueEnum};
-use error::ErrorWrapper;
-use foxglove_server::{create_foxglove_url, start_foxglove_bridge, FoxgloveServerConfiguration};
-use gamepad::{start_gamepad_reader, start_schema_queryable};
-use tailscale::TailscaleStatus;
+use clap::{
+    parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use deck_robot_remote::{
+    annotations::{start_annotation_log, ANNOTATION_TOPIC, DEFAULT_ANNOTATION_NOTE},
+    audio::{start_audio_downlink, start_audio_uplink},
+    clock::{SharedClock, SystemClock},
+    config::{add_profiles, load_app_config, load_profiles, AppConfig, RobotProfile},
+    connection::{start_connection_monitor, ZenohPeers, CONNECTION_TOPIC},
+    control_panel::{start_control_panel, ControlPanel},
+    crash_report::install_panic_hook,
+    doctor::{
+        check_gamepads, foxglove_port_check, tailscale_checks, topic_checks, zenoh_link_check,
+        CheckStatus, DoctorCheck, DoctorReport,
+    },
+    error::{diagnose, ConfigError, ZenohError},
+    failover::start_failover_monitor,
+    foxglove_layouts::{read_layout_file, LayoutClient, DEFAULT_FOXGLOVE_API_URL},
+    foxglove_server::{
+        start_foxglove_bridge, FoxgloveHost, FoxgloveUrlBuilder, JsonSubscription, LogSubscription,
+        DEFAULT_QUEUE_SIZE,
+    },
+    gamepad::{
+        start_feedback_subscriber, start_gamepad_reader, CongestionControlMode, EstopLatch,
+        GamepadReaderConfig, GilrsBackend, InputBackend, PublisherPriority, PublisherQos,
+    },
+    gamepad_battery::{DEFAULT_LOW_BATTERY_PERCENT, GAMEPAD_ALERT_TOPIC},
+    health::start_health_publisher,
+    imu::start_imu_publisher,
+    json_schemas::{load_json_schemas, JsonSchemas},
+    keyboard_input::KeyboardInput,
+    link_stats::{start_link_monitor, DEFAULT_ECHO_TOPIC, LINK_STATS_TOPIC},
+    messages::{Button, ButtonCounterPolicy, GamepadEncoding, InputMessage, PublishMode},
+    mqtt_bridge::start_mqtt_bridge,
+    operator_station::{start_operator_station_publisher, OPERATOR_STATION_TOPIC},
+    permissions::check_input_permissions,
+    proto_descriptors::load_descriptor_files,
+    reconnect::start_session_watchdog,
+    replay::{read_recording, replay, ReplayControls},
+    robot_registry::{start_robot_selector, RobotRegistry},
+    schema_registry::{start_schema_queryable, SchemaRegistry},
+    self_test::{log_report, run_self_test, SELF_TEST_TIMEOUT},
+    shared_memory::{enable_shared_memory, SharedMemoryPayloads},
+    sim_input::{SimInput, SimScript},
+    stats::{
+        start_bridge_stats_publisher, start_latency_publisher, start_stats_dump_on_signal,
+        start_stats_queryable, StatsRegistry, BRIDGE_STATS_TOPIC,
+    },
+    supervisor::Supervisor,
+    tailscale::{
+        add_tailscale_endpoints, peer_endpoints, start_tailscale_discovery, TailscaleStatus,
+    },
+    tls_proxy::{free_loopback_address, load_tls_acceptor, start_tls_proxy},
+    touchpad::start_touchpad_publisher,
+    tui::{start_dashboard_updater, start_tui, Dashboard, LogBuffer},
+    zenoh_auth::ZenohAuth,
+};
 
 use schemars::schema_for;
 use tracing::*;
 use zenoh::{config::Config, prelude::r#async::*};
 
-use once_cell::sync::Lazy;
-use prost_reflect::DescriptorPool;
+/// Used when the number of cores can't be determined
+const DEFAULT_WORKER_THREADS: usize = 2;
 
-use crate::messages::InputMessage;
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
-const ZENOH_TCP_DISCOVERY_PORT: u16 = 7436;
-
-const HAMILTON_FOXGLOVE_LAYOUT_ID: &str = "0948be25-5808-40db-a1d3-75e7810fe349";
-const HOPPER_FOXGLOVE_LAYOUT_ID: &str = "ea22e72c-f654-4743-925a-7143a510d390";
 const FLATPAK_CHROME_PATH: &str =
     "/var/lib/flatpak/app/com.google.Chrome/x86_64/stable/active/export/bin/com.google.Chrome";
 
 #[derive(Parser)]
 #[command(author, version)]
 struct Args {
-    #[clap(short, long, default_value = "hamilton")]
-    mode: Mode,
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+
+    /// YAML file with defaults for these flags and additional robot profiles
+    #[clap(long)]
+    config: Option<PathBuf>,
+
+    /// Profiles defined in --config
+    #[clap(skip)]
+    config_profiles: Vec<RobotProfile>,
+
+    /// Robot profile, built-in or from --profile-dir
+    ///
+    /// Several comma separated robots are all connected at once, gamepad input goes to the
+    /// active one under its topic prefix.
+    #[clap(short, long, default_value = "hamilton", value_delimiter = ',')]
+    mode: Vec<String>,
+
+    /// Directory with additional robot profiles
+    #[clap(long)]
+    profile_dir: Option<PathBuf>,
+
+    /// Directory with additional JSON schemas, named after the file without `.json`
+    #[clap(long)]
+    schema_dir: Option<PathBuf>,
 
-    /// The key expression to publish onto.
+    /// The key expression to publish onto, under the active robot's topic prefix or name.
     #[clap(short, long, default_value = "remote-control/gamepad")]
     gamepad_topic: String,
 
+    /// Publish on --gamepad-topic as is instead of under the active robot's namespace
+    #[clap(long)]
+    global_gamepad_topic: bool,
+
     /// Endpoints to connect to.
     #[clap(short, long)]
     connect: Vec<zenoh_config::EndPoint>,
@@ -56,44 +132,659 @@ struct Args {
     #[clap(long)]
     zenoh_config: Option<String>,
 
+    #[clap(flatten)]
+    zenoh_auth: ZenohAuth,
+
+    /// Also use IPv6 tailscale addresses, for peers only reachable over IPv6
+    #[clap(long)]
+    ipv6: bool,
+
+    /// Hand gamepad and IMU messages to subscribers on this machine through zenoh shared memory
+    #[clap(long)]
+    shared_memory: bool,
+
     /// Loop sleep time
     #[clap(short, long, default_value = "50")]
     sleep_ms: u64,
 
+    /// How button event counters behave over long sessions
+    #[clap(long, value_enum, default_value_t = ButtonCounterPolicy::Wrapping)]
+    button_counter_policy: ButtonCounterPolicy,
+
+    /// Largest value button event counters reach before wrapping or saturating
+    #[clap(long)]
+    button_counter_limit: Option<usize>,
+
+    /// Wire format of the published gamepad messages
+    #[clap(long, value_enum, default_value_t = GamepadEncoding::Json)]
+    gamepad_encoding: GamepadEncoding,
+
+    /// Button that latches the emergency stop
+    #[clap(long, default_value = "Mode")]
+    estop_button: Button,
+
+    /// Button that re-arms the emergency stop
+    #[clap(long, default_value = "Start")]
+    estop_rearm_button: Button,
+
+    /// Button that switches gamepad input to the next robot given with --mode
+    #[clap(long)]
+    target_button: Option<Button>,
+
+    /// Button that records a timestamped annotation, shown in Foxglove and the annotation log
+    #[clap(long)]
+    annotation_button: Option<Button>,
+
+    /// Note recorded by --annotation-button
+    #[clap(long, default_value = DEFAULT_ANNOTATION_NOTE)]
+    annotation_note: String,
+
+    /// Append every annotation to this file as JSON lines
+    #[clap(long)]
+    annotation_log: Option<PathBuf>,
+
+    /// Warn in Foxglove when a gamepad battery drops below this percentage, 0 turns it off
+    #[clap(long, default_value_t = DEFAULT_LOW_BATTERY_PERCENT)]
+    low_battery_percent: u8,
+
+    /// Rumble a gamepad when it runs low on battery
+    #[clap(long)]
+    low_battery_rumble: bool,
+
+    /// Publish a neutral message if no gamepad input arrives for this long
+    #[clap(long, default_value_t = 500)]
+    watchdog_timeout_ms: u64,
+
+    /// When gamepad messages are published, on-change and hybrid save bandwidth on slow links
+    #[clap(long, value_enum, default_value_t = PublishMode::Periodic)]
+    publish_mode: PublishMode,
+
+    /// Zenoh priority of gamepad messages, real-time keeps them ahead of camera frames
+    #[clap(long, value_enum, default_value_t = PublisherPriority::Data)]
+    gamepad_priority: PublisherPriority,
+
+    /// What the gamepad publisher does when the link is congested
+    #[clap(long, value_enum, default_value_t = CongestionControlMode::Drop)]
+    gamepad_congestion_control: CongestionControlMode,
+
+    /// Send gamepad messages immediately instead of batching them
+    #[clap(long)]
+    gamepad_express: bool,
+
+    /// Longest time without a gamepad message in hybrid publish mode
+    #[clap(long, default_value_t = 1000)]
+    keepalive_ms: u64,
+
+    /// Echo queryable on the robot used to measure round trip time
+    #[clap(long, default_value = DEFAULT_ECHO_TOPIC)]
+    echo_topic: String,
+
+    /// Bridge every topic matching this key expression (e.g. "hopper/**") to Foxglove
+    #[clap(long)]
+    auto_discover: Option<String>,
+
+    /// Publish Steam Deck gyro and accelerometer readings on <gamepad topic>/imu
+    #[clap(long)]
+    enable_imu: bool,
+
+    /// Publish the Steam Deck trackpads on <gamepad topic>/touchpad
+    #[clap(long)]
+    enable_touchpad: bool,
+
+    /// Where gamepad input comes from
+    #[clap(long, value_enum, default_value_t = Input::Gilrs)]
+    input: Input,
+
+    /// Script played by the simulated gamepad
+    #[clap(long, required_if_eq("input", "sim"))]
+    sim_script: Option<PathBuf>,
+
+    /// Publish the microphone as Opus on remote-control/audio/uplink
+    #[clap(long)]
+    audio_uplink: bool,
+
+    /// Only send microphone audio while this button is held
+    #[clap(long, requires = "audio_uplink")]
+    push_to_talk: Option<Button>,
+
+    /// Play Opus audio published by the robot on this topic
+    #[clap(long)]
+    audio_downlink: Option<String>,
+
+    /// Also write the stats dump triggered by SIGUSR1 to this file
+    #[clap(long)]
+    stats_dump_file: Option<PathBuf>,
+
+    /// Check that every bridged topic has a publisher or queryable before starting
+    #[clap(long)]
+    self_test: bool,
+
+    /// Number of tokio worker threads, defaults to the number of cores
+    #[clap(long)]
+    worker_threads: Option<NonZeroUsize>,
+
     /// verbosity level
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 
-    /// foxglove bind address
+    /// foxglove bind address, `tailscale` or `tailscale:<port>` binds our tailscale address
     #[clap(long, default_value = "127.0.0.1:8765")]
-    host: SocketAddr,
+    host: FoxgloveHost,
+
+    /// PEM certificate chain, serves wss:// so app.foxglove.dev can connect to a remote host
+    #[clap(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[clap(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Serve the web control panel on this port of the Foxglove bind address
+    #[clap(long)]
+    control_panel_port: Option<u16>,
 
     #[clap(long, default_value = "david-weis")]
     foxglove_user: String,
 
+    /// Studio link template for self-hosted Foxglove or Lichtblick, overrides the robot profile
+    #[clap(long)]
+    foxglove_studio_url: Option<String>,
+
     #[clap(long)]
     foxglove_layout_id: Option<String>,
 
     /// Open browser
     #[clap(short, long, default_value = "true")]
     browser: bool,
+
+    /// Browser command used to open the foxglove link, the link is appended as the last argument
+    #[clap(long)]
+    browser_cmd: Option<String>,
+
+    /// Don't open a browser or wait for enter, for running as a service
+    #[clap(long)]
+    headless: bool,
+
+    /// Show a live dashboard in the terminal instead of the scrolling log
+    #[clap(long, conflicts_with = "headless")]
+    tui: bool,
+
+    /// Serve task instrumentation for tokio-console
+    #[cfg(feature = "tokio-console")]
+    #[clap(long)]
+    tokio_console: bool,
+}
+
+impl Args {
+    /// Take values from the config file for every flag that wasn't given on the command line
+    fn apply_config(&mut self, config: AppConfig, matches: &ArgMatches) {
+        fn merge<T>(target: &mut T, value: Option<T>, id: &str, matches: &ArgMatches) {
+            if matches.value_source(id) == Some(ValueSource::CommandLine) {
+                return;
+            }
+            if let Some(value) = value {
+                *target = value;
+            }
+        }
+        merge(
+            &mut self.mode,
+            config
+                .mode
+                .map(|mode| mode.split(',').map(|name| name.trim().to_owned()).collect()),
+            "mode",
+            matches,
+        );
+        merge(
+            &mut self.profile_dir,
+            config.profile_dir.map(Some),
+            "profile_dir",
+            matches,
+        );
+        merge(
+            &mut self.schema_dir,
+            config.schema_dir.map(Some),
+            "schema_dir",
+            matches,
+        );
+        merge(
+            &mut self.gamepad_topic,
+            config.gamepad_topic,
+            "gamepad_topic",
+            matches,
+        );
+        merge(
+            &mut self.global_gamepad_topic,
+            config.global_gamepad_topic,
+            "global_gamepad_topic",
+            matches,
+        );
+        merge(&mut self.sleep_ms, config.sleep_ms, "sleep_ms", matches);
+        merge(&mut self.connect, config.connect, "connect", matches);
+        merge(&mut self.listen, config.listen, "listen", matches);
+        merge(
+            &mut self.shared_memory,
+            config.shared_memory,
+            "shared_memory",
+            matches,
+        );
+        if let Some(zenoh_auth) = config.zenoh_auth {
+            self.zenoh_auth = std::mem::take(&mut self.zenoh_auth).or(zenoh_auth);
+        }
+        merge(&mut self.host, config.host, "host", matches);
+        merge(
+            &mut self.foxglove_user,
+            config.foxglove_user,
+            "foxglove_user",
+            matches,
+        );
+        self.config_profiles = config.profiles;
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    /// Check that input devices are readable and print how to fix them if not
+    CheckPermissions,
+    /// Check gamepad, tailscale, zenoh, robot telemetry and the Foxglove port and print a report
+    Doctor {
+        /// Print the report as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Publish a recorded session (.mcap or JSON lines) onto zenoh with its original timing
+    Replay {
+        recording: PathBuf,
+        /// Playback speed, 2.0 plays twice as fast
+        #[clap(long, default_value_t = 1.0)]
+        speed: f64,
+    },
+    /// Manage Foxglove layouts referenced by `foxglove_layout_id` in robot profiles
+    Layouts {
+        /// Foxglove API key with layout access
+        #[clap(long, env = "FOXGLOVE_API_KEY", hide_env_values = true)]
+        api_key: String,
+        #[clap(long, default_value = DEFAULT_FOXGLOVE_API_URL)]
+        api_url: String,
+        #[command(subcommand)]
+        command: LayoutsCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum LayoutsCommand {
+    /// Print the id and name of every layout
+    List,
+    /// Save the data of a layout as JSON, to stdout without a file
+    Pull { id: String, file: Option<PathBuf> },
+    /// Upload a layout exported from Studio and print its id
+    Push {
+        file: PathBuf,
+        /// Name of a new layout, or the new name of an updated one
+        #[clap(long, required_unless_present = "id")]
+        name: Option<String>,
+        /// Replace the data of this layout instead of creating a new one
+        #[clap(long)]
+        id: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
-enum Mode {
-    Hamilton,
-    Guppy,
-    Hopper,
+enum Input {
+    /// Real gamepads read through gilrs
+    Gilrs,
+    /// Scripted gamepad for testing without a human on the controller
+    Sim,
+    /// WASD and arrow keys in the terminal for driving without a controller
+    Keyboard,
+}
+
+fn main() {
+    if let Err(err) = run_command() {
+        eprintln!("Error: {err:?}");
+        if let Some(hint) = diagnose(&err).and_then(|diagnostic| diagnostic.hint) {
+            eprintln!("\nHint: {hint}");
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run_command() -> anyhow::Result<()> {
+    let matches = Args::command().get_matches();
+    let mut args = parse_args(&matches)?;
+    if let Some(CliCommand::CheckPermissions) = args.command {
+        let report = check_input_permissions()?;
+        report.print();
+        if !report.is_ok() {
+            anyhow::bail!("Input devices are not readable");
+        }
+        return Ok(());
+    }
+
+    let runtime = build_runtime(args.worker_threads)?;
+    if let Some(CliCommand::Doctor { json }) = args.command {
+        return runtime.block_on(run_doctor(args, json));
+    }
+    if let Some(CliCommand::Replay { recording, speed }) = args.command.take() {
+        return runtime.block_on(run_replay(args, &recording, speed));
+    }
+    if let Some(CliCommand::Layouts {
+        api_key,
+        api_url,
+        command,
+    }) = args.command.take()
+    {
+        let client = LayoutClient::new(&api_url, &api_key);
+        return runtime.block_on(run_layouts(&client, command));
+    }
+    // logging and the browser are kept when the configuration is reloaded
+    let log_buffer = setup_logging(&args);
+    let mut browser = BrowserState::default();
+    let mut setup = Setup::from_args(args)?;
+    // a reader per run would be left waiting on stdin after every reload
+    let enter_pressed = watch_enter(&runtime, &setup.args);
+    loop {
+        let running = run(setup, &matches, &log_buffer, &mut browser, &enter_pressed);
+        match runtime.block_on(running)? {
+            Exit::Quit => return Ok(()),
+            Exit::Reload(reloaded) => setup = *reloaded,
+        }
+    }
 }
 
-#[tokio::main(worker_threads = 2)]
-async fn main() -> anyhow::Result<()> {
-    let args: Args = Args::parse();
+/// Merge the config file into the command line arguments
+fn parse_args(matches: &ArgMatches) -> anyhow::Result<Args> {
+    let mut args = Args::from_arg_matches(matches)?;
+    if let Some(config_path) = args.config.clone() {
+        args.apply_config(load_app_config(&config_path)?, matches);
+    }
+    if args.tui && matches!(args.input, Input::Keyboard) {
+        anyhow::bail!("--tui can't be used with keyboard input, both need the terminal");
+    }
+    args.zenoh_auth
+        .validate()
+        .context("Invalid zenoh authentication")?;
+    Ok(args)
+}
+
+/// Arguments together with every robot profile they select from
+struct Setup {
+    args: Args,
+    profiles: BTreeMap<String, RobotProfile>,
+    robot_registry: RobotRegistry,
+    json_schemas: JsonSchemas,
+}
+
+impl Setup {
+    fn from_args(mut args: Args) -> anyhow::Result<Self> {
+        let mut profiles = load_profiles(args.profile_dir.as_deref())?;
+        add_profiles(&mut profiles, std::mem::take(&mut args.config_profiles))?;
+        let robot_registry = robot_registry(&profiles, &args.mode)?;
+        let json_schemas = load_json_schemas(args.schema_dir.as_deref())?;
+        Ok(Self {
+            args,
+            profiles,
+            robot_registry,
+            json_schemas,
+        })
+    }
+
+    /// Read the config file and the profile directory again
+    fn reload(matches: &ArgMatches) -> anyhow::Result<Self> {
+        Self::from_args(parse_args(matches)?)
+    }
+}
+
+/// Why [`run`] returned
+enum Exit {
+    Quit,
+    /// SIGHUP with a configuration that loaded, run again with it
+    Reload(Box<Setup>),
+}
+
+/// Browser opened on the first run, Foxglove reconnects on its own after a reload
+#[derive(Default)]
+struct BrowserState {
+    opened: bool,
+    process: Option<Child>,
+}
+
+/// Publish a recording on the zenoh network of the selected robot
+async fn run_replay(args: Args, recording: &Path, speed: f64) -> anyhow::Result<()> {
     setup_tracing(args.verbose);
+    let samples = read_recording(recording)?;
+    let Setup {
+        args,
+        profiles,
+        robot_registry,
+        ..
+    } = Setup::from_args(args)?;
+    let controls = ReplayControls {
+        gamepad_topic: Some(args.gamepad_topic.clone()),
+        gamepad_encoding: args.gamepad_encoding,
+        twist_topics: profiles
+            .values()
+            .filter_map(|profile| Some(profile.twist.as_ref()?.topic.clone()))
+            .collect(),
+    };
+    let zenoh_session = start_zenoh_session(&args, &robot_registry).await?;
 
-    let zenoh_session = start_zenoh_session(&args).await?;
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            _ = tokio::signal::ctrl_c().await;
+            info!("Stopping replay");
+            shutdown.cancel();
+        }
+    });
+    replay(
+        zenoh_session.clone(),
+        &samples,
+        speed,
+        &controls,
+        &SystemClock,
+        shutdown,
+    )
+    .await?;
+    close_zenoh_session(zenoh_session).await
+}
 
-    info!("Publishing on topic {:?}", args.gamepad_topic);
+async fn run_doctor(args: Args, json: bool) -> anyhow::Result<()> {
+    let Setup {
+        args,
+        mut profiles,
+        robot_registry,
+        ..
+    } = Setup::from_args(args)?;
+    let profile = profiles
+        .remove(&robot_registry.active().name)
+        .context("Active profile missing")?;
+
+    let mut report = DoctorReport::default();
+    report.push(match args.input {
+        Input::Gilrs => tokio::task::spawn_blocking(check_gamepads).await?,
+        _ => DoctorCheck::new("gamepad", CheckStatus::Skipped, "Input isn't gilrs"),
+    });
+    report.push(match args.input {
+        Input::Gilrs => check_input_permissions()?.doctor_check(),
+        _ => DoctorCheck::new(
+            "input permissions",
+            CheckStatus::Skipped,
+            "Input isn't gilrs",
+        ),
+    });
+    let tailscale_status = TailscaleStatus::read().await;
+    report.extend(tailscale_checks(
+        &tailscale_status,
+        &robot_registry.connected_targets(),
+    ));
+    match start_zenoh_session(&args, &robot_registry).await {
+        Ok(zenoh_session) => {
+            report.push(DoctorCheck::new(
+                "zenoh session",
+                CheckStatus::Ok,
+                zenoh_session.zid().to_string(),
+            ));
+            // also gives the link to the robot time to come up
+            let topics = run_self_test(
+                zenoh_session.clone(),
+                profile.foxglove.topics(),
+                SELF_TEST_TIMEOUT,
+            )
+            .await?;
+            report.push(zenoh_link_check(&ZenohPeers::read(&zenoh_session).await));
+            report.extend(topic_checks(&topics));
+            close_zenoh_session(zenoh_session).await?;
+        }
+        Err(err) => {
+            report.push(DoctorCheck::new(
+                "zenoh session",
+                CheckStatus::Failed,
+                format!("{err:#}"),
+            ));
+            report.push(DoctorCheck::new(
+                "telemetry",
+                CheckStatus::Skipped,
+                "No zenoh session",
+            ));
+        }
+    }
+    let tailscale_status = tailscale_status.unwrap_or_default();
+    report.push(foxglove_port_check(&args.host, &tailscale_status).await);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        report.print();
+    }
+    if !report.is_ok() {
+        anyhow::bail!("Doctor found failing checks");
+    }
+    Ok(())
+}
+
+async fn run_layouts(client: &LayoutClient, command: LayoutsCommand) -> anyhow::Result<()> {
+    match command {
+        LayoutsCommand::List => {
+            for layout in client.list().await? {
+                println!("{}  {}", layout.id, layout.name);
+            }
+        }
+        LayoutsCommand::Pull { id, file } => {
+            let layout = client.get(&id).await?;
+            let data = layout.data.context("Layout has no data")?;
+            let data = serde_json::to_string_pretty(&data)?;
+            match file {
+                Some(file) => std::fs::write(&file, data)
+                    .with_context(|| format!("Failed to write {}", file.display()))?,
+                None => println!("{data}"),
+            }
+        }
+        LayoutsCommand::Push { file, name, id } => {
+            let data = read_layout_file(&file)?;
+            let layout = match id {
+                Some(id) => client.update(&id, name.as_deref(), &data).await?,
+                None => {
+                    let name = name.context("Missing layout name")?;
+                    client.create(&name, &data).await?
+                }
+            };
+            println!("{}", layout.id);
+        }
+    }
+    Ok(())
+}
+
+/// Bridging several camera topics saturates two workers so default to one per core
+fn build_runtime(worker_threads: Option<NonZeroUsize>) -> anyhow::Result<tokio::runtime::Runtime> {
+    let worker_threads = worker_threads
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(DEFAULT_WORKER_THREADS, NonZeroUsize::get);
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(worker_threads)
+        .enable_all()
+        .build()
+        .context("Failed to build tokio runtime")
+}
+
+/// Log to stdout, or into the returned buffer while the dashboard owns the terminal
+fn setup_logging(args: &Args) -> LogBuffer {
+    let log_buffer = LogBuffer::default();
+    #[cfg(feature = "tokio-console")]
+    if args.tokio_console {
+        setup_tracing_with_console(args.verbose);
+    } else if args.tui {
+        setup_tracing_to_buffer(args.verbose, log_buffer.clone());
+    } else {
+        setup_tracing(args.verbose);
+    }
+    #[cfg(not(feature = "tokio-console"))]
+    if args.tui {
+        setup_tracing_to_buffer(args.verbose, log_buffer.clone());
+    } else {
+        setup_tracing(args.verbose);
+    }
+    log_buffer
+}
+
+async fn run(
+    setup: Setup,
+    matches: &ArgMatches,
+    log_buffer: &LogBuffer,
+    browser: &mut BrowserState,
+    enter_pressed: &CancellationToken,
+) -> anyhow::Result<Exit> {
+    let Setup {
+        args,
+        mut profiles,
+        robot_registry,
+        json_schemas,
+    } = setup;
+    let mut profile = profiles
+        .remove(&robot_registry.active().name)
+        .context("Active profile missing")?;
+    if let Some(key_expr) = &args.auto_discover {
+        profile.foxglove.auto_discover = Some(key_expr.clone());
+    }
+    info!("Using robot profile {}", profile.name);
+    load_descriptor_files(&profile.foxglove.descriptor_files)?;
+    if args.global_gamepad_topic && robot_registry.is_simultaneous() {
+        anyhow::bail!("--global-gamepad-topic can't be used with several robots at once");
+    }
+    let robot_namespaces = !args.global_gamepad_topic;
+    // every robot would keep receiving input on the shared topic after a switch
+    let robot_registry = if robot_namespaces {
+        robot_registry
+    } else {
+        robot_registry.with_fixed_selection()
+    };
+    // topics of the robot active at startup, gamepad input follows switches on its own
+    let gamepad_topic = if robot_namespaces {
+        robot_registry.active().gamepad_topic(&args.gamepad_topic)
+    } else {
+        args.gamepad_topic.clone()
+    };
+
+    let zenoh_session = start_zenoh_session(&args, &robot_registry).await?;
+    install_panic_hook(&zenoh_session);
+    let shared_memory = if args.shared_memory {
+        info!("Publishing through zenoh shared memory");
+        Some(Arc::new(SharedMemoryPayloads::new(&zenoh_session)?))
+    } else {
+        None
+    };
+
+    if robot_registry.is_simultaneous() {
+        for target in robot_registry.targets() {
+            info!(
+                "Publishing for {} on topic {:?}",
+                target.name,
+                target.gamepad_topic(&args.gamepad_topic)
+            );
+        }
+    } else {
+        info!("Publishing on topic {:?}", gamepad_topic);
+    }
 
     let schema = schema_for!(InputMessage);
     info!(
@@ -101,71 +792,508 @@ async fn main() -> anyhow::Result<()> {
         serde_json::to_string_pretty(&schema)?
     );
 
-    start_schema_queryable(zenoh_session.clone(), &args.gamepad_topic).await?;
-    start_gamepad_reader(zenoh_session.clone(), &args.gamepad_topic, args.sleep_ms).await?;
+    let supervisor = Supervisor::new(zenoh_session.clone(), CancellationToken::new());
+    start_session_watchdog(&supervisor, zenoh_session.clone());
+    start_tailscale_discovery(
+        &supervisor,
+        zenoh_session.clone(),
+        robot_registry.clone(),
+        args.ipv6,
+    );
+    start_robot_selector(&supervisor, zenoh_session.clone(), robot_registry.clone());
 
-    // read foxglove config
-    let foxglove_config = match args.mode {
-        Mode::Hamilton => {
-            let config = include_str!("../config/hamilton_config.yaml");
-            let config: FoxgloveServerConfiguration = serde_yaml::from_str(config)?;
-            config
-        }
-        Mode::Guppy => {
-            let config = include_str!("../config/hamilton_config.yaml");
-            let config: FoxgloveServerConfiguration = serde_yaml::from_str(config)?;
-            config
-        }
-        Mode::Hopper => {
-            let config = include_str!("../config/hopper_config.yaml");
-            let config: FoxgloveServerConfiguration = serde_yaml::from_str(config)?;
-            config
+    let stats = StatsRegistry::default();
+    let schemas = SchemaRegistry::default();
+    start_schema_queryable(&supervisor, zenoh_session.clone(), schemas.clone());
+    let clock: SharedClock = Arc::new(SystemClock);
+    start_health_publisher(&supervisor, zenoh_session.clone());
+    start_stats_queryable(&supervisor, zenoh_session.clone(), stats.clone());
+    start_latency_publisher(&supervisor, zenoh_session.clone(), stats.clone());
+    start_bridge_stats_publisher(
+        &supervisor,
+        zenoh_session.clone(),
+        stats.clone(),
+        clock.clone(),
+    );
+    start_link_monitor(
+        &supervisor,
+        zenoh_session.clone(),
+        args.echo_topic.clone(),
+        clock.clone(),
+    );
+    start_operator_station_publisher(&supervisor, zenoh_session.clone(), clock.clone());
+    if let Some(path) = &args.annotation_log {
+        start_annotation_log(&supervisor, zenoh_session.clone(), path.clone());
+    }
+    if let Some(mqtt) = &profile.mqtt {
+        start_mqtt_bridge(&supervisor, zenoh_session.clone(), mqtt.clone());
+    }
+    let estop = EstopLatch::default();
+    let gamepad_reader_config = GamepadReaderConfig {
+        // prefixed by the gamepad reader
+        pub_topic: args.gamepad_topic.clone(),
+        sleep_ms: args.sleep_ms,
+        button_counter_policy: args.button_counter_policy,
+        button_counter_limit: args.button_counter_limit,
+        encoding: args.gamepad_encoding,
+        estop_button: args.estop_button,
+        estop_rearm_button: args.estop_rearm_button,
+        estop: estop.clone(),
+        axis_mapping: profile.axis_mapping.clone(),
+        axis_smoothing: profile.axis_smoothing.clone(),
+        axis_event_thresholds: profile.axis_event_thresholds.clone(),
+        button_mapping: profile.button_mapping.clone(),
+        watchdog_timeout: Duration::from_millis(args.watchdog_timeout_ms),
+        publish_mode: args.publish_mode,
+        keepalive_interval: Duration::from_millis(args.keepalive_ms),
+        robot_registry: Some(robot_registry.clone()),
+        robot_namespaces,
+        target_button: args.target_button,
+        qos: PublisherQos {
+            priority: args.gamepad_priority,
+            congestion_control: args.gamepad_congestion_control,
+            express: args.gamepad_express,
+        },
+        gamepad_roles: profile.gamepad_roles.clone(),
+        twist: profile.twist.clone(),
+        combos: profile.combos.clone(),
+        ros2_joy: profile.ros2_joy.clone(),
+        macros: profile.macros,
+        script: profile.script.clone(),
+        deadman: profile.deadman.clone(),
+        modifiers: profile.modifiers.clone(),
+        shared_memory: shared_memory.clone(),
+        annotation_button: args.annotation_button,
+        annotation_note: args.annotation_note.clone(),
+        low_battery_percent: args.low_battery_percent,
+        low_battery_rumble: args.low_battery_rumble,
+    };
+    gamepad_reader_config.register_schemas(&schemas)?;
+    // cancelled by Ctrl-C on keyboard input and the dashboard where it doesn't raise SIGINT
+    let terminal_quit = CancellationToken::new();
+    let input_backend: Arc<dyn InputBackend> = match args.input {
+        Input::Gilrs => Arc::new(GilrsBackend::default()),
+        Input::Sim => {
+            let script_path = args.sim_script.as_deref().context("Missing sim script")?;
+            Arc::new(SimInput::new(SimScript::from_file(script_path)?))
         }
+        Input::Keyboard => Arc::new(KeyboardInput::new(terminal_quit.clone())),
     };
+    start_feedback_subscriber(
+        &supervisor,
+        zenoh_session.clone(),
+        &gamepad_topic,
+        input_backend.clone(),
+    );
+    start_gamepad_reader(
+        &supervisor,
+        zenoh_session.clone(),
+        gamepad_reader_config,
+        stats.gamepad(),
+        clock.clone(),
+        input_backend,
+    );
+    if args.enable_imu {
+        start_imu_publisher(
+            &supervisor,
+            zenoh_session.clone(),
+            format!("{gamepad_topic}/imu"),
+            clock.clone(),
+            shared_memory.clone(),
+        );
+    }
+    if args.enable_touchpad {
+        start_touchpad_publisher(
+            &supervisor,
+            zenoh_session.clone(),
+            format!("{gamepad_topic}/touchpad"),
+            clock.clone(),
+        );
+    }
 
-    start_foxglove_bridge(foxglove_config, args.host, zenoh_session.clone()).await?;
+    if args.audio_uplink {
+        start_audio_uplink(
+            &supervisor,
+            zenoh_session.clone(),
+            args.push_to_talk,
+            stats.gamepad(),
+        );
+    }
+    if let Some(topic) = &args.audio_downlink {
+        start_audio_downlink(&supervisor, zenoh_session.clone(), topic.clone());
+    }
 
-    let layout_id = match args.mode {
-        Mode::Hamilton => HAMILTON_FOXGLOVE_LAYOUT_ID,
-        Mode::Guppy => HAMILTON_FOXGLOVE_LAYOUT_ID,
-        Mode::Hopper => HOPPER_FOXGLOVE_LAYOUT_ID,
+    if args.self_test {
+        let checks = run_self_test(
+            zenoh_session.clone(),
+            profile.foxglove.topics(),
+            SELF_TEST_TIMEOUT,
+        )
+        .await?;
+        log_report(&checks);
+    }
+
+    start_connection_monitor(
+        &supervisor,
+        zenoh_session.clone(),
+        robot_registry.clone(),
+        stats.clone(),
+        profile.foxglove.telemetry_topics(),
+        clock.clone(),
+    );
+    start_failover_monitor(
+        &supervisor,
+        zenoh_session.clone(),
+        robot_registry.clone(),
+        stats.clone(),
+        profile.foxglove.telemetry_topics(),
+        args.ipv6,
+    );
+    // latched so a freshly opened panel shows the connection state right away
+    for (topic, type_name, latched) in [
+        (LINK_STATS_TOPIC, "LinkStats", None),
+        (BRIDGE_STATS_TOPIC, "BridgeStats", None),
+        (CONNECTION_TOPIC, "ConnectionState", Some(true)),
+        (OPERATOR_STATION_TOPIC, "OperatorStationHealth", None),
+    ] {
+        profile.foxglove.json_subscriptions.push(JsonSubscription {
+            topic: topic.to_owned(),
+            type_name: type_name.to_owned(),
+            json_schema_name: Some("GENERIC_JSON_SCHEMA".to_owned()),
+            infer_schema: None,
+            latched,
+            transforms: vec![],
+            queue_size: DEFAULT_QUEUE_SIZE,
+        });
+    }
+    for topic in [ANNOTATION_TOPIC, GAMEPAD_ALERT_TOPIC] {
+        profile.foxglove.log_subscriptions.push(LogSubscription {
+            topic: topic.to_owned(),
+            queue_size: DEFAULT_QUEUE_SIZE,
+        });
+    }
+    let tailscale_status = match args.host {
+        FoxgloveHost::Tailscale { .. } => TailscaleStatus::read().await?,
+        FoxgloveHost::Address(_) => TailscaleStatus::default(),
+    };
+    let (host, url_host) = args.host.resolve(&tailscale_status)?;
+    let tls_acceptor = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(load_tls_acceptor(cert, key)?),
+        _ => None,
+    };
+    // with TLS the server is only reachable through the proxy
+    let bridge_address = if tls_acceptor.is_some() {
+        free_loopback_address()?
+    } else {
+        host
+    };
+    start_foxglove_bridge(
+        profile.foxglove,
+        &json_schemas,
+        bridge_address,
+        zenoh_session.clone(),
+        &supervisor,
+        &stats,
+        &schemas,
+        clock.clone(),
+        estop.clone(),
+    )
+    .await?;
+    start_stats_dump_on_signal(
+        &supervisor,
+        stats.clone(),
+        args.stats_dump_file.clone(),
+        bridge_address.port(),
+    );
+    let scheme = match tls_acceptor {
+        Some(tls_acceptor) => {
+            start_tls_proxy(&supervisor, host, bridge_address, tls_acceptor).await?;
+            "wss"
+        }
+        None => "ws",
     };
 
-    let foxglove_link = create_foxglove_url(
-        &args.foxglove_user,
-        &args.host.ip().to_string(),
-        &args.host.port().to_string(),
+    let layout_id = args
+        .foxglove_layout_id
+        .as_deref()
+        .unwrap_or(&profile.foxglove_layout_id);
+
+    let foxglove_url =
+        FoxgloveUrlBuilder::new(scheme, &url_host, host.port()).user(&args.foxglove_user);
+    let foxglove_link = foxglove_link(
+        &foxglove_url,
+        args.foxglove_studio_url
+            .as_deref()
+            .or(profile.foxglove_studio_url.as_deref()),
         layout_id,
     );
 
     info!("Foxglove link {foxglove_link}");
+    if let Some(port) = args.control_panel_port {
+        let foxglove_links = robot_registry
+            .targets()
+            .iter()
+            .map(|target| {
+                // --foxglove-layout-id only applies to the robot of --mode
+                if target.name == profile.name {
+                    return (target.name.clone(), foxglove_link.clone());
+                }
+                let studio_url = args
+                    .foxglove_studio_url
+                    .as_deref()
+                    .or(target.foxglove_studio_url.as_deref());
+                let link = foxglove_link(&foxglove_url, studio_url, &target.foxglove_layout_id);
+                (target.name.clone(), link)
+            })
+            .collect();
+        let panel = ControlPanel::new(
+            zenoh_session.clone(),
+            robot_registry.clone(),
+            estop,
+            foxglove_links,
+            clock,
+        );
+        start_control_panel(&supervisor, panel, SocketAddr::new(host.ip(), port));
+        info!("Control panel at http://{url_host}:{port}");
+    }
+    start_layout_announcer(
+        &supervisor,
+        robot_registry,
+        foxglove_url,
+        args.foxglove_studio_url.clone(),
+    );
+
+    if args.browser && !args.headless && !browser.opened {
+        browser.opened = true;
+        match open_browser(args.browser_cmd.as_deref(), &foxglove_link) {
+            Ok(process) => browser.process = process,
+            Err(err) => {
+                warn!("Failed to open browser {err:?}");
+                print_url_banner(&foxglove_link);
+            }
+        }
+    } else {
+        print_url_banner(&foxglove_link);
+    }
+
+    let tui = if args.tui {
+        let dashboard = Dashboard::new(&gamepad_topic, &foxglove_link);
+        start_dashboard_updater(
+            &supervisor,
+            zenoh_session.clone(),
+            stats.clone(),
+            dashboard.clone(),
+            bridge_address.port(),
+        );
+        Some(start_tui(
+            dashboard,
+            log_buffer.clone(),
+            terminal_quit.clone(),
+        )?)
+    } else {
+        None
+    };
+
+    let browser_exited = async {
+        match browser.process.as_mut() {
+            Some(process) => {
+                _ = process.wait().await;
+                info!("Browser process exited");
+            }
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(browser_exited);
+    let mut sigterm = terminate_signal();
+    let mut sighup = hangup_signal();
+
+    let exit = loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break Exit::Quit,
+            _ = recv_signal(&mut sigterm) => break Exit::Quit,
+            _ = enter_pressed.cancelled() => break Exit::Quit,
+            _ = terminal_quit.cancelled() => break Exit::Quit,
+            _ = &mut browser_exited => break Exit::Quit,
+            _ = recv_signal(&mut sighup) => match Setup::reload(matches) {
+                Ok(reloaded) => break Exit::Reload(Box::new(reloaded)),
+                Err(err) => error!("Keeping the running configuration, reload failed {err:?}"),
+            },
+        }
+    };
+
+    if let Some(tui) = tui {
+        tui.stop();
+        // the log was only shown on the dashboard
+        if matches!(exit, Exit::Quit) {
+            for line in log_buffer.lines() {
+                println!("{line}");
+            }
+        }
+    }
+    match exit {
+        Exit::Quit => info!("Shutting down"),
+        Exit::Reload(_) => info!("Reloading configuration"),
+    }
+    supervisor.shutdown(SHUTDOWN_TIMEOUT).await;
+
+    close_zenoh_session(zenoh_session).await?;
+    Ok(exit)
+}
+
+/// SIGTERM, sent by systemd and other service managers to stop the service
+#[cfg(unix)]
+fn terminate_signal() -> Option<tokio::signal::unix::Signal> {
+    listen_for_signal(tokio::signal::unix::SignalKind::terminate(), "SIGTERM")
+}
+
+/// SIGHUP, sent by `systemctl reload` to read the configuration again
+#[cfg(unix)]
+fn hangup_signal() -> Option<tokio::signal::unix::Signal> {
+    listen_for_signal(tokio::signal::unix::SignalKind::hangup(), "SIGHUP")
+}
+
+#[cfg(unix)]
+fn listen_for_signal(
+    kind: tokio::signal::unix::SignalKind,
+    name: &str,
+) -> Option<tokio::signal::unix::Signal> {
+    match tokio::signal::unix::signal(kind) {
+        Ok(signal) => Some(signal),
+        Err(err) => {
+            error!("Failed to listen for {name} {err:?}");
+            None
+        }
+    }
+}
+
+/// Waits forever if listening for the signal failed
+#[cfg(unix)]
+async fn recv_signal(signal: &mut Option<tokio::signal::unix::Signal>) {
+    match signal {
+        Some(signal) => {
+            signal.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate_signal() -> Option<()> {
+    None
+}
+
+#[cfg(not(unix))]
+fn hangup_signal() -> Option<()> {
+    None
+}
+
+#[cfg(not(unix))]
+async fn recv_signal(_signal: &mut Option<()>) {
+    std::future::pending().await
+}
 
-    if args.browser {
-        // open::that(foxglove_link)?;
-        // open::with(&foxglove_link, "chrome")?;
-        let mut browser_process_handle = Command::new(FLATPAK_CHROME_PATH)
+/// Open the foxglove link with `browser_cmd`, fullscreen flatpak chrome if installed or the default browser
+///
+/// Returns the browser process if it can be waited on.
+fn open_browser(browser_cmd: Option<&str>, url: &str) -> anyhow::Result<Option<Child>> {
+    if let Some(browser_cmd) = browser_cmd {
+        let mut parts = browser_cmd.split_whitespace();
+        let program = parts.next().context("Empty browser command")?;
+        let process = Command::new(program)
+            .args(parts)
+            .arg(url)
+            .spawn()
+            .with_context(|| format!("Failed to start {browser_cmd:?}"))?;
+        return Ok(Some(process));
+    }
+
+    if Path::new(FLATPAK_CHROME_PATH).exists() {
+        let process = Command::new(FLATPAK_CHROME_PATH)
             .arg("--start-fullscreen")
-            .arg(foxglove_link)
+            .arg(url)
             .arg("--noerrdialogs")
             .arg("--no-first-run")
             .arg("--start-maximized")
-            .spawn()?;
+            .spawn()
+            .context("Failed to start chrome")?;
+        return Ok(Some(process));
+    }
 
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {}
-            _ = read_line() => {}
-            _ = browser_process_handle.wait() => {
-                info!("Browser process exited");
-            }
-        };
-    } else {
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {}
-            _ = read_line() => {}
-        };
+    open::that_detached(url).context("Failed to open default browser")?;
+    Ok(None)
+}
+
+fn foxglove_link(
+    foxglove_url: &FoxgloveUrlBuilder,
+    studio_url: Option<&str>,
+    layout_id: &str,
+) -> String {
+    let foxglove_url = foxglove_url.clone().layout_id(layout_id);
+    match studio_url {
+        Some(studio_url) => foxglove_url.studio_url(studio_url).build(),
+        None => foxglove_url.build(),
     }
+}
 
-    Ok(())
+/// Print the Foxglove link with the layout of every newly selected robot
+///
+/// `studio_url_override` from the command line wins over the studio of each robot profile.
+fn start_layout_announcer(
+    supervisor: &Supervisor,
+    robot_registry: RobotRegistry,
+    foxglove_url: FoxgloveUrlBuilder,
+    studio_url_override: Option<String>,
+) {
+    supervisor.spawn("layout_announcer", move |shutdown| {
+        let mut active = robot_registry.subscribe();
+        let foxglove_url = foxglove_url.clone();
+        let studio_url_override = studio_url_override.clone();
+        async move {
+            loop {
+                tokio::select! {
+                    changed = active.changed() => changed?,
+                    _ = shutdown.cancelled() => break,
+                }
+                let target = active.borrow_and_update().clone();
+                let foxglove_link = foxglove_link(
+                    &foxglove_url,
+                    studio_url_override
+                        .as_deref()
+                        .or(target.foxglove_studio_url.as_deref()),
+                    &target.foxglove_layout_id,
+                );
+                info!("Foxglove link for {} {foxglove_link}", target.name);
+                print_url_banner(&foxglove_link);
+            }
+            Ok(())
+        }
+    });
+}
+
+fn print_url_banner(url: &str) {
+    let line = "=".repeat(url.len() + 4);
+    println!("\n{line}\n  Open Foxglove at:\n  {url}\n{line}\n");
+}
+
+/// Cancelled once enter is pressed, or stdin is closed
+///
+/// stdin is usually closed when running headless and owned by keyboard input, so it isn't
+/// read then.
+fn watch_enter(runtime: &tokio::runtime::Runtime, args: &Args) -> CancellationToken {
+    let enter_pressed = CancellationToken::new();
+    if args.headless || args.tui || matches!(args.input, Input::Keyboard) {
+        return enter_pressed;
+    }
+    runtime.spawn({
+        let enter_pressed = enter_pressed.clone();
+        async move {
+            if let Err(err) = read_line().await {
+                debug!("Failed to read stdin {err:?}");
+            }
+            enter_pressed.cancel();
+        }
+    });
+    enter_pressed
 }
 
 async fn read_line() -> anyhow::Result<()> {
@@ -174,38 +1302,61 @@ async fn read_line() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn setup_tracing(verbosity_level: u8) {
-    let filter = match verbosity_level {
+fn verbosity_filter(verbosity_level: u8) -> tracing::level_filters::LevelFilter {
+    match verbosity_level {
         0 => tracing::level_filters::LevelFilter::INFO,
         1 => tracing::level_filters::LevelFilter::DEBUG,
         2 => tracing::level_filters::LevelFilter::TRACE,
         _ => tracing::level_filters::LevelFilter::TRACE,
-    };
+    }
+}
+
+pub fn setup_tracing(verbosity_level: u8) {
+    let filter = verbosity_filter(verbosity_level);
     tracing_subscriber::fmt().with_max_level(filter).init();
 }
 
-static FILE_DESCRIPTOR_SET: &[u8] =
-    include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+/// Log into `log_buffer` while the dashboard owns the terminal
+pub fn setup_tracing_to_buffer(verbosity_level: u8, log_buffer: LogBuffer) {
+    let filter = verbosity_filter(verbosity_level);
+    tracing_subscriber::fmt()
+        .with_max_level(filter)
+        .with_ansi(false)
+        .with_writer(move || log_buffer.clone())
+        .init();
+}
 
-static DESCRIPTOR_POOL: Lazy<DescriptorPool> = Lazy::new(|| {
-    DescriptorPool::decode(FILE_DESCRIPTOR_SET).expect("Failed to load file descriptor set")
-});
+/// Log to stdout and serve task instrumentation for tokio-console on the default port
+#[cfg(feature = "tokio-console")]
+pub fn setup_tracing_with_console(verbosity_level: u8) {
+    use tracing_subscriber::prelude::*;
 
-/// protobuf
-pub mod foxglove {
-    #![allow(non_snake_case)]
-    include!(concat!(env!("OUT_DIR"), "/foxglove.rs"));
+    let filter = verbosity_filter(verbosity_level);
+    tracing_subscriber::registry()
+        .with(console_subscriber::spawn())
+        .with(tracing_subscriber::fmt::layer().with_filter(filter))
+        .init();
+    info!("Serving tokio-console instrumentation");
 }
 
-pub mod hopper {
-    #![allow(non_snake_case)]
-    include!(concat!(env!("OUT_DIR"), "/hopper.rs"));
+/// Registry of every profile with one robot selected, or of only the robots selected at once
+fn robot_registry(
+    profiles: &BTreeMap<String, RobotProfile>,
+    modes: &[String],
+) -> Result<RobotRegistry, ConfigError> {
+    match modes {
+        [mode] => RobotRegistry::new(profiles, mode),
+        modes => RobotRegistry::simultaneous(profiles, modes),
+    }
 }
 
-async fn start_zenoh_session(args: &Args) -> anyhow::Result<Arc<Session>> {
+async fn start_zenoh_session(
+    args: &Args,
+    robot_registry: &RobotRegistry,
+) -> anyhow::Result<Arc<Session>> {
     // load config
     let mut zenoh_config = if let Some(conf_file) = &args.zenoh_config {
-        Config::from_file(conf_file).map_err(ErrorWrapper::ZenohError)?
+        Config::from_file(conf_file).map_err(ZenohError)?
     } else {
         Config::default()
     };
@@ -216,61 +1367,35 @@ async fn start_zenoh_session(args: &Args) -> anyhow::Result<Arc<Session>> {
     if !args.listen.is_empty() {
         zenoh_config.listen.endpoints.clone_from(&args.listen);
     }
+    if args.shared_memory {
+        enable_shared_memory(&mut zenoh_config)?;
+    }
+    args.zenoh_auth.apply(&mut zenoh_config)?;
 
     // add tailscale config
-    let tailscale_status = TailscaleStatus::read_from_command().await?;
-
-    // listening address
-    for local_address in &tailscale_status.tailscale_ip_list {
-        let address: std::net::IpAddr = local_address.parse().context("Failed to parse address")?;
-        if !address.is_ipv4() {
-            // skip IPv6 because pain
-            continue;
-        }
-        let tcp = zenoh_config::EndPoint::new("tcp", format!("{}:{}", local_address, 0), "", "")
-            .map_err(ErrorWrapper::ZenohError)?;
-        zenoh_config.listen.endpoints.push(tcp)
-    }
-
-    // peer address
-    for peer in tailscale_status.peers.values() {
-        // select target based on host
-        match args.mode {
-            Mode::Hamilton => {
-                if !peer.host_name.to_lowercase().contains("hamilton") {
-                    // skip others
-                    continue;
-                }
-            }
-            Mode::Guppy => {
-                if !peer.host_name.to_lowercase().contains("guppy") {
-                    // skip others
-                    continue;
-                }
-            }
-            Mode::Hopper => {
-                if !peer.host_name.to_lowercase().contains("hopper") {
-                    // skip others
-                    continue;
-                }
-            }
-        }
+    let tailscale_status = TailscaleStatus::read().await?;
+    if let Some(exit_node) = tailscale_status.exit_node() {
+        // robots are reached directly but everything else goes through the exit node
+        info!("Tailscale exit node {} in use", exit_node.host_name);
+    }
 
-        for local_address in &peer.tailscale_ip_list {
-            let address: std::net::IpAddr =
-                local_address.parse().context("Failed to parse address")?;
-            if !address.is_ipv4() {
-                // skip IPv6 because pain
-                continue;
+    let targets = robot_registry.connected_targets();
+    let (first, others) = targets.split_first().context("No robot selected")?;
+    add_tailscale_endpoints(
+        &mut zenoh_config,
+        &tailscale_status,
+        &robot_registry.peer_rules(first),
+        args.ipv6,
+    )?;
+    for target in others {
+        for endpoint in peer_endpoints(
+            &tailscale_status,
+            &robot_registry.peer_rules(target),
+            args.ipv6,
+        )? {
+            if !zenoh_config.connect.endpoints.contains(&endpoint) {
+                zenoh_config.connect.endpoints.push(endpoint);
             }
-            let tcp = zenoh_config::EndPoint::new(
-                "tcp",
-                format!("{}:{}", local_address, ZENOH_TCP_DISCOVERY_PORT),
-                "",
-                "",
-            )
-            .map_err(ErrorWrapper::ZenohError)?;
-            zenoh_config.connect.endpoints.push(tcp)
         }
     }
 
@@ -284,13 +1409,33 @@ async fn start_zenoh_session(args: &Args) -> anyhow::Result<Arc<Session>> {
     if !zenoh_config.listen.endpoints.is_empty() {
         info!("Zenoh listening on {:?}", zenoh_config.listen.endpoints);
     }
+    if let Some(user) = &args.zenoh_auth.user {
+        info!("Zenoh authenticating as {user}");
+    }
+    if args.zenoh_auth.certificate.is_some() {
+        info!("Zenoh TLS with client certificate");
+    }
 
     debug!("Starting zenoh session");
     let zenoh_session = zenoh::open(zenoh_config)
         .res()
         .await
-        .map_err(ErrorWrapper::ZenohError)?
+        .map_err(ZenohError)?
         .into_arc();
 
     Ok(zenoh_session)
 }
+
+async fn close_zenoh_session(zenoh_session: Arc<Session>) -> anyhow::Result<()> {
+    // all declarations hold a reference to the session so this only succeeds once tasks stopped
+    let Ok(zenoh_session) = Arc::try_unwrap(zenoh_session) else {
+        warn!("Zenoh session still in use, skipping close");
+        return Ok(());
+    };
+    debug!("Closing zenoh session");
+    match tokio::time::timeout(SHUTDOWN_TIMEOUT, zenoh_session.close().res()).await {
+        Ok(result) => result.map_err(ZenohError)?,
+        Err(_) => warn!("Timed out closing zenoh session"),
+    }
+    Ok(())
+}
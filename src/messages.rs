@@ -1,29 +1,341 @@
 use chrono::prelude::{DateTime, Utc};
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use clap::ValueEnum;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::json;
 use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+use crate::remote_control;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 pub struct InputMessage {
     pub gamepads: HashMap<usize, GamepadMessage>,
     pub time: DateTime<Utc>,
+    /// Semantics of the button event counters in `gamepads`
+    #[serde(default)]
+    pub button_counter_policy: ButtonCounterPolicy,
+    /// All axes are held at zero while the emergency stop is engaged
+    #[serde(default)]
+    pub estop_engaged: bool,
+}
+
+/// Published on `{gamepad_topic}/touchpad`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct TouchpadMessage {
+    pub left: TouchpadState,
+    pub right: TouchpadState,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct TouchpadState {
+    pub touched: bool,
+    /// -1.0 (left) to 1.0 (right), 0.0 while not touched
+    pub x: f32,
+    /// -1.0 (bottom) to 1.0 (top), 0.0 while not touched
+    pub y: f32,
+    pub clicked: bool,
+}
+
+/// Published on `remote-control/estop` when the emergency stop is engaged or re-armed
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct EstopMessage {
+    pub engaged: bool,
+    pub time: DateTime<Utc>,
+}
+
+/// Published on `<gamepad topic>/combos` once per press when a configured combo is held
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, JsonSchema)]
+pub struct ComboMessage {
+    pub combo: String,
+    pub gamepad_id: usize,
+    pub time: DateTime<Utc>,
+}
+
+/// Published on `<gamepad topic>/events` as soon as the input event arrives
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, JsonSchema)]
+pub struct GamepadEventMessage {
+    pub gamepad_id: usize,
+    pub event: GamepadEvent,
+    pub time: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Default, JsonSchema)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GamepadEvent {
+    ButtonDown {
+        button: Button,
+    },
+    ButtonUp {
+        button: Button,
+    },
+    /// The mapped axis value moved past its threshold in either direction
+    AxisEngaged {
+        axis: Axis,
+        value: f32,
+    },
+    /// The mapped axis value fell back below its threshold
+    AxisReleased {
+        axis: Axis,
+        value: f32,
+    },
+}
+
+/// Published on `remote-control/watchdog` when a neutral message was sent because input was lost
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
+pub struct WatchdogMessage {
+    pub reason: WatchdogReason,
+    /// Gamepad that disconnected, not set when all input stopped
+    pub gamepad_id: Option<usize>,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchdogReason {
+    GamepadDisconnected,
+    InputStalled,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default, JsonSchema)]
 pub struct GamepadMessage {
     pub name: String,
     pub connected: bool,
     pub last_event_time: DateTime<Utc>,
+    /// Number of button press events, see `button_counter_policy`
     pub button_down_event_counter: BTreeMap<Button, usize>,
+    /// Number of button release events, see `button_counter_policy`
     pub button_up_event_counter: BTreeMap<Button, usize>,
     pub button_down: BTreeMap<Button, bool>,
     pub axis_state: BTreeMap<Axis, f32>,
+    /// Number of presses that followed the previous one quickly, see `button_counter_policy`
+    #[serde(default)]
+    pub button_double_press_counter: BTreeMap<Button, usize>,
+    /// Milliseconds since each held button was pressed
+    #[serde(default)]
+    pub button_hold_ms: BTreeMap<Button, u64>,
+    /// Motion axes are held at zero because the dead man's switch isn't held
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default)]
+    pub power_state: PowerState,
+    /// Battery charge from 0 to 100, not set for wired gamepads or if the driver doesn't know
+    #[serde(default)]
+    pub battery_percent: Option<u8>,
+    /// Name of the held speed modifier, not set without one
+    #[serde(default)]
+    pub mode: Option<String>,
+}
+
+/// Power supply of a gamepad as reported by its driver
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerState {
+    #[default]
+    Unknown,
+    Wired,
+    Discharging,
+    Charging,
+    Charged,
+}
+
+impl InputMessage {
+    /// Same gamepad state, ignoring when the message was created
+    pub fn same_state(&self, other: &InputMessage) -> bool {
+        self.gamepads == other.gamepads
+            && self.button_counter_policy == other.button_counter_policy
+            && self.estop_engaged == other.estop_engaged
+    }
+}
+
+/// When gamepad messages are published
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum PublishMode {
+    /// Every publish interval
+    #[default]
+    Periodic,
+    /// Only when the gamepad state changed
+    OnChange,
+    /// On change and at least once per keepalive interval
+    Hybrid,
 }
 
+/// Wire format of published gamepad messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum GamepadEncoding {
+    #[default]
+    Json,
+    /// `remote_control.InputMessage`, cheaper to decode on embedded robots
+    Protobuf,
+    /// Same structure as JSON, published as `application/cbor`
+    Cbor,
+    /// Same structure as JSON, published as `application/msgpack`
+    Msgpack,
+}
+
+impl From<&InputMessage> for remote_control::InputMessage {
+    fn from(message: &InputMessage) -> Self {
+        Self {
+            gamepads: message
+                .gamepads
+                .iter()
+                .map(|(gamepad_id, gamepad)| (*gamepad_id as u64, gamepad.into()))
+                .collect(),
+            time: Some(timestamp(message.time)),
+            button_counter_policy: remote_control::ButtonCounterPolicy::from(
+                message.button_counter_policy,
+            ) as i32,
+            estop_engaged: message.estop_engaged,
+        }
+    }
+}
+
+impl From<&GamepadMessage> for remote_control::GamepadMessage {
+    fn from(gamepad: &GamepadMessage) -> Self {
+        // Debug names match the serde names used as JSON keys
+        Self {
+            name: gamepad.name.clone(),
+            connected: gamepad.connected,
+            last_event_time: Some(timestamp(gamepad.last_event_time)),
+            button_down_event_counter: gamepad
+                .button_down_event_counter
+                .iter()
+                .map(|(button, count)| (format!("{button:?}"), *count as u64))
+                .collect(),
+            button_up_event_counter: gamepad
+                .button_up_event_counter
+                .iter()
+                .map(|(button, count)| (format!("{button:?}"), *count as u64))
+                .collect(),
+            button_down: gamepad
+                .button_down
+                .iter()
+                .map(|(button, down)| (format!("{button:?}"), *down))
+                .collect(),
+            axis_state: gamepad
+                .axis_state
+                .iter()
+                .map(|(axis, value)| (format!("{axis:?}"), *value))
+                .collect(),
+            disabled: gamepad.disabled,
+            button_double_press_counter: gamepad
+                .button_double_press_counter
+                .iter()
+                .map(|(button, count)| (format!("{button:?}"), *count as u64))
+                .collect(),
+            button_hold_ms: gamepad
+                .button_hold_ms
+                .iter()
+                .map(|(button, hold_ms)| (format!("{button:?}"), *hold_ms))
+                .collect(),
+            power_state: remote_control::PowerState::from(gamepad.power_state) as i32,
+            battery_percent: gamepad.battery_percent.map(u32::from),
+            mode: gamepad.mode.clone(),
+        }
+    }
+}
+
+impl From<PowerState> for remote_control::PowerState {
+    fn from(power_state: PowerState) -> Self {
+        match power_state {
+            PowerState::Unknown => Self::Unknown,
+            PowerState::Wired => Self::Wired,
+            PowerState::Discharging => Self::Discharging,
+            PowerState::Charging => Self::Charging,
+            PowerState::Charged => Self::Charged,
+        }
+    }
+}
+
+impl From<ButtonCounterPolicy> for remote_control::ButtonCounterPolicy {
+    fn from(policy: ButtonCounterPolicy) -> Self {
+        match policy {
+            ButtonCounterPolicy::Wrapping => Self::Wrapping,
+            ButtonCounterPolicy::Saturating => Self::Saturating,
+            ButtonCounterPolicy::ResetOnPublish => Self::ResetOnPublish,
+        }
+    }
+}
+
+pub(crate) fn timestamp(time: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+/// Rumble command sent from the robot to the gamepad
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, JsonSchema)]
+pub struct RumbleCommand {
+    /// Gamepad to rumble, all gamepads if not set
+    #[serde(default)]
+    pub gamepad_id: Option<usize>,
+    /// Low frequency motor magnitude from 0.0 to 1.0
+    #[serde(default)]
+    pub strong: f32,
+    /// High frequency motor magnitude from 0.0 to 1.0
+    #[serde(default)]
+    pub weak: f32,
+    pub duration_ms: u64,
+}
+
+/// How button event counters behave over a long session
 #[derive(
-    Debug, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, JsonSchema,
+    Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default, JsonSchema, ValueEnum,
 )]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonCounterPolicy {
+    /// Counters count up for the whole session and wrap around to zero after the counter limit
+    #[default]
+    Wrapping,
+    /// Counters count up for the whole session and stop at the counter limit
+    Saturating,
+    /// Counters only contain events since the previous message
+    ResetOnPublish,
+}
+
+impl ButtonCounterPolicy {
+    pub fn increment(&self, counter: &mut usize, limit: usize) {
+        *counter = match self {
+            ButtonCounterPolicy::Wrapping | ButtonCounterPolicy::ResetOnPublish => {
+                if *counter >= limit {
+                    0
+                } else {
+                    *counter + 1
+                }
+            }
+            ButtonCounterPolicy::Saturating => counter.saturating_add(1).min(limit),
+        }
+    }
+}
+
+impl GamepadMessage {
+    /// Release all buttons and center all axes while keeping event counters intact
+    pub fn clear_input_state(&mut self) {
+        self.button_down
+            .values_mut()
+            .for_each(|pressed| *pressed = false);
+        self.button_hold_ms.clear();
+        self.mode = None;
+        self.center_axes();
+    }
+
+    pub fn center_axes(&mut self) {
+        self.axis_state.values_mut().for_each(|value| *value = 0.0);
+    }
+
+    pub fn clear_event_counters(&mut self) {
+        self.button_down_event_counter.clear();
+        self.button_up_event_counter.clear();
+        self.button_double_press_counter.clear();
+    }
+}
+
+/// Serialized as its name, `Raw(<code>)` for controls gilrs has no name for
+///
+/// Names are used as JSON object keys, so raw controls are strings too instead of `{"Raw": 305}`.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
 pub enum Button {
     South,
     East,
@@ -47,9 +359,45 @@ pub enum Button {
     Unknown,
     LeftPaddle,
     RightPaddle,
+    /// Event code of a button gilrs doesn't recognize, such as extra buttons of some controllers
+    Raw(u32),
 }
 
 impl Button {
+    /// Every button with a name
+    const NAMED: &'static [Button] = &[
+        Button::South,
+        Button::East,
+        Button::North,
+        Button::West,
+        Button::C,
+        Button::Z,
+        Button::LeftTrigger,
+        Button::LeftTrigger2,
+        Button::RightTrigger,
+        Button::RightTrigger2,
+        Button::Select,
+        Button::Start,
+        Button::Mode,
+        Button::LeftThumb,
+        Button::RightThumb,
+        Button::DPadUp,
+        Button::DPadDown,
+        Button::DPadLeft,
+        Button::DPadRight,
+        Button::Unknown,
+        Button::LeftPaddle,
+        Button::RightPaddle,
+    ];
+
+    /// Unknown buttons keep their event code so they can still be told apart
+    pub fn from_gilrs(button: gilrs::ev::Button, code: gilrs::ev::Code) -> Self {
+        match button {
+            gilrs::ev::Button::Unknown => Button::Raw(code.into_u32()),
+            button => button.into(),
+        }
+    }
+
     pub fn all_gilrs_buttons() -> &'static [gilrs::ev::Button] {
         &[
             gilrs::ev::Button::South,
@@ -102,9 +450,8 @@ impl From<gilrs::ev::Button> for Button {
     }
 }
 
-#[derive(
-    Debug, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, JsonSchema,
-)]
+/// Serialized like [`Button`]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
 pub enum Axis {
     LeftStickX,
     LeftStickY,
@@ -115,6 +462,131 @@ pub enum Axis {
     DPadX,
     DPadY,
     Unknown,
+    /// Event code of an axis gilrs doesn't recognize
+    Raw(u32),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown {kind} {name:?}")]
+pub struct UnknownControl {
+    kind: &'static str,
+    name: String,
+}
+
+/// Code of a `Raw(<code>)` name
+fn raw_code(name: &str) -> Option<u32> {
+    name.strip_prefix("Raw(")?.strip_suffix(')')?.parse().ok()
+}
+
+/// Known names, or any raw code
+fn control_schema<T: std::fmt::Debug>(named: &[T]) -> Schema {
+    let names: Vec<String> = named.iter().map(|control| format!("{control:?}")).collect();
+    serde_json::from_value(json!({
+        "anyOf": [
+            { "type": "string", "enum": names },
+            { "type": "string", "pattern": "^Raw\\([0-9]+\\)$" },
+        ]
+    }))
+    .expect("Control schema is valid")
+}
+
+/// Debug names are the serialized names
+impl std::fmt::Display for Button {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Parse the serialized name of a button, used for command line options
+impl std::str::FromStr for Button {
+    type Err = UnknownControl;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some(code) = raw_code(name) {
+            return Ok(Button::Raw(code));
+        }
+        Button::NAMED
+            .iter()
+            .find(|button| button.to_string() == name)
+            .copied()
+            .ok_or_else(|| UnknownControl {
+                kind: "button",
+                name: name.to_owned(),
+            })
+    }
+}
+
+impl Serialize for Button {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Button {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for Button {
+    fn schema_name() -> String {
+        "Button".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        control_schema(Button::NAMED)
+    }
+}
+
+impl std::fmt::Display for Axis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::str::FromStr for Axis {
+    type Err = UnknownControl;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some(code) = raw_code(name) {
+            return Ok(Axis::Raw(code));
+        }
+        Axis::all_axes()
+            .iter()
+            .chain([&Axis::Unknown])
+            .find(|axis| axis.to_string() == name)
+            .copied()
+            .ok_or_else(|| UnknownControl {
+                kind: "axis",
+                name: name.to_owned(),
+            })
+    }
+}
+
+impl Serialize for Axis {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Axis {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for Axis {
+    fn schema_name() -> String {
+        "Axis".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let mut named = Axis::all_axes().to_vec();
+        named.push(Axis::Unknown);
+        control_schema(&named)
+    }
 }
 
 impl Axis {
@@ -131,6 +603,14 @@ impl Axis {
             Axis::DPadY,
         ]
     }
+
+    /// Unknown axes keep their event code so they can still be told apart
+    pub fn from_gilrs(axis: gilrs::ev::Axis, code: gilrs::ev::Code) -> Self {
+        match axis {
+            gilrs::ev::Axis::Unknown => Axis::Raw(code.into_u32()),
+            axis => axis.into(),
+        }
+    }
 }
 
 impl From<gilrs::ev::Axis> for Axis {
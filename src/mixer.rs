@@ -0,0 +1,96 @@
+//! Velocity commands mixed from stick input for robots that don't want raw gamepad state
+//!
+//! Sticks are read after axis mapping, so deadzones and expo also shape the velocity.
+//! Pushing the left stick forward drives forward and pushing the right stick right turns
+//! right, which is a negative rotation around z.
+
+use serde::Deserialize;
+
+use crate::{
+    config::GamepadSelector,
+    foxglove,
+    messages::{timestamp, Axis, InputMessage},
+    remote_control,
+};
+
+/// `twist` section of a robot profile
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TwistMixerConfig {
+    /// Topic the protobuf `remote_control.Twist` is published on
+    pub topic: String,
+    /// m/s at full stick deflection
+    pub max_linear_velocity: f64,
+    /// rad/s at full stick deflection
+    pub max_angular_velocity: f64,
+    #[serde(default = "default_linear_x_axis")]
+    pub linear_x_axis: Option<Axis>,
+    /// Strafing for holonomic robots, not mixed by default
+    #[serde(default)]
+    pub linear_y_axis: Option<Axis>,
+    #[serde(default = "default_angular_z_axis")]
+    pub angular_z_axis: Option<Axis>,
+    /// Gamepad that drives, defaults to the connected gamepad with the lowest id
+    #[serde(default)]
+    pub gamepad: Option<GamepadSelector>,
+}
+
+fn default_linear_x_axis() -> Option<Axis> {
+    Some(Axis::LeftStickY)
+}
+
+fn default_angular_z_axis() -> Option<Axis> {
+    Some(Axis::RightStickX)
+}
+
+impl TwistMixerConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (name, velocity) in [
+            ("max_linear_velocity", self.max_linear_velocity),
+            ("max_angular_velocity", self.max_angular_velocity),
+        ] {
+            if !(velocity.is_finite() && velocity >= 0.0) {
+                anyhow::bail!("{name} must be a positive number, got {velocity}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Zero velocity without a driving gamepad, while the emergency stop holds the axes at zero
+pub fn mix_twist(message: &InputMessage, config: &TwistMixerConfig) -> remote_control::Twist {
+    let gamepad = message
+        .gamepads
+        .iter()
+        .filter(|(gamepad_id, gamepad)| {
+            gamepad.connected
+                && config
+                    .gamepad
+                    .as_ref()
+                    .is_none_or(|selector| selector.matches(**gamepad_id, &gamepad.name))
+        })
+        .min_by_key(|(gamepad_id, _)| **gamepad_id)
+        .map(|(_, gamepad)| gamepad);
+    let axis = |axis: Option<Axis>| -> f64 {
+        match (gamepad, axis) {
+            (Some(gamepad), Some(axis)) => {
+                f64::from(gamepad.axis_state.get(&axis).copied().unwrap_or_default())
+            }
+            _ => 0.0,
+        }
+    };
+    remote_control::Twist {
+        time: Some(timestamp(message.time)),
+        linear: Some(foxglove::Vector3 {
+            x: axis(config.linear_x_axis) * config.max_linear_velocity,
+            // stick right is towards negative y
+            y: -axis(config.linear_y_axis) * config.max_linear_velocity,
+            z: 0.0,
+        }),
+        angular: Some(foxglove::Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: -axis(config.angular_z_axis) * config.max_angular_velocity,
+        }),
+    }
+}
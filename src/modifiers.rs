@@ -0,0 +1,98 @@
+//! Named speed modes selected by holding a button
+//!
+//! Holding a modifier button, for example `LeftTrigger2` for turbo or `RightTrigger2`
+//! for precision, scales the motion axes and reports the mode's name in `mode` of the
+//! gamepad message. Without a held modifier the axes get the section's base `scale`.
+
+use std::collections::HashSet;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::messages::{Axis, Button, GamepadMessage, InputMessage};
+
+/// `modifiers` section of a robot profile
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModifierConfig {
+    /// Axis scale while no modifier is held
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// Axes that are scaled, all axes if empty
+    #[serde(default)]
+    pub axes: Vec<Axis>,
+    /// The first modifier whose button is held is active
+    pub modes: Vec<Modifier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Modifier {
+    /// Reported as `mode` while the modifier is active
+    pub name: String,
+    pub button: Button,
+    pub scale: f32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn validate_scale(scale: f32) -> anyhow::Result<()> {
+    if !(scale > 0.0 && scale <= 1.0) {
+        anyhow::bail!("scale must be above 0.0 and at most 1.0, got {scale}");
+    }
+    Ok(())
+}
+
+impl ModifierConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        validate_scale(self.scale)?;
+        if self.modes.is_empty() {
+            anyhow::bail!("modes must not be empty");
+        }
+        let mut names = HashSet::new();
+        let mut buttons = HashSet::new();
+        for modifier in &self.modes {
+            if modifier.name.is_empty() {
+                anyhow::bail!("Modifier names must not be empty");
+            }
+            if !names.insert(&modifier.name) {
+                anyhow::bail!("Modifier {:?} is defined twice", modifier.name);
+            }
+            if !buttons.insert(modifier.button) {
+                anyhow::bail!("{:?} selects more than one modifier", modifier.button);
+            }
+            validate_scale(modifier.scale)
+                .with_context(|| format!("Invalid modifier {:?}", modifier.name))?;
+        }
+        Ok(())
+    }
+
+    /// Modifier selected by the buttons held on `gamepad`
+    pub fn active(&self, gamepad: &GamepadMessage) -> Option<&Modifier> {
+        self.modes
+            .iter()
+            .find(|modifier| gamepad.button_down.get(&modifier.button) == Some(&true))
+    }
+
+    /// Scale the axes of every gamepad and set its `mode`
+    ///
+    /// Held buttons are read from `live` input like the dead man's switch, so replayed
+    /// macros and scripts can't select turbo for the operator.
+    pub fn apply(&self, live: &InputMessage, message: &mut InputMessage) {
+        for (gamepad_id, gamepad) in message.gamepads.iter_mut() {
+            let active = live
+                .gamepads
+                .get(gamepad_id)
+                .and_then(|live| self.active(live));
+            gamepad.mode = active.map(|modifier| modifier.name.clone());
+            let scale = active.map_or(self.scale, |modifier| modifier.scale);
+            for (axis, value) in gamepad.axis_state.iter_mut() {
+                if self.axes.is_empty() || self.axes.contains(axis) {
+                    *value *= scale;
+                }
+            }
+        }
+    }
+}
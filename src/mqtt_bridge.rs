@@ -0,0 +1,152 @@
+//! MQTT topics republished onto zenoh
+//!
+//! zigbee2mqtt only talks MQTT. Subscribing to its topics here removes the need for a
+//! separate MQTT to zenoh shim, the samples reach Foxglove through the regular
+//! `json_subscriptions` of the profile.
+
+use std::{sync::Arc, time::Duration};
+
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Outgoing, Packet, QoS};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    error::ZenohError,
+    health::{Heartbeat, HEARTBEAT_INTERVAL},
+    log_throttle::{LogThrottle, DEFAULT_THROTTLE_WINDOW},
+    supervisor::Supervisor,
+};
+
+const DEFAULT_MQTT_PORT: u16 = 1883;
+const MQTT_KEEP_ALIVE: Duration = Duration::from_secs(5);
+const MQTT_REQUEST_QUEUE_SIZE: usize = 64;
+const MQTT_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// `mqtt` section of a robot profile
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MqttBridgeConfig {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    /// Defaults to a name unique to this process
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// MQTT topic filters, `+` and `#` wildcards are allowed
+    pub topics: Vec<String>,
+}
+
+fn default_mqtt_port() -> u16 {
+    DEFAULT_MQTT_PORT
+}
+
+pub fn start_mqtt_bridge(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    config: MqttBridgeConfig,
+) {
+    let heartbeat = supervisor.health().heartbeat("mqtt_bridge");
+    supervisor.spawn("mqtt_bridge", move |shutdown| {
+        run_mqtt_bridge(
+            zenoh_session.clone(),
+            config.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_mqtt_bridge(
+    zenoh_session: Arc<Session>,
+    config: MqttBridgeConfig,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let client_id = config
+        .client_id
+        .clone()
+        .unwrap_or_else(|| format!("deck-robot-remote-{}", std::process::id()));
+    let mut options = MqttOptions::new(client_id, &config.host, config.port);
+    options.set_keep_alive(MQTT_KEEP_ALIVE);
+    let (client, mut event_loop) = AsyncClient::new(options, MQTT_REQUEST_QUEUE_SIZE);
+    info!(
+        "Bridging MQTT topics {:?} from {}:{}",
+        config.topics, config.host, config.port
+    );
+
+    let mut error_log = LogThrottle::new("mqtt_bridge", DEFAULT_THROTTLE_WINDOW);
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        let event = tokio::select! {
+            // connection errors restart the bridge with the supervisor's backoff
+            event = event_loop.poll() => event?,
+            _ = heartbeat_interval.tick() => {
+                heartbeat.beat();
+                error_log.flush();
+                continue;
+            }
+            _ = shutdown.cancelled() => break,
+        };
+        match event {
+            // subscriptions don't survive a reconnect with a clean session
+            Event::Incoming(Packet::ConnAck(_)) => {
+                // queued without waiting since the event loop is what sends them
+                for topic in &config.topics {
+                    client.try_subscribe(topic, QoS::AtMostOnce)?;
+                }
+            }
+            Event::Incoming(Packet::Publish(publish)) => {
+                let key_expr = match KeyExpr::try_from(publish.topic.as_str()) {
+                    Ok(key_expr) => key_expr,
+                    Err(err) => {
+                        error_log.error(&format!(
+                            "MQTT topic {} is not a valid key expression: {err}",
+                            publish.topic
+                        ));
+                        continue;
+                    }
+                };
+                // zigbee2mqtt payloads are JSON text
+                let value = match String::from_utf8(publish.payload.to_vec()) {
+                    Ok(text) => Value::from(text),
+                    Err(err) => Value::from(err.into_bytes()),
+                };
+                zenoh_session
+                    .put(&key_expr, value)
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            _ => {}
+        }
+    }
+
+    // the request queue may be full, the event loop is what drains it
+    if let Err(err) = client.try_disconnect() {
+        debug!("Failed to disconnect from MQTT broker {err:?}");
+        return Ok(());
+    }
+    if tokio::time::timeout(MQTT_DISCONNECT_TIMEOUT, flush_disconnect(&mut event_loop))
+        .await
+        .is_err()
+    {
+        debug!("Timed out disconnecting from MQTT broker");
+    }
+    Ok(())
+}
+
+/// Poll the event loop until the queued DISCONNECT is written to the broker
+async fn flush_disconnect(event_loop: &mut EventLoop) {
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => return,
+            Ok(_) => {}
+            Err(err) => {
+                debug!("MQTT connection closed while disconnecting {err:?}");
+                return;
+            }
+        }
+    }
+}
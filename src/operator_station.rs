@@ -0,0 +1,188 @@
+//! Battery, temperature and WiFi signal of the Deck itself
+//!
+//! Read from the Linux power supply and thermal classes in sysfs and from
+//! `/proc/net/wireless`. Values that aren't available are left out.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    error::ZenohError,
+    health::Heartbeat,
+    supervisor::Supervisor,
+};
+
+pub const OPERATOR_STATION_TOPIC: &str = "remote-control/operator_station/health";
+const OPERATOR_STATION_PUBLISH_INTERVAL: Duration = Duration::from_secs(5);
+/// A discharging battery below this is reported as low
+pub const LOW_BATTERY_PERCENT: f64 = 15.0;
+
+/// Published on `remote-control/operator_station/health`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct OperatorStationMessage {
+    pub battery_percent: Option<f64>,
+    pub battery_charging: Option<bool>,
+    pub battery_low: bool,
+    /// Hottest thermal zone
+    pub cpu_temperature_celsius: Option<f64>,
+    pub wifi_signal_dbm: Option<f64>,
+    pub time: DateTime<Utc>,
+}
+
+/// Where the readings come from, the defaults are the standard Linux locations
+#[derive(Debug, Clone)]
+pub struct SystemPaths {
+    pub power_supply_dir: PathBuf,
+    pub thermal_dir: PathBuf,
+    pub wireless_file: PathBuf,
+}
+
+impl Default for SystemPaths {
+    fn default() -> Self {
+        Self {
+            power_supply_dir: PathBuf::from("/sys/class/power_supply"),
+            thermal_dir: PathBuf::from("/sys/class/thermal"),
+            wireless_file: PathBuf::from("/proc/net/wireless"),
+        }
+    }
+}
+
+impl SystemPaths {
+    pub fn read(&self, time: DateTime<Utc>) -> OperatorStationMessage {
+        let battery = self.battery();
+        let battery_percent = battery.as_ref().map(|(percent, _)| *percent);
+        let battery_charging = battery.as_ref().map(|(_, charging)| *charging);
+        OperatorStationMessage {
+            battery_percent,
+            battery_charging,
+            battery_low: battery
+                .is_some_and(|(percent, charging)| !charging && percent < LOW_BATTERY_PERCENT),
+            cpu_temperature_celsius: self.cpu_temperature(),
+            wifi_signal_dbm: self.wifi_signal(),
+            time,
+        }
+    }
+
+    /// Capacity in percent and whether the first battery is charging
+    fn battery(&self) -> Option<(f64, bool)> {
+        sorted_entries(&self.power_supply_dir)
+            .into_iter()
+            .filter(|supply| read_trimmed(&supply.join("type")).as_deref() == Some("Battery"))
+            .find_map(|battery| {
+                let capacity = read_trimmed(&battery.join("capacity"))?.parse().ok()?;
+                let status = read_trimmed(&battery.join("status")).unwrap_or_default();
+                Some((capacity, status == "Charging" || status == "Full"))
+            })
+    }
+
+    fn cpu_temperature(&self) -> Option<f64> {
+        sorted_entries(&self.thermal_dir)
+            .into_iter()
+            .filter_map(|zone| read_trimmed(&zone.join("temp"))?.parse::<f64>().ok())
+            // millidegrees
+            .map(|temperature| temperature / 1000.0)
+            .reduce(f64::max)
+    }
+
+    fn wifi_signal(&self) -> Option<f64> {
+        parse_wireless_signal(&std::fs::read_to_string(&self.wireless_file).ok()?)
+    }
+}
+
+/// Signal level in dBm of the first interface in `/proc/net/wireless`
+pub fn parse_wireless_signal(wireless: &str) -> Option<f64> {
+    // two header lines, then `wlan0: 0000   56.  -54.  -256 ...`
+    let line = wireless.lines().nth(2)?;
+    let (_, values) = line.split_once(':')?;
+    let level = values.split_whitespace().nth(2)?;
+    level.trim_end_matches('.').parse().ok()
+}
+
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|value| value.trim().to_owned())
+}
+
+pub fn start_operator_station_publisher(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    clock: SharedClock,
+) {
+    let heartbeat = supervisor.health().heartbeat("operator_station");
+    supervisor.spawn("operator_station", move |shutdown| {
+        run_operator_station_publisher(
+            zenoh_session.clone(),
+            clock.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_operator_station_publisher(
+    zenoh_session: Arc<Session>,
+    clock: SharedClock,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let publisher = zenoh_session
+        .declare_publisher(OPERATOR_STATION_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let paths = SystemPaths::default();
+    let mut battery_was_low = false;
+    let mut interval = tokio::time::interval(OPERATOR_STATION_PUBLISH_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                heartbeat.beat();
+                let time = clock.now().into();
+                let reader = paths.clone();
+                let message = tokio::task::spawn_blocking(move || reader.read(time)).await?;
+                if message.battery_low && !battery_was_low {
+                    warn!(
+                        "Deck battery low at {:.0}%",
+                        message.battery_percent.unwrap_or_default()
+                    );
+                }
+                battery_was_low = message.battery_low;
+                publisher
+                    .put(serde_json::to_string(&message)?)
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,281 @@
+//! Checks that the current user can read the devices gilrs needs
+//!
+//! Missing permissions are the most common reason no gamepads are found on a fresh install.
+//! Only gamepads and joysticks have to be readable, a working Deck still has root only nodes
+//! for the power button, lid switch and unrelated HID devices.
+
+use std::{
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::doctor::{CheckStatus, DoctorCheck};
+
+const INPUT_DEVICE_DIR: &str = "/dev/input";
+const DEV_DIR: &str = "/dev";
+const INPUT_SYSFS_DIR: &str = "/sys/class/input";
+const HIDRAW_SYSFS_DIR: &str = "/sys/class/hidraw";
+/// `BTN_JOYSTICK` and `BTN_GAMEPAD` from `linux/input-event-codes.h`, what udev tags joysticks by
+const JOYSTICK_BUTTON_CODES: [usize; 2] = [0x120, 0x130];
+const VALVE_VENDOR_ID: u32 = 0x28de;
+const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/60-deck-robot-remote.rules";
+/// Valve vendor id, covers the Steam Deck controls and Steam controllers
+const UDEV_RULE: &str =
+    r#"KERNEL=="hidraw*", ATTRS{idVendor}=="28de", MODE="0660", TAG+="uaccess""#;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceAccess {
+    Readable,
+    PermissionDenied,
+    Error(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceStatus {
+    pub path: PathBuf,
+    /// Group owning the device node
+    pub group: Option<String>,
+    pub access: DeviceAccess,
+    /// Gamepad or joystick that has to be readable, other devices are only listed
+    pub gamepad: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PermissionReport {
+    pub event_devices: Vec<DeviceStatus>,
+    pub hidraw_devices: Vec<DeviceStatus>,
+}
+
+impl PermissionReport {
+    /// A gamepad is connected and every gamepad device is readable
+    pub fn is_ok(&self) -> bool {
+        self.event_devices.iter().any(|device| device.gamepad)
+            && self
+                .gamepad_devices()
+                .all(|device| device.access == DeviceAccess::Readable)
+    }
+
+    /// Devices that have to be readable
+    pub fn gamepad_devices(&self) -> impl Iterator<Item = &DeviceStatus> {
+        self.event_devices
+            .iter()
+            .chain(self.hidraw_devices.iter())
+            .filter(|device| device.gamepad)
+    }
+
+    /// Groups owning gamepad event devices the user can't read
+    pub fn denied_groups(&self) -> Vec<String> {
+        let mut denied_groups: Vec<String> = self
+            .event_devices
+            .iter()
+            .filter(|device| device.gamepad && device.access == DeviceAccess::PermissionDenied)
+            .filter_map(|device| device.group.clone())
+            .collect();
+        denied_groups.sort();
+        denied_groups.dedup();
+        denied_groups
+    }
+
+    /// Whether the Steam Deck controls need the udev rule
+    pub fn needs_udev_rule(&self) -> bool {
+        self.hidraw_devices
+            .iter()
+            .any(|device| device.gamepad && device.access == DeviceAccess::PermissionDenied)
+    }
+
+    /// Summary for the `doctor` subcommand
+    pub fn doctor_check(&self) -> DoctorCheck {
+        if !self.event_devices.iter().any(|device| device.gamepad) {
+            return DoctorCheck::new(
+                "input permissions",
+                CheckStatus::Failed,
+                "No gamepad input devices found, is a gamepad connected?",
+            );
+        }
+        let unreadable: Vec<String> = self
+            .gamepad_devices()
+            .filter(|device| device.access != DeviceAccess::Readable)
+            .map(|device| device.path.display().to_string())
+            .collect();
+        if unreadable.is_empty() {
+            let count = self.gamepad_devices().count();
+            DoctorCheck::new(
+                "input permissions",
+                CheckStatus::Ok,
+                format!("{count} gamepad devices readable"),
+            )
+        } else {
+            DoctorCheck::new(
+                "input permissions",
+                CheckStatus::Failed,
+                format!(
+                    "Can't read {}, run check-permissions",
+                    unreadable.join(", ")
+                ),
+            )
+        }
+    }
+
+    /// Print the status of every device and the changes needed to fix missing permissions
+    pub fn print(&self) {
+        println!("Input devices in {INPUT_DEVICE_DIR}:");
+        print_devices(&self.event_devices);
+        println!("hidraw devices in {DEV_DIR}:");
+        print_devices(&self.hidraw_devices);
+
+        if !self.event_devices.iter().any(|device| device.gamepad) {
+            println!();
+            println!("No gamepad input devices found, is a gamepad connected?");
+        }
+
+        for group in self.denied_groups() {
+            println!();
+            println!("Add your user to the {group:?} group and log in again:");
+            println!("    sudo usermod -aG {group} $USER");
+        }
+
+        if self.needs_udev_rule() {
+            println!();
+            println!("Allow access to hidraw devices with a udev rule:");
+            println!("    echo '{UDEV_RULE}' | sudo tee {UDEV_RULE_PATH}");
+            println!("    sudo udevadm control --reload-rules && sudo udevadm trigger");
+        }
+
+        if self.is_ok() {
+            println!();
+            println!("All gamepad devices are readable");
+        }
+    }
+}
+
+fn print_devices(devices: &[DeviceStatus]) {
+    if devices.is_empty() {
+        println!("    none");
+    }
+    for device in devices {
+        let access = match &device.access {
+            DeviceAccess::Readable => String::from("ok"),
+            DeviceAccess::PermissionDenied => String::from("permission denied"),
+            DeviceAccess::Error(err) => format!("error: {err}"),
+        };
+        let group = device.group.as_deref().unwrap_or("?");
+        let ignored = if device.gamepad {
+            ""
+        } else {
+            ", not a gamepad"
+        };
+        println!(
+            "    {} (group {group}): {access}{ignored}",
+            device.path.display()
+        );
+    }
+}
+
+/// Whether the `capabilities/key` bitmap of an input device has joystick or gamepad buttons
+///
+/// The bitmap is a list of hex words with the highest word first, like udev reads it.
+pub fn has_joystick_buttons(capabilities_key: &str) -> bool {
+    let words: Vec<u64> = capabilities_key
+        .split_whitespace()
+        .rev()
+        .map(|word| u64::from_str_radix(word, 16).unwrap_or_default())
+        .collect();
+    JOYSTICK_BUTTON_CODES.iter().any(|code| {
+        words
+            .get(code / 64)
+            .is_some_and(|word| word & (1u64 << (code % 64)) != 0)
+    })
+}
+
+/// Whether the `uevent` of a hidraw device's HID parent is a Valve device
+pub fn is_valve_hid_device(uevent: &str) -> bool {
+    uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("HID_ID="))
+        .and_then(|hid_id| hid_id.split(':').nth(1))
+        .and_then(|vendor| u32::from_str_radix(vendor, 16).ok())
+        == Some(VALVE_VENDOR_ID)
+}
+
+fn is_gamepad_event_device(name: &str) -> bool {
+    std::fs::read_to_string(format!("{INPUT_SYSFS_DIR}/{name}/device/capabilities/key"))
+        .is_ok_and(|capabilities_key| has_joystick_buttons(&capabilities_key))
+}
+
+fn is_gamepad_hidraw_device(name: &str) -> bool {
+    std::fs::read_to_string(format!("{HIDRAW_SYSFS_DIR}/{name}/device/uevent"))
+        .is_ok_and(|uevent| is_valve_hid_device(&uevent))
+}
+
+/// Try opening every input event and hidraw device for reading
+pub fn check_input_permissions() -> anyhow::Result<PermissionReport> {
+    Ok(PermissionReport {
+        event_devices: check_devices(
+            Path::new(INPUT_DEVICE_DIR),
+            "event",
+            is_gamepad_event_device,
+        )?,
+        hidraw_devices: check_devices(Path::new(DEV_DIR), "hidraw", is_gamepad_hidraw_device)?,
+    })
+}
+
+fn check_devices(
+    dir: &Path,
+    prefix: &str,
+    is_gamepad: fn(&str) -> bool,
+) -> anyhow::Result<Vec<DeviceStatus>> {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+            .map(|entry| entry.path())
+            .collect(),
+        Err(err) if err.kind() == ErrorKind::NotFound => vec![],
+        Err(err) => return Err(err.into()),
+    };
+    paths.sort();
+
+    let devices = paths
+        .into_iter()
+        .map(|path| {
+            let access = match File::open(&path) {
+                Ok(_) => DeviceAccess::Readable,
+                Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+                    DeviceAccess::PermissionDenied
+                }
+                Err(err) => DeviceAccess::Error(err.to_string()),
+            };
+            let gamepad = path
+                .file_name()
+                .is_some_and(|name| is_gamepad(&name.to_string_lossy()));
+            DeviceStatus {
+                group: device_group(&path),
+                path,
+                access,
+                gamepad,
+            }
+        })
+        .collect();
+    Ok(devices)
+}
+
+#[cfg(unix)]
+fn device_group(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let gid = std::fs::metadata(path).ok()?.gid();
+    let groups = std::fs::read_to_string("/etc/group").ok()?;
+    let name = groups.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let group_id: u32 = fields.nth(1)?.parse().ok()?;
+        (group_id == gid).then(|| name.to_owned())
+    });
+    Some(name.unwrap_or_else(|| gid.to_string()))
+}
+
+#[cfg(not(unix))]
+fn device_group(_path: &Path) -> Option<String> {
+    None
+}
@@ -0,0 +1,93 @@
+//! Protobuf message types loaded at runtime
+//!
+//! `descriptor_files` of a profile adds message types to the ones compiled into the remote,
+//! so new robot messages can be bridged without recompiling. Files ending in `.proto` are
+//! compiled with `protoc` at startup, anything else is read as an encoded
+//! `FileDescriptorSet` such as the output of `protoc --include_imports -o`.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::RwLock,
+};
+
+use once_cell::sync::Lazy;
+use prost_reflect::DescriptorPool;
+use tracing::*;
+
+use crate::{error::ConfigError, DESCRIPTOR_POOL};
+
+static RUNTIME_POOL: Lazy<RwLock<DescriptorPool>> =
+    Lazy::new(|| RwLock::new(DESCRIPTOR_POOL.clone()));
+
+/// Built-in message types plus the ones from the last [`load_descriptor_files`]
+pub fn descriptor_pool() -> DescriptorPool {
+    RUNTIME_POOL.read().unwrap().clone()
+}
+
+/// Replace the runtime message types with the ones in `paths`
+///
+/// Starts over from the built-in types so a reloaded configuration can change a file.
+/// Files may repeat built-in files, such as imported `foxglove` messages, if they are identical.
+pub fn load_descriptor_files(paths: &[PathBuf]) -> Result<(), ConfigError> {
+    let mut pool = DESCRIPTOR_POOL.clone();
+    for path in paths {
+        let file_descriptor_set = if path
+            .extension()
+            .is_some_and(|extension| extension == "proto")
+        {
+            compile_proto(path)?
+        } else {
+            std::fs::read(path).map_err(|error| ConfigError::Read {
+                path: path.clone(),
+                error,
+            })?
+        };
+        pool.decode_file_descriptor_set(file_descriptor_set.as_slice())
+            .map_err(|err| ConfigError::Invalid {
+                name: format!("descriptor set {}", path.display()),
+                reason: err.to_string(),
+            })?;
+        info!("Loaded protobuf descriptors from {}", path.display());
+    }
+    *RUNTIME_POOL.write().unwrap() = pool;
+    Ok(())
+}
+
+/// Run `protoc`, or `$PROTOC`, with the directory of `path` as include path
+fn compile_proto(path: &Path) -> Result<Vec<u8>, ConfigError> {
+    let protoc_error = |reason: String| ConfigError::Protoc {
+        path: path.to_owned(),
+        reason,
+    };
+    let protoc = std::env::var_os("PROTOC").unwrap_or_else(|| "protoc".into());
+    let include_dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // removed when dropped, whether protoc succeeded or not
+    let output_file = tempfile::Builder::new()
+        .prefix("deck-robot-remote-")
+        .suffix(".bin")
+        .tempfile()
+        .map_err(|err| protoc_error(format!("failed to create output file: {err}")))?;
+    let output = Command::new(&protoc)
+        .arg("--include_imports")
+        .arg(format!("--proto_path={}", include_dir.display()))
+        .arg(format!(
+            "--descriptor_set_out={}",
+            output_file.path().display()
+        ))
+        .arg(path)
+        .output()
+        .map_err(|err| {
+            protoc_error(format!("failed to run {}: {err}", protoc.to_string_lossy()))
+        })?;
+    if !output.status.success() {
+        return Err(protoc_error(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    std::fs::read(output_file.path())
+        .map_err(|err| protoc_error(format!("failed to read output: {err}")))
+}
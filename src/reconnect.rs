@@ -0,0 +1,130 @@
+//! Reconnecting to the robot after the zenoh link was lost
+//!
+//! The local zenoh session stays open when the robot reboots, but the transport to it
+//! isn't always re-established. The watchdog re-applies the connect endpoints with backoff
+//! until a peer or router is back and then restarts the supervised tasks so that every
+//! publisher, queryable and bridge subscriber is declared again on the new link.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    backoff::Backoff, connection::ZenohPeers, error::ZenohError, health::Heartbeat,
+    supervisor::Supervisor,
+};
+
+const SESSION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Time a lost link gets to come back on its own before reconnecting
+const RECONNECT_GRACE: Duration = Duration::from_secs(3);
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkTransition {
+    Lost,
+    Restored,
+}
+
+/// Turns polled link state into lost and restored transitions
+///
+/// Coming up for the first time isn't a transition since nothing was declared on a lost link.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkTracker {
+    #[default]
+    NeverConnected,
+    Up,
+    Down,
+}
+
+impl LinkTracker {
+    pub fn update(&mut self, has_link: bool) -> Option<LinkTransition> {
+        let (next, transition) = match (*self, has_link) {
+            (LinkTracker::Up, false) => (LinkTracker::Down, Some(LinkTransition::Lost)),
+            (LinkTracker::Down, true) => (LinkTracker::Up, Some(LinkTransition::Restored)),
+            (LinkTracker::NeverConnected, true) => (LinkTracker::Up, None),
+            (state, _) => (state, None),
+        };
+        *self = next;
+        transition
+    }
+}
+
+/// Watch the zenoh link and reconnect and redeclare everything after it was lost
+pub fn start_session_watchdog(supervisor: &Supervisor, zenoh_session: Arc<Session>) {
+    let heartbeat = supervisor.health().heartbeat("session_watchdog");
+    // restarting itself would forget that the link was lost
+    supervisor.spawn_persistent("session_watchdog", {
+        let supervisor = supervisor.clone();
+        move |shutdown| {
+            run_session_watchdog(
+                zenoh_session.clone(),
+                supervisor.clone(),
+                heartbeat.clone(),
+                shutdown,
+            )
+        }
+    });
+}
+
+async fn run_session_watchdog(
+    zenoh_session: Arc<Session>,
+    supervisor: Supervisor,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut tracker = LinkTracker::default();
+    let mut backoff = Backoff::new(INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY);
+    let mut next_reconnect = Instant::now();
+    let mut interval = tokio::time::interval(SESSION_POLL_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => heartbeat.beat(),
+            _ = shutdown.cancelled() => break,
+        }
+        let peers = ZenohPeers::read(&zenoh_session).await;
+        let has_link = !peers.peers.is_empty() || !peers.routers.is_empty();
+        match tracker.update(has_link) {
+            Some(LinkTransition::Lost) => {
+                warn!("Lost zenoh link to the robot");
+                backoff.reset();
+                next_reconnect = Instant::now() + RECONNECT_GRACE;
+            }
+            Some(LinkTransition::Restored) => {
+                info!("Zenoh link restored, redeclaring publishers and subscribers");
+                supervisor.restart_tasks();
+            }
+            None if tracker == LinkTracker::Down && Instant::now() >= next_reconnect => {
+                info!("Reconnecting zenoh");
+                reapply_connect_endpoints(&zenoh_session)?;
+                next_reconnect = Instant::now() + backoff.next_delay();
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Setting the connect endpoints again makes zenoh retry all of them
+fn reapply_connect_endpoints(zenoh_session: &Session) -> anyhow::Result<()> {
+    let connect_endpoints: Vec<String> = zenoh_session
+        .config()
+        .lock()
+        .connect
+        .endpoints
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    zenoh_session
+        .config()
+        .insert_json5(
+            "connect/endpoints",
+            &serde_json::to_string(&connect_endpoints)?,
+        )
+        .map_err(ZenohError)?;
+    Ok(())
+}
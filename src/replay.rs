@@ -0,0 +1,215 @@
+//! Republishing recorded sessions onto zenoh with their original timing
+//!
+//! Recordings are either MCAP files, such as the ones Foxglove records from the bridge,
+//! or JSON lines with one sample per line:
+//!
+//! ```text
+//! {"log_time_ns": 1714564800000000000, "topic": "remote-control/gamepad", "payload": {"gamepads": {}}}
+//! ```
+//!
+//! String payloads are published as they are, any other JSON value is published serialized.
+//!
+//! Replaying drives the robot like a live session would, so when the replay finishes or is
+//! stopped, the replayed gamepad and twist topics receive neutral input.
+
+use std::{collections::BTreeSet, path::Path, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::{prelude::r#async::*, publication::CongestionControl};
+
+use crate::{
+    clock::Clock,
+    error::ZenohError,
+    gamepad::{serialize_input, serialize_twist},
+    messages::{GamepadEncoding, InputMessage},
+    remote_control,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedPayload {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedSample {
+    /// Time since the first sample of the recording
+    pub offset: Duration,
+    pub topic: String,
+    pub payload: RecordedPayload,
+}
+
+#[derive(Deserialize)]
+struct JsonLine {
+    log_time_ns: u64,
+    topic: String,
+    payload: serde_json::Value,
+}
+
+/// Read a `.mcap` file or JSON lines, sorted by time
+pub fn read_recording(path: &Path) -> anyhow::Result<Vec<RecordedSample>> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let samples = if path
+        .extension()
+        .is_some_and(|extension| extension == "mcap")
+    {
+        parse_mcap(&data)
+    } else {
+        parse_json_lines(std::str::from_utf8(&data)?)
+    };
+    samples.with_context(|| format!("Invalid recording {}", path.display()))
+}
+
+pub fn parse_json_lines(recording: &str) -> anyhow::Result<Vec<RecordedSample>> {
+    let mut samples = vec![];
+    for (index, line) in recording.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line: JsonLine =
+            serde_json::from_str(line).with_context(|| format!("line {}", index + 1))?;
+        let payload = match line.payload {
+            serde_json::Value::String(text) => RecordedPayload::Text(text),
+            value => RecordedPayload::Text(value.to_string()),
+        };
+        samples.push((line.log_time_ns, line.topic, payload));
+    }
+    Ok(relative_to_start(samples))
+}
+
+fn parse_mcap(recording: &[u8]) -> anyhow::Result<Vec<RecordedSample>> {
+    let mut samples = vec![];
+    for message in mcap::MessageStream::new(recording)? {
+        let message = message?;
+        let payload = if message.channel.message_encoding == "json" {
+            RecordedPayload::Text(String::from_utf8(message.data.into_owned())?)
+        } else {
+            RecordedPayload::Binary(message.data.into_owned())
+        };
+        samples.push((message.log_time, message.channel.topic.clone(), payload));
+    }
+    Ok(relative_to_start(samples))
+}
+
+fn relative_to_start(mut samples: Vec<(u64, String, RecordedPayload)>) -> Vec<RecordedSample> {
+    samples.sort_by_key(|(log_time_ns, _, _)| *log_time_ns);
+    let start = samples.first().map(|(log_time_ns, _, _)| *log_time_ns);
+    samples
+        .into_iter()
+        .map(|(log_time_ns, topic, payload)| RecordedSample {
+            offset: Duration::from_nanos(log_time_ns - start.unwrap_or_default()),
+            topic,
+            payload,
+        })
+        .collect()
+}
+
+/// Topics that move the robot, released when a replay stops
+#[derive(Debug, Clone, Default)]
+pub struct ReplayControls {
+    /// Matched with or without a robot namespace in front
+    pub gamepad_topic: Option<String>,
+    pub gamepad_encoding: GamepadEncoding,
+    /// Twist topics of the robot profiles, matched like `gamepad_topic`
+    pub twist_topics: Vec<String>,
+}
+
+impl ReplayControls {
+    pub fn is_gamepad_topic(&self, topic: &str) -> bool {
+        self.gamepad_topic
+            .as_deref()
+            .is_some_and(|gamepad_topic| is_topic_or_namespaced(topic, gamepad_topic))
+    }
+
+    pub fn is_twist_topic(&self, topic: &str) -> bool {
+        self.twist_topics
+            .iter()
+            .any(|twist_topic| is_topic_or_namespaced(topic, twist_topic))
+    }
+}
+
+/// `topic` is `name` or `{namespace}/{name}`
+fn is_topic_or_namespaced(topic: &str, name: &str) -> bool {
+    topic
+        .strip_suffix(name)
+        .is_some_and(|namespace| namespace.is_empty() || namespace.ends_with('/'))
+}
+
+/// Publish `samples` keeping their spacing, `speed` of 2.0 plays twice as fast
+pub async fn replay(
+    zenoh_session: Arc<Session>,
+    samples: &[RecordedSample],
+    speed: f64,
+    controls: &ReplayControls,
+    clock: &dyn Clock,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    if !(speed.is_finite() && speed > 0.0) {
+        anyhow::bail!("Replay speed must be positive, got {speed}");
+    }
+    let Some(last) = samples.last() else {
+        warn!("Recording is empty");
+        return Ok(());
+    };
+    info!(
+        "Replaying {} samples over {:?}",
+        samples.len(),
+        last.offset.div_f64(speed)
+    );
+    let start = tokio::time::Instant::now();
+    for sample in samples {
+        tokio::select! {
+            _ = tokio::time::sleep_until(start + sample.offset.div_f64(speed)) => {}
+            _ = shutdown.cancelled() => break,
+        }
+        let value = match &sample.payload {
+            RecordedPayload::Text(text) => Value::from(text.clone()),
+            RecordedPayload::Binary(data) => Value::from(data.clone()),
+        };
+        zenoh_session
+            .put(&sample.topic, value)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+    }
+    release_controls(&zenoh_session, samples, controls, clock).await
+}
+
+/// Neutral input on every replayed control topic so the robot doesn't keep the last command
+///
+/// Blocking so that the release isn't dropped under congestion, like the gamepad reader's.
+async fn release_controls(
+    zenoh_session: &Session,
+    samples: &[RecordedSample],
+    controls: &ReplayControls,
+    clock: &dyn Clock,
+) -> anyhow::Result<()> {
+    let topics: BTreeSet<&str> = samples.iter().map(|sample| sample.topic.as_str()).collect();
+    let mut buffer = vec![];
+    for topic in topics {
+        let value = if controls.is_gamepad_topic(topic) {
+            let neutral = InputMessage {
+                gamepads: Default::default(),
+                time: clock.now().into(),
+                button_counter_policy: Default::default(),
+                estop_engaged: false,
+            };
+            serialize_input(controls.gamepad_encoding, &mut buffer, &neutral)?
+        } else if controls.is_twist_topic(topic) {
+            serialize_twist(&mut buffer, &remote_control::Twist::default())?
+        } else {
+            continue;
+        };
+        info!("Releasing replayed controls on {topic}");
+        zenoh_session
+            .put(topic, value)
+            .congestion_control(CongestionControl::Block)
+            .res()
+            .await
+            .map_err(ZenohError)?;
+    }
+    Ok(())
+}
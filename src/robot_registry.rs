@@ -0,0 +1,326 @@
+//! Selecting which robot the remote talks to while it's running
+//!
+//! The active robot decides which tailscale peers are connected to and which Foxglove
+//! layout is shown. It can be changed by publishing a profile name on
+//! `remote-control/select_robot` or with the select + d-pad combo on the gamepad.
+//!
+//! With several robots given on the command line all of them stay connected and the
+//! active one only decides which of them receives gamepad input.
+//!
+//! Switching releases the previous robot: it receives neutral input on its own topics
+//! and its tailscale peers are no longer connected to. Without robot namespaces every
+//! robot listens on the same topics, so the selection is fixed.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    config::RobotProfile,
+    error::{ConfigError, ZenohError},
+    failover::FailoverConfig,
+    health::{Heartbeat, HEARTBEAT_INTERVAL},
+    supervisor::Supervisor,
+    tailscale::PeerMatch,
+};
+
+pub const SELECT_ROBOT_TOPIC: &str = "remote-control/select_robot";
+pub const ACTIVE_ROBOT_TOPIC: &str = "remote-control/active_robot";
+
+/// Parts of a robot profile that can change without restarting
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RobotTarget {
+    pub name: String,
+    pub peer_rules: Vec<PeerMatch>,
+    pub foxglove_layout_id: String,
+    pub foxglove_studio_url: Option<String>,
+    pub topic_prefix: Option<String>,
+    /// Only one of `peer_rules` is connected at a time, in order of priority
+    pub failover: Option<FailoverConfig>,
+}
+
+impl RobotTarget {
+    /// Prefix of the keys gamepad input is sent to
+    ///
+    /// Robots without a `topic_prefix` are told apart by their name.
+    pub fn key_prefix(&self) -> &str {
+        self.topic_prefix
+            .as_deref()
+            .map_or(self.name.as_str(), |prefix| prefix.trim_end_matches('/'))
+    }
+
+    /// `gamepad_topic` in the robot's namespace, for example `hopper/remote-control/gamepad`
+    pub fn gamepad_topic(&self, gamepad_topic: &str) -> String {
+        format!("{}/{gamepad_topic}", self.key_prefix())
+    }
+}
+
+impl From<&RobotProfile> for RobotTarget {
+    fn from(profile: &RobotProfile) -> Self {
+        Self {
+            name: profile.name.clone(),
+            peer_rules: profile.peer_rules(),
+            foxglove_layout_id: profile.foxglove_layout_id.clone(),
+            foxglove_studio_url: profile.foxglove_studio_url.clone(),
+            topic_prefix: profile.topic_prefix.clone(),
+            failover: profile.failover.clone(),
+        }
+    }
+}
+
+/// All known robots and the active one
+#[derive(Debug, Clone)]
+pub struct RobotRegistry {
+    targets: Arc<Vec<RobotTarget>>,
+    active: Arc<watch::Sender<RobotTarget>>,
+    simultaneous: bool,
+    /// Set when gamepad topics have no robot namespace, the previous robot couldn't be released
+    fixed: bool,
+    /// Index of the peer rule robots with failover are connected to
+    peer_rule_index: Arc<Mutex<BTreeMap<String, usize>>>,
+}
+
+impl RobotRegistry {
+    pub fn new(
+        profiles: &BTreeMap<String, RobotProfile>,
+        active: &str,
+    ) -> Result<Self, ConfigError> {
+        let targets: Vec<RobotTarget> = profiles.values().map(RobotTarget::from).collect();
+        let active = targets
+            .iter()
+            .find(|target| target.name == active)
+            .cloned()
+            .ok_or_else(|| ConfigError::UnknownProfile {
+                name: active.to_owned(),
+                available: profiles.keys().cloned().collect(),
+            })?;
+        Ok(Self {
+            targets: Arc::new(targets),
+            active: Arc::new(watch::channel(active).0),
+            simultaneous: false,
+            fixed: false,
+            peer_rule_index: Arc::default(),
+        })
+    }
+
+    /// Only the robots in `names`, all connected at once with the first one active
+    ///
+    /// [`RobotRegistry::select_next`] cycles through them in the given order.
+    pub fn simultaneous(
+        profiles: &BTreeMap<String, RobotProfile>,
+        names: &[String],
+    ) -> Result<Self, ConfigError> {
+        let invalid_selection = |reason: String| ConfigError::Invalid {
+            name: "robot selection".to_owned(),
+            reason,
+        };
+        let mut targets: Vec<RobotTarget> = vec![];
+        for name in names {
+            if targets.iter().any(|target| &target.name == name) {
+                return Err(invalid_selection(format!(
+                    "Robot {name:?} selected more than once"
+                )));
+            }
+            let profile = profiles
+                .get(name)
+                .ok_or_else(|| ConfigError::UnknownProfile {
+                    name: name.clone(),
+                    available: profiles.keys().cloned().collect(),
+                })?;
+            targets.push(RobotTarget::from(profile));
+        }
+        let active = targets
+            .first()
+            .cloned()
+            .ok_or_else(|| invalid_selection("No robot selected".to_owned()))?;
+        Ok(Self {
+            targets: Arc::new(targets),
+            active: Arc::new(watch::channel(active).0),
+            simultaneous: true,
+            fixed: false,
+            peer_rule_index: Arc::default(),
+        })
+    }
+
+    /// Refuse switching away from the active robot
+    pub fn with_fixed_selection(mut self) -> Self {
+        self.fixed = true;
+        self
+    }
+
+    /// Whether every robot stays connected instead of only the active one
+    pub fn is_simultaneous(&self) -> bool {
+        self.simultaneous
+    }
+
+    pub fn targets(&self) -> &[RobotTarget] {
+        &self.targets
+    }
+
+    /// Robots whose tailscale peers should be connected to
+    pub fn connected_targets(&self) -> Vec<RobotTarget> {
+        if self.simultaneous {
+            self.targets.to_vec()
+        } else {
+            vec![self.active()]
+        }
+    }
+
+    /// Rules of the tailscale peers `target` is connected to right now
+    ///
+    /// Robots with failover use one rule at a time, starting with the first.
+    pub fn peer_rules(&self, target: &RobotTarget) -> Vec<PeerMatch> {
+        if target.failover.is_none() || target.peer_rules.is_empty() {
+            return target.peer_rules.clone();
+        }
+        let index = self
+            .peer_rule_index
+            .lock()
+            .unwrap()
+            .get(&target.name)
+            .copied()
+            .unwrap_or_default();
+        vec![target.peer_rules[index % target.peer_rules.len()].clone()]
+    }
+
+    /// Move the robot `name` to its next peer rule, wrapping around, and return it
+    pub fn fail_over(&self, name: &str) -> Vec<PeerMatch> {
+        let Some(target) = self.targets.iter().find(|target| target.name == name) else {
+            return vec![];
+        };
+        if target.peer_rules.is_empty() {
+            return vec![];
+        }
+        let mut peer_rule_index = self.peer_rule_index.lock().unwrap();
+        let index = peer_rule_index.entry(name.to_owned()).or_default();
+        *index = (*index + 1) % target.peer_rules.len();
+        vec![target.peer_rules[*index].clone()]
+    }
+
+    pub fn active(&self) -> RobotTarget {
+        self.active.borrow().clone()
+    }
+
+    /// Notified every time another robot is selected
+    pub fn subscribe(&self) -> watch::Receiver<RobotTarget> {
+        self.active.subscribe()
+    }
+
+    pub fn select(&self, name: &str) -> anyhow::Result<()> {
+        if self.fixed && self.active.borrow().name != name {
+            bail!("Robot selection is fixed, gamepad topics have no robot namespace");
+        }
+        let target = self
+            .targets
+            .iter()
+            .find(|target| target.name == name)
+            .with_context(|| {
+                let available: Vec<_> = self.targets.iter().map(|target| &target.name).collect();
+                format!("Unknown robot {name:?}, available robots: {available:?}")
+            })?;
+        self.set_active(target.clone());
+        Ok(())
+    }
+
+    /// Select the robot `step` places after the active one in name order, wrapping around
+    pub fn select_next(&self, step: isize) {
+        if self.fixed {
+            warn!("Robot selection is fixed, gamepad topics have no robot namespace");
+            return;
+        }
+        let active = self.active.borrow().name.clone();
+        let index = self
+            .targets
+            .iter()
+            .position(|target| target.name == active)
+            .unwrap_or_default();
+        let index = (index as isize + step).rem_euclid(self.targets.len() as isize) as usize;
+        self.set_active(self.targets[index].clone());
+    }
+
+    fn set_active(&self, target: RobotTarget) {
+        let changed = self.active.send_if_modified(|active| {
+            if *active == target {
+                return false;
+            }
+            *active = target.clone();
+            true
+        });
+        if changed {
+            info!("Switched to robot {}", target.name);
+        }
+    }
+}
+
+/// Select robots from `remote-control/select_robot` and announce the active one
+pub fn start_robot_selector(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    registry: RobotRegistry,
+) {
+    let heartbeat = supervisor.health().heartbeat("robot_selector");
+    supervisor.spawn("robot_selector", move |shutdown| {
+        run_robot_selector(
+            zenoh_session.clone(),
+            registry.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_robot_selector(
+    zenoh_session: Arc<Session>,
+    registry: RobotRegistry,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let subscriber = zenoh_session
+        .declare_subscriber(SELECT_ROBOT_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let publisher = zenoh_session
+        .declare_publisher(ACTIVE_ROBOT_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut active = registry.subscribe();
+    // announce the current robot after every restart
+    active.mark_changed();
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        tokio::select! {
+            sample = subscriber.recv_async() => {
+                let sample = sample?;
+                let selected = String::try_from(sample.value)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|name| registry.select(name.trim()));
+                if let Err(err) = selected {
+                    warn!("Can't select robot {err:?}");
+                }
+            }
+            changed = active.changed() => {
+                changed?;
+                let target = active.borrow_and_update().clone();
+                publisher
+                    .put(serde_json::to_string(&target)?)
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            _ = heartbeat_interval.tick() => heartbeat.beat(),
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,255 @@
+//! Gamepad state as a ROS 2 `sensor_msgs/Joy` for robots running ROS 2
+//!
+//! `zenoh-bridge-ros2dds` maps the ROS topic `/joy` to the zenoh key `joy` and expects
+//! CDR payloads, so publishing there makes the gamepad show up as a regular ROS topic.
+//! The axes and buttons arrays follow the order of the `joy` package's game controller
+//! node by default. Axis values follow the ROS joy convention by default, stick left and
+//! stick up are positive and triggers rest at 1.0 and go to -1.0 when fully pressed.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    config::GamepadSelector,
+    error::GamepadError,
+    gamepad::take_payload,
+    messages::{Axis, Button, InputMessage},
+};
+
+/// `ros2_joy` section of a robot profile
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ros2JoyConfig {
+    /// Zenoh key, `joy` is the `/joy` topic of zenoh-bridge-ros2dds without a namespace
+    #[serde(default = "default_joy_key_expr")]
+    pub key_expr: String,
+    #[serde(default)]
+    pub encoding: Ros2Encoding,
+    #[serde(default = "default_frame_id")]
+    pub frame_id: String,
+    /// Order of the `axes` array, axes a gamepad doesn't have are at rest
+    #[serde(default = "default_joy_axes")]
+    pub axes: Vec<Axis>,
+    #[serde(default)]
+    pub axis_convention: JoyAxisConvention,
+    /// Order of the `buttons` array
+    #[serde(default = "default_joy_buttons")]
+    pub buttons: Vec<Button>,
+    /// Gamepad that is mirrored, defaults to the connected gamepad with the lowest id
+    #[serde(default)]
+    pub gamepad: Option<GamepadSelector>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ros2Encoding {
+    /// Little endian CDR as used by DDS and zenoh-bridge-ros2dds
+    #[default]
+    Cdr,
+    /// Same fields as JSON, for rosbridge style consumers
+    Json,
+}
+
+/// Sign and range of the published axes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JoyAxisConvention {
+    /// Like the `joy` package, X axes are positive to the left and triggers go from 1.0
+    /// released to -1.0 pressed
+    #[default]
+    Ros,
+    /// As in the gamepad message, X axes are positive to the right and triggers go from 0.0
+    /// released to 1.0 pressed
+    Gamepad,
+}
+
+impl JoyAxisConvention {
+    fn convert(self, axis: Axis, value: f32) -> f32 {
+        match (self, axis) {
+            (Self::Gamepad, _) => value,
+            (Self::Ros, Axis::LeftStickX | Axis::RightStickX | Axis::DPadX) => -value,
+            (Self::Ros, Axis::LeftZ | Axis::RightZ) => 1.0 - 2.0 * value,
+            (Self::Ros, _) => value,
+        }
+    }
+}
+
+fn default_joy_key_expr() -> String {
+    String::from("joy")
+}
+
+fn default_frame_id() -> String {
+    String::from("joy")
+}
+
+fn default_joy_axes() -> Vec<Axis> {
+    vec![
+        Axis::LeftStickX,
+        Axis::LeftStickY,
+        Axis::RightStickX,
+        Axis::RightStickY,
+        Axis::LeftZ,
+        Axis::RightZ,
+    ]
+}
+
+fn default_joy_buttons() -> Vec<Button> {
+    vec![
+        Button::South,
+        Button::East,
+        Button::West,
+        Button::North,
+        Button::Select,
+        Button::Mode,
+        Button::Start,
+        Button::LeftThumb,
+        Button::RightThumb,
+        Button::LeftTrigger,
+        Button::RightTrigger,
+        Button::DPadUp,
+        Button::DPadDown,
+        Button::DPadLeft,
+        Button::DPadRight,
+    ]
+}
+
+/// `builtin_interfaces/Time`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Ros2Time {
+    pub sec: i32,
+    pub nanosec: u32,
+}
+
+impl From<DateTime<Utc>> for Ros2Time {
+    fn from(time: DateTime<Utc>) -> Self {
+        Self {
+            sec: time.timestamp() as i32,
+            nanosec: time.timestamp_subsec_nanos(),
+        }
+    }
+}
+
+/// `std_msgs/Header`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Ros2Header {
+    pub stamp: Ros2Time,
+    pub frame_id: String,
+}
+
+/// `sensor_msgs/Joy`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JoyMessage {
+    pub header: Ros2Header,
+    pub axes: Vec<f32>,
+    pub buttons: Vec<i32>,
+}
+
+/// Everything at rest without a matching connected gamepad
+pub fn joy_message(message: &InputMessage, config: &Ros2JoyConfig) -> JoyMessage {
+    let gamepad = message
+        .gamepads
+        .iter()
+        .filter(|(gamepad_id, gamepad)| {
+            gamepad.connected
+                && config
+                    .gamepad
+                    .as_ref()
+                    .is_none_or(|selector| selector.matches(**gamepad_id, &gamepad.name))
+        })
+        .min_by_key(|(gamepad_id, _)| **gamepad_id)
+        .map(|(_, gamepad)| gamepad);
+    let axes = config
+        .axes
+        .iter()
+        .map(|axis| {
+            let value = gamepad
+                .and_then(|gamepad| gamepad.axis_state.get(axis).copied())
+                .unwrap_or_default();
+            config.axis_convention.convert(*axis, value)
+        })
+        .collect();
+    let buttons = config
+        .buttons
+        .iter()
+        .map(|button| {
+            let pressed = gamepad
+                .and_then(|gamepad| gamepad.button_down.get(button).copied())
+                .unwrap_or_default();
+            i32::from(pressed)
+        })
+        .collect();
+    JoyMessage {
+        header: Ros2Header {
+            stamp: message.time.into(),
+            frame_id: config.frame_id.clone(),
+        },
+        axes,
+        buttons,
+    }
+}
+
+/// Little endian CDR with its encapsulation header
+pub fn encode_joy_cdr(buffer: &mut Vec<u8>, joy: &JoyMessage) {
+    // CDR_LE representation identifier and options
+    buffer.extend_from_slice(&[0x00, 0x01, 0x00, 0x00]);
+    // every field is 4 byte aligned relative to the end of the encapsulation header
+    let mut cdr = CdrWriter { buffer, start: 4 };
+    cdr.write_u32(joy.header.stamp.sec as u32);
+    cdr.write_u32(joy.header.stamp.nanosec);
+    cdr.write_string(&joy.header.frame_id);
+    cdr.write_u32(joy.axes.len() as u32);
+    for axis in &joy.axes {
+        cdr.write_u32(axis.to_bits());
+    }
+    cdr.write_u32(joy.buttons.len() as u32);
+    for button in &joy.buttons {
+        cdr.write_u32(*button as u32);
+    }
+}
+
+struct CdrWriter<'a> {
+    buffer: &'a mut Vec<u8>,
+    start: usize,
+}
+
+impl CdrWriter<'_> {
+    fn align4(&mut self) {
+        while (self.buffer.len() - self.start) % 4 != 0 {
+            self.buffer.push(0);
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.align4();
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Length includes the terminating null
+    fn write_string(&mut self, value: &str) {
+        self.write_u32(value.len() as u32 + 1);
+        self.buffer.extend_from_slice(value.as_bytes());
+        self.buffer.push(0);
+    }
+}
+
+/// `sensor_msgs/Joy` from [`joy_message`] in the configured encoding
+pub fn serialize_joy(
+    buffer: &mut Vec<u8>,
+    joy: &JoyMessage,
+    encoding: Ros2Encoding,
+) -> Result<Value, GamepadError> {
+    buffer.clear();
+    match encoding {
+        Ros2Encoding::Cdr => {
+            encode_joy_cdr(buffer, joy);
+            Ok(Value::from(take_payload(buffer))
+                .encoding(Encoding::Exact(KnownEncoding::AppOctetStream)))
+        }
+        Ros2Encoding::Json => {
+            serde_json::to_writer(&mut *buffer, joy)
+                .map_err(|err| GamepadError::encoding("joy", err))?;
+            Ok(Value::from(take_payload(buffer)).encoding(Encoding::Exact(KnownEncoding::AppJson)))
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! JSON schemas inferred from sampled messages
+//!
+//! Foxglove only offers fields for plotting when the channel's schema lists them, so JSON
+//! topics without a named schema can have one built from their first messages instead of
+//! advertising an empty object.
+
+use serde_json::{json, Map, Value};
+
+/// Schema describing every message in `messages`, `None` without messages
+pub fn infer_schema(title: &str, messages: &[Value]) -> Option<Value> {
+    let mut schema = messages.iter().map(value_schema).reduce(merge_schemas)?;
+    if let Some(object) = schema.as_object_mut() {
+        object.insert("title".to_owned(), json!(title));
+    }
+    Some(schema)
+}
+
+fn value_schema(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({ "type": "null" }),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(number) if number.is_f64() => json!({ "type": "number" }),
+        Value::Number(_) => json!({ "type": "integer" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Array(values) => {
+            let items = values
+                .iter()
+                .map(value_schema)
+                .reduce(merge_schemas)
+                .unwrap_or_else(|| json!({}));
+            json!({ "type": "array", "items": items })
+        }
+        Value::Object(fields) => {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value_schema(value)))
+                .collect();
+            json!({ "type": "object", "properties": properties })
+        }
+    }
+}
+
+/// Schema that both `a` and `b` match, fields of objects are merged
+fn merge_schemas(a: Value, b: Value) -> Value {
+    let mut types = schema_types(&a);
+    for schema_type in schema_types(&b) {
+        if !types.contains(&schema_type) {
+            types.push(schema_type);
+        }
+    }
+    // integers are numbers too
+    if types.contains(&"number".to_owned()) {
+        types.retain(|schema_type| schema_type != "integer");
+    }
+    types.sort();
+
+    let mut merged = Map::new();
+    merged.insert(
+        "type".to_owned(),
+        match types.as_slice() {
+            [schema_type] => json!(schema_type),
+            _ => json!(types),
+        },
+    );
+    if let Some(properties) = merge_properties(a.get("properties"), b.get("properties")) {
+        merged.insert("properties".to_owned(), properties);
+    }
+    match (a.get("items"), b.get("items")) {
+        (Some(a), Some(b)) if a.as_object().is_some_and(Map::is_empty) => {
+            merged.insert("items".to_owned(), b.clone());
+        }
+        (Some(a), Some(b)) if b.as_object().is_some_and(Map::is_empty) => {
+            merged.insert("items".to_owned(), a.clone());
+        }
+        (Some(a), Some(b)) => {
+            merged.insert("items".to_owned(), merge_schemas(a.clone(), b.clone()));
+        }
+        (Some(items), None) | (None, Some(items)) => {
+            merged.insert("items".to_owned(), items.clone());
+        }
+        (None, None) => {}
+    }
+    Value::Object(merged)
+}
+
+fn merge_properties(a: Option<&Value>, b: Option<&Value>) -> Option<Value> {
+    let (a, b) = match (a.and_then(Value::as_object), b.and_then(Value::as_object)) {
+        (Some(a), Some(b)) => (a, b),
+        (Some(properties), None) | (None, Some(properties)) => {
+            return Some(Value::Object(properties.clone()))
+        }
+        (None, None) => return None,
+    };
+    let mut merged = a.clone();
+    for (name, schema) in b {
+        let schema = match merged.remove(name) {
+            Some(existing) => merge_schemas(existing, schema.clone()),
+            None => schema.clone(),
+        };
+        merged.insert(name.clone(), schema);
+    }
+    Some(Value::Object(merged))
+}
+
+fn schema_types(schema: &Value) -> Vec<String> {
+    match schema.get("type") {
+        Some(Value::String(schema_type)) => vec![schema_type.clone()],
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(|schema_type| schema_type.as_str().map(str::to_owned))
+            .collect(),
+        _ => vec![],
+    }
+}
@@ -0,0 +1,149 @@
+//! Schemas of every topic the remote publishes or bridges, served on `<topic>/__schema__`
+//!
+//! JSON topics reply with their JSON schema. Protobuf topics reply with the full message
+//! name, the same convention topic discovery reads from robots, or with the encoded
+//! `FileDescriptorSet` when the query has a `descriptor` parameter:
+//!
+//! ```text
+//! hopper/camera/__schema__?descriptor
+//! ```
+//!
+//! Wildcard queries such as `**/__schema__` get a reply for every known topic.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use prost_reflect::MessageDescriptor;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    error::ZenohError,
+    health::{Heartbeat, HEARTBEAT_INTERVAL},
+    supervisor::Supervisor,
+};
+
+pub const SCHEMA_QUERY_SUFFIX: &str = "/__schema__";
+const SCHEMA_QUERYABLE_KEY_EXPR: &str = "**/__schema__";
+const DESCRIPTOR_PARAMETER: &str = "descriptor";
+
+#[derive(Debug, Clone)]
+pub enum TopicSchema {
+    Json(String),
+    Protobuf(MessageDescriptor),
+}
+
+impl TopicSchema {
+    /// Full name of protobuf messages unless the encoded descriptor set is requested
+    pub fn reply(&self, descriptor: bool) -> Value {
+        match self {
+            TopicSchema::Json(schema) => {
+                Value::from(schema.clone()).encoding(Encoding::Exact(KnownEncoding::AppJson))
+            }
+            TopicSchema::Protobuf(message_descriptor) if descriptor => {
+                Value::from(message_descriptor.parent_pool().encode_to_vec())
+                    .encoding(Encoding::Exact(KnownEncoding::AppOctetStream))
+            }
+            TopicSchema::Protobuf(message_descriptor) => {
+                Value::from(message_descriptor.full_name().to_owned())
+            }
+        }
+    }
+}
+
+/// Topic to schema, shared by everything that declares publishers or bridge subscribers
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: Arc<Mutex<BTreeMap<String, TopicSchema>>>,
+}
+
+impl SchemaRegistry {
+    pub fn register(&self, topic: &str, schema: TopicSchema) {
+        self.schemas
+            .lock()
+            .unwrap()
+            .insert(topic.to_owned(), schema);
+    }
+
+    pub fn get(&self, topic: &str) -> Option<TopicSchema> {
+        self.schemas.lock().unwrap().get(topic).cloned()
+    }
+
+    /// Schema replies for every registered topic whose schema key intersects `key_expr`
+    pub fn replies(&self, key_expr: &keyexpr, parameters: &str) -> Vec<Sample> {
+        let descriptor = wants_descriptor(parameters);
+        self.schemas
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(topic, schema)| {
+                let schema_key = KeyExpr::try_from(format!("{topic}{SCHEMA_QUERY_SUFFIX}")).ok()?;
+                key_expr
+                    .intersects(&schema_key)
+                    .then(|| Sample::new(schema_key, schema.reply(descriptor)))
+            })
+            .collect()
+    }
+}
+
+/// `descriptor` or `descriptor=true` among the selector parameters
+pub fn wants_descriptor(parameters: &str) -> bool {
+    parameters
+        .split(['&', ';'])
+        .any(|parameter| match parameter.split_once('=') {
+            Some((name, value)) => name == DESCRIPTOR_PARAMETER && value != "false",
+            None => parameter == DESCRIPTOR_PARAMETER,
+        })
+}
+
+/// Answer `<topic>/__schema__` queries for every topic in `registry`
+pub fn start_schema_queryable(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    registry: SchemaRegistry,
+) {
+    let heartbeat = supervisor.health().heartbeat("schema_queryable");
+    supervisor.spawn("schema_queryable", move |shutdown| {
+        run_schema_queryable(
+            zenoh_session.clone(),
+            registry.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_schema_queryable(
+    zenoh_session: Arc<Session>,
+    registry: SchemaRegistry,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let queryable = zenoh_session
+        .declare_queryable(SCHEMA_QUERYABLE_KEY_EXPR)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        let query = tokio::select! {
+            query = queryable.recv_async() => query?,
+            _ = heartbeat_interval.tick() => {
+                heartbeat.beat();
+                continue;
+            }
+            _ = shutdown.cancelled() => break,
+        };
+        for reply in registry.replies(query.key_expr(), query.parameters()) {
+            if let Err(err) = query.reply(Ok(reply)).res().await {
+                debug!("Failed to reply to schema query {err:?}");
+            }
+        }
+    }
+
+    Ok(())
+}
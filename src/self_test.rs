@@ -0,0 +1,114 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::task::JoinSet;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::error::ZenohError;
+
+/// How long each topic gets to show a publisher or queryable
+pub const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicStatus {
+    /// A sample was received
+    Publisher,
+    /// A queryable replied
+    Queryable,
+    Missing,
+}
+
+#[derive(Debug, Clone)]
+pub struct TopicCheck {
+    pub topic: String,
+    pub status: TopicStatus,
+}
+
+/// Check all topics in parallel and return the results in the order of `topics`
+pub async fn run_self_test(
+    zenoh_session: Arc<Session>,
+    topics: Vec<String>,
+    timeout: Duration,
+) -> anyhow::Result<Vec<TopicCheck>> {
+    let mut checks = JoinSet::new();
+    for (index, topic) in topics.into_iter().enumerate() {
+        let zenoh_session = zenoh_session.clone();
+        checks.spawn(async move {
+            let status = check_topic(&zenoh_session, &topic, timeout).await?;
+            anyhow::Ok((index, TopicCheck { topic, status }))
+        });
+    }
+
+    let mut results = vec![];
+    while let Some(result) = checks.join_next().await {
+        results.push(result??);
+    }
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, check)| check).collect())
+}
+
+async fn check_topic(
+    zenoh_session: &Session,
+    topic: &str,
+    timeout: Duration,
+) -> anyhow::Result<TopicStatus> {
+    let subscriber = zenoh_session
+        .declare_subscriber(topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let replies = zenoh_session
+        .get(topic)
+        .timeout(timeout)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+    let mut waiting_for_replies = true;
+    loop {
+        tokio::select! {
+            sample = subscriber.recv_async() => {
+                if sample.is_ok() {
+                    return Ok(TopicStatus::Publisher);
+                }
+            }
+            reply = replies.recv_async(), if waiting_for_replies => match reply {
+                Ok(reply) if reply.sample.is_ok() => return Ok(TopicStatus::Queryable),
+                Ok(_) => {}
+                // query finished without a reply, keep waiting for samples
+                Err(_) => waiting_for_replies = false,
+            },
+            _ = &mut deadline => return Ok(TopicStatus::Missing),
+        }
+    }
+}
+
+/// Log a table of all checked topics
+pub fn log_report(checks: &[TopicCheck]) {
+    let width = checks
+        .iter()
+        .map(|check| check.topic.len())
+        .max()
+        .unwrap_or_default();
+    let mut table = format!("{:width$}  status", "topic");
+    for check in checks {
+        let status = match check.status {
+            TopicStatus::Publisher => "OK (publisher)",
+            TopicStatus::Queryable => "OK (queryable)",
+            TopicStatus::Missing => "MISSING",
+        };
+        table.push_str(&format!("\n{:width$}  {status}", check.topic));
+    }
+
+    let missing = checks
+        .iter()
+        .filter(|check| check.status == TopicStatus::Missing)
+        .count();
+    if missing == 0 {
+        info!("Self-test passed\n{table}");
+    } else {
+        warn!("Self-test found {missing} missing topic(s)\n{table}");
+    }
+}
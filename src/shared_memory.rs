@@ -0,0 +1,75 @@
+//! Zenoh shared memory for subscribers on the same machine
+//!
+//! With `--shared-memory` the gamepad reader and sensor publishers write their payloads into
+//! a shared memory segment. Subscribers on the Deck that enable shared memory too, such as a
+//! visualization connected over a unix socket, then receive a reference to the segment
+//! instead of a copy sent through the loopback link. Remote peers still get a copy.
+
+use std::sync::Mutex;
+
+use zenoh::{prelude::r#async::*, shm::SharedMemoryManager};
+
+use crate::error::ZenohError;
+
+/// Enough for several seconds of gamepad messages that subscribers haven't released yet
+pub const SHARED_MEMORY_SIZE: usize = 1024 * 1024;
+
+/// Let the session exchange shared memory buffers with local peers
+pub fn enable_shared_memory(zenoh_config: &mut Config) -> anyhow::Result<()> {
+    zenoh_config
+        .insert_json5("transport/shared_memory/enabled", "true")
+        .map_err(ZenohError)?;
+    Ok(())
+}
+
+pub struct SharedMemoryPayloads {
+    manager: Mutex<SharedMemoryManager>,
+}
+
+impl std::fmt::Debug for SharedMemoryPayloads {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedMemoryPayloads")
+            .finish_non_exhaustive()
+    }
+}
+
+impl SharedMemoryPayloads {
+    /// Segment named after the session so several remotes on one machine don't collide
+    pub fn new(zenoh_session: &Session) -> anyhow::Result<Self> {
+        let manager = SharedMemoryManager::make(
+            format!("deck-robot-remote-{}", zenoh_session.zid()),
+            SHARED_MEMORY_SIZE,
+        )
+        .map_err(ZenohError)?;
+        Ok(Self {
+            manager: Mutex::new(manager),
+        })
+    }
+
+    /// `value` with its payload copied into shared memory, unchanged if the segment is full
+    pub fn share(&self, value: Value) -> Value {
+        let buffer = {
+            let payload = value.payload.contiguous();
+            let mut manager = self.manager.lock().unwrap();
+            // reclaim buffers every subscriber has released
+            manager.garbage_collect();
+            manager.alloc(payload.len()).ok().map(|mut buffer| {
+                // SAFETY: the buffer was just allocated and nothing else references it yet
+                unsafe { buffer.as_mut_slice() }.copy_from_slice(&payload);
+                buffer
+            })
+        };
+        match buffer {
+            Some(buffer) => Value::from(buffer).encoding(value.encoding),
+            None => value,
+        }
+    }
+}
+
+/// Shares `value` if shared memory is enabled
+pub fn maybe_share(shared_memory: Option<&SharedMemoryPayloads>, value: Value) -> Value {
+    match shared_memory {
+        Some(shared_memory) => shared_memory.share(value),
+        None => value,
+    }
+}
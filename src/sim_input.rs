@@ -0,0 +1,286 @@
+//! Simulated gamepad driven by a script instead of a human
+//!
+//! Scripts are CSV-like with one step per line, `#` starts a comment:
+//!
+//! ```text
+//! # time_ms, action, arguments...
+//! 0, axis, LeftStickX, 0.0
+//! 500, press, South
+//! 700, release, South
+//! 1000, ramp, LeftStickY, 1.0, 2000
+//! 4000, loop
+//! ```
+//!
+//! Times are relative to the start of the script. `ramp` moves an axis from its
+//! current value to the target over the given duration in milliseconds and `loop`
+//! starts the script over.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use serde::de::DeserializeOwned;
+use tokio::{sync::mpsc, time::Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use crate::{
+    gamepad::{GamepadStatus, InputBackend, InputEvent},
+    messages::{Axis, Button, PowerState},
+};
+
+const SIM_GAMEPAD_ID: usize = 0;
+const SIM_GAMEPAD_NAME: &str = "Simulated gamepad";
+/// How often ramps are advanced
+const SIM_TICK: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimAction {
+    Press(Button),
+    Release(Button),
+    Axis(Axis, f32),
+    Ramp {
+        axis: Axis,
+        target: f32,
+        duration: Duration,
+    },
+    Loop,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimStep {
+    pub at: Duration,
+    pub action: SimAction,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimScript {
+    pub steps: Vec<SimStep>,
+}
+
+impl SimScript {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let script = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read sim script {}", path.display()))?;
+        Self::parse(&script).with_context(|| format!("Invalid sim script {}", path.display()))
+    }
+
+    pub fn parse(script: &str) -> anyhow::Result<Self> {
+        let mut steps = vec![];
+        for (index, line) in script.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let step = parse_step(line).with_context(|| format!("line {}: {line}", index + 1))?;
+            if steps
+                .last()
+                .is_some_and(|previous: &SimStep| previous.at > step.at)
+            {
+                bail!("line {}: steps must be in chronological order", index + 1);
+            }
+            steps.push(step);
+        }
+        Ok(Self { steps })
+    }
+}
+
+fn parse_step(line: &str) -> anyhow::Result<SimStep> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let at = Duration::from_millis(fields[0].parse().context("Invalid time")?);
+    let arguments = &fields[1..];
+    let action = match arguments {
+        ["press", button] => SimAction::Press(parse_name(button)?),
+        ["release", button] => SimAction::Release(parse_name(button)?),
+        ["axis", axis, value] => SimAction::Axis(parse_name(axis)?, parse_axis_value(value)?),
+        ["ramp", axis, target, duration_ms] => SimAction::Ramp {
+            axis: parse_name(axis)?,
+            target: parse_axis_value(target)?,
+            duration: Duration::from_millis(duration_ms.parse().context("Invalid ramp duration")?),
+        },
+        ["loop"] => SimAction::Loop,
+        _ => bail!("Unknown action"),
+    };
+    Ok(SimStep { at, action })
+}
+
+/// Buttons and axes use the same names as the published gamepad message
+fn parse_name<T: DeserializeOwned>(name: &str) -> anyhow::Result<T> {
+    serde_json::from_value(serde_json::Value::String(name.to_owned()))
+        .with_context(|| format!("Unknown button or axis {name}"))
+}
+
+fn parse_axis_value(value: &str) -> anyhow::Result<f32> {
+    let value: f32 = value.parse().context("Invalid axis value")?;
+    if !(-1.0..=1.0).contains(&value) {
+        bail!("Axis value {value} is outside of -1.0..=1.0");
+    }
+    Ok(value)
+}
+
+/// Input backend that plays a [`SimScript`] as a single connected gamepad
+#[derive(Debug, Clone)]
+pub struct SimInput {
+    script: SimScript,
+}
+
+impl SimInput {
+    pub fn new(script: SimScript) -> Self {
+        Self { script }
+    }
+}
+
+impl InputBackend for SimInput {
+    fn start(
+        &self,
+        input_sender: mpsc::Sender<InputEvent>,
+        status_interval: Duration,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let script = self.script.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                result = run_script(script, input_sender, status_interval) => {
+                    if result.is_err() {
+                        debug!("Gamepad reader stopped listening to simulated input");
+                    }
+                }
+                _ = shutdown.cancelled() => {}
+            }
+        });
+        Ok(())
+    }
+}
+
+struct Ramp {
+    axis: Axis,
+    from: f32,
+    target: f32,
+    start: Instant,
+    duration: Duration,
+}
+
+/// Runs until the script finishes and then keeps reporting the final state
+async fn run_script(
+    script: SimScript,
+    input_sender: mpsc::Sender<InputEvent>,
+    status_interval: Duration,
+) -> anyhow::Result<()> {
+    info!(
+        "Playing simulated input script with {} steps",
+        script.steps.len()
+    );
+    input_sender
+        .send(InputEvent::Connected {
+            gamepad_id: SIM_GAMEPAD_ID,
+        })
+        .await?;
+
+    let mut pressed: HashSet<Button> = HashSet::new();
+    let mut axes: HashMap<Axis, f32> = HashMap::new();
+    let mut ramps: Vec<Ramp> = vec![];
+    let mut next_step = 0;
+    let mut script_start = Instant::now();
+    let mut last_status: Option<Instant> = None;
+
+    let mut tick = tokio::time::interval(SIM_TICK);
+    loop {
+        tick.tick().await;
+        let now = Instant::now();
+
+        while let Some(step) = script.steps.get(next_step) {
+            if now.duration_since(script_start) < step.at {
+                break;
+            }
+            next_step += 1;
+            let event = match step.action {
+                SimAction::Press(button) => {
+                    pressed.insert(button);
+                    InputEvent::ButtonPressed {
+                        gamepad_id: SIM_GAMEPAD_ID,
+                        button,
+                    }
+                }
+                SimAction::Release(button) => {
+                    pressed.remove(&button);
+                    InputEvent::ButtonReleased {
+                        gamepad_id: SIM_GAMEPAD_ID,
+                        button,
+                    }
+                }
+                SimAction::Axis(axis, value) => {
+                    ramps.retain(|ramp| ramp.axis != axis);
+                    axes.insert(axis, value);
+                    InputEvent::AxisChanged {
+                        gamepad_id: SIM_GAMEPAD_ID,
+                        axis,
+                        value,
+                    }
+                }
+                SimAction::Ramp {
+                    axis,
+                    target,
+                    duration,
+                } => {
+                    ramps.retain(|ramp| ramp.axis != axis);
+                    ramps.push(Ramp {
+                        axis,
+                        from: axes.get(&axis).copied().unwrap_or_default(),
+                        target,
+                        start: now,
+                        duration,
+                    });
+                    continue;
+                }
+                SimAction::Loop => {
+                    debug!("Restarting simulated input script");
+                    next_step = 0;
+                    script_start = now;
+                    break;
+                }
+            };
+            input_sender.send(event).await?;
+        }
+
+        for ramp in &ramps {
+            let progress = if ramp.duration.is_zero() {
+                1.0
+            } else {
+                (now.duration_since(ramp.start).as_secs_f32() / ramp.duration.as_secs_f32())
+                    .min(1.0)
+            };
+            let value = ramp.from + (ramp.target - ramp.from) * progress;
+            axes.insert(ramp.axis, value);
+            input_sender
+                .send(InputEvent::AxisChanged {
+                    gamepad_id: SIM_GAMEPAD_ID,
+                    axis: ramp.axis,
+                    value,
+                })
+                .await?;
+        }
+        ramps.retain(|ramp| now.duration_since(ramp.start) < ramp.duration);
+
+        if last_status.map_or(true, |last| now.duration_since(last) >= status_interval) {
+            last_status = Some(now);
+            let status = GamepadStatus {
+                gamepad_id: SIM_GAMEPAD_ID,
+                name: SIM_GAMEPAD_NAME.to_owned(),
+                connected: true,
+                button_down: Button::all_gilrs_buttons()
+                    .iter()
+                    .map(|button| Button::from(*button))
+                    .map(|button| (button, pressed.contains(&button)))
+                    .collect(),
+                power_state: PowerState::Unknown,
+                battery_percent: None,
+            };
+            input_sender
+                .send(InputEvent::Gamepads(vec![status]))
+                .await?;
+        }
+    }
+}
@@ -0,0 +1,611 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use hdrhistogram::Histogram;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    error::ZenohError,
+    foxglove_clients::foxglove_client_count,
+    health::{HealthMessage, HealthRegistry, Heartbeat, HEARTBEAT_INTERVAL},
+    messages::InputMessage,
+    supervisor::Supervisor,
+};
+
+const STATS_TOPIC: &str = "remote-control/admin/stats";
+const LATENCY_TOPIC: &str = "remote-control/diagnostics/bridge_latency";
+const LATENCY_PUBLISH_INTERVAL: Duration = Duration::from_secs(10);
+pub const BRIDGE_STATS_TOPIC: &str = "bridge/stats";
+const BRIDGE_STATS_PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+/// Latencies above this are recorded as this value
+const MAX_TRACKED_LATENCY_MICROS: u64 = 60_000_000;
+
+/// Live counters shared between the gamepad loop, the bridge and the stats queryable
+#[derive(Debug, Clone)]
+pub struct StatsRegistry {
+    started: Instant,
+    gamepad: Arc<GamepadStats>,
+    topics: Arc<Mutex<BTreeMap<String, Arc<TopicStats>>>>,
+}
+
+impl Default for StatsRegistry {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            gamepad: Arc::default(),
+            topics: Arc::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GamepadStats {
+    published: AtomicU64,
+    input_events: AtomicU64,
+    last_publish_micros: AtomicU64,
+    max_publish_micros: AtomicU64,
+    last_message: Mutex<Option<InputMessage>>,
+}
+
+#[derive(Debug)]
+pub struct TopicStats {
+    received: AtomicU64,
+    forwarded: AtomicU64,
+    forwarded_bytes: AtomicU64,
+    dropped: AtomicU64,
+    /// Skipped by the subscription's rate limit
+    decimated: AtomicU64,
+    last_received: Mutex<Option<Instant>>,
+    queue_capacity: AtomicU64,
+    /// Most samples ever waiting in the subscriber queue
+    queue_high_watermark: AtomicU64,
+    /// Time from zenoh receive to websocket send in microseconds
+    latency: Mutex<Histogram<u64>>,
+    /// Whether a zenoh subscriber is declared, on demand topics drop theirs without clients
+    subscribed: AtomicBool,
+}
+
+/// Keeps a topic marked as subscribed, see [`TopicStats::subscribed`]
+pub struct SubscribedGuard<'a>(&'a TopicStats);
+
+impl Drop for SubscribedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.subscribed.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Default for TopicStats {
+    fn default() -> Self {
+        Self {
+            received: AtomicU64::default(),
+            forwarded: AtomicU64::default(),
+            forwarded_bytes: AtomicU64::default(),
+            dropped: AtomicU64::default(),
+            decimated: AtomicU64::default(),
+            last_received: Mutex::default(),
+            queue_capacity: AtomicU64::default(),
+            queue_high_watermark: AtomicU64::default(),
+            latency: Mutex::new(
+                Histogram::new_with_bounds(1, MAX_TRACKED_LATENCY_MICROS, 3)
+                    .expect("Invalid latency histogram bounds"),
+            ),
+            subscribed: AtomicBool::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsSnapshot {
+    pub uptime_seconds: u64,
+    pub gamepad: GamepadStatsSnapshot,
+    pub topics: BTreeMap<String, TopicStatsSnapshot>,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GamepadStatsSnapshot {
+    pub published: u64,
+    pub input_events: u64,
+    pub last_publish_micros: u64,
+    pub max_publish_micros: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopicStatsSnapshot {
+    pub received: u64,
+    pub forwarded: u64,
+    pub dropped: u64,
+    pub decimated: u64,
+    pub queue_capacity: u64,
+    pub queue_high_watermark: u64,
+    pub latency: LatencySummary,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub p50_micros: u64,
+    pub p95_micros: u64,
+    pub p99_micros: u64,
+    pub max_micros: u64,
+}
+
+/// Published on `bridge/stats` and bridged to Foxglove
+#[derive(Debug, Serialize)]
+pub struct BridgeStatsMessage {
+    pub topics: BTreeMap<String, ChannelStats>,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelStats {
+    /// Forwarded messages per second since the previous message
+    pub rate_hz: f64,
+    pub bytes_per_second: f64,
+    /// Not set if nothing was received yet
+    pub last_receive_age_seconds: Option<f64>,
+    pub forwarded: u64,
+    pub dropped: u64,
+}
+
+/// Turns the forwarded counters into rates between two calls of [`BridgeStatsTracker::update`]
+#[derive(Debug, Default)]
+pub struct BridgeStatsTracker {
+    /// Forwarded messages and bytes at the previous update
+    previous: BTreeMap<String, (u64, u64)>,
+}
+
+impl BridgeStatsTracker {
+    pub fn update(
+        &mut self,
+        stats: &StatsRegistry,
+        elapsed: Duration,
+        clock: &dyn Clock,
+    ) -> BridgeStatsMessage {
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        let topics = stats
+            .topics
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(topic, topic_stats)| {
+                let forwarded = topic_stats.forwarded.load(Ordering::Relaxed);
+                let forwarded_bytes = topic_stats.forwarded_bytes.load(Ordering::Relaxed);
+                let (previous_forwarded, previous_bytes) = self
+                    .previous
+                    .insert(topic.clone(), (forwarded, forwarded_bytes))
+                    .unwrap_or_default();
+                let channel_stats = ChannelStats {
+                    rate_hz: forwarded.saturating_sub(previous_forwarded) as f64 / seconds,
+                    bytes_per_second: forwarded_bytes.saturating_sub(previous_bytes) as f64
+                        / seconds,
+                    last_receive_age_seconds: topic_stats
+                        .last_received
+                        .lock()
+                        .unwrap()
+                        .map(|last_received| last_received.elapsed().as_secs_f64()),
+                    forwarded,
+                    dropped: topic_stats.dropped.load(Ordering::Relaxed),
+                };
+                (topic.clone(), channel_stats)
+            })
+            .collect();
+        BridgeStatsMessage {
+            topics,
+            time: clock.now().into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencyMessage {
+    pub topics: BTreeMap<String, LatencySummary>,
+    pub time: DateTime<Utc>,
+}
+
+impl StatsRegistry {
+    pub fn gamepad(&self) -> Arc<GamepadStats> {
+        self.gamepad.clone()
+    }
+
+    /// Counters for a bridged topic, created on first use
+    pub fn topic(&self, topic: &str) -> Arc<TopicStats> {
+        self.topics
+            .lock()
+            .unwrap()
+            .entry(topic.to_owned())
+            .or_default()
+            .clone()
+    }
+
+    /// Most recent sample received on any of `topics`
+    pub fn last_received(&self, topics: &[String]) -> Option<Instant> {
+        let topic_stats = self.topics.lock().unwrap();
+        topics
+            .iter()
+            .filter_map(|topic| topic_stats.get(topic))
+            .filter_map(|stats| *stats.last_received.lock().unwrap())
+            .max()
+    }
+
+    /// Forwarding latency of every bridged topic
+    pub fn latency(&self) -> LatencyMessage {
+        let topics = self
+            .topics
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(topic, stats)| (topic.clone(), stats.latency_summary()))
+            .collect();
+        LatencyMessage {
+            topics,
+            time: Utc::now(),
+        }
+    }
+
+    /// Topics with a declared zenoh subscriber
+    pub fn active_subscriptions(&self) -> Vec<String> {
+        self.topics
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, stats)| stats.subscribed.load(Ordering::Relaxed))
+            .map(|(topic, _)| topic.clone())
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let topics = self
+            .topics
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(topic, stats)| (topic.clone(), stats.snapshot()))
+            .collect();
+        StatsSnapshot {
+            uptime_seconds: self.started.elapsed().as_secs(),
+            gamepad: self.gamepad.snapshot(),
+            topics,
+            time: Utc::now(),
+        }
+    }
+}
+
+impl GamepadStats {
+    pub fn record_input_event(&self) {
+        self.input_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_publish(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        self.published.fetch_add(1, Ordering::Relaxed);
+        self.last_publish_micros.store(micros, Ordering::Relaxed);
+        self.max_publish_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub fn record_message(&self, message: &InputMessage) {
+        *self.last_message.lock().unwrap() = Some(message.clone());
+    }
+
+    /// Most recently published gamepad state
+    pub fn last_message(&self) -> Option<InputMessage> {
+        self.last_message.lock().unwrap().clone()
+    }
+
+    fn snapshot(&self) -> GamepadStatsSnapshot {
+        GamepadStatsSnapshot {
+            published: self.published.load(Ordering::Relaxed),
+            input_events: self.input_events.load(Ordering::Relaxed),
+            last_publish_micros: self.last_publish_micros.load(Ordering::Relaxed),
+            max_publish_micros: self.max_publish_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl TopicStats {
+    pub fn record_received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        *self.last_received.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub fn record_forwarded(&self, bytes: usize) {
+        self.forwarded.fetch_add(1, Ordering::Relaxed);
+        self.forwarded_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_decimated(&self) {
+        self.decimated.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_queue_depth(&self, depth: usize, capacity: usize) {
+        self.queue_capacity
+            .store(capacity as u64, Ordering::Relaxed);
+        self.queue_high_watermark
+            .fetch_max(depth as u64, Ordering::Relaxed);
+    }
+
+    /// Mark the topic as subscribed on zenoh until the returned guard is dropped
+    pub fn subscribed(&self) -> SubscribedGuard<'_> {
+        self.subscribed.store(true, Ordering::Relaxed);
+        SubscribedGuard(self)
+    }
+
+    pub fn record_latency(&self, latency: Duration) {
+        let micros = latency.as_micros() as u64;
+        self.latency.lock().unwrap().saturating_record(micros);
+    }
+
+    fn latency_summary(&self) -> LatencySummary {
+        let latency = self.latency.lock().unwrap();
+        LatencySummary {
+            count: latency.len(),
+            p50_micros: latency.value_at_quantile(0.5),
+            p95_micros: latency.value_at_quantile(0.95),
+            p99_micros: latency.value_at_quantile(0.99),
+            max_micros: latency.max(),
+        }
+    }
+
+    fn snapshot(&self) -> TopicStatsSnapshot {
+        TopicStatsSnapshot {
+            received: self.received.load(Ordering::Relaxed),
+            forwarded: self.forwarded.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            decimated: self.decimated.load(Ordering::Relaxed),
+            queue_capacity: self.queue_capacity.load(Ordering::Relaxed),
+            queue_high_watermark: self.queue_high_watermark.load(Ordering::Relaxed),
+            latency: self.latency_summary(),
+        }
+    }
+}
+
+/// Publish rate, throughput and last receive age of every bridged topic on `bridge/stats`
+pub fn start_bridge_stats_publisher(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    stats: StatsRegistry,
+    clock: SharedClock,
+) {
+    let heartbeat = supervisor.health().heartbeat("bridge_stats_publisher");
+    supervisor.spawn("bridge_stats_publisher", move |shutdown| {
+        run_bridge_stats_publisher(
+            zenoh_session.clone(),
+            stats.clone(),
+            clock.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_bridge_stats_publisher(
+    zenoh_session: Arc<Session>,
+    stats: StatsRegistry,
+    clock: SharedClock,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let publisher = zenoh_session
+        .declare_publisher(BRIDGE_STATS_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut tracker = BridgeStatsTracker::default();
+    let mut last_update = Instant::now();
+    let mut interval = tokio::time::interval(BRIDGE_STATS_PUBLISH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                heartbeat.beat();
+                let message = tracker.update(&stats, last_update.elapsed(), clock.as_ref());
+                last_update = Instant::now();
+                publisher
+                    .put(serde_json::to_string(&message)?)
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
+
+/// Answer queries on `remote-control/admin/stats` with a snapshot of all counters
+pub fn start_stats_queryable(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    stats: StatsRegistry,
+) {
+    let heartbeat = supervisor.health().heartbeat("stats_queryable");
+    supervisor.spawn("stats_queryable", move |shutdown| {
+        run_stats_queryable(
+            zenoh_session.clone(),
+            stats.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_stats_queryable(
+    zenoh_session: Arc<Session>,
+    stats: StatsRegistry,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let queryable = zenoh_session
+        .declare_queryable(STATS_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        let query = tokio::select! {
+            query = queryable.recv_async() => query?,
+            _ = heartbeat_interval.tick() => {
+                heartbeat.beat();
+                continue;
+            }
+            _ = shutdown.cancelled() => break,
+        };
+        let json = serde_json::to_string(&stats.snapshot())?;
+        let key_expr = KeyExpr::<'static>::from_str(STATS_TOPIC).map_err(ZenohError)?;
+        if let Err(err) = query.reply(Ok(Sample::new(key_expr, json))).res().await {
+            warn!("Failed to reply to stats query {err:?}");
+        }
+    }
+    Ok(())
+}
+
+/// Periodically publish forwarding latency percentiles of every bridged topic
+pub fn start_latency_publisher(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    stats: StatsRegistry,
+) {
+    let heartbeat = supervisor.health().heartbeat("latency_publisher");
+    supervisor.spawn("latency_publisher", move |shutdown| {
+        run_latency_publisher(
+            zenoh_session.clone(),
+            stats.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_latency_publisher(
+    zenoh_session: Arc<Session>,
+    stats: StatsRegistry,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let publisher = zenoh_session
+        .declare_publisher(LATENCY_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut publish_interval = tokio::time::interval(LATENCY_PUBLISH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = publish_interval.tick() => {
+                let json = serde_json::to_string(&stats.latency())?;
+                publisher
+                    .put(json)
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            _ = heartbeat_interval.tick() => heartbeat.beat(),
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct DebugDump {
+    pub stats: StatsSnapshot,
+    pub health: HealthMessage,
+    /// Not set without `/proc`
+    pub foxglove_clients: Option<usize>,
+    /// Bridged topics subscribed on zenoh
+    pub subscriptions: Vec<String>,
+}
+
+impl DebugDump {
+    /// `foxglove_port` is the port Foxglove clients connect to
+    pub fn collect(stats: &StatsRegistry, health: &HealthRegistry, foxglove_port: u16) -> Self {
+        Self {
+            stats: stats.snapshot(),
+            health: health.snapshot(),
+            foxglove_clients: foxglove_client_count(foxglove_port),
+            subscriptions: stats.active_subscriptions(),
+        }
+    }
+}
+
+/// Dump all counters and task health to the log, and to `dump_file` if set, on SIGUSR1
+pub fn start_stats_dump_on_signal(
+    supervisor: &Supervisor,
+    stats: StatsRegistry,
+    dump_file: Option<PathBuf>,
+    foxglove_port: u16,
+) {
+    let health = supervisor.health();
+    supervisor.spawn("stats_dump", move |shutdown| {
+        run_stats_dump(
+            stats.clone(),
+            health.clone(),
+            dump_file.clone(),
+            foxglove_port,
+            shutdown,
+        )
+    });
+}
+
+#[cfg(unix)]
+async fn run_stats_dump(
+    stats: StatsRegistry,
+    health: HealthRegistry,
+    dump_file: Option<PathBuf>,
+    foxglove_port: u16,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigusr1 = signal(SignalKind::user_defined1())?;
+    loop {
+        tokio::select! {
+            _ = sigusr1.recv() => {}
+            _ = shutdown.cancelled() => break,
+        }
+        let dump = DebugDump::collect(&stats, &health, foxglove_port);
+        let json = serde_json::to_string_pretty(&dump)?;
+        info!("Stats dump\n{json}");
+        if let Some(dump_file) = &dump_file {
+            if let Err(err) = tokio::fs::write(dump_file, &json).await {
+                error!(
+                    "Failed to write stats dump to {} {err:?}",
+                    dump_file.display()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn run_stats_dump(
+    _stats: StatsRegistry,
+    _health: HealthRegistry,
+    _dump_file: Option<PathBuf>,
+    _foxglove_port: u16,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    shutdown.cancelled().await;
+    Ok(())
+}
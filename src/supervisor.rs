@@ -0,0 +1,245 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::{task::JoinHandle, time::Instant};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    backoff::Backoff,
+    error::{diagnose, Diagnostic, ZenohError},
+    health::{HealthRegistry, SubsystemState},
+};
+
+const SUPERVISOR_DIAGNOSTICS_TOPIC: &str = "remote-control/diagnostics/supervisor";
+
+const INITIAL_RESTART_DELAY: Duration = Duration::from_millis(500);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+/// Tasks that ran at least this long before exiting restart with the initial delay again
+const HEALTHY_RUN_TIME: Duration = Duration::from_secs(60);
+
+/// Owns long running tasks and restarts them with backoff when they exit, fail or panic
+///
+/// Tasks are expected to return once the shutdown token they are given is cancelled.
+/// Clones share the same tasks so supervised tasks can spawn further tasks.
+#[derive(Clone)]
+pub struct Supervisor {
+    zenoh_session: Arc<Session>,
+    shutdown: CancellationToken,
+    /// Child of `shutdown` that [`Supervisor::restart_tasks`] cancels and replaces
+    generation: Arc<Mutex<CancellationToken>>,
+    tasks: TaskTracker,
+    health: HealthRegistry,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskExit {
+    Exited,
+    Failed,
+    Panicked,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SupervisorEvent {
+    pub task: String,
+    pub exit: TaskExit,
+    pub message: String,
+    pub restart_count: u32,
+    pub restart_delay_ms: u64,
+    pub time: DateTime<Utc>,
+    /// Set for errors of a known subsystem
+    pub diagnostic: Option<Diagnostic>,
+}
+
+impl Supervisor {
+    pub fn new(zenoh_session: Arc<Session>, shutdown: CancellationToken) -> Self {
+        Self {
+            zenoh_session,
+            generation: Arc::new(Mutex::new(shutdown.child_token())),
+            shutdown,
+            tasks: TaskTracker::new(),
+            health: HealthRegistry::default(),
+        }
+    }
+
+    /// Registry that supervised tasks report their heartbeats to
+    pub fn health(&self) -> HealthRegistry {
+        self.health.clone()
+    }
+
+    /// Spawn a task created by `task_factory` and recreate it every time it stops
+    ///
+    /// The task is also recreated by [`Supervisor::restart_tasks`].
+    pub fn spawn<F, Fut>(&self, name: &str, task_factory: F)
+    where
+        F: Fn(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.spawn_task(name, task_factory, Some(self.generation.clone()));
+    }
+
+    /// Like [`Supervisor::spawn`] but left running by [`Supervisor::restart_tasks`]
+    ///
+    /// For tasks that don't use zenoh, such as servers whose clients shouldn't be dropped.
+    pub fn spawn_persistent<F, Fut>(&self, name: &str, task_factory: F)
+    where
+        F: Fn(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.spawn_task(name, task_factory, None);
+    }
+
+    /// Stop and immediately recreate every task started with [`Supervisor::spawn`]
+    ///
+    /// Used after the zenoh link was re-established so that all declarations are made again.
+    pub fn restart_tasks(&self) {
+        let mut generation = self.generation.lock().unwrap();
+        let previous = std::mem::replace(&mut *generation, self.shutdown.child_token());
+        previous.cancel();
+    }
+
+    fn spawn_task<F, Fut>(
+        &self,
+        name: &str,
+        task_factory: F,
+        generation: Option<Arc<Mutex<CancellationToken>>>,
+    ) where
+        F: Fn(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let name = name.to_owned();
+        let shutdown = self.shutdown.clone();
+        let zenoh_session = self.zenoh_session.clone();
+        let health = self.health.clone();
+        self.tasks.spawn(async move {
+            let mut restart_count = 0;
+            let mut backoff = Backoff::new(INITIAL_RESTART_DELAY, MAX_RESTART_DELAY);
+            loop {
+                debug!(task = %name, "Starting supervised task");
+                health.set_state(&name, SubsystemState::Starting, None);
+                let started = Instant::now();
+                let task_shutdown = match &generation {
+                    Some(generation) => generation.lock().unwrap().clone(),
+                    None => shutdown.clone(),
+                };
+                let result = spawn_named(&name, task_factory(task_shutdown.clone())).await;
+                if shutdown.is_cancelled() {
+                    break;
+                }
+                if task_shutdown.is_cancelled() {
+                    // restart requested, not a failure
+                    info!(task = %name, "Restarting supervised task");
+                    continue;
+                }
+
+                if started.elapsed() >= HEALTHY_RUN_TIME {
+                    backoff.reset();
+                }
+                let restart_delay = backoff.next_delay();
+                let (exit, message, diagnostic) = match result {
+                    Ok(Ok(())) => (TaskExit::Exited, String::from("task exited"), None),
+                    Ok(Err(err)) => (TaskExit::Failed, format!("{err:?}"), diagnose(&err)),
+                    Err(err) if err.is_panic() => {
+                        (TaskExit::Panicked, panic_message(err.into_panic()), None)
+                    }
+                    Err(err) => (TaskExit::Failed, err.to_string(), None),
+                };
+                restart_count += 1;
+                health.set_state(&name, SubsystemState::Restarting, Some(message.clone()));
+                error!(
+                    task = %name,
+                    ?exit,
+                    restart_count,
+                    "Supervised task stopped, restarting in {:?}: {}",
+                    restart_delay,
+                    message
+                );
+                if let Some(hint) = diagnostic
+                    .as_ref()
+                    .and_then(|diagnostic| diagnostic.hint.as_ref())
+                {
+                    warn!(task = %name, "{hint}");
+                }
+
+                let event = SupervisorEvent {
+                    task: name.clone(),
+                    exit,
+                    message,
+                    restart_count,
+                    restart_delay_ms: restart_delay.as_millis() as u64,
+                    time: Utc::now(),
+                    diagnostic,
+                };
+                if let Err(err) = publish_event(&zenoh_session, &event).await {
+                    warn!(task = %name, "Failed to publish supervisor event {err:?}");
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(restart_delay) => {}
+                    _ = shutdown.cancelled() => break,
+                }
+            }
+            health.set_state(&name, SubsystemState::Stopped, None);
+            debug!(task = %name, "Supervised task stopped");
+        });
+    }
+
+    /// Cancel all tasks and wait up to `timeout` for them to stop
+    pub async fn shutdown(self, timeout: Duration) {
+        self.shutdown.cancel();
+        self.tasks.close();
+        if tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .is_err()
+        {
+            warn!("Timed out waiting for tasks to stop");
+        }
+    }
+}
+
+async fn publish_event(zenoh_session: &Session, event: &SupervisorEvent) -> anyhow::Result<()> {
+    let json = serde_json::to_string(event)?;
+    zenoh_session
+        .put(SUPERVISOR_DIAGNOSTICS_TOPIC, json)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    Ok(())
+}
+
+/// Spawn a task that shows up under `name` in tokio-console
+fn spawn_named<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(tokio_unstable)]
+    {
+        tokio::task::Builder::new()
+            .name(name)
+            .spawn(future)
+            .expect("Failed to spawn task")
+    }
+    #[cfg(not(tokio_unstable))]
+    {
+        let _ = name;
+        tokio::spawn(future)
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        format!("panicked: {message}")
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        format!("panicked: {message}")
+    } else {
+        String::from("panicked")
+    }
+}
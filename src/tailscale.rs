@@ -1,57 +1,538 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{AddrParseError, IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Context;
-use serde::{Deserialize, Serialize};
-use tokio::process::Command;
+use regex::{Regex, RegexBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::Command,
+};
+use tracing::*;
+use zenoh::{config::Config, Session};
+use zenoh_config::EndPoint;
+
+use crate::{
+    error::{DiscoveryError, ZenohError},
+    robot_registry::RobotRegistry,
+    supervisor::Supervisor,
+};
+
+/// Port robots listen on for zenoh connections over tailscale
+pub const ZENOH_TCP_DISCOVERY_PORT: u16 = 7436;
+
+/// How often tailscale is polled for robots that came online after startup
+pub const TAILSCALE_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long the previous robot stays connected after switching robots, so that the
+/// gamepad reader can release its input first
+pub const PEER_RELEASE_DELAY: Duration = Duration::from_secs(1);
+
+/// tailscaled local API socket, overridden with `TAILSCALE_SOCKET`
+pub const DEFAULT_TAILSCALE_SOCKET: &str = "/var/run/tailscale/tailscaled.sock";
+const LOCAL_API_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Rule deciding whether a tailscale peer belongs to a robot
+///
+/// Host names are compared case insensitively.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerMatch {
+    /// Host name contains this
+    Contains(String),
+    /// Host name is exactly this
+    Exact(String),
+    /// Host name matches this regular expression
+    Regex(String),
+    /// Peer has this ACL tag, such as `tag:hopper`
+    Tag(String),
+}
+
+impl PeerMatch {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Self::Regex(pattern) = self {
+            host_name_regex(pattern)?;
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, peer: &TailscalePeer) -> bool {
+        match self {
+            Self::Contains(part) => peer.host_name.to_lowercase().contains(&part.to_lowercase()),
+            Self::Exact(host_name) => peer.host_name.eq_ignore_ascii_case(host_name),
+            Self::Regex(pattern) => match host_name_regex(pattern) {
+                Ok(regex) => regex.is_match(&peer.host_name),
+                Err(err) => {
+                    warn!("Invalid peer pattern {err:?}");
+                    false
+                }
+            },
+            Self::Tag(tag) => peer.tags.iter().any(|peer_tag| peer_tag == tag),
+        }
+    }
+}
+
+fn host_name_regex(pattern: &str) -> anyhow::Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .with_context(|| format!("Invalid host name pattern {pattern:?}"))
+}
+
+/// Peers matching any of `rules`
+pub fn matching_peers<'a>(
+    tailscale_status: &'a TailscaleStatus,
+    rules: &'a [PeerMatch],
+) -> impl Iterator<Item = &'a TailscalePeer> {
+    tailscale_status
+        .peers
+        .values()
+        .filter(|peer| rules.iter().any(|rule| rule.matches(peer)))
+}
+
+/// Listen on our tailscale addresses and connect to peers matching any of `peer_rules`
+///
+/// IPv6 addresses are only used with `ipv6`.
+pub fn add_tailscale_endpoints(
+    zenoh_config: &mut Config,
+    tailscale_status: &TailscaleStatus,
+    peer_rules: &[PeerMatch],
+    ipv6: bool,
+) -> Result<(), DiscoveryError> {
+    // listening address
+    for local_address in &tailscale_status.tailscale_ip_list {
+        if let Some(tcp) = tcp_endpoint(local_address, 0, ipv6)? {
+            zenoh_config.listen.endpoints.push(tcp)
+        }
+    }
+
+    // peer address
+    zenoh_config
+        .connect
+        .endpoints
+        .extend(peer_endpoints(tailscale_status, peer_rules, ipv6)?);
+    Ok(())
+}
+
+/// Zenoh endpoints of peers matching any of `peer_rules`
+pub fn peer_endpoints(
+    tailscale_status: &TailscaleStatus,
+    peer_rules: &[PeerMatch],
+    ipv6: bool,
+) -> Result<Vec<EndPoint>, DiscoveryError> {
+    endpoints_of(matching_peers(tailscale_status, peer_rules), ipv6)
+}
+
+/// [`peer_endpoints`] of the matching peers that are online
+pub fn online_peer_endpoints(
+    tailscale_status: &TailscaleStatus,
+    peer_rules: &[PeerMatch],
+    ipv6: bool,
+) -> Result<Vec<EndPoint>, DiscoveryError> {
+    endpoints_of(
+        matching_peers(tailscale_status, peer_rules).filter(|peer| peer.online),
+        ipv6,
+    )
+}
+
+fn endpoints_of<'a>(
+    peers: impl Iterator<Item = &'a TailscalePeer>,
+    ipv6: bool,
+) -> Result<Vec<EndPoint>, DiscoveryError> {
+    let mut endpoints = vec![];
+    for peer in peers {
+        for peer_address in &peer.tailscale_ip_list {
+            if let Some(tcp) = tcp_endpoint(peer_address, ZENOH_TCP_DISCOVERY_PORT, ipv6)? {
+                endpoints.push(tcp)
+            }
+        }
+    }
+    endpoints.sort_by_key(|endpoint| endpoint.to_string());
+    Ok(endpoints)
+}
+
+/// `tcp/<address>:<port>` with IPv6 addresses in brackets, `None` for skipped IPv6 addresses
+fn tcp_endpoint(address: &str, port: u16, ipv6: bool) -> Result<Option<EndPoint>, DiscoveryError> {
+    let invalid_address = |reason: String| DiscoveryError::InvalidAddress {
+        address: address.to_owned(),
+        reason,
+    };
+    let ip_address: IpAddr = address
+        .parse()
+        .map_err(|err: AddrParseError| invalid_address(err.to_string()))?;
+    if ip_address.is_ipv6() && !ipv6 {
+        return Ok(None);
+    }
+    let tcp = EndPoint::new("tcp", SocketAddr::new(ip_address, port).to_string(), "", "")
+        .map_err(|err| invalid_address(err.to_string()))?;
+    Ok(Some(tcp))
+}
+
+/// Keep polling tailscale and connect to robots that weren't online at startup
+///
+/// Peers are matched against the peer rules of the robot that is currently active in
+/// `registry`, or of every robot if it has several connected at once. Switching robots
+/// triggers a poll right away, and the peers of the previous robot are no longer
+/// connected to after [`PEER_RELEASE_DELAY`].
+pub fn start_tailscale_discovery(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    registry: RobotRegistry,
+    ipv6: bool,
+) {
+    let heartbeat = supervisor.health().heartbeat("tailscale_discovery");
+    supervisor.spawn("tailscale_discovery", move |shutdown| {
+        let zenoh_session = zenoh_session.clone();
+        let registry = registry.clone();
+        let mut active = registry.subscribe();
+        let heartbeat = heartbeat.clone();
+        async move {
+            let mut selected = registry.active();
+            let mut interval = tokio::time::interval(TAILSCALE_POLL_INTERVAL);
+            // the initial status was already applied when the session was opened
+            interval.tick().await;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => heartbeat.beat(),
+                    changed = active.changed() => {
+                        changed?;
+                        interval.reset();
+                    }
+                    _ = shutdown.cancelled() => break,
+                }
+                // mark the selection as seen, every poll looks at the current one
+                active.borrow_and_update();
+                match TailscaleStatus::read().await {
+                    Ok(status) => {
+                        for target in registry.connected_targets() {
+                            add_new_peers(
+                                &zenoh_session,
+                                &status,
+                                &registry.peer_rules(&target),
+                                ipv6,
+                            )?;
+                        }
+                        if registry.is_simultaneous() || registry.active().name == selected.name {
+                            continue;
+                        }
+                        tokio::select! {
+                            _ = tokio::time::sleep(PEER_RELEASE_DELAY) => {}
+                            _ = shutdown.cancelled() => break,
+                        }
+                        let active = registry.active();
+                        let released = peer_endpoints(&status, &selected.peer_rules, ipv6)?;
+                        let kept = peer_endpoints(&status, &active.peer_rules, ipv6)?;
+                        release_peers(&zenoh_session, &released, &kept)?;
+                        selected = active;
+                    }
+                    Err(err) => warn!("Failed to read tailscale status {err:?}"),
+                }
+            }
+            Ok(())
+        }
+    });
+}
+
+fn add_new_peers(
+    zenoh_session: &Session,
+    tailscale_status: &TailscaleStatus,
+    peer_rules: &[PeerMatch],
+    ipv6: bool,
+) -> anyhow::Result<()> {
+    let mut connect_endpoints = zenoh_session.config().lock().connect.endpoints.clone();
+    let new_endpoints: Vec<EndPoint> = peer_endpoints(tailscale_status, peer_rules, ipv6)?
+        .into_iter()
+        .filter(|endpoint| !connect_endpoints.contains(endpoint))
+        .collect();
+    if new_endpoints.is_empty() {
+        return Ok(());
+    }
+
+    info!("Discovered new tailscale peers {:?}", new_endpoints);
+    connect_endpoints.extend(new_endpoints);
+    let connect_endpoints: Vec<String> =
+        connect_endpoints.iter().map(ToString::to_string).collect();
+    zenoh_session
+        .config()
+        .insert_json5(
+            "connect/endpoints",
+            &serde_json::to_string(&connect_endpoints)?,
+        )
+        .map_err(ZenohError)?;
+    Ok(())
+}
+
+/// Stop connecting to the `released` endpoints of a robot that was switched away from
+fn release_peers(
+    zenoh_session: &Session,
+    released: &[EndPoint],
+    kept: &[EndPoint],
+) -> anyhow::Result<()> {
+    let current = zenoh_session.config().lock().connect.endpoints.clone();
+    let connect_endpoints = remaining_endpoints(&current, released, kept);
+    if connect_endpoints.len() == current.len() {
+        return Ok(());
+    }
+    info!("Releasing tailscale peers {:?}", released);
+    let connect_endpoints: Vec<String> =
+        connect_endpoints.iter().map(ToString::to_string).collect();
+    zenoh_session
+        .config()
+        .insert_json5(
+            "connect/endpoints",
+            &serde_json::to_string(&connect_endpoints)?,
+        )
+        .map_err(ZenohError)?;
+    Ok(())
+}
+
+/// `current` endpoints without the `released` ones, unless they are `kept`
+///
+/// Robots can share peers, such as a router that both are reached through.
+pub fn remaining_endpoints(
+    current: &[EndPoint],
+    released: &[EndPoint],
+    kept: &[EndPoint],
+) -> Vec<EndPoint> {
+    current
+        .iter()
+        .filter(|endpoint| !released.contains(endpoint) || kept.contains(endpoint))
+        .cloned()
+        .collect()
+}
 
 impl TailscaleStatus {
-    pub async fn read_from_command() -> anyhow::Result<Self> {
+    /// Status from the tailscaled local API, falling back to the `tailscale` CLI
+    ///
+    /// The CLI isn't on the PATH in sandboxes such as flatpak while the socket usually is.
+    pub async fn read() -> Result<Self, DiscoveryError> {
+        let socket = std::env::var("TAILSCALE_SOCKET")
+            .unwrap_or_else(|_| DEFAULT_TAILSCALE_SOCKET.to_owned());
+        match Self::read_from_local_api(&socket).await {
+            Ok(status) => Ok(status),
+            Err(err) => {
+                debug!("Tailscale local API unavailable, falling back to CLI {err:?}");
+                Self::read_from_command().await
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    pub async fn read_from_local_api(socket: &str) -> Result<Self, DiscoveryError> {
+        let request = async {
+            let mut stream = tokio::net::UnixStream::connect(socket)
+                .await
+                .with_context(|| format!("Failed to connect to {socket}"))?;
+            // HTTP/1.0 so that the body isn't chunked and ends when the connection closes
+            stream
+                .write_all(
+                    b"GET /localapi/v0/status HTTP/1.0\r\nHost: local-tailscaled.sock\r\n\r\n",
+                )
+                .await?;
+            let mut response = vec![];
+            stream.read_to_end(&mut response).await?;
+            anyhow::Ok(response)
+        };
+        let body = async {
+            let response = tokio::time::timeout(LOCAL_API_TIMEOUT, request)
+                .await
+                .context("Tailscale local API timed out")??;
+            anyhow::Ok(http_body(&response)?.to_vec())
+        };
+        let body = body
+            .await
+            .map_err(|err| DiscoveryError::Unavailable(format!("{err:#}")))?;
+        Self::parse(&body)
+    }
+
+    #[cfg(not(unix))]
+    pub async fn read_from_local_api(_socket: &str) -> Result<Self, DiscoveryError> {
+        Err(DiscoveryError::Unavailable(
+            "Tailscale local API is only reachable over a unix socket".to_owned(),
+        ))
+    }
+
+    pub async fn read_from_command() -> Result<Self, DiscoveryError> {
         let output = Command::new("tailscale")
             .arg("status")
             .arg("--json")
             .output()
             .await
-            .context("failed to spawn")?;
+            .map_err(|err| {
+                DiscoveryError::Unavailable(format!("failed to spawn tailscale {err}"))
+            })?;
 
         if !output.status.success() {
-            anyhow::bail!("querying tailscale status failed");
+            return Err(DiscoveryError::Unavailable(format!(
+                "querying tailscale status failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
         }
 
-        let parsed = serde_json::from_slice(&output.stdout)?;
-        Ok(parsed)
+        Self::parse(&output.stdout)
+    }
+
+    /// Peer traffic is currently routed through, if any
+    pub fn exit_node(&self) -> Option<&TailscalePeer> {
+        self.peers.values().find(|peer| peer.exit_node)
+    }
+
+    /// IPv4 address of this machine on the tailnet
+    pub fn local_ipv4(&self) -> Option<Ipv4Addr> {
+        let mut addresses: Vec<Ipv4Addr> = self
+            .self_status
+            .tailscale_ip_list
+            .iter()
+            .chain(&self.tailscale_ip_list)
+            .filter_map(|address| address.parse().ok())
+            .collect();
+        addresses.sort();
+        addresses.first().copied()
+    }
+
+    /// MagicDNS name of this machine such as `steamdeck.tail1234.ts.net`
+    pub fn magic_dns_name(&self) -> Option<&str> {
+        let dns_name = self.self_status.dns_name.trim_end_matches('.');
+        (!dns_name.is_empty()).then_some(dns_name)
+    }
+
+    /// Parse the output of `tailscale status --json`
+    ///
+    /// The shape of the output changes between tailscale versions so missing or null
+    /// fields fall back to defaults and peers that don't match the model are skipped.
+    /// Only output that isn't a JSON object is rejected.
+    pub fn parse(json: &[u8]) -> Result<Self, DiscoveryError> {
+        serde_json::from_slice(json).map_err(DiscoveryError::InvalidStatus)
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 pub struct TailscaleStatus {
-    #[serde(rename = "TailscaleIPs")]
+    #[serde(rename = "TailscaleIPs", default, deserialize_with = "lenient")]
     pub tailscale_ip_list: HashSet<String>,
-    #[serde(rename = "Self")]
+    #[serde(rename = "Self", default, deserialize_with = "lenient")]
     pub self_status: TailscaleStatusSelf,
-    #[serde(rename = "Peer")]
+    #[serde(rename = "Peer", default, deserialize_with = "lenient_peers")]
     pub peers: HashMap<String, TailscalePeer>,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 pub struct TailscaleStatusSelf {
-    #[serde(rename = "ID")]
+    #[serde(rename = "ID", default, deserialize_with = "null_as_default")]
     pub id: String,
-    #[serde(rename = "HostName")]
+    #[serde(rename = "HostName", default, deserialize_with = "null_as_default")]
     pub host_name: String,
-    #[serde(rename = "DNSName")]
+    #[serde(rename = "DNSName", default, deserialize_with = "null_as_default")]
     pub dns_name: String,
-    #[serde(rename = "TailscaleIPs")]
+    #[serde(rename = "TailscaleIPs", default, deserialize_with = "null_as_default")]
     pub tailscale_ip_list: HashSet<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 pub struct TailscalePeer {
-    #[serde(rename = "ID")]
+    #[serde(rename = "ID", default, deserialize_with = "null_as_default")]
     pub id: String,
-    #[serde(rename = "HostName")]
+    #[serde(rename = "HostName", default, deserialize_with = "null_as_default")]
     pub host_name: String,
-    #[serde(rename = "DNSName")]
+    #[serde(rename = "DNSName", default, deserialize_with = "null_as_default")]
     pub dns_name: String,
-    #[serde(rename = "TailscaleIPs")]
+    #[serde(rename = "TailscaleIPs", default, deserialize_with = "null_as_default")]
     pub tailscale_ip_list: HashSet<String>,
+    #[serde(rename = "Online", default, deserialize_with = "null_as_default")]
+    pub online: bool,
+    /// ACL tags such as `tag:robot`, empty for machines owned by a user
+    #[serde(rename = "Tags", default, deserialize_with = "null_as_default")]
+    pub tags: Vec<String>,
+    /// Traffic of this machine is routed through the peer
+    #[serde(rename = "ExitNode", default, deserialize_with = "null_as_default")]
+    pub exit_node: bool,
+    /// The peer offers to be an exit node
+    #[serde(
+        rename = "ExitNodeOption",
+        default,
+        deserialize_with = "null_as_default"
+    )]
+    pub exit_node_option: bool,
+}
+
+/// Body of an HTTP response from the local API, non 2xx responses are errors
+pub fn http_body(response: &[u8]) -> anyhow::Result<&[u8]> {
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .context("Incomplete HTTP response")?;
+    let (head, body) = (&response[..header_end], &response[header_end + 4..]);
+    let status_line = std::str::from_utf8(head)?
+        .lines()
+        .next()
+        .unwrap_or_default();
+    let status: u16 = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .with_context(|| format!("Invalid HTTP status line {status_line:?}"))?;
+    if !(200..300).contains(&status) {
+        anyhow::bail!(
+            "Tailscale local API returned {status}: {}",
+            String::from_utf8_lossy(body).trim()
+        );
+    }
+    Ok(body)
+}
+
+/// tailscale reports empty lists and maps as null
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Fall back to the default instead of failing the whole status
+fn lenient<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + DeserializeOwned,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    if value.is_null() {
+        return Ok(T::default());
+    }
+    match serde_json::from_value(value) {
+        Ok(parsed) => Ok(parsed),
+        Err(err) => {
+            warn!("Ignoring unexpected tailscale status field {err}");
+            Ok(T::default())
+        }
+    }
+}
+
+fn lenient_peers<'de, D>(deserializer: D) -> Result<HashMap<String, TailscalePeer>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let peers = match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Object(peers) => peers,
+        serde_json::Value::Null => return Ok(HashMap::new()),
+        _ => {
+            warn!("Ignoring unexpected tailscale peer list");
+            return Ok(HashMap::new());
+        }
+    };
+    let peers = peers
+        .into_iter()
+        .filter_map(|(key, peer)| match serde_json::from_value(peer) {
+            Ok(peer) => Some((key, peer)),
+            Err(err) => {
+                warn!("Ignoring unexpected tailscale peer {key} {err}");
+                None
+            }
+        })
+        .collect();
+    Ok(peers)
 }
@@ -0,0 +1,123 @@
+//! TLS termination in front of the Foxglove websocket server
+//!
+//! foxglove-ws only serves plain `ws://`. app.foxglove.dev is served over HTTPS and browsers
+//! block plain websockets to anything but localhost, so for remote access the server listens
+//! on loopback and this proxy accepts `wss://` connections on the public address.
+
+use std::{fs::File, io::BufReader, net::SocketAddr, path::Path, sync::Arc};
+
+use anyhow::Context;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::JoinSet,
+};
+use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
+use tracing::*;
+
+use crate::{foxglove_gateway::CLOSE_TIMEOUT, health::HEARTBEAT_INTERVAL, supervisor::Supervisor};
+
+/// Load a PEM certificate chain and private key
+pub fn load_tls_acceptor(cert_path: &Path, key_path: &Path) -> anyhow::Result<TlsAcceptor> {
+    let cert_file = File::open(cert_path)
+        .with_context(|| format!("Failed to open certificate {}", cert_path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid certificate {}", cert_path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates in {}", cert_path.display());
+    }
+
+    let key_file = File::open(key_path)
+        .with_context(|| format!("Failed to open private key {}", key_path.display()))?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+        .with_context(|| format!("Invalid private key {}", key_path.display()))?
+        .with_context(|| format!("No private key in {}", key_path.display()))?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("Certificate doesn't match private key")?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Unused loopback address for a server that is only reached through the proxy
+pub fn free_loopback_address() -> std::io::Result<SocketAddr> {
+    std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()
+}
+
+/// Accept TLS connections on `listen` and forward the decrypted stream to `backend`
+pub async fn start_tls_proxy(
+    supervisor: &Supervisor,
+    listen: SocketAddr,
+    backend: SocketAddr,
+    acceptor: TlsAcceptor,
+) -> anyhow::Result<()> {
+    // fail fast like the plain server does
+    drop(
+        TcpListener::bind(listen)
+            .await
+            .with_context(|| format!("TLS proxy can't bind {listen}"))?,
+    );
+    info!("Serving wss on {listen}");
+
+    let heartbeat = supervisor.health().heartbeat("tls_proxy");
+    supervisor.spawn_persistent("tls_proxy", move |shutdown| {
+        let acceptor = acceptor.clone();
+        let heartbeat = heartbeat.clone();
+        async move {
+            let listener = TcpListener::bind(listen).await?;
+            let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            let mut connections = JoinSet::new();
+            loop {
+                let (stream, peer) = tokio::select! {
+                    accepted = listener.accept() => accepted?,
+                    _ = heartbeat_interval.tick() => {
+                        heartbeat.beat();
+                        continue;
+                    }
+                    Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+                    _ = shutdown.cancelled() => break,
+                };
+                let acceptor = acceptor.clone();
+                let shutdown = shutdown.clone();
+                connections.spawn(async move {
+                    let connection = proxy_connection(stream, acceptor, backend);
+                    tokio::pin!(connection);
+                    let result = tokio::select! {
+                        result = &mut connection => result,
+                        // the gateway says goodbye on shutdown and then closes the backend side
+                        _ = shutdown.cancelled() => {
+                            tokio::time::timeout(CLOSE_TIMEOUT, &mut connection)
+                                .await
+                                .context("Timed out closing TLS connection")
+                                .and_then(|result| result)
+                        }
+                    };
+                    if let Err(err) = result {
+                        debug!(%peer, "TLS connection closed with error {err:?}");
+                    }
+                });
+            }
+            while connections.join_next().await.is_some() {}
+            Ok(())
+        }
+    });
+    Ok(())
+}
+
+async fn proxy_connection(
+    stream: TcpStream,
+    acceptor: TlsAcceptor,
+    backend: SocketAddr,
+) -> anyhow::Result<()> {
+    let mut tls_stream = acceptor
+        .accept(stream)
+        .await
+        .context("TLS handshake failed")?;
+    let mut backend_stream = TcpStream::connect(backend)
+        .await
+        .context("Failed to connect to foxglove server")?;
+    backend_stream.set_nodelay(true)?;
+    tokio::io::copy_bidirectional(&mut tls_stream, &mut backend_stream).await?;
+    Ok(())
+}
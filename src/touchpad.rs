@@ -0,0 +1,156 @@
+//! Steam Deck trackpads read through evdev
+//!
+//! gilrs doesn't report the trackpads. The hid-steam driver exposes them on the
+//! Steam Deck input device as `ABS_HAT0X/Y` (left) and `ABS_HAT1X/Y` (right) with
+//! `BTN_THUMB` and `BTN_THUMB2` for clicks. Axes read 0 while a pad isn't touched.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio_util::sync::CancellationToken;
+use zenoh::prelude::r#async::*;
+
+use crate::{clock::SharedClock, health::Heartbeat, supervisor::Supervisor};
+
+pub const TOUCHPAD_PUBLISH_INTERVAL: Duration = Duration::from_millis(20);
+
+pub fn start_touchpad_publisher(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    topic: String,
+    clock: SharedClock,
+) {
+    let heartbeat = supervisor.health().heartbeat("touchpad_publisher");
+    supervisor.spawn("touchpad_publisher", move |shutdown| {
+        run_touchpad_publisher(
+            zenoh_session.clone(),
+            topic.clone(),
+            clock.clone(),
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+/// Map a raw axis value from `[minimum, maximum]` to `[-1.0, 1.0]` keeping 0 at the center
+pub fn normalize_axis(value: i32, minimum: i32, maximum: i32) -> f32 {
+    let normalized = match value {
+        value if value > 0 && maximum > 0 => value as f32 / maximum as f32,
+        value if value < 0 && minimum < 0 => -(value as f32 / minimum as f32),
+        _ => 0.0,
+    };
+    normalized.clamp(-1.0, 1.0)
+}
+
+#[cfg(target_os = "linux")]
+async fn run_touchpad_publisher(
+    zenoh_session: Arc<Session>,
+    topic: String,
+    clock: SharedClock,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use evdev::{AbsoluteAxisType, InputEventKind, Key};
+    use tokio::time::MissedTickBehavior;
+    use tracing::*;
+
+    use crate::{
+        clock::Clock,
+        error::ZenohError,
+        messages::{TouchpadMessage, TouchpadState},
+    };
+
+    let (path, device) = evdev::enumerate()
+        .find(|(_, device)| {
+            device.supported_absolute_axes().is_some_and(|axes| {
+                axes.contains(AbsoluteAxisType::ABS_HAT0X)
+                    && axes.contains(AbsoluteAxisType::ABS_HAT1X)
+            })
+        })
+        .context("No input device with trackpads found")?;
+    info!(
+        "Reading trackpads from {} ({})",
+        path.display(),
+        device.name().unwrap_or_default()
+    );
+    let abs_state = device.get_abs_state()?;
+    let range = |axis: AbsoluteAxisType| {
+        let info = abs_state[axis.0 as usize];
+        (info.minimum, info.maximum)
+    };
+    let mut events = device.into_event_stream()?;
+
+    let publisher = zenoh_session
+        .declare_publisher(topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut message = TouchpadMessage::default();
+    let mut interval = tokio::time::interval(TOUCHPAD_PUBLISH_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            event = events.next_event() => {
+                let event = event.context("Failed to read trackpad event")?;
+                match event.kind() {
+                    InputEventKind::AbsAxis(axis) => {
+                        let (minimum, maximum) = range(axis);
+                        let value = normalize_axis(event.value(), minimum, maximum);
+                        let (pad, value) = match axis {
+                            AbsoluteAxisType::ABS_HAT0X => (&mut message.left.x, value),
+                            // evdev y grows downwards
+                            AbsoluteAxisType::ABS_HAT0Y => (&mut message.left.y, -value),
+                            AbsoluteAxisType::ABS_HAT1X => (&mut message.right.x, value),
+                            AbsoluteAxisType::ABS_HAT1Y => (&mut message.right.y, -value),
+                            _ => continue,
+                        };
+                        *pad = value;
+                    }
+                    InputEventKind::Key(Key::BTN_THUMB) => {
+                        message.left.clicked = event.value() != 0
+                    }
+                    InputEventKind::Key(Key::BTN_THUMB2) => {
+                        message.right.clicked = event.value() != 0
+                    }
+                    _ => {}
+                }
+            }
+            _ = interval.tick() => {
+                heartbeat.beat();
+                for pad in [&mut message.left, &mut message.right] {
+                    pad.touched = pad.x != 0.0 || pad.y != 0.0;
+                }
+                message.time = clock.now().into();
+                publisher
+                    .put(serde_json::to_string(&message)?)
+                    .res()
+                    .await
+                    .map_err(ZenohError)?;
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+
+    // don't leave the robot panning
+    message.left = TouchpadState::default();
+    message.right = TouchpadState::default();
+    message.time = clock.now().into();
+    publisher
+        .put(serde_json::to_string(&message)?)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn run_touchpad_publisher(
+    _zenoh_session: Arc<Session>,
+    _topic: String,
+    _clock: SharedClock,
+    _heartbeat: Heartbeat,
+    _shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    anyhow::bail!("Trackpads are only supported on Linux")
+}
@@ -0,0 +1,282 @@
+//! Fixing up robot messages before they are forwarded to Foxglove
+//!
+//! Each subscription can list `transforms` that are applied in order. Fields are
+//! addressed by dotted paths such as `pose.position.x`. JSON payloads are edited as
+//! `serde_json` values, protobuf payloads are decoded as [`DynamicMessage`]s of the
+//! subscription's type.
+
+use anyhow::Context;
+use prost::Message;
+use prost_reflect::{DynamicMessage, Kind, MessageDescriptor, Value};
+use serde::Deserialize;
+
+/// One step of a subscription's `transforms`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum PayloadTransform {
+    /// Move a field, protobuf fields have to be of the same type
+    Rename { from: String, to: String },
+    /// Multiply a number, or every number of a list
+    Scale { field: String, factor: f64 },
+    /// Replace the message with one of its nested fields
+    Extract { field: String },
+}
+
+impl PayloadTransform {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            PayloadTransform::Rename { from, to } => {
+                path(from)?;
+                path(to)?;
+            }
+            PayloadTransform::Scale { field, factor } => {
+                path(field)?;
+                if !factor.is_finite() {
+                    anyhow::bail!("Scale factor of {field:?} must be finite");
+                }
+            }
+            PayloadTransform::Extract { field } => {
+                path(field)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn path(field: &str) -> anyhow::Result<Vec<&str>> {
+    let path: Vec<&str> = field.split('.').collect();
+    if path.iter().any(|name| name.is_empty()) {
+        anyhow::bail!("Invalid field path {field:?}");
+    }
+    Ok(path)
+}
+
+/// Apply `transforms` to a JSON message
+pub fn transform_json(
+    mut message: serde_json::Value,
+    transforms: &[PayloadTransform],
+) -> anyhow::Result<serde_json::Value> {
+    for transform in transforms {
+        match transform {
+            PayloadTransform::Rename { from, to } => {
+                let value = take_json(&mut message, from)?;
+                *json_field_or_insert(&mut message, to)? = value;
+            }
+            PayloadTransform::Scale { field, factor } => {
+                scale_json(json_field(&mut message, field)?, *factor)
+                    .with_context(|| format!("Can't scale {field:?}"))?;
+            }
+            PayloadTransform::Extract { field } => {
+                message = take_json(&mut message, field)?;
+            }
+        }
+    }
+    Ok(message)
+}
+
+fn json_field<'a>(
+    message: &'a mut serde_json::Value,
+    field: &str,
+) -> anyhow::Result<&'a mut serde_json::Value> {
+    path(field)?
+        .into_iter()
+        .try_fold(message, |value, name| value.get_mut(name))
+        .with_context(|| format!("Missing field {field:?}"))
+}
+
+/// Missing parents are created as empty objects
+fn json_field_or_insert<'a>(
+    message: &'a mut serde_json::Value,
+    field: &str,
+) -> anyhow::Result<&'a mut serde_json::Value> {
+    let mut value = message;
+    for name in path(field)? {
+        value = value
+            .as_object_mut()
+            .with_context(|| format!("Parent of {field:?} isn't an object"))?
+            .entry(name)
+            .or_insert_with(|| serde_json::Value::Object(Default::default()));
+    }
+    Ok(value)
+}
+
+fn take_json(message: &mut serde_json::Value, field: &str) -> anyhow::Result<serde_json::Value> {
+    let (parent, name) = match field.rsplit_once('.') {
+        Some((parent, name)) => (json_field(message, parent)?, name),
+        None => (message, field),
+    };
+    parent
+        .as_object_mut()
+        .and_then(|object| object.remove(name))
+        .with_context(|| format!("Missing field {field:?}"))
+}
+
+fn scale_json(value: &mut serde_json::Value, factor: f64) -> anyhow::Result<()> {
+    match value {
+        serde_json::Value::Number(number) => {
+            let scaled = number.as_f64().context("Number out of range")? * factor;
+            *value = serde_json::Value::from(scaled);
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                scale_json(value, factor)?;
+            }
+        }
+        _ => anyhow::bail!("Not a number"),
+    }
+    Ok(())
+}
+
+/// Type of the messages `transforms` produce from `descriptor` messages
+///
+/// Also checks that every field exists, so broken transforms fail at startup.
+pub fn output_descriptor(
+    descriptor: &MessageDescriptor,
+    transforms: &[PayloadTransform],
+) -> anyhow::Result<MessageDescriptor> {
+    let mut descriptor = descriptor.clone();
+    for transform in transforms {
+        match transform {
+            PayloadTransform::Rename { from, to } => {
+                let from_kind = proto_field_kind(&descriptor, from)?;
+                let to_kind = proto_field_kind(&descriptor, to)?;
+                if from_kind != to_kind {
+                    anyhow::bail!("Can't rename {from:?} to {to:?} of a different type");
+                }
+            }
+            PayloadTransform::Scale { field, .. } => {
+                let (kind, _) = proto_field_kind(&descriptor, field)?;
+                if !is_number(&kind) {
+                    anyhow::bail!("Can't scale {field:?}, it isn't a number");
+                }
+            }
+            PayloadTransform::Extract { field } => {
+                let (Kind::Message(nested), false) = proto_field_kind(&descriptor, field)? else {
+                    anyhow::bail!("Can't extract {field:?}, it isn't a single message");
+                };
+                descriptor = nested;
+            }
+        }
+    }
+    Ok(descriptor)
+}
+
+/// Kind of a field and whether it is repeated
+fn proto_field_kind(descriptor: &MessageDescriptor, field: &str) -> anyhow::Result<(Kind, bool)> {
+    let mut descriptor = descriptor.clone();
+    let path = path(field)?;
+    let (last, parents) = path.split_last().context("Empty field path")?;
+    for name in parents {
+        let parent = descriptor
+            .get_field_by_name(name)
+            .with_context(|| format!("{} has no field {name:?}", descriptor.full_name()))?;
+        match parent.kind() {
+            Kind::Message(nested) if !parent.is_list() => descriptor = nested,
+            _ => anyhow::bail!("{name:?} in {field:?} isn't a message"),
+        }
+    }
+    let field_descriptor = descriptor
+        .get_field_by_name(last)
+        .with_context(|| format!("{} has no field {last:?}", descriptor.full_name()))?;
+    Ok((field_descriptor.kind(), field_descriptor.is_list()))
+}
+
+fn is_number(kind: &Kind) -> bool {
+    matches!(
+        kind,
+        Kind::Double
+            | Kind::Float
+            | Kind::Int32
+            | Kind::Int64
+            | Kind::Uint32
+            | Kind::Uint64
+            | Kind::Sint32
+            | Kind::Sint64
+            | Kind::Fixed32
+            | Kind::Fixed64
+            | Kind::Sfixed32
+            | Kind::Sfixed64
+    )
+}
+
+/// Decode a `descriptor` message, apply `transforms` and encode the result
+///
+/// `transforms` have to be checked with [`output_descriptor`] first.
+pub fn transform_proto(
+    payload: &[u8],
+    descriptor: &MessageDescriptor,
+    transforms: &[PayloadTransform],
+) -> anyhow::Result<Vec<u8>> {
+    let mut message = DynamicMessage::decode(descriptor.clone(), payload)?;
+    for transform in transforms {
+        match transform {
+            PayloadTransform::Rename { from, to } => {
+                let value = take_proto(&mut message, from)?;
+                let (parent, name) = proto_parent(&mut message, to)?;
+                parent
+                    .try_set_field_by_name(name, value)
+                    .with_context(|| format!("Can't set {to:?}"))?;
+            }
+            PayloadTransform::Scale { field, factor } => {
+                let (parent, name) = proto_parent(&mut message, field)?;
+                let value = parent
+                    .get_field_by_name_mut(name)
+                    .with_context(|| format!("Missing field {field:?}"))?;
+                scale_proto(value, *factor);
+            }
+            PayloadTransform::Extract { field } => {
+                let Value::Message(nested) = take_proto(&mut message, field)? else {
+                    anyhow::bail!("{field:?} isn't a message");
+                };
+                message = nested;
+            }
+        }
+    }
+    Ok(message.encode_to_vec())
+}
+
+/// Message holding the last field of `field` and that field's name
+fn proto_parent<'a, 'f>(
+    message: &'a mut DynamicMessage,
+    field: &'f str,
+) -> anyhow::Result<(&'a mut DynamicMessage, &'f str)> {
+    let Some((parents, name)) = field.rsplit_once('.') else {
+        return Ok((message, field));
+    };
+    let mut parent = message;
+    for parent_name in parents.split('.') {
+        parent = match parent.get_field_by_name_mut(parent_name) {
+            Some(Value::Message(nested)) => nested,
+            _ => anyhow::bail!("{parent_name:?} in {field:?} isn't a message"),
+        };
+    }
+    Ok((parent, name))
+}
+
+fn take_proto(message: &mut DynamicMessage, field: &str) -> anyhow::Result<Value> {
+    let (parent, name) = proto_parent(message, field)?;
+    // unset fields are taken as their default value
+    let value = parent
+        .get_field_by_name(name)
+        .with_context(|| format!("Missing field {field:?}"))?
+        .into_owned();
+    parent.clear_field_by_name(name);
+    Ok(value)
+}
+
+fn scale_proto(value: &mut Value, factor: f64) {
+    match value {
+        Value::F64(number) => *number *= factor,
+        Value::F32(number) => *number = (f64::from(*number) * factor) as f32,
+        Value::I32(number) => *number = (f64::from(*number) * factor).round() as i32,
+        Value::I64(number) => *number = (*number as f64 * factor).round() as i64,
+        Value::U32(number) => *number = (f64::from(*number) * factor).round() as u32,
+        Value::U64(number) => *number = (*number as f64 * factor).round() as u64,
+        Value::List(values) => {
+            for value in values {
+                scale_proto(value, factor);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,399 @@
+//! Terminal dashboard shown by `--tui` instead of the scrolling log
+//!
+//! A supervised task collects gamepad state, bridge rates and the connection state of the
+//! active robot into a [`Dashboard`], a separate thread owns the terminal and redraws it.
+//! Logs are captured into a [`LogBuffer`] and shown at the bottom. `q` or Ctrl-C quits.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::Write,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Row, Table},
+    Frame,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+use zenoh::prelude::r#async::*;
+
+use crate::{
+    connection::{ConnectionMessage, CONNECTION_TOPIC},
+    error::ZenohError,
+    foxglove_clients::foxglove_client_count,
+    health::Heartbeat,
+    messages::InputMessage,
+    stats::{BridgeStatsTracker, ChannelStats, StatsRegistry},
+    supervisor::Supervisor,
+};
+
+const DASHBOARD_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+const TUI_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const LOG_BUFFER_LINES: usize = 200;
+
+/// Everything the dashboard shows
+#[derive(Debug, Clone, Default)]
+pub struct DashboardState {
+    pub gamepad_topic: String,
+    pub gamepad: Option<InputMessage>,
+    pub channels: BTreeMap<String, ChannelStats>,
+    pub connection: Option<ConnectionMessage>,
+    pub foxglove_link: String,
+    /// Not set where open connections can't be counted
+    pub foxglove_clients: Option<usize>,
+    pub logs: Vec<String>,
+}
+
+/// Dashboard state shared between the collecting task and the drawing thread
+#[derive(Debug, Clone, Default)]
+pub struct Dashboard {
+    state: Arc<Mutex<DashboardState>>,
+}
+
+impl Dashboard {
+    pub fn new(gamepad_topic: &str, foxglove_link: &str) -> Self {
+        let state = DashboardState {
+            gamepad_topic: gamepad_topic.to_owned(),
+            foxglove_link: foxglove_link.to_owned(),
+            ..Default::default()
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    pub fn update(&self, update: impl FnOnce(&mut DashboardState)) {
+        update(&mut self.state.lock().unwrap());
+    }
+
+    pub fn snapshot(&self) -> DashboardState {
+        self.state.lock().unwrap().clone()
+    }
+}
+
+/// Most recent log lines, used as the tracing writer while the dashboard owns the terminal
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl LogBuffer {
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut lines = self.lines.lock().unwrap();
+        for line in String::from_utf8_lossy(buf).lines() {
+            if lines.len() == LOG_BUFFER_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_owned());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn render(frame: &mut Frame, state: &DashboardState) {
+    let [connection_area, middle_area, log_area] = Layout::vertical([
+        Constraint::Length(6),
+        Constraint::Min(8),
+        Constraint::Percentage(30),
+    ])
+    .areas(frame.area());
+    let [gamepad_area, topics_area] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .areas(middle_area);
+
+    render_connection(frame, connection_area, state);
+    render_gamepad(frame, gamepad_area, state);
+    render_topics(frame, topics_area, state);
+
+    let visible_lines = log_area.height.saturating_sub(2) as usize;
+    let logs: Vec<Line> = state
+        .logs
+        .iter()
+        .skip(state.logs.len().saturating_sub(visible_lines))
+        .map(|line| Line::raw(line.as_str()))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(logs).block(Block::bordered().title("Log")),
+        log_area,
+    );
+}
+
+fn render_connection(frame: &mut Frame, area: Rect, state: &DashboardState) {
+    let mut lines = Vec::new();
+    match &state.connection {
+        Some(connection) => {
+            let (status, color) = if connection.connected {
+                ("connected", Color::Green)
+            } else {
+                ("disconnected", Color::Red)
+            };
+            let telemetry_age = connection
+                .last_telemetry_age_seconds
+                .map_or("never".to_owned(), |age| format!("{age:.1}s ago"));
+            lines.push(Line::from(vec![
+                Span::styled(
+                    connection.robot.clone(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(" "),
+                Span::styled(status, Style::default().fg(color)),
+                Span::raw(format!(
+                    "  zenoh peers {} routers {}  telemetry {telemetry_age}",
+                    connection.zenoh_peers.len(),
+                    connection.zenoh_routers.len(),
+                )),
+            ]));
+            let mut peers = vec![Span::raw("tailscale ")];
+            if connection.tailscale_peers.is_empty() {
+                peers.push(Span::raw("no matching peers"));
+            }
+            for (host_name, online) in &connection.tailscale_peers {
+                let color = if *online { Color::Green } else { Color::Red };
+                peers.push(Span::styled(
+                    format!("{host_name} "),
+                    Style::default().fg(color),
+                ));
+            }
+            lines.push(Line::from(peers));
+        }
+        None => lines.push(Line::raw("Waiting for connection state")),
+    }
+    let clients = state
+        .foxglove_clients
+        .map_or("unknown".to_owned(), |clients| clients.to_string());
+    lines.push(Line::raw(format!("foxglove clients {clients}")));
+    lines.push(Line::raw(state.foxglove_link.as_str()));
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Connection")),
+        area,
+    );
+}
+
+fn render_gamepad(frame: &mut Frame, area: Rect, state: &DashboardState) {
+    let mut lines = Vec::new();
+    match &state.gamepad {
+        Some(message) => {
+            if message.estop_engaged {
+                lines.push(Line::styled(
+                    "EMERGENCY STOP",
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ));
+            }
+            let mut gamepads: Vec<_> = message.gamepads.iter().collect();
+            gamepads.sort_by_key(|(gamepad_id, _)| **gamepad_id);
+            for (gamepad_id, gamepad) in gamepads {
+                let connected = if gamepad.connected {
+                    ""
+                } else {
+                    " (disconnected)"
+                };
+                lines.push(Line::styled(
+                    format!("{gamepad_id}: {}{connected}", gamepad.name),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ));
+                for (axis, value) in &gamepad.axis_state {
+                    lines.push(Line::raw(format!(
+                        "  {:<14} {value:+.2}",
+                        format!("{axis:?}")
+                    )));
+                }
+                let pressed: Vec<String> = gamepad
+                    .button_down
+                    .iter()
+                    .filter(|(_, pressed)| **pressed)
+                    .map(|(button, _)| format!("{button:?}"))
+                    .collect();
+                if !pressed.is_empty() {
+                    lines.push(Line::raw(format!("  pressed {}", pressed.join(" "))));
+                }
+            }
+            if message.gamepads.is_empty() {
+                lines.push(Line::raw("No gamepads"));
+            }
+        }
+        None => lines.push(Line::raw("Nothing published yet")),
+    }
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(state.gamepad_topic.as_str())),
+        area,
+    );
+}
+
+fn render_topics(frame: &mut Frame, area: Rect, state: &DashboardState) {
+    let rows = state.channels.iter().map(|(topic, channel)| {
+        let age = channel
+            .last_receive_age_seconds
+            .map_or("-".to_owned(), |age| format!("{age:.1}s"));
+        Row::new(vec![
+            topic.clone(),
+            format!("{:.1}", channel.rate_hz),
+            format!("{:.1}", channel.bytes_per_second / 1024.0),
+            age,
+            channel.dropped.to_string(),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+        ],
+    )
+    .header(
+        Row::new(vec!["topic", "Hz", "KiB/s", "age", "dropped"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::bordered().title("Bridged topics"));
+    frame.render_widget(table, area);
+}
+
+/// Keep `dashboard` up to date, `foxglove_port` is where the Foxglove server itself listens
+pub fn start_dashboard_updater(
+    supervisor: &Supervisor,
+    zenoh_session: Arc<Session>,
+    stats: StatsRegistry,
+    dashboard: Dashboard,
+    foxglove_port: u16,
+) {
+    let heartbeat = supervisor.health().heartbeat("dashboard");
+    supervisor.spawn("dashboard", move |shutdown| {
+        run_dashboard_updater(
+            zenoh_session.clone(),
+            stats.clone(),
+            dashboard.clone(),
+            foxglove_port,
+            heartbeat.clone(),
+            shutdown,
+        )
+    });
+}
+
+async fn run_dashboard_updater(
+    zenoh_session: Arc<Session>,
+    stats: StatsRegistry,
+    dashboard: Dashboard,
+    foxglove_port: u16,
+    heartbeat: Heartbeat,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let connection_subscriber = zenoh_session
+        .declare_subscriber(CONNECTION_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let mut tracker = BridgeStatsTracker::default();
+    let mut last_update = Instant::now();
+    let mut interval = tokio::time::interval(DASHBOARD_UPDATE_INTERVAL);
+    loop {
+        tokio::select! {
+            sample = connection_subscriber.recv_async() => {
+                let payload: Vec<u8> = Vec::<u8>::try_from(sample?.value)?;
+                match serde_json::from_slice::<ConnectionMessage>(&payload) {
+                    Ok(connection) => dashboard.update(|state| state.connection = Some(connection)),
+                    Err(err) => debug!("Invalid connection message {err:?}"),
+                }
+            }
+            _ = interval.tick() => {
+                heartbeat.beat();
+                let channels = tracker.update(&stats, last_update.elapsed()).topics;
+                last_update = Instant::now();
+                let foxglove_clients = foxglove_client_count(foxglove_port);
+                let gamepad = stats.gamepad().last_message();
+                dashboard.update(|state| {
+                    state.channels = channels;
+                    state.foxglove_clients = foxglove_clients;
+                    state.gamepad = gamepad;
+                });
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    Ok(())
+}
+
+/// The thread drawing the dashboard, [`TuiHandle::stop`] restores the terminal
+pub struct TuiHandle {
+    stop: CancellationToken,
+    thread: JoinHandle<()>,
+}
+
+impl TuiHandle {
+    pub fn stop(self) {
+        self.stop.cancel();
+        if self.thread.join().is_err() {
+            error!("Dashboard thread panicked");
+        }
+    }
+}
+
+/// Take over the terminal and draw `dashboard` until stopped
+///
+/// Ctrl-C doesn't raise a signal in raw mode so it cancels `quit` instead, as does `q`.
+pub fn start_tui(
+    dashboard: Dashboard,
+    logs: LogBuffer,
+    quit: CancellationToken,
+) -> anyhow::Result<TuiHandle> {
+    let mut terminal = ratatui::try_init()?;
+    let stop = CancellationToken::new();
+    let thread = std::thread::Builder::new()
+        .name(String::from("tui"))
+        .spawn({
+            let stop = stop.clone();
+            move || {
+                let result = run_tui(&mut terminal, &dashboard, &logs, &quit, &stop);
+                ratatui::restore();
+                if let Err(err) = result {
+                    error!("Dashboard stopped {err:?}");
+                    quit.cancel();
+                }
+            }
+        })?;
+    Ok(TuiHandle { stop, thread })
+}
+
+fn run_tui(
+    terminal: &mut ratatui::DefaultTerminal,
+    dashboard: &Dashboard,
+    logs: &LogBuffer,
+    quit: &CancellationToken,
+    stop: &CancellationToken,
+) -> anyhow::Result<()> {
+    while !stop.is_cancelled() {
+        let mut state = dashboard.snapshot();
+        state.logs = logs.lines();
+        terminal.draw(|frame| render(frame, &state))?;
+        if !event::poll(TUI_REDRAW_INTERVAL)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            let ctrl_c =
+                key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if key.kind == KeyEventKind::Press && (ctrl_c || key.code == KeyCode::Char('q')) {
+                quit.cancel();
+            }
+        }
+    }
+    Ok(())
+}
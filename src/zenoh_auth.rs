@@ -0,0 +1,148 @@
+//! Authentication of the zenoh link
+//!
+//! Robots reached through a Tailscale exit node are exposed to more than the tailnet, so
+//! their routers can require a user and password (zenoh `usrpwd`) or TLS client certificates
+//! on `tls/` endpoints. Everything can be set in the `zenoh_auth` section of `--config`,
+//! flags override it field by field.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+use zenoh::config::Config;
+
+use crate::error::ZenohError;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, clap::Args)]
+#[serde(deny_unknown_fields)]
+pub struct ZenohAuth {
+    /// User for zenoh user/password authentication
+    #[arg(long = "zenoh-user", id = "zenoh_user")]
+    pub user: Option<String>,
+
+    /// Password for zenoh user/password authentication
+    #[arg(
+        long = "zenoh-password",
+        id = "zenoh_password",
+        env = "ZENOH_PASSWORD",
+        hide_env_values = true
+    )]
+    pub password: Option<String>,
+
+    /// File with the zenoh password, keeps it out of the command line and config file
+    #[arg(long = "zenoh-password-file", id = "zenoh_password_file")]
+    pub password_file: Option<PathBuf>,
+
+    /// CA that signed the certificates of robots reached on tls/ endpoints
+    #[arg(long = "zenoh-root-ca", id = "zenoh_root_ca")]
+    pub root_ca_certificate: Option<PathBuf>,
+
+    /// Certificate presented to robots that require client certificates, also used for tls/ listen endpoints
+    #[arg(long = "zenoh-certificate", id = "zenoh_certificate")]
+    pub certificate: Option<PathBuf>,
+
+    /// Private key of --zenoh-certificate
+    #[arg(long = "zenoh-private-key", id = "zenoh_private_key")]
+    pub private_key: Option<PathBuf>,
+}
+
+impl ZenohAuth {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let has_password = self.password.is_some() || self.password_file.is_some();
+        if self.password.is_some() && self.password_file.is_some() {
+            anyhow::bail!("Use either a zenoh password or a password file, not both");
+        }
+        if self.user.is_some() != has_password {
+            anyhow::bail!("Zenoh user and password have to be set together");
+        }
+        if self.certificate.is_some() != self.private_key.is_some() {
+            anyhow::bail!("Zenoh certificate and private key have to be set together");
+        }
+        Ok(())
+    }
+
+    /// Add the credentials to `zenoh_config`
+    ///
+    /// With a certificate, peers connecting to our `tls/` listen endpoints have to present
+    /// a certificate signed by the root CA too.
+    pub fn apply(&self, zenoh_config: &mut Config) -> anyhow::Result<()> {
+        self.validate()?;
+        if let Some(user) = &self.user {
+            let password = match &self.password_file {
+                Some(path) => std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?
+                    .trim_end()
+                    .to_owned(),
+                None => self.password.clone().context("Missing zenoh password")?,
+            };
+            insert(zenoh_config, "transport/auth/usrpwd/user", user)?;
+            insert(zenoh_config, "transport/auth/usrpwd/password", &password)?;
+        }
+        if let Some(root_ca) = &self.root_ca_certificate {
+            insert_path(
+                zenoh_config,
+                "transport/link/tls/root_ca_certificate",
+                root_ca,
+            )?;
+        }
+        if let (Some(certificate), Some(private_key)) = (&self.certificate, &self.private_key) {
+            insert_path(
+                zenoh_config,
+                "transport/link/tls/client_certificate",
+                certificate,
+            )?;
+            insert_path(
+                zenoh_config,
+                "transport/link/tls/client_private_key",
+                private_key,
+            )?;
+            insert_path(
+                zenoh_config,
+                "transport/link/tls/server_certificate",
+                certificate,
+            )?;
+            insert_path(
+                zenoh_config,
+                "transport/link/tls/server_private_key",
+                private_key,
+            )?;
+            zenoh_config
+                .insert_json5("transport/link/tls/client_auth", "true")
+                .map_err(ZenohError)?;
+        }
+        Ok(())
+    }
+
+    /// Fill fields that aren't set from `fallback`, for flags over the config file
+    ///
+    /// A password given either way replaces both password settings of `fallback`.
+    pub fn or(self, fallback: ZenohAuth) -> ZenohAuth {
+        let (password, password_file) = if self.password.is_some() || self.password_file.is_some() {
+            (self.password, self.password_file)
+        } else {
+            (fallback.password, fallback.password_file)
+        };
+        ZenohAuth {
+            user: self.user.or(fallback.user),
+            password,
+            password_file,
+            root_ca_certificate: self.root_ca_certificate.or(fallback.root_ca_certificate),
+            certificate: self.certificate.or(fallback.certificate),
+            private_key: self.private_key.or(fallback.private_key),
+        }
+    }
+}
+
+fn insert(zenoh_config: &mut Config, key: &str, value: &str) -> anyhow::Result<()> {
+    zenoh_config
+        .insert_json5(key, &serde_json::to_string(value)?)
+        .map_err(ZenohError)?;
+    Ok(())
+}
+
+fn insert_path(zenoh_config: &mut Config, key: &str, path: &Path) -> anyhow::Result<()> {
+    let path = path
+        .to_str()
+        .with_context(|| format!("{} isn't valid UTF-8", path.display()))?;
+    insert(zenoh_config, key, path)
+}
@@ -0,0 +1,69 @@
+use chrono::{TimeZone, Utc};
+use deck_robot_remote::{
+    annotations::{read_annotation_log, Annotation},
+    foxglove,
+};
+
+#[test]
+fn annotation_is_an_info_log_named_after_the_robot() {
+    let annotation = Annotation {
+        time: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+        note: "robot tripped here".to_owned(),
+        robot: Some("hopper".to_owned()),
+    };
+    let log = annotation.to_log();
+    assert_eq!(log.message, "robot tripped here");
+    assert_eq!(log.name, "operator/hopper");
+    assert_eq!(log.level, foxglove::log::Level::Info as i32);
+    assert_eq!(log.timestamp.unwrap().seconds, annotation.time.timestamp());
+}
+
+#[test]
+fn annotation_robot_is_optional() {
+    let annotation: Annotation =
+        serde_json::from_str(r#"{"time": "2024-05-01T12:00:00Z", "note": "fell over"}"#).unwrap();
+    assert_eq!(annotation.robot, None);
+    assert_eq!(annotation.to_log().name, "operator");
+}
+
+#[test]
+fn annotation_log_is_read_line_by_line() {
+    let path = std::env::temp_dir().join(format!(
+        "deck-robot-remote-annotations-{}.jsonl",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        concat!(
+            r#"{"time": "2024-05-01T12:00:00Z", "note": "first"}"#,
+            "\n\n",
+            r#"{"time": "2024-05-01T12:00:05Z", "note": "second", "robot": "hopper"}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+    let annotations = read_annotation_log(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let notes: Vec<_> = annotations
+        .iter()
+        .map(|annotation| annotation.note.as_str())
+        .collect();
+    assert_eq!(notes, ["first", "second"]);
+}
+
+#[test]
+fn invalid_annotation_line_is_reported() {
+    let path = std::env::temp_dir().join(format!(
+        "deck-robot-remote-invalid-annotations-{}.jsonl",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        "{\"time\": \"2024-05-01T12:00:00Z\", \"note\": \"ok\"}\nnot json\n",
+    )
+    .unwrap();
+    let err = read_annotation_log(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(format!("{err:#}").contains("line 2"));
+}
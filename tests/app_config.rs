@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use deck_robot_remote::config::{add_profiles, load_app_config, load_profiles};
+
+#[test]
+fn example_config_loads() {
+    let config = load_app_config(Path::new("config/app_example.yaml")).unwrap();
+    assert_eq!(config.mode.as_deref(), Some("rover"));
+    assert_eq!(config.sleep_ms, Some(20));
+    assert_eq!(config.connect.unwrap().len(), 1);
+    assert_eq!(config.foxglove_user, None);
+    assert_eq!(config.profiles.len(), 1);
+}
+
+#[test]
+fn inline_profiles_are_added_to_builtin_ones() {
+    let config = load_app_config(Path::new("config/app_example.yaml")).unwrap();
+    let mut profiles = load_profiles(None).unwrap();
+    add_profiles(&mut profiles, config.profiles).unwrap();
+    assert!(profiles.contains_key("rover"));
+    assert!(profiles.contains_key("hopper"));
+}
+
+#[test]
+fn unknown_config_keys_are_rejected() {
+    let path = std::env::temp_dir().join(format!(
+        "deck-robot-remote-config-{}.yaml",
+        std::process::id()
+    ));
+    std::fs::write(&path, "sleep: 20\n").unwrap();
+    let result = load_app_config(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(result.is_err());
+}
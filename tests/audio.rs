@@ -0,0 +1,46 @@
+use deck_robot_remote::audio::{downmix, FrameAssembler, PlaybackBuffer, AUDIO_FRAME_SAMPLES};
+
+#[test]
+fn stereo_is_averaged_to_mono() {
+    assert_eq!(downmix(&[1.0, 0.0, -0.5, -0.5], 2), vec![0.5, -0.5]);
+    assert_eq!(downmix(&[0.25, 0.5], 1), vec![0.25, 0.5]);
+}
+
+#[test]
+fn frames_are_cut_at_20_ms() {
+    let mut frames = FrameAssembler::default();
+    assert!(frames.push(&[0.0; 500]).is_empty());
+    let complete = frames.push(&[0.1; 1500]);
+    assert_eq!(complete.len(), 2);
+    assert!(complete
+        .iter()
+        .all(|frame| frame.len() == AUDIO_FRAME_SAMPLES));
+    assert_eq!(complete[0][499], 0.0);
+    assert_eq!(complete[0][500], 0.1);
+
+    // 80 samples were left over
+    frames.clear();
+    assert!(frames.push(&[0.0; 900]).is_empty());
+}
+
+#[test]
+fn playback_duplicates_mono_and_pads_with_silence() {
+    let playback = PlaybackBuffer::default();
+    playback.push(&[0.5, -0.5]);
+    let mut output = [1.0; 6];
+    playback.fill(&mut output, 2);
+    assert_eq!(output, [0.5, 0.5, -0.5, -0.5, 0.0, 0.0]);
+    assert!(playback.is_empty());
+}
+
+#[test]
+fn playback_drops_oldest_audio_beyond_200_ms() {
+    let playback = PlaybackBuffer::default();
+    playback.push(&[0.0; 9_000]);
+    playback.push(&[1.0; 2_000]);
+    assert_eq!(playback.len(), 9_600);
+    let mut output = vec![0.0; 9_600];
+    playback.fill(&mut output, 1);
+    assert_eq!(output[7_599], 0.0);
+    assert_eq!(output[7_600], 1.0);
+}
@@ -0,0 +1,50 @@
+use deck_robot_remote::{
+    config::{load_profile, AxisCurve},
+    messages::Axis,
+};
+use proptest::prelude::*;
+
+#[test]
+fn deadzone_reports_zero_and_rescales() {
+    let curve = AxisCurve {
+        deadzone: 0.2,
+        ..Default::default()
+    };
+    assert_eq!(curve.apply(0.1), 0.0);
+    assert_eq!(curve.apply(-0.2), 0.0);
+    assert!((curve.apply(0.6) - 0.5).abs() < 1e-6);
+    assert_eq!(curve.apply(1.0), 1.0);
+    assert_eq!(curve.apply(-1.0), -1.0);
+}
+
+#[test]
+fn invert_flips_sign() {
+    let curve = AxisCurve {
+        invert: true,
+        ..Default::default()
+    };
+    assert_eq!(curve.apply(0.5), -0.5);
+}
+
+#[test]
+fn hopper_profile_has_axis_mapping() {
+    let profile = load_profile(None, "hopper").unwrap();
+    assert!(profile.axis_mapping.contains_key(&Axis::LeftStickX));
+}
+
+proptest! {
+    #[test]
+    fn shaped_values_stay_in_range(
+        value in -1.5f32..1.5,
+        deadzone in 0.0f32..0.9,
+        expo in 0.0f32..=1.0,
+        invert in any::<bool>(),
+    ) {
+        let curve = AxisCurve { deadzone, expo, invert };
+        let shaped = curve.apply(value);
+        prop_assert!((-1.0..=1.0).contains(&shaped));
+        if shaped != 0.0 {
+            prop_assert_eq!(shaped.signum() == value.signum(), !invert);
+        }
+    }
+}
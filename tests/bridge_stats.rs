@@ -0,0 +1,50 @@
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use deck_robot_remote::{
+    clock::MockClock,
+    stats::{BridgeStatsTracker, StatsRegistry},
+};
+
+#[test]
+fn rates_are_computed_between_updates() {
+    let stats = StatsRegistry::default();
+    let topic_stats = stats.topic("hopper/camera/image");
+    let mut tracker = BridgeStatsTracker::default();
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let clock = MockClock::new(start);
+
+    let message = tracker.update(&stats, Duration::from_secs(1), &clock);
+    assert_eq!(message.time, DateTime::<Utc>::from(start));
+    let channel = &message.topics["hopper/camera/image"];
+    assert_eq!(channel.rate_hz, 0.0);
+    assert_eq!(channel.last_receive_age_seconds, None);
+
+    for _ in 0..10 {
+        topic_stats.record_received();
+        topic_stats.record_forwarded(1000);
+    }
+    let message = tracker.update(&stats, Duration::from_secs(2), &clock);
+    let channel = &message.topics["hopper/camera/image"];
+    assert_eq!(channel.rate_hz, 5.0);
+    assert_eq!(channel.bytes_per_second, 5000.0);
+    assert_eq!(channel.forwarded, 10);
+    assert!(channel.last_receive_age_seconds.is_some());
+
+    // nothing new since the previous update
+    let message = tracker.update(&stats, Duration::from_secs(1), &clock);
+    assert_eq!(message.topics["hopper/camera/image"].rate_hz, 0.0);
+}
+
+#[test]
+fn subscribed_topics_are_listed_until_the_guard_drops() {
+    let stats = StatsRegistry::default();
+    let camera = stats.topic("hopper/camera/image");
+    stats.topic("hopper/tf");
+    assert!(stats.active_subscriptions().is_empty());
+
+    let subscribed = camera.subscribed();
+    assert_eq!(stats.active_subscriptions(), vec!["hopper/camera/image"]);
+    drop(subscribed);
+    assert!(stats.active_subscriptions().is_empty());
+}
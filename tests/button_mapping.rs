@@ -0,0 +1,61 @@
+use deck_robot_remote::{
+    config::ButtonMapping,
+    gamepad::{remap_buttons, GamepadStatus, InputEvent},
+    messages::{Button, PowerState},
+};
+
+fn nintendo_layout() -> ButtonMapping {
+    serde_yaml::from_str("South: East\nEast: South\n").unwrap()
+}
+
+#[test]
+fn swaps_pressed_buttons() {
+    let event = remap_buttons(
+        InputEvent::ButtonPressed {
+            gamepad_id: 0,
+            button: Button::South,
+        },
+        &nintendo_layout(),
+    );
+    let InputEvent::ButtonPressed { button, .. } = event else {
+        panic!("Unexpected event {event:?}");
+    };
+    assert_eq!(button, Button::East);
+}
+
+#[test]
+fn unmapped_buttons_are_unchanged() {
+    let event = remap_buttons(
+        InputEvent::ButtonReleased {
+            gamepad_id: 0,
+            button: Button::North,
+        },
+        &nintendo_layout(),
+    );
+    let InputEvent::ButtonReleased { button, .. } = event else {
+        panic!("Unexpected event {event:?}");
+    };
+    assert_eq!(button, Button::North);
+}
+
+#[test]
+fn remaps_button_state_in_status() {
+    let event = remap_buttons(
+        InputEvent::Gamepads(vec![GamepadStatus {
+            gamepad_id: 0,
+            name: "pad".to_owned(),
+            connected: true,
+            button_down: vec![(Button::South, true), (Button::East, false)],
+            power_state: PowerState::Unknown,
+            battery_percent: None,
+        }]),
+        &nintendo_layout(),
+    );
+    let InputEvent::Gamepads(statuses) = event else {
+        panic!("Unexpected event {event:?}");
+    };
+    assert_eq!(
+        statuses[0].button_down,
+        vec![(Button::East, true), (Button::South, false)]
+    );
+}
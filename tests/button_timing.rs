@@ -0,0 +1,70 @@
+mod common;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    gamepad::{ButtonTimer, InputEvent},
+    messages::{Button, GamepadMessage},
+};
+
+fn pressed(button: Button) -> InputEvent {
+    InputEvent::ButtonPressed {
+        gamepad_id: 0,
+        button,
+    }
+}
+
+fn released(button: Button) -> InputEvent {
+    InputEvent::ButtonReleased {
+        gamepad_id: 0,
+        button,
+    }
+}
+
+fn at(start: DateTime<Utc>, millis: i64) -> DateTime<Utc> {
+    start + TimeDelta::milliseconds(millis)
+}
+
+#[test]
+fn quick_second_press_is_a_double_press() {
+    let start = Utc::now();
+    let mut timer = ButtonTimer::default();
+    assert_eq!(timer.update(&pressed(Button::South), start), None);
+    timer.update(&released(Button::South), at(start, 80));
+    assert_eq!(
+        timer.update(&pressed(Button::South), at(start, 200)),
+        Some((0, Button::South))
+    );
+    // the third press starts over
+    assert_eq!(timer.update(&pressed(Button::South), at(start, 300)), None);
+}
+
+#[test]
+fn slow_or_different_presses_are_not_double_presses() {
+    let start = Utc::now();
+    let mut timer = ButtonTimer::default();
+    timer.update(&pressed(Button::South), start);
+    assert_eq!(timer.update(&pressed(Button::East), at(start, 100)), None);
+    assert_eq!(timer.update(&pressed(Button::South), at(start, 500)), None);
+}
+
+#[test]
+fn held_buttons_report_their_hold_duration() {
+    let start = Utc::now();
+    let mut timer = ButtonTimer::default();
+    timer.update(&pressed(Button::South), start);
+    timer.update(&pressed(Button::East), at(start, 100));
+    timer.update(&released(Button::East), at(start, 200));
+
+    let mut message = InputMessageBuilder::default()
+        .gamepad(0, GamepadMessage::default())
+        .build();
+    timer.apply_hold_durations(&mut message, at(start, 750));
+    let hold = &message.gamepads[&0].button_hold_ms;
+    assert_eq!(hold.get(&Button::South), Some(&750));
+    assert!(!hold.contains_key(&Button::East));
+
+    timer.clear();
+    timer.apply_hold_durations(&mut message, at(start, 800));
+    assert!(message.gamepads[&0].button_hold_ms.is_empty());
+}
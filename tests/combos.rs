@@ -0,0 +1,115 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    config::{load_profiles, ButtonCombo, ButtonCombos},
+    gamepad::ComboDetector,
+    messages::{Button, GamepadMessage, InputMessage},
+};
+
+fn input_message(held: &[Button]) -> InputMessage {
+    let gamepad = GamepadMessage {
+        name: "Steam Deck".to_owned(),
+        connected: true,
+        button_down: held.iter().map(|button| (*button, true)).collect(),
+        ..Default::default()
+    };
+    InputMessageBuilder::default().gamepad(0, gamepad).build()
+}
+
+fn detector(hold_ms: u64) -> ComboDetector {
+    ComboDetector::new(ButtonCombos::from([(
+        "dock".to_owned(),
+        ButtonCombo {
+            buttons: vec![Button::Select, Button::Start],
+            hold_ms,
+        },
+    )]))
+}
+
+#[test]
+fn combo_fires_once_per_press() {
+    let mut combos = detector(0);
+    let now = Instant::now();
+    assert!(combos
+        .update(&input_message(&[Button::Select]), now)
+        .is_empty());
+    assert_eq!(
+        combos.update(&input_message(&[Button::Select, Button::Start]), now),
+        vec![(0, "dock".to_owned())]
+    );
+    // still held
+    assert!(combos
+        .update(&input_message(&[Button::Select, Button::Start]), now)
+        .is_empty());
+
+    assert!(combos
+        .update(&input_message(&[Button::Start]), now)
+        .is_empty());
+    assert_eq!(
+        combos
+            .update(&input_message(&[Button::Select, Button::Start]), now)
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn combo_with_hold_time_fires_after_holding() {
+    let mut combos = detector(2000);
+    let held = input_message(&[Button::Select, Button::Start]);
+    let start = Instant::now();
+    assert!(combos.update(&held, start).is_empty());
+    assert!(combos
+        .update(&held, start + Duration::from_millis(1500))
+        .is_empty());
+    assert_eq!(
+        combos.update(&held, start + Duration::from_millis(2000)),
+        vec![(0, "dock".to_owned())]
+    );
+}
+
+#[test]
+fn releasing_early_restarts_the_hold() {
+    let mut combos = detector(2000);
+    let held = input_message(&[Button::Select, Button::Start]);
+    let start = Instant::now();
+    combos.update(&held, start);
+    combos.update(
+        &input_message(&[Button::Select]),
+        start + Duration::from_millis(1000),
+    );
+    assert!(combos
+        .update(&held, start + Duration::from_millis(2500))
+        .is_empty());
+    assert_eq!(
+        combos
+            .update(&held, start + Duration::from_millis(4500))
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn combo_without_buttons_is_rejected() {
+    let profile_dir =
+        std::env::temp_dir().join(format!("deck-robot-remote-combos-{}", std::process::id()));
+    std::fs::create_dir_all(&profile_dir).unwrap();
+    std::fs::write(
+        profile_dir.join("tank.yaml"),
+        r#"
+name: tank
+foxglove_layout_id: "layout"
+tailscale_host_filter: tank
+combos:
+  dock:
+    buttons: []
+"#,
+    )
+    .unwrap();
+    let result = load_profiles(Some(&profile_dir));
+    std::fs::remove_dir_all(&profile_dir).unwrap();
+    assert!(result.is_err());
+}
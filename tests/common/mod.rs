@@ -0,0 +1,414 @@
+#![allow(dead_code)]
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use deck_robot_remote::{
+    error::ZenohError,
+    gamepad::{InputBackend, InputEvent},
+    messages::{GamepadMessage, InputMessage},
+};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio::{net::TcpStream, sync::mpsc};
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, protocol::CloseFrame, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use tokio_util::sync::CancellationToken;
+use zenoh::prelude::r#async::*;
+
+pub const TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Zenoh session that doesn't scout or listen so tests stay inside the process
+pub async fn open_local_session() -> anyhow::Result<Arc<Session>> {
+    let mut config = Config::default();
+    config
+        .scouting
+        .multicast
+        .set_enabled(Some(false))
+        .map_err(|_| anyhow::anyhow!("Failed to disable multicast scouting"))?;
+    let session = zenoh::open(config).res().await.map_err(ZenohError)?;
+    Ok(Arc::new(session))
+}
+
+/// Localhost address with a port that was free a moment ago
+pub fn free_local_address() -> anyhow::Result<SocketAddr> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?)
+}
+
+/// `InputMessage` as the gamepad reader publishes it, stamped now with no gamepads
+#[derive(Debug)]
+pub struct InputMessageBuilder {
+    message: InputMessage,
+}
+
+impl Default for InputMessageBuilder {
+    fn default() -> Self {
+        Self {
+            message: InputMessage {
+                gamepads: HashMap::new(),
+                time: Utc::now(),
+                button_counter_policy: Default::default(),
+                estop_engaged: false,
+            },
+        }
+    }
+}
+
+impl InputMessageBuilder {
+    pub fn gamepad(mut self, id: usize, gamepad: GamepadMessage) -> Self {
+        self.message.gamepads.insert(id, gamepad);
+        self
+    }
+
+    pub fn gamepads(mut self, gamepads: impl IntoIterator<Item = (usize, GamepadMessage)>) -> Self {
+        self.message.gamepads.extend(gamepads);
+        self
+    }
+
+    pub fn time(mut self, time: DateTime<Utc>) -> Self {
+        self.message.time = time;
+        self
+    }
+
+    pub fn estop_engaged(mut self, estop_engaged: bool) -> Self {
+        self.message.estop_engaged = estop_engaged;
+        self
+    }
+
+    pub fn build(self) -> InputMessage {
+        self.message
+    }
+}
+
+/// Input backend that replays a fixed list of events every time it's started
+#[derive(Debug, Clone)]
+pub struct ScriptedInput {
+    events: Vec<InputEvent>,
+}
+
+impl ScriptedInput {
+    pub fn new(events: Vec<InputEvent>) -> Self {
+        Self { events }
+    }
+}
+
+impl InputBackend for ScriptedInput {
+    fn start(
+        &self,
+        input_sender: mpsc::Sender<InputEvent>,
+        _status_interval: Duration,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            for event in events {
+                if input_sender.send(event).await.is_err() {
+                    return;
+                }
+            }
+            // keep the sender alive, the reader treats a closed channel as a failed backend
+            shutdown.cancelled().await;
+        });
+        Ok(())
+    }
+}
+
+/// Input backend that only reports gamepad status, like gilrs with an idle gamepad
+#[derive(Debug, Clone, Default)]
+pub struct IdleInput;
+
+impl InputBackend for IdleInput {
+    fn start(
+        &self,
+        input_sender: mpsc::Sender<InputEvent>,
+        status_interval: Duration,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        tokio::spawn(async move {
+            let mut status = tokio::time::interval(status_interval);
+            loop {
+                tokio::select! {
+                    _ = status.tick() => {
+                        if input_sender.send(InputEvent::Gamepads(vec![])).await.is_err() {
+                            return;
+                        }
+                    }
+                    _ = shutdown.cancelled() => return,
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Minimal client for the foxglove websocket protocol
+pub struct FoxgloveTestClient {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_subscription_id: u32,
+}
+
+impl FoxgloveTestClient {
+    /// Connect, retrying until the server accepts connections
+    pub async fn connect(address: SocketAddr) -> anyhow::Result<Self> {
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            loop {
+                let mut request = format!("ws://{address}").into_client_request()?;
+                request.headers_mut().insert(
+                    "Sec-WebSocket-Protocol",
+                    HeaderValue::from_static("foxglove.websocket.v1"),
+                );
+                match tokio_tungstenite::connect_async(request).await {
+                    Ok((socket, _)) => {
+                        return Ok(Self {
+                            socket,
+                            next_subscription_id: 1,
+                        })
+                    }
+                    Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+                }
+            }
+        })
+        .await
+        .context("Timed out connecting to foxglove server")?
+    }
+
+    /// Wait for the channel advertisement of `topic` and return its channel id
+    pub async fn wait_for_channel(&mut self, topic: &str) -> anyhow::Result<u64> {
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            loop {
+                let message = self
+                    .socket
+                    .next()
+                    .await
+                    .context("Foxglove connection closed")??;
+                let Message::Text(text) = message else {
+                    continue;
+                };
+                let message: serde_json::Value = serde_json::from_str(&text)?;
+                if message["op"] != "advertise" {
+                    continue;
+                }
+                let channels = message["channels"].as_array().cloned().unwrap_or_default();
+                if let Some(channel) = channels.iter().find(|channel| channel["topic"] == topic) {
+                    return channel["id"].as_u64().context("Channel without id");
+                }
+            }
+        })
+        .await
+        .context("Timed out waiting for channel advertisement")?
+    }
+
+    /// Subscribe to a channel and return the subscription id
+    pub async fn subscribe(&mut self, channel_id: u64) -> anyhow::Result<u32> {
+        let subscription_id = self.next_subscription_id;
+        self.next_subscription_id += 1;
+        let subscribe = json!({
+            "op": "subscribe",
+            "subscriptions": [{ "id": subscription_id, "channelId": channel_id }],
+        });
+        self.socket
+            .send(Message::Text(subscribe.to_string()))
+            .await?;
+        Ok(subscription_id)
+    }
+
+    /// Wait for the next message data for `subscription_id` and return its payload
+    pub async fn next_message_data(&mut self, subscription_id: u32) -> anyhow::Result<Vec<u8>> {
+        loop {
+            let message = self
+                .socket
+                .next()
+                .await
+                .context("Foxglove connection closed")??;
+            let Message::Binary(data) = message else {
+                continue;
+            };
+            // opcode, subscription id, timestamp, payload
+            if data.len() < 13 || data[0] != 0x01 {
+                continue;
+            }
+            let id = u32::from_le_bytes(data[1..5].try_into()?);
+            if id == subscription_id {
+                return Ok(data[13..].to_vec());
+            }
+        }
+    }
+
+    /// Wait for the next JSON message with `op` and return it
+    pub async fn wait_for_op(&mut self, op: &str) -> anyhow::Result<serde_json::Value> {
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            loop {
+                let message = self
+                    .socket
+                    .next()
+                    .await
+                    .context("Foxglove connection closed")??;
+                let Message::Text(text) = message else {
+                    continue;
+                };
+                let message: serde_json::Value = serde_json::from_str(&text)?;
+                if message["op"] == op {
+                    return Ok(message);
+                }
+            }
+        })
+        .await
+        .with_context(|| format!("Timed out waiting for {op}"))?
+    }
+
+    pub async fn send_json(&mut self, message: serde_json::Value) -> anyhow::Result<()> {
+        self.socket.send(Message::Text(message.to_string())).await?;
+        Ok(())
+    }
+
+    /// Advertise a JSON channel to publish on
+    pub async fn advertise(&mut self, channel_id: u32, topic: &str) -> anyhow::Result<()> {
+        self.send_json(json!({
+            "op": "advertise",
+            "channels": [{
+                "id": channel_id,
+                "topic": topic,
+                "encoding": "json",
+                "schemaName": "TestMessage",
+            }],
+        }))
+        .await
+    }
+
+    /// Publish a message on a channel advertised by the client
+    pub async fn publish(&mut self, channel_id: u32, payload: &[u8]) -> anyhow::Result<()> {
+        let mut data = vec![0x01];
+        data.extend_from_slice(&channel_id.to_le_bytes());
+        data.extend_from_slice(payload);
+        self.socket.send(Message::Binary(data)).await?;
+        Ok(())
+    }
+
+    /// Call a service with a JSON request
+    pub async fn call_service(
+        &mut self,
+        service_id: u32,
+        call_id: u32,
+        request: &[u8],
+    ) -> anyhow::Result<()> {
+        let encoding = b"json";
+        let mut data = vec![0x02];
+        data.extend_from_slice(&service_id.to_le_bytes());
+        data.extend_from_slice(&call_id.to_le_bytes());
+        data.extend_from_slice(&(encoding.len() as u32).to_le_bytes());
+        data.extend_from_slice(encoding);
+        data.extend_from_slice(request);
+        self.socket.send(Message::Binary(data)).await?;
+        Ok(())
+    }
+
+    /// Wait for the next service call response and return its call id and payload
+    pub async fn next_service_response(&mut self) -> anyhow::Result<(u32, Vec<u8>)> {
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            loop {
+                let message = self
+                    .socket
+                    .next()
+                    .await
+                    .context("Foxglove connection closed")??;
+                let Message::Binary(data) = message else {
+                    continue;
+                };
+                // opcode, service id, call id, encoding length, encoding, payload
+                if data.len() < 13 || data[0] != 0x03 {
+                    continue;
+                }
+                let call_id = u32::from_le_bytes(data[5..9].try_into()?);
+                let encoding_length = u32::from_le_bytes(data[9..13].try_into()?) as usize;
+                return Ok((call_id, data[13 + encoding_length..].to_vec()));
+            }
+        })
+        .await
+        .context("Timed out waiting for service call response")?
+    }
+
+    /// Wait for the reply to a `fetchAsset` and return its request id and the asset or error
+    pub async fn next_asset_response(&mut self) -> anyhow::Result<(u32, Result<Vec<u8>, String>)> {
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            loop {
+                let message = self
+                    .socket
+                    .next()
+                    .await
+                    .context("Foxglove connection closed")??;
+                let Message::Binary(data) = message else {
+                    continue;
+                };
+                // opcode, request id, status, error length, error, asset
+                if data.len() < 10 || data[0] != 0x04 {
+                    continue;
+                }
+                let request_id = u32::from_le_bytes(data[1..5].try_into()?);
+                let error_length = u32::from_le_bytes(data[6..10].try_into()?) as usize;
+                let asset = if data[5] == 0 {
+                    Ok(data[10 + error_length..].to_vec())
+                } else {
+                    Err(String::from_utf8(data[10..10 + error_length].to_vec())?)
+                };
+                return Ok((request_id, asset));
+            }
+        })
+        .await
+        .context("Timed out waiting for asset response")?
+    }
+
+    /// Wait for the next server time in nanoseconds
+    pub async fn next_time(&mut self) -> anyhow::Result<u64> {
+        loop {
+            let message = self
+                .socket
+                .next()
+                .await
+                .context("Foxglove connection closed")??;
+            let Message::Binary(data) = message else {
+                continue;
+            };
+            if data.len() == 9 && data[0] == 0x02 {
+                return Ok(u64::from_le_bytes(data[1..9].try_into()?));
+            }
+        }
+    }
+
+    /// Wait for the server to close the connection
+    ///
+    /// Returns the channel ids unadvertised before the close and the close frame.
+    pub async fn wait_for_close(
+        &mut self,
+    ) -> anyhow::Result<(Vec<u64>, Option<CloseFrame<'static>>)> {
+        tokio::time::timeout(TEST_TIMEOUT, async {
+            let mut unadvertised = vec![];
+            loop {
+                let message = self
+                    .socket
+                    .next()
+                    .await
+                    .context("Foxglove connection closed without close frame")??;
+                match message {
+                    Message::Text(text) => {
+                        let message: serde_json::Value = serde_json::from_str(&text)?;
+                        if message["op"] == "unadvertise" {
+                            let channel_ids = message["channelIds"]
+                                .as_array()
+                                .cloned()
+                                .unwrap_or_default();
+                            unadvertised.extend(channel_ids.iter().filter_map(|id| id.as_u64()));
+                        }
+                    }
+                    Message::Close(frame) => return Ok((unadvertised, frame)),
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .context("Timed out waiting for the server to close")?
+    }
+}
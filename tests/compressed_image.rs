@@ -0,0 +1,66 @@
+use deck_robot_remote::{
+    foxglove,
+    foxglove_server::{compressed_image_payload, FoxgloveServerConfiguration, ImageFormat},
+};
+use prost::Message;
+use zenoh::prelude::r#async::*;
+
+const JPEG_HEADER: [u8; 4] = [0xff, 0xd8, 0xff, 0xe0];
+
+fn sample(payload: &[u8]) -> Sample {
+    Sample::new(
+        KeyExpr::try_from("hopper/camera/jpeg").unwrap(),
+        Value::from(payload.to_vec()),
+    )
+}
+
+#[test]
+fn jpeg_is_wrapped_in_compressed_image() {
+    let payload =
+        compressed_image_payload(sample(&JPEG_HEADER), ImageFormat::Jpeg, "camera_optical")
+            .unwrap();
+    let image = foxglove::CompressedImage::decode(payload.as_slice()).unwrap();
+    assert_eq!(image.data, JPEG_HEADER);
+    assert_eq!(image.format, "jpeg");
+    assert_eq!(image.frame_id, "camera_optical");
+    assert!(image.timestamp.is_some());
+}
+
+#[test]
+fn h264_is_wrapped_in_compressed_video() {
+    let nal_unit = [0x00, 0x00, 0x00, 0x01, 0x67];
+    let payload = compressed_image_payload(sample(&nal_unit), ImageFormat::H264, "camera").unwrap();
+    let video = foxglove::CompressedVideo::decode(payload.as_slice()).unwrap();
+    assert_eq!(video.data, nal_unit);
+    assert_eq!(video.format, "h264");
+    assert_eq!(video.frame_id, "camera");
+}
+
+#[test]
+fn subscriptions_are_bridged_topics() {
+    let config: FoxgloveServerConfiguration = serde_yaml::from_str(
+        r#"
+compressed_image_subscriptions:
+  - topic: hopper/camera/jpeg
+    format: jpeg
+    frame_id: camera_optical
+  - topic: hopper/camera/h265
+    format: h265
+"#,
+    )
+    .unwrap();
+    let subscriptions = &config.compressed_image_subscriptions;
+    assert_eq!(
+        subscriptions[0].format.proto_type(),
+        "foxglove.CompressedImage"
+    );
+    assert_eq!(
+        subscriptions[1].format.proto_type(),
+        "foxglove.CompressedVideo"
+    );
+    assert_eq!(subscriptions[1].frame_id, "");
+    assert_eq!(
+        config.topics(),
+        vec!["hopper/camera/jpeg", "hopper/camera/h265"]
+    );
+}
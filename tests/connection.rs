@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use deck_robot_remote::{
+    connection::{connection_message, ZenohPeers},
+    robot_registry::RobotTarget,
+    tailscale::{PeerMatch, TailscaleStatus},
+};
+
+const STATUS: &[u8] = include_bytes!("fixtures/tailscale/status.json");
+
+fn robot(tailscale_host_filter: &str) -> RobotTarget {
+    RobotTarget {
+        name: "hopper".to_owned(),
+        peer_rules: vec![PeerMatch::Contains(tailscale_host_filter.to_owned())],
+        foxglove_layout_id: "layout".to_owned(),
+        foxglove_studio_url: None,
+        topic_prefix: None,
+        failover: None,
+    }
+}
+
+fn zenoh_peers() -> ZenohPeers {
+    ZenohPeers {
+        peers: vec!["a1b2c3".to_owned()],
+        routers: vec![],
+    }
+}
+
+#[test]
+fn recent_telemetry_over_zenoh_is_connected() {
+    let status = TailscaleStatus::parse(STATUS).unwrap();
+    let message = connection_message(
+        &robot("hopper"),
+        zenoh_peers(),
+        &status,
+        Some(Duration::from_millis(200)),
+        Utc::now(),
+    );
+    assert!(message.connected);
+    assert_eq!(message.robot, "hopper");
+    // only peers matching the host filter are listed
+    assert_eq!(message.tailscale_peers.len(), 1);
+    assert_eq!(message.tailscale_peers.get("hopper"), Some(&false));
+}
+
+#[test]
+fn stale_or_missing_telemetry_is_disconnected() {
+    let status = TailscaleStatus::parse(STATUS).unwrap();
+    let stale = connection_message(
+        &robot("hamilton"),
+        zenoh_peers(),
+        &status,
+        Some(Duration::from_secs(10)),
+        Utc::now(),
+    );
+    assert!(!stale.connected);
+    assert_eq!(stale.tailscale_peers.get("hamilton"), Some(&true));
+
+    let missing = connection_message(&robot("hamilton"), zenoh_peers(), &status, None, Utc::now());
+    assert!(!missing.connected);
+    assert_eq!(missing.last_telemetry_age_seconds, None);
+}
+
+#[test]
+fn telemetry_without_zenoh_peers_is_disconnected() {
+    let message = connection_message(
+        &robot("hopper"),
+        ZenohPeers::default(),
+        &TailscaleStatus::default(),
+        Some(Duration::from_millis(200)),
+        Utc::now(),
+    );
+    assert!(!message.connected);
+    assert!(message.tailscale_peers.is_empty());
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::{
+    collections::BTreeMap,
+    future::IntoFuture,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+use common::{free_local_address, open_local_session, TEST_TIMEOUT};
+use deck_robot_remote::{
+    clock::MockClock,
+    config::load_profiles,
+    control_panel::{ControlPanel, ControlPanelStatus, EstopRequest},
+    error::ZenohError,
+    gamepad::{EstopLatch, ESTOP_TOPIC},
+    messages::EstopMessage,
+    robot_registry::RobotRegistry,
+};
+use zenoh::prelude::r#async::*;
+
+#[tokio::test]
+async fn estop_is_engaged_and_rearmed_over_http() -> anyhow::Result<()> {
+    let zenoh_session = open_local_session().await?;
+    let estop_subscriber = zenoh_session
+        .declare_subscriber(ESTOP_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let registry = RobotRegistry::new(&load_profiles(None)?, "hamilton")?;
+    let estop = EstopLatch::default();
+    let links = BTreeMap::from([(
+        "hamilton".to_owned(),
+        "https://app.foxglove.dev/view".to_owned(),
+    )]);
+    let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let clock = Arc::new(MockClock::new(now));
+    let panel = ControlPanel::new(zenoh_session.clone(), registry, estop.clone(), links, clock);
+    let address = free_local_address()?;
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    tokio::spawn(axum::serve(listener, panel.router()).into_future());
+
+    let client = reqwest::Client::new();
+    let status: ControlPanelStatus = client
+        .get(format!("http://{address}/api/status"))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(status.active_robot, "hamilton");
+    assert!(!status.estop_engaged);
+    assert!(status.connection.is_none());
+    assert_eq!(
+        status.foxglove_link.as_deref(),
+        Some("https://app.foxglove.dev/view")
+    );
+
+    for engaged in [true, false] {
+        let status: ControlPanelStatus = client
+            .post(format!("http://{address}/api/estop"))
+            .json(&EstopRequest { engaged })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        assert_eq!(status.estop_engaged, engaged);
+        assert_eq!(estop.is_engaged(), engaged);
+
+        let sample = tokio::time::timeout(TEST_TIMEOUT, estop_subscriber.recv_async()).await??;
+        let payload: Vec<u8> = sample.value.try_into()?;
+        let message: EstopMessage = serde_json::from_slice(&payload)?;
+        assert_eq!(message.engaged, engaged);
+        assert_eq!(message.time, DateTime::<Utc>::from(now));
+    }
+
+    let page = client
+        .get(format!("http://{address}/"))
+        .send()
+        .await?
+        .text()
+        .await?;
+    assert!(page.contains("/api/estop"));
+    Ok(())
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    deadman::DeadmanConfig,
+    messages::{Axis, Button, GamepadMessage, InputMessage},
+};
+
+fn input_message(enable_held: bool) -> InputMessage {
+    let gamepad = GamepadMessage {
+        button_down: [(Button::LeftTrigger2, enable_held)].into_iter().collect(),
+        axis_state: [(Axis::LeftStickY, 0.8), (Axis::RightZ, 0.5)]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+    InputMessageBuilder::default().gamepad(0, gamepad).build()
+}
+
+fn deadman(axes: Vec<Axis>) -> DeadmanConfig {
+    DeadmanConfig {
+        button: Button::LeftTrigger2,
+        axes,
+    }
+}
+
+#[test]
+fn axes_are_forwarded_while_the_button_is_held() {
+    let live = input_message(true);
+    let mut message = live.clone();
+    deadman(vec![]).apply(&live, &mut message);
+    assert_eq!(message, live);
+}
+
+#[test]
+fn released_button_zeroes_motion_axes_and_disables_the_gamepad() {
+    let live = input_message(false);
+    let mut message = live.clone();
+    deadman(vec![Axis::LeftStickY]).apply(&live, &mut message);
+    let gamepad = &message.gamepads[&0];
+    assert!(gamepad.disabled);
+    assert_eq!(gamepad.axis_state[&Axis::LeftStickY], 0.0);
+    assert_eq!(gamepad.axis_state[&Axis::RightZ], 0.5);
+
+    let mut message = live.clone();
+    deadman(vec![]).apply(&live, &mut message);
+    assert!(message.gamepads[&0]
+        .axis_state
+        .values()
+        .all(|value| *value == 0.0));
+}
+
+#[test]
+fn only_live_input_holds_the_switch() {
+    // a replayed message recorded with the button held
+    let mut message = input_message(true);
+    deadman(vec![]).apply(&input_message(false), &mut message);
+    assert!(message.gamepads[&0].disabled);
+    assert_eq!(message.gamepads[&0].axis_state[&Axis::LeftStickY], 0.0);
+}
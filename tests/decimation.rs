@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+use deck_robot_remote::{
+    decimation::{DecimationConfig, Decimator},
+    foxglove::{self, log::Level},
+    DESCRIPTOR_POOL,
+};
+use prost::Message;
+
+/// `foxglove.Log` messages with `level` standing in for a keyframe flag
+fn decimator(max_rate_hz: f64, keyframe_field: Option<&str>) -> anyhow::Result<Decimator> {
+    let descriptor = DESCRIPTOR_POOL.get_message_by_name("foxglove.Log").unwrap();
+    let config = DecimationConfig {
+        max_rate_hz,
+        keyframe_field: keyframe_field.map(str::to_owned),
+    };
+    Decimator::new(&config, &descriptor)
+}
+
+fn frame(keyframe: bool) -> Vec<u8> {
+    let level = if keyframe {
+        Level::Info
+    } else {
+        Level::Unknown
+    };
+    foxglove::Log {
+        level: level as i32,
+        ..Default::default()
+    }
+    .encode_to_vec()
+}
+
+/// Which of `frames` received every `interval` are forwarded
+fn kept(decimator: &mut Decimator, frames: &[bool], interval: Duration) -> Vec<bool> {
+    let start = Instant::now();
+    frames
+        .iter()
+        .enumerate()
+        .map(|(index, keyframe)| decimator.keep(&frame(*keyframe), start + interval * index as u32))
+        .collect()
+}
+
+#[test]
+fn messages_over_the_rate_are_dropped() {
+    let mut decimator = decimator(10.0, None).unwrap();
+    let kept = kept(&mut decimator, &[false; 7], Duration::from_millis(50));
+    assert_eq!(kept, [true, false, true, false, true, false, true]);
+}
+
+#[test]
+fn a_dropped_delta_drops_the_rest_of_its_group() {
+    let mut decimator = decimator(10.0, Some("level")).unwrap();
+    let frames = [true, false, false, false, true, false];
+    let kept = kept(&mut decimator, &frames, Duration::from_millis(50));
+    assert_eq!(kept, [true, false, false, false, true, false]);
+}
+
+#[test]
+fn groups_under_the_rate_are_forwarded_whole() {
+    let mut decimator = decimator(10.0, Some("level")).unwrap();
+    let frames = [true, false, false, true, false];
+    let kept = kept(&mut decimator, &frames, Duration::from_millis(100));
+    assert_eq!(kept, [true; 5]);
+}
+
+#[test]
+fn keyframes_are_forwarded_over_the_rate() {
+    let mut decimator = decimator(1.0, Some("level")).unwrap();
+    let kept = kept(&mut decimator, &[true; 4], Duration::from_millis(10));
+    assert_eq!(kept, [true; 4]);
+}
+
+#[test]
+fn invalid_decimation_fails() {
+    assert!(decimator(0.0, None).is_err());
+    assert!(decimator(10.0, Some("missing")).is_err());
+    // messages and lists can't mark keyframes
+    assert!(decimator(10.0, Some("timestamp")).is_err());
+}
@@ -0,0 +1,121 @@
+use deck_robot_remote::{
+    connection::ZenohPeers,
+    doctor::{
+        foxglove_port_check, tailscale_checks, topic_checks, zenoh_link_check, CheckStatus,
+        DoctorCheck, DoctorReport,
+    },
+    error::DiscoveryError,
+    foxglove_server::FoxgloveHost,
+    robot_registry::RobotTarget,
+    self_test::{TopicCheck, TopicStatus},
+    tailscale::{PeerMatch, TailscaleStatus},
+};
+
+const STATUS: &[u8] = include_bytes!("fixtures/tailscale/status.json");
+
+fn robot(name: &str) -> RobotTarget {
+    RobotTarget {
+        name: name.to_owned(),
+        peer_rules: vec![PeerMatch::Contains(name.to_owned())],
+        foxglove_layout_id: "layout".to_owned(),
+        foxglove_studio_url: None,
+        topic_prefix: None,
+        failover: None,
+    }
+}
+
+fn statuses(checks: &[DoctorCheck]) -> Vec<(&str, CheckStatus)> {
+    checks
+        .iter()
+        .map(|check| (check.name.as_str(), check.status))
+        .collect()
+}
+
+#[test]
+fn offline_and_missing_robot_peers_fail() {
+    let status = TailscaleStatus::parse(STATUS);
+    let checks = tailscale_checks(
+        &status,
+        &[robot("hamilton"), robot("hopper"), robot("rover")],
+    );
+    assert_eq!(
+        statuses(&checks),
+        [
+            ("tailscale", CheckStatus::Ok),
+            ("robot peer hamilton", CheckStatus::Ok),
+            ("robot peer hopper", CheckStatus::Failed),
+            ("robot peer rover", CheckStatus::Failed),
+        ]
+    );
+    assert!(checks[0].detail.contains("100.101.102.103"));
+    assert!(checks[2].detail.contains("offline"));
+}
+
+#[test]
+fn unreachable_tailscale_skips_peer_checks() {
+    let status = Err(DiscoveryError::Unavailable(
+        "tailscaled isn't running".to_owned(),
+    ));
+    let checks = tailscale_checks(&status, &[robot("hopper")]);
+    assert_eq!(
+        statuses(&checks),
+        [
+            ("tailscale", CheckStatus::Failed),
+            ("robot peer hopper", CheckStatus::Skipped),
+        ]
+    );
+    assert!(checks[0].detail.contains("tailscale status"));
+}
+
+#[test]
+fn missing_topics_and_zenoh_link_fail() {
+    let checks = topic_checks(&[
+        TopicCheck {
+            topic: "hopper/odometry".to_owned(),
+            status: TopicStatus::Publisher,
+        },
+        TopicCheck {
+            topic: "hopper/logs".to_owned(),
+            status: TopicStatus::Missing,
+        },
+    ]);
+    assert_eq!(
+        statuses(&checks),
+        [
+            ("topic hopper/odometry", CheckStatus::Ok),
+            ("topic hopper/logs", CheckStatus::Failed),
+        ]
+    );
+    assert_eq!(topic_checks(&[])[0].status, CheckStatus::Skipped);
+
+    let link = zenoh_link_check(&ZenohPeers::default());
+    assert_eq!(link.status, CheckStatus::Failed);
+}
+
+#[test]
+fn report_fails_with_any_failed_check() {
+    let mut report = DoctorReport::default();
+    report.push(DoctorCheck::new(
+        "gamepad",
+        CheckStatus::Skipped,
+        "Input isn't gilrs",
+    ));
+    assert!(report.is_ok());
+    report.push(DoctorCheck::new("zenoh link", CheckStatus::Failed, ""));
+    assert!(!report.is_ok());
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["checks"][1]["status"], "failed");
+}
+
+#[tokio::test]
+async fn taken_foxglove_port_fails() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = FoxgloveHost::Address(listener.local_addr().unwrap());
+    let check = foxglove_port_check(&host, &TailscaleStatus::default()).await;
+    assert_eq!(check.status, CheckStatus::Failed);
+
+    drop(listener);
+    let check = foxglove_port_check(&host, &TailscaleStatus::default()).await;
+    assert_eq!(check.status, CheckStatus::Ok);
+}
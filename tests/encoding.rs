@@ -0,0 +1,59 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    gamepad::{serialize_cbor, serialize_json, serialize_msgpack},
+    messages::{Axis, Button, GamepadMessage, InputMessage, PowerState},
+};
+use zenoh::prelude::Encoding;
+
+fn input_message() -> InputMessage {
+    let gamepad = GamepadMessage {
+        name: "Steam Deck".to_owned(),
+        connected: true,
+        last_event_time: Utc::now(),
+        button_down_event_counter: BTreeMap::from([(Button::South, 3)]),
+        button_up_event_counter: BTreeMap::from([(Button::South, 2)]),
+        button_down: BTreeMap::from([(Button::South, true)]),
+        axis_state: BTreeMap::from([(Axis::LeftStickX, -0.25)]),
+        disabled: false,
+        button_double_press_counter: BTreeMap::from([(Button::South, 1)]),
+        button_hold_ms: BTreeMap::from([(Button::South, 250)]),
+        power_state: PowerState::Discharging,
+        battery_percent: Some(80),
+        mode: Some("turbo".to_owned()),
+    };
+    InputMessageBuilder::default().gamepad(0, gamepad).build()
+}
+
+#[test]
+fn cbor_round_trips_and_is_smaller_than_json() {
+    let message = input_message();
+    let mut buffer = Vec::new();
+    let json_size = Vec::<u8>::try_from(serialize_json(&mut buffer, &message).unwrap())
+        .unwrap()
+        .len();
+
+    let value = serialize_cbor(&mut buffer, &message).unwrap();
+    assert_eq!(value.encoding, Encoding::from("application/cbor"));
+    let payload = Vec::<u8>::try_from(value).unwrap();
+    assert!(payload.len() < json_size);
+    let decoded: InputMessage = ciborium::from_reader(payload.as_slice()).unwrap();
+    assert_eq!(decoded, message);
+}
+
+#[test]
+fn msgpack_round_trips_with_named_fields() {
+    let message = input_message();
+    let mut buffer = Vec::new();
+    let value = serialize_msgpack(&mut buffer, &message).unwrap();
+    assert_eq!(value.encoding, Encoding::from("application/msgpack"));
+    let payload = Vec::<u8>::try_from(value).unwrap();
+    let decoded: InputMessage = rmp_serde::from_slice(&payload).unwrap();
+    assert_eq!(decoded, message);
+    // field names are kept so dynamic decoders see the same keys as in JSON
+    assert!(payload.windows(8).any(|window| window == b"gamepads"));
+}
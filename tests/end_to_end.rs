@@ -0,0 +1,1243 @@
+mod common;
+
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use common::{
+    free_local_address, open_local_session, FoxgloveTestClient, IdleInput, ScriptedInput,
+    TEST_TIMEOUT,
+};
+use deck_robot_remote::{
+    clock::{MockClock, SharedClock},
+    error::ZenohError,
+    foxglove_gateway::{ClientPublishTopic, FoxgloveService},
+    foxglove_server::{
+        start_foxglove_bridge, FoxgloveServerConfiguration, JsonSubscription, ProtobufSubscription,
+        DEFAULT_QUEUE_SIZE,
+    },
+    gamepad::{start_gamepad_reader, EstopLatch, GamepadReaderConfig, InputEvent},
+    input_script::ScriptSource,
+    json_schemas::load_json_schemas,
+    messages::{
+        Axis, Button, ButtonCounterPolicy, GamepadEncoding, InputMessage, PublishMode,
+        WatchdogMessage, WatchdogReason,
+    },
+    schema_registry::{SchemaRegistry, SCHEMA_QUERY_SUFFIX},
+    shared_memory::SharedMemoryPayloads,
+    stats::StatsRegistry,
+    supervisor::Supervisor,
+};
+use serde_json::json;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_util::sync::CancellationToken;
+use zenoh::prelude::r#async::*;
+
+const GAMEPAD_TOPIC: &str = "test/remote-control/gamepad";
+const JSON_TOPIC: &str = "test/json";
+const LATCHED_TOPIC: &str = "test/latched/frames";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn scripted_input_is_published_over_zenoh() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+
+    let subscriber = session
+        .declare_subscriber(GAMEPAD_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let input = ScriptedInput::new(vec![
+        InputEvent::Connected { gamepad_id: 0 },
+        InputEvent::ButtonPressed {
+            gamepad_id: 0,
+            button: Button::South,
+        },
+        InputEvent::AxisChanged {
+            gamepad_id: 0,
+            axis: Axis::LeftStickX,
+            value: 0.5,
+        },
+    ]);
+    start_gamepad_reader(
+        &supervisor,
+        session.clone(),
+        GamepadReaderConfig {
+            pub_topic: GAMEPAD_TOPIC.to_owned(),
+            sleep_ms: 10,
+            button_counter_policy: ButtonCounterPolicy::Wrapping,
+            button_counter_limit: None,
+            encoding: GamepadEncoding::Json,
+            ..Default::default()
+        },
+        stats.gamepad(),
+        clock,
+        Arc::new(input),
+    );
+
+    let message = tokio::time::timeout(TEST_TIMEOUT, async {
+        loop {
+            let sample = subscriber.recv_async().await?;
+            let payload: String = sample.value.try_into()?;
+            let message: InputMessage = serde_json::from_str(&payload)?;
+            let ready = message
+                .gamepads
+                .get(&0)
+                .is_some_and(|gamepad| gamepad.axis_state.contains_key(&Axis::LeftStickX));
+            if ready {
+                return anyhow::Ok(message);
+            }
+        }
+    })
+    .await
+    .context("Timed out waiting for gamepad message")??;
+
+    let gamepad = &message.gamepads[&0];
+    assert!(gamepad.connected);
+    assert_eq!(
+        gamepad.button_down_event_counter.get(&Button::South),
+        Some(&1)
+    );
+    assert_eq!(gamepad.axis_state.get(&Axis::LeftStickX), Some(&0.5));
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn published_messages_are_stamped_by_the_clock() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let start = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let mock_clock = MockClock::new(start);
+    let clock: SharedClock = Arc::new(mock_clock.clone());
+    let gamepad_topic = "test/remote-control/clock-gamepad";
+
+    let subscriber = session
+        .declare_subscriber(gamepad_topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    start_gamepad_reader(
+        &supervisor,
+        session.clone(),
+        GamepadReaderConfig {
+            pub_topic: gamepad_topic.to_owned(),
+            sleep_ms: 10,
+            encoding: GamepadEncoding::Json,
+            ..Default::default()
+        },
+        stats.gamepad(),
+        clock,
+        Arc::new(ScriptedInput::new(vec![InputEvent::Connected {
+            gamepad_id: 0,
+        }])),
+    );
+
+    let wait_for_stamp = |stamp: SystemTime| {
+        let subscriber = &subscriber;
+        async move {
+            tokio::time::timeout(TEST_TIMEOUT, async {
+                loop {
+                    let sample = subscriber.recv_async().await?;
+                    let payload: String = sample.value.try_into()?;
+                    let message: InputMessage = serde_json::from_str(&payload)?;
+                    if SystemTime::from(message.time) == stamp {
+                        return anyhow::Ok(());
+                    }
+                }
+            })
+            .await
+            .context("Timed out waiting for stamped gamepad message")?
+        }
+    };
+
+    wait_for_stamp(start).await?;
+    // the mock clock stays put while the publish interval keeps ticking
+    mock_clock.advance(Duration::from_secs(5));
+    wait_for_stamp(start + Duration::from_secs(5)).await?;
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn estop_holds_axes_at_zero() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let gamepad_topic = "test/remote-control/estop-gamepad";
+
+    let subscriber = session
+        .declare_subscriber(gamepad_topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let input = ScriptedInput::new(vec![
+        InputEvent::Connected { gamepad_id: 0 },
+        InputEvent::ButtonPressed {
+            gamepad_id: 0,
+            button: Button::Mode,
+        },
+        InputEvent::AxisChanged {
+            gamepad_id: 0,
+            axis: Axis::LeftStickX,
+            value: 0.5,
+        },
+    ]);
+    start_gamepad_reader(
+        &supervisor,
+        session.clone(),
+        GamepadReaderConfig {
+            pub_topic: gamepad_topic.to_owned(),
+            sleep_ms: 10,
+            ..Default::default()
+        },
+        stats.gamepad(),
+        clock,
+        Arc::new(input),
+    );
+
+    let message = tokio::time::timeout(TEST_TIMEOUT, async {
+        loop {
+            let sample = subscriber.recv_async().await?;
+            let payload: String = sample.value.try_into()?;
+            let message: InputMessage = serde_json::from_str(&payload)?;
+            let ready = message
+                .gamepads
+                .get(&0)
+                .is_some_and(|gamepad| gamepad.axis_state.contains_key(&Axis::LeftStickX));
+            if ready {
+                return anyhow::Ok(message);
+            }
+        }
+    })
+    .await
+    .context("Timed out waiting for gamepad message")??;
+
+    assert!(message.estop_engaged);
+    assert_eq!(
+        message.gamepads[&0].axis_state.get(&Axis::LeftStickX),
+        Some(&0.0)
+    );
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn script_messages_are_dropped_while_estop_is_engaged() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let gamepad_topic = "test/remote-control/script-gamepad";
+    let script_topic = "test/script/cmd_vel";
+
+    let gamepad_subscriber = session
+        .declare_subscriber(gamepad_topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let script_subscriber = session
+        .declare_subscriber(script_topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let input = ScriptedInput::new(vec![
+        InputEvent::Connected { gamepad_id: 0 },
+        InputEvent::ButtonPressed {
+            gamepad_id: 0,
+            button: Button::Mode,
+        },
+    ]);
+    let script =
+        format!(r#"fn on_tick(state) {{ publish("{script_topic}", #{{ linear: 1.0 }}); state }}"#);
+    start_gamepad_reader(
+        &supervisor,
+        session.clone(),
+        GamepadReaderConfig {
+            pub_topic: gamepad_topic.to_owned(),
+            sleep_ms: 10,
+            script: Some(ScriptSource::Source(script)),
+            ..Default::default()
+        },
+        stats.gamepad(),
+        clock,
+        Arc::new(input),
+    );
+
+    let mut engaged_ticks = 0;
+    tokio::time::timeout(TEST_TIMEOUT, async {
+        while engaged_ticks < 3 {
+            let sample = gamepad_subscriber.recv_async().await?;
+            let payload: String = sample.value.try_into()?;
+            let message: InputMessage = serde_json::from_str(&payload)?;
+            if message.estop_engaged {
+                // anything sent before the latch engaged has arrived by now
+                if engaged_ticks == 0 {
+                    while script_subscriber.try_recv().is_ok() {}
+                }
+                engaged_ticks += 1;
+            }
+        }
+        anyhow::Ok(())
+    })
+    .await
+    .context("Timed out waiting for the emergency stop")??;
+
+    assert!(script_subscriber.try_recv().is_err());
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watchdog_publishes_neutral_message_when_input_stops() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let gamepad_topic = "test/remote-control/watchdog-gamepad";
+
+    let subscriber = session
+        .declare_subscriber(gamepad_topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let watchdog_subscriber = session
+        .declare_subscriber("remote-control/watchdog")
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    // scripted input goes silent after the last event
+    let input = ScriptedInput::new(vec![
+        InputEvent::Connected { gamepad_id: 0 },
+        InputEvent::AxisChanged {
+            gamepad_id: 0,
+            axis: Axis::LeftStickX,
+            value: 0.5,
+        },
+    ]);
+    start_gamepad_reader(
+        &supervisor,
+        session.clone(),
+        GamepadReaderConfig {
+            pub_topic: gamepad_topic.to_owned(),
+            sleep_ms: 10,
+            watchdog_timeout: Duration::from_millis(100),
+            ..Default::default()
+        },
+        stats.gamepad(),
+        clock,
+        Arc::new(input),
+    );
+
+    let message = tokio::time::timeout(TEST_TIMEOUT, async {
+        loop {
+            let sample = subscriber.recv_async().await?;
+            let payload: String = sample.value.try_into()?;
+            let message: InputMessage = serde_json::from_str(&payload)?;
+            let stalled = message
+                .gamepads
+                .get(&0)
+                .is_some_and(|gamepad| !gamepad.connected);
+            if stalled {
+                return anyhow::Ok(message);
+            }
+        }
+    })
+    .await
+    .context("Timed out waiting for neutral message")??;
+    assert_eq!(
+        message.gamepads[&0].axis_state.get(&Axis::LeftStickX),
+        Some(&0.0)
+    );
+
+    let sample = tokio::time::timeout(TEST_TIMEOUT, watchdog_subscriber.recv_async())
+        .await
+        .context("Timed out waiting for watchdog message")??;
+    let payload: String = sample.value.try_into()?;
+    let watchdog: WatchdogMessage = serde_json::from_str(&payload)?;
+    assert_eq!(watchdog.reason, WatchdogReason::InputStalled);
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn idle_gamepad_doesnt_trip_watchdog_with_slow_publishing() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+
+    let watchdog_subscriber = session
+        .declare_subscriber("remote-control/watchdog")
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    // publishing exactly as often as the watchdog timeout
+    let config = GamepadReaderConfig {
+        pub_topic: "test/remote-control/idle-gamepad".to_owned(),
+        sleep_ms: 100,
+        watchdog_timeout: Duration::from_millis(100),
+        ..Default::default()
+    };
+    assert!(config.status_interval() < config.watchdog_timeout);
+    start_gamepad_reader(
+        &supervisor,
+        session.clone(),
+        config,
+        stats.gamepad(),
+        clock,
+        Arc::new(IdleInput),
+    );
+
+    let watchdog =
+        tokio::time::timeout(Duration::from_secs(1), watchdog_subscriber.recv_async()).await;
+    assert!(watchdog.is_err(), "watchdog tripped with an idle gamepad");
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn on_change_mode_skips_unchanged_messages() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let gamepad_topic = "test/remote-control/on-change-gamepad";
+
+    let subscriber = session
+        .declare_subscriber(gamepad_topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let input = ScriptedInput::new(vec![
+        InputEvent::Connected { gamepad_id: 0 },
+        InputEvent::AxisChanged {
+            gamepad_id: 0,
+            axis: Axis::LeftStickX,
+            value: 0.5,
+        },
+    ]);
+    start_gamepad_reader(
+        &supervisor,
+        session.clone(),
+        GamepadReaderConfig {
+            pub_topic: gamepad_topic.to_owned(),
+            sleep_ms: 10,
+            publish_mode: PublishMode::OnChange,
+            watchdog_timeout: Duration::from_secs(60),
+            ..Default::default()
+        },
+        stats.gamepad(),
+        clock,
+        Arc::new(input),
+    );
+
+    // 50 publish intervals with the state only changing at the start
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let messages: Vec<InputMessage> = subscriber
+        .drain()
+        .map(|sample| {
+            let payload: String = sample.value.try_into()?;
+            Ok(serde_json::from_str(&payload)?)
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    assert!(!messages.is_empty());
+    assert!(messages.len() <= 3, "published {} messages", messages.len());
+    let last = messages.last().unwrap();
+    assert_eq!(
+        last.gamepads[&0].axis_state.get(&Axis::LeftStickX),
+        Some(&0.5)
+    );
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn zenoh_json_is_forwarded_to_foxglove_clients() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let address = free_local_address()?;
+
+    let config = FoxgloveServerConfiguration {
+        descriptor_files: vec![],
+        protobuf_subscriptions: vec![],
+        json_subscriptions: vec![JsonSubscription {
+            topic: JSON_TOPIC.to_owned(),
+            type_name: "TestMessage".to_owned(),
+            json_schema_name: Some("GENERIC_JSON_SCHEMA".to_owned()),
+            infer_schema: None,
+            latched: None,
+            transforms: vec![],
+            queue_size: DEFAULT_QUEUE_SIZE,
+        }],
+        auto_discover: None,
+        log_subscriptions: vec![],
+        compressed_image_subscriptions: vec![],
+        client_publish: vec![],
+        services: vec![],
+        parameter_prefix: None,
+        asset_dir: None,
+        asset_key_prefix: None,
+        broadcast_time: false,
+        time_key_expr: None,
+    };
+    start_foxglove_bridge(
+        config,
+        &load_json_schemas(None)?,
+        address,
+        session.clone(),
+        &supervisor,
+        &stats,
+        &SchemaRegistry::default(),
+        clock,
+        EstopLatch::default(),
+    )
+    .await?;
+
+    let mut client = FoxgloveTestClient::connect(address).await?;
+    let channel_id = client.wait_for_channel(JSON_TOPIC).await?;
+    let subscription_id = client.subscribe(channel_id).await?;
+
+    let payload = r#"{"value":42}"#;
+    let received = tokio::time::timeout(TEST_TIMEOUT, async {
+        loop {
+            // keep publishing until the subscription reached the server
+            session
+                .put(JSON_TOPIC, payload)
+                .res()
+                .await
+                .map_err(ZenohError)?;
+            if let Ok(data) = tokio::time::timeout(
+                Duration::from_millis(100),
+                client.next_message_data(subscription_id),
+            )
+            .await
+            {
+                return data;
+            }
+        }
+    })
+    .await
+    .context("Timed out waiting for forwarded message")??;
+
+    assert_eq!(received, payload.as_bytes());
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn foxglove_clients_are_disconnected_on_shutdown() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let address = free_local_address()?;
+
+    let config = FoxgloveServerConfiguration {
+        descriptor_files: vec![],
+        protobuf_subscriptions: vec![],
+        json_subscriptions: vec![JsonSubscription {
+            topic: JSON_TOPIC.to_owned(),
+            type_name: "TestMessage".to_owned(),
+            json_schema_name: Some("GENERIC_JSON_SCHEMA".to_owned()),
+            infer_schema: None,
+            latched: None,
+            transforms: vec![],
+            queue_size: DEFAULT_QUEUE_SIZE,
+        }],
+        auto_discover: None,
+        log_subscriptions: vec![],
+        compressed_image_subscriptions: vec![],
+        client_publish: vec![],
+        services: vec![],
+        parameter_prefix: None,
+        asset_dir: None,
+        asset_key_prefix: None,
+        broadcast_time: false,
+        time_key_expr: None,
+    };
+    start_foxglove_bridge(
+        config,
+        &load_json_schemas(None)?,
+        address,
+        session.clone(),
+        &supervisor,
+        &stats,
+        &SchemaRegistry::default(),
+        clock,
+        EstopLatch::default(),
+    )
+    .await?;
+    let mut client = FoxgloveTestClient::connect(address).await?;
+    let channel_id = client.wait_for_channel(JSON_TOPIC).await?;
+
+    let shutdown = tokio::spawn(supervisor.shutdown(Duration::from_secs(2)));
+    let (unadvertised, close_frame) = client.wait_for_close().await?;
+    shutdown.await?;
+
+    assert_eq!(unadvertised, vec![channel_id]);
+    assert_eq!(close_frame.map(|frame| frame.code), Some(CloseCode::Away));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn messages_published_by_foxglove_clients_are_put_on_zenoh() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let address = free_local_address()?;
+    let estop = EstopLatch::default();
+
+    let config = FoxgloveServerConfiguration {
+        descriptor_files: vec![],
+        protobuf_subscriptions: vec![],
+        json_subscriptions: vec![],
+        auto_discover: None,
+        log_subscriptions: vec![],
+        compressed_image_subscriptions: vec![],
+        client_publish: vec![ClientPublishTopic {
+            topic: "/test/cmd_vel".to_owned(),
+            key_expr: None,
+        }],
+        services: vec![],
+        parameter_prefix: None,
+        asset_dir: None,
+        asset_key_prefix: None,
+        broadcast_time: false,
+        time_key_expr: None,
+    };
+    start_foxglove_bridge(
+        config,
+        &load_json_schemas(None)?,
+        address,
+        session.clone(),
+        &supervisor,
+        &stats,
+        &SchemaRegistry::default(),
+        clock,
+        estop.clone(),
+    )
+    .await?;
+    let subscriber = session
+        .declare_subscriber("test/cmd_vel")
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let mut client = FoxgloveTestClient::connect(address).await?;
+
+    let server_info = client.wait_for_op("serverInfo").await?;
+    let capabilities = server_info["capabilities"].as_array().unwrap();
+    assert!(capabilities.contains(&"clientPublish".into()));
+
+    client.advertise(1, "/not/allowed").await?;
+    let status = client.wait_for_op("status").await?;
+    assert_eq!(status["level"], 2);
+
+    client.advertise(2, "/test/cmd_vel").await?;
+    client.publish(2, br#"{"linear":{"x":0.5}}"#).await?;
+    let sample = tokio::time::timeout(TEST_TIMEOUT, subscriber.recv_async()).await??;
+    let payload: Vec<u8> = sample.value.try_into()?;
+    assert_eq!(payload, br#"{"linear":{"x":0.5}}"#);
+
+    estop.set(true);
+    client.publish(2, br#"{"linear":{"x":1.0}}"#).await?;
+    let status = client.wait_for_op("status").await?;
+    assert_eq!(status["level"], 1);
+    assert!(subscriber.try_recv().is_err());
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn foxglove_service_calls_query_zenoh() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let address = free_local_address()?;
+
+    let config = FoxgloveServerConfiguration {
+        descriptor_files: vec![],
+        protobuf_subscriptions: vec![],
+        json_subscriptions: vec![],
+        auto_discover: None,
+        log_subscriptions: vec![],
+        compressed_image_subscriptions: vec![],
+        client_publish: vec![],
+        services: vec![
+            FoxgloveService {
+                name: "/test/stance".to_owned(),
+                key_expr: "test/service/stance".to_owned(),
+                type_name: None,
+                timeout_ms: None,
+            },
+            FoxgloveService {
+                name: "/test/missing".to_owned(),
+                key_expr: "test/service/missing".to_owned(),
+                type_name: None,
+                timeout_ms: Some(100),
+            },
+        ],
+        parameter_prefix: None,
+        asset_dir: None,
+        asset_key_prefix: None,
+        broadcast_time: false,
+        time_key_expr: None,
+    };
+    start_foxglove_bridge(
+        config,
+        &load_json_schemas(None)?,
+        address,
+        session.clone(),
+        &supervisor,
+        &stats,
+        &SchemaRegistry::default(),
+        clock,
+        EstopLatch::default(),
+    )
+    .await?;
+    let queryable = session
+        .declare_queryable("test/service/stance")
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let replier = tokio::spawn(async move {
+        while let Ok(query) = queryable.recv_async().await {
+            let request: Vec<u8> = query
+                .value()
+                .map(|value| value.payload.contiguous().into_owned())
+                .unwrap_or_default();
+            let mut reply = br#"{"ok":true,"request":"#.to_vec();
+            reply.extend_from_slice(&request);
+            reply.push(b'}');
+            let sample = Sample::new(query.key_expr().clone(), reply);
+            query.reply(Ok(sample)).res().await.map_err(ZenohError)?;
+        }
+        anyhow::Ok(())
+    });
+    let mut client = FoxgloveTestClient::connect(address).await?;
+
+    let server_info = client.wait_for_op("serverInfo").await?;
+    let capabilities = server_info["capabilities"].as_array().unwrap();
+    assert!(capabilities.contains(&"services".into()));
+    let services = client.wait_for_op("advertiseServices").await?;
+    assert_eq!(services["services"][0]["name"], "/test/stance");
+    let service_id = services["services"][0]["id"].as_u64().unwrap() as u32;
+
+    client
+        .call_service(service_id, 7, br#"{"height":0.2}"#)
+        .await?;
+    let (call_id, response) = client.next_service_response().await?;
+    assert_eq!(call_id, 7);
+    assert_eq!(response, br#"{"ok":true,"request":{"height":0.2}}"#);
+
+    let missing_id = services["services"][1]["id"].as_u64().unwrap() as u32;
+    client.call_service(missing_id, 8, b"{}").await?;
+    let failure = client.wait_for_op("serviceCallFailure").await?;
+    assert_eq!(failure["callId"], 8);
+
+    replier.abort();
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn foxglove_parameters_are_read_and_set_over_zenoh() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let address = free_local_address()?;
+
+    let config = FoxgloveServerConfiguration {
+        descriptor_files: vec![],
+        protobuf_subscriptions: vec![],
+        json_subscriptions: vec![],
+        auto_discover: None,
+        log_subscriptions: vec![],
+        compressed_image_subscriptions: vec![],
+        client_publish: vec![],
+        services: vec![],
+        parameter_prefix: Some("test/parameters".to_owned()),
+        asset_dir: None,
+        asset_key_prefix: None,
+        broadcast_time: false,
+        time_key_expr: None,
+    };
+    start_foxglove_bridge(
+        config,
+        &load_json_schemas(None)?,
+        address,
+        session.clone(),
+        &supervisor,
+        &stats,
+        &SchemaRegistry::default(),
+        clock,
+        EstopLatch::default(),
+    )
+    .await?;
+    // stands in for the robot's parameter storage
+    let queryable = session
+        .declare_queryable("test/parameters/**")
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let storage = tokio::spawn(async move {
+        while let Ok(query) = queryable.recv_async().await {
+            let sample = Sample::new(
+                KeyExpr::try_from("test/parameters/gait/step_height")?,
+                "0.05",
+            );
+            query.reply(Ok(sample)).res().await.map_err(ZenohError)?;
+        }
+        anyhow::Ok(())
+    });
+    let subscriber = session
+        .declare_subscriber("test/parameters/gait/step_height")
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let mut client = FoxgloveTestClient::connect(address).await?;
+
+    let server_info = client.wait_for_op("serverInfo").await?;
+    let capabilities = server_info["capabilities"].as_array().unwrap();
+    assert!(capabilities.contains(&"parameters".into()));
+
+    client
+        .send_json(json!({ "op": "getParameters", "parameterNames": [], "id": "get" }))
+        .await?;
+    let values = client.wait_for_op("parameterValues").await?;
+    assert_eq!(
+        values,
+        json!({
+            "op": "parameterValues",
+            "parameters": [{ "name": "gait/step_height", "value": 0.05 }],
+            "id": "get",
+        })
+    );
+
+    client
+        .send_json(json!({
+            "op": "subscribeParameterUpdates",
+            "parameterNames": ["gait/step_height"],
+        }))
+        .await?;
+    client
+        .send_json(json!({
+            "op": "setParameters",
+            "parameters": [{ "name": "gait/step_height", "value": 0.08 }],
+            "id": "set",
+        }))
+        .await?;
+    let sample = tokio::time::timeout(TEST_TIMEOUT, subscriber.recv_async()).await??;
+    let payload: String = sample.value.try_into()?;
+    assert_eq!(payload, "0.08");
+
+    // the reply to the set and the update of the subscription
+    let mut ids = vec![];
+    for _ in 0..2 {
+        let values = client.wait_for_op("parameterValues").await?;
+        assert_eq!(values["parameters"][0]["value"], 0.08);
+        ids.push(values["id"].clone());
+    }
+    ids.sort_by_key(|id| id.is_null());
+    assert_eq!(ids, vec![json!("set"), serde_json::Value::Null]);
+
+    storage.abort();
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn discovered_json_topics_are_advertised() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let address = free_local_address()?;
+    let discovered_topic = "test/discover/value";
+    let slow_topic = "test/discover/slow";
+
+    let config = FoxgloveServerConfiguration {
+        descriptor_files: vec![],
+        protobuf_subscriptions: vec![],
+        json_subscriptions: vec![],
+        auto_discover: Some("test/discover/**".to_owned()),
+        log_subscriptions: vec![],
+        compressed_image_subscriptions: vec![],
+        client_publish: vec![],
+        services: vec![],
+        parameter_prefix: None,
+        asset_dir: None,
+        asset_key_prefix: None,
+        broadcast_time: false,
+        time_key_expr: None,
+    };
+    start_foxglove_bridge(
+        config,
+        &load_json_schemas(None)?,
+        address,
+        session.clone(),
+        &supervisor,
+        &stats,
+        &SchemaRegistry::default(),
+        clock,
+        EstopLatch::default(),
+    )
+    .await?;
+    let mut client = FoxgloveTestClient::connect(address).await?;
+
+    // a schema queryable that never answers mustn't hold up other topics
+    let slow_queryable = session
+        .declare_queryable(format!("{slow_topic}{SCHEMA_QUERY_SUFFIX}"))
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let slow_schema = tokio::spawn(async move {
+        let mut unanswered = vec![];
+        while let Ok(query) = slow_queryable.recv_async().await {
+            unanswered.push(query);
+        }
+    });
+
+    let publisher = tokio::spawn({
+        let session = session.clone();
+        async move {
+            loop {
+                for topic in [slow_topic, discovered_topic] {
+                    let value = Value::from(r#"{"value":42}"#)
+                        .encoding(Encoding::Exact(KnownEncoding::TextJson));
+                    // keep publishing until discovery subscribed and created the channel
+                    if session.put(topic, value).res().await.is_err() {
+                        return;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+    });
+
+    // schema queries time out after 2 seconds
+    tokio::time::timeout(
+        Duration::from_millis(1500),
+        client.wait_for_channel(discovered_topic),
+    )
+    .await
+    .context("Discovery waited for the schema query of another topic")??;
+
+    publisher.abort();
+    slow_schema.abort();
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn foxglove_assets_are_read_from_the_directory_and_zenoh() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let address = free_local_address()?;
+    let asset_dir =
+        std::env::temp_dir().join(format!("deck-robot-remote-assets-{}", std::process::id()));
+    std::fs::create_dir_all(asset_dir.join("hopper/meshes"))?;
+    std::fs::write(asset_dir.join("hopper/meshes/base.stl"), b"solid base")?;
+
+    let config = FoxgloveServerConfiguration {
+        descriptor_files: vec![],
+        protobuf_subscriptions: vec![],
+        json_subscriptions: vec![],
+        auto_discover: None,
+        log_subscriptions: vec![],
+        compressed_image_subscriptions: vec![],
+        client_publish: vec![],
+        services: vec![],
+        parameter_prefix: None,
+        asset_dir: Some(asset_dir.clone()),
+        asset_key_prefix: Some("test/assets".to_owned()),
+        broadcast_time: false,
+        time_key_expr: None,
+    };
+    start_foxglove_bridge(
+        config,
+        &load_json_schemas(None)?,
+        address,
+        session.clone(),
+        &supervisor,
+        &stats,
+        &SchemaRegistry::default(),
+        clock,
+        EstopLatch::default(),
+    )
+    .await?;
+    // stands in for a robot serving its own meshes
+    let queryable = session
+        .declare_queryable("test/assets/hopper/meshes/leg.stl")
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let replier = tokio::spawn(async move {
+        while let Ok(query) = queryable.recv_async().await {
+            let sample = Sample::new(query.key_expr().clone(), b"solid leg".to_vec());
+            query.reply(Ok(sample)).res().await.map_err(ZenohError)?;
+        }
+        anyhow::Ok(())
+    });
+    let mut client = FoxgloveTestClient::connect(address).await?;
+
+    let server_info = client.wait_for_op("serverInfo").await?;
+    let capabilities = server_info["capabilities"].as_array().unwrap();
+    assert!(capabilities.contains(&"assets".into()));
+
+    let fetches = [
+        "package://hopper/meshes/base.stl",
+        "package://hopper/meshes/leg.stl",
+        "package://../secrets.txt",
+    ];
+    for (request_id, uri) in (1..).zip(fetches) {
+        client
+            .send_json(json!({ "op": "fetchAsset", "uri": uri, "requestId": request_id }))
+            .await?;
+    }
+    let mut responses = BTreeMap::new();
+    for _ in 0..fetches.len() {
+        let (request_id, asset) = client.next_asset_response().await?;
+        responses.insert(request_id, asset);
+    }
+    assert_eq!(responses[&1], Ok(b"solid base".to_vec()));
+    assert_eq!(responses[&2], Ok(b"solid leg".to_vec()));
+    assert!(responses[&3].is_err());
+
+    replier.abort();
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    std::fs::remove_dir_all(asset_dir)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn foxglove_clients_are_sent_the_robot_clock() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let address = free_local_address()?;
+
+    let config = FoxgloveServerConfiguration {
+        descriptor_files: vec![],
+        protobuf_subscriptions: vec![],
+        json_subscriptions: vec![],
+        auto_discover: None,
+        log_subscriptions: vec![],
+        compressed_image_subscriptions: vec![],
+        client_publish: vec![],
+        services: vec![],
+        parameter_prefix: None,
+        asset_dir: None,
+        asset_key_prefix: None,
+        broadcast_time: false,
+        time_key_expr: Some("test/clock".to_owned()),
+    };
+    start_foxglove_bridge(
+        config,
+        &load_json_schemas(None)?,
+        address,
+        session.clone(),
+        &supervisor,
+        &stats,
+        &SchemaRegistry::default(),
+        clock,
+        EstopLatch::default(),
+    )
+    .await?;
+    let mut client = FoxgloveTestClient::connect(address).await?;
+
+    let server_info = client.wait_for_op("serverInfo").await?;
+    let capabilities = server_info["capabilities"].as_array().unwrap();
+    assert!(capabilities.contains(&"time".into()));
+
+    // far from the Deck's clock so the time can only come from the robot
+    let robot_time: u64 = 1_000_000_000_000;
+    tokio::time::timeout(TEST_TIMEOUT, async {
+        loop {
+            session
+                .put("test/clock", robot_time.to_string())
+                .res()
+                .await
+                .map_err(ZenohError)?;
+            if let Ok(time) =
+                tokio::time::timeout(Duration::from_millis(500), client.next_time()).await
+            {
+                let time = time?;
+                assert!(time >= robot_time, "{time}");
+                assert!(time < robot_time + TEST_TIMEOUT.as_nanos() as u64, "{time}");
+                return anyhow::Ok(());
+            }
+        }
+    })
+    .await??;
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn latched_protobuf_topic_is_queried_on_start() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let address = free_local_address()?;
+
+    // stands in for a zenoh storage holding the last published transforms
+    let payload = vec![0x0a, 0x00];
+    let queryable = session
+        .declare_queryable(LATCHED_TOPIC)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+    let storage = tokio::spawn({
+        let payload = payload.clone();
+        async move {
+            while let Ok(query) = queryable.recv_async().await {
+                let sample = Sample::new(KeyExpr::try_from(LATCHED_TOPIC)?, payload.clone());
+                query.reply(Ok(sample)).res().await.map_err(ZenohError)?;
+            }
+            anyhow::Ok(())
+        }
+    });
+
+    let config = FoxgloveServerConfiguration {
+        descriptor_files: vec![],
+        protobuf_subscriptions: vec![ProtobufSubscription {
+            topic: LATCHED_TOPIC.to_owned(),
+            proto_type: "foxglove.FrameTransforms".to_owned(),
+            latched: Some(true),
+            transforms: vec![],
+            queue_size: DEFAULT_QUEUE_SIZE,
+            decimation: None,
+            on_demand: false,
+        }],
+        json_subscriptions: vec![],
+        auto_discover: None,
+        log_subscriptions: vec![],
+        compressed_image_subscriptions: vec![],
+        client_publish: vec![],
+        services: vec![],
+        parameter_prefix: None,
+        asset_dir: None,
+        asset_key_prefix: None,
+        broadcast_time: false,
+        time_key_expr: None,
+    };
+    start_foxglove_bridge(
+        config,
+        &load_json_schemas(None)?,
+        address,
+        session.clone(),
+        &supervisor,
+        &stats,
+        &SchemaRegistry::default(),
+        clock,
+        EstopLatch::default(),
+    )
+    .await?;
+
+    // the client connects after the query was answered and nothing is ever published
+    let mut client = FoxgloveTestClient::connect(address).await?;
+    let channel_id = client.wait_for_channel(LATCHED_TOPIC).await?;
+    let subscription_id = client.subscribe(channel_id).await?;
+    let received = tokio::time::timeout(TEST_TIMEOUT, client.next_message_data(subscription_id))
+        .await
+        .context("Timed out waiting for latched message")??;
+    assert_eq!(received, payload);
+
+    storage.abort();
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shared_memory_payloads_are_received_unchanged() -> anyhow::Result<()> {
+    let session = open_local_session().await?;
+    let supervisor = Supervisor::new(session.clone(), CancellationToken::new());
+    let stats = StatsRegistry::default();
+    let clock: SharedClock = Arc::new(MockClock::default());
+    let gamepad_topic = "test/remote-control/shm-gamepad";
+
+    let subscriber = session
+        .declare_subscriber(gamepad_topic)
+        .res()
+        .await
+        .map_err(ZenohError)?;
+
+    let input = ScriptedInput::new(vec![
+        InputEvent::Connected { gamepad_id: 0 },
+        InputEvent::ButtonPressed {
+            gamepad_id: 0,
+            button: Button::South,
+        },
+    ]);
+    start_gamepad_reader(
+        &supervisor,
+        session.clone(),
+        GamepadReaderConfig {
+            pub_topic: gamepad_topic.to_owned(),
+            sleep_ms: 10,
+            encoding: GamepadEncoding::Json,
+            shared_memory: Some(Arc::new(SharedMemoryPayloads::new(&session)?)),
+            ..Default::default()
+        },
+        stats.gamepad(),
+        clock,
+        Arc::new(input),
+    );
+
+    let message = tokio::time::timeout(TEST_TIMEOUT, async {
+        loop {
+            let sample = subscriber.recv_async().await?;
+            let payload: String = sample.value.try_into()?;
+            let message: InputMessage = serde_json::from_str(&payload)?;
+            if message
+                .gamepads
+                .get(&0)
+                .is_some_and(|gamepad| gamepad.button_down.get(&Button::South) == Some(&true))
+            {
+                return anyhow::Ok(message);
+            }
+        }
+    })
+    .await
+    .context("Timed out waiting for gamepad message")??;
+
+    assert!(message.gamepads[&0].connected);
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use anyhow::Context;
+use deck_robot_remote::{
+    config::load_profile,
+    error::{
+        diagnose, BridgeError, ConfigError, DiscoveryError, Error, GamepadError, UnknownProtoType,
+        ZenohError,
+    },
+    foxglove_server::{check_bind, close_proto_type_matches, edit_distance},
+    tailscale::TailscaleStatus,
+    DESCRIPTOR_POOL,
+};
+
+#[test]
+fn unknown_profile_lists_available_ones() {
+    let err = load_profile(None, "bender").unwrap_err();
+    let ConfigError::UnknownProfile { name, available } = &err else {
+        panic!("Expected an unknown profile, got {err:?}");
+    };
+    assert_eq!(name, "bender");
+    assert!(available.contains(&"hopper".to_owned()));
+    assert!(!err.is_retryable());
+}
+
+#[test]
+fn diagnostic_is_found_behind_context() {
+    let err = anyhow::Error::from(TailscaleStatus::parse(b"[]").unwrap_err())
+        .context("Failed to start zenoh session");
+    let diagnostic = diagnose(&err).unwrap();
+    assert_eq!(diagnostic.subsystem, "tailscale");
+    assert!(!diagnostic.retryable);
+    assert!(diagnostic
+        .message
+        .starts_with("Failed to parse tailscale status"));
+
+    assert!(diagnose(&anyhow::anyhow!("Zenoh subscriber closed")).is_none());
+}
+
+#[test]
+fn unavailable_tailscale_is_retryable() {
+    let err = Error::from(DiscoveryError::Unavailable(
+        "Failed to connect to /var/run/tailscale/tailscaled.sock".to_owned(),
+    ));
+    assert!(err.is_retryable());
+    assert!(err.diagnostic().hint.unwrap().contains("tailscale status"));
+}
+
+#[test]
+fn zenoh_errors_are_diagnosed_as_zenoh() {
+    let err = GamepadError::from(ZenohError("Session closed".into()));
+    assert!(err.is_retryable());
+
+    let err = anyhow::Error::from(err).context("Gamepad reader failed");
+    let diagnostic = diagnose(&err).unwrap();
+    assert_eq!(diagnostic.subsystem, "zenoh");
+    assert!(diagnostic.retryable);
+}
+
+#[tokio::test]
+async fn port_in_use_is_retryable() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let err = check_bind(address).await.unwrap_err();
+    assert!(matches!(err, BridgeError::Bind { .. }));
+    assert!(err.is_retryable());
+
+    let err: anyhow::Result<()> = Err(err).context("Failed to start Foxglove bridge");
+    let diagnostic = diagnose(&err.unwrap_err()).unwrap();
+    assert_eq!(diagnostic.subsystem, "foxglove bridge");
+    assert!(diagnostic.retryable);
+}
+
+#[test]
+fn unknown_proto_types_are_listed_with_suggestions() {
+    let err = BridgeError::UnknownProtoTypes {
+        unknown: vec![
+            UnknownProtoType {
+                topic: "hopper/tf".to_owned(),
+                proto_type: "foxglove.FrameTransform".to_owned(),
+                suggestions: vec!["foxglove.FrameTransforms".to_owned()],
+            },
+            UnknownProtoType {
+                topic: "hopper/battery".to_owned(),
+                proto_type: "rover.Battery".to_owned(),
+                suggestions: vec![],
+            },
+        ],
+        packages: vec!["foxglove".to_owned()],
+    };
+    assert_eq!(
+        err.to_string(),
+        "Unknown protobuf message types in foxglove config:\n  \
+         \"foxglove.FrameTransform\" for topic \"hopper/tf\", did you mean foxglove.FrameTransforms?\n  \
+         \"rover.Battery\" for topic \"hopper/battery\"\n\
+         Available packages: foxglove"
+    );
+    assert!(!err.is_retryable());
+}
+
+#[test]
+fn edit_distance_counts_single_character_edits() {
+    assert_eq!(edit_distance("PointCloud", "PointCloud"), 0);
+    assert_eq!(edit_distance("PointCloud", "PointClod"), 1);
+    assert_eq!(edit_distance("PointCloud", "PiontCloud"), 2);
+    assert_eq!(edit_distance("", "Log"), 3);
+}
+
+#[test]
+fn close_proto_types_are_suggested() {
+    let pool = &*DESCRIPTOR_POOL;
+    let exact = close_proto_type_matches(pool, "foxglove.PointCloud");
+    assert_eq!(
+        exact.first().map(String::as_str),
+        Some("foxglove.PointCloud")
+    );
+
+    let package_typo = close_proto_type_matches(pool, "foxglov.PointCloud");
+    assert_eq!(
+        package_typo.first().map(String::as_str),
+        Some("foxglove.PointCloud")
+    );
+
+    assert!(close_proto_type_matches(pool, "hopper.Zzzzzzzzzzzzzzzz").is_empty());
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use deck_robot_remote::{
+    config::{load_profiles, RobotProfile},
+    failover::{replace_peer_endpoints, telemetry_stale, FailoverConfig},
+    robot_registry::RobotRegistry,
+    tailscale::{online_peer_endpoints, peer_endpoints, PeerMatch, TailscaleStatus},
+};
+use serde_json::{json, Value};
+use zenoh_config::EndPoint;
+
+const STATUS: &[u8] = include_bytes!("fixtures/tailscale/status.json");
+
+const NUC_PROFILE: &str = r#"
+name: nuc
+foxglove_layout_id: "layout"
+tailscale_peers:
+  - exact: hopper
+  - exact: hamilton
+failover:
+  telemetry_timeout_ms: 2000
+"#;
+
+fn load_profile(name: &str, yaml: &str) -> anyhow::Result<BTreeMap<String, RobotProfile>> {
+    let profile_dir = std::env::temp_dir().join(format!(
+        "deck-robot-remote-failover-{name}-{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&profile_dir).unwrap();
+    std::fs::write(profile_dir.join(format!("{name}.yaml")), yaml).unwrap();
+    let result = load_profiles(Some(&profile_dir));
+    std::fs::remove_dir_all(&profile_dir).unwrap();
+    Ok(result?)
+}
+
+fn endpoint(address: &str) -> EndPoint {
+    format!("tcp/{address}:7447").parse().unwrap()
+}
+
+fn exact(host_name: &str) -> Vec<PeerMatch> {
+    vec![PeerMatch::Exact(host_name.to_owned())]
+}
+
+#[test]
+fn telemetry_goes_stale_after_the_timeout() {
+    let start = Instant::now();
+    let timeout = Duration::from_secs(5);
+    let telemetry = Some(start + Duration::from_secs(1));
+
+    assert!(!telemetry_stale(
+        telemetry,
+        start,
+        start + Duration::from_secs(5),
+        timeout
+    ));
+    assert!(telemetry_stale(
+        telemetry,
+        start,
+        start + Duration::from_secs(6),
+        timeout
+    ));
+    assert!(telemetry_stale(None, start, start + timeout, timeout));
+}
+
+#[test]
+fn switching_restarts_the_timeout() {
+    let start = Instant::now();
+    let timeout = Duration::from_secs(5);
+    let switch = start + Duration::from_secs(10);
+
+    assert!(!telemetry_stale(
+        Some(start),
+        switch,
+        switch + Duration::from_secs(4),
+        timeout
+    ));
+    assert!(telemetry_stale(
+        Some(start),
+        switch,
+        switch + timeout,
+        timeout
+    ));
+}
+
+#[test]
+fn only_the_robots_endpoints_are_replaced() {
+    let manual = endpoint("10.0.0.1");
+    let primary = endpoint("100.64.0.1");
+    let backup = endpoint("100.64.0.2");
+
+    let endpoints = replace_peer_endpoints(
+        &[manual.clone(), primary.clone()],
+        &[primary.clone(), backup.clone()],
+        &[backup.clone()],
+    );
+    assert_eq!(endpoints, vec![manual.clone(), backup.clone()]);
+
+    // failing back from a backup that was never connected
+    let endpoints =
+        replace_peer_endpoints(&[manual.clone()], &[primary.clone()], &[primary.clone()]);
+    assert_eq!(endpoints, vec![manual, primary]);
+}
+
+#[test]
+fn offline_peers_arent_failed_over_to() {
+    let status = TailscaleStatus::parse(STATUS).unwrap();
+    let online = online_peer_endpoints(&status, &exact("hamilton"), false).unwrap();
+    assert_eq!(online.len(), 1);
+    assert_eq!(
+        online,
+        peer_endpoints(&status, &exact("hamilton"), false).unwrap()
+    );
+
+    let mut status: Value = serde_json::from_slice(STATUS).unwrap();
+    for peer in status["Peer"].as_object_mut().unwrap().values_mut() {
+        peer["Online"] = json!(false);
+    }
+    let status = TailscaleStatus::parse(&serde_json::to_vec(&status).unwrap()).unwrap();
+    assert!(online_peer_endpoints(&status, &exact("hamilton"), false)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        peer_endpoints(&status, &exact("hamilton"), false)
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn failover_walks_the_peer_rules_in_order() {
+    let profiles = load_profile("nuc", NUC_PROFILE).unwrap();
+    let registry = RobotRegistry::new(&profiles, "nuc").unwrap();
+    let target = registry.active();
+    assert_eq!(
+        target.failover,
+        Some(FailoverConfig {
+            telemetry_timeout_ms: 2000
+        })
+    );
+
+    assert_eq!(registry.peer_rules(&target), exact("hopper"));
+    assert_eq!(registry.fail_over("nuc"), exact("hamilton"));
+    assert_eq!(registry.peer_rules(&target), exact("hamilton"));
+    assert_eq!(registry.fail_over("nuc"), exact("hopper"));
+    assert!(registry.fail_over("bender").is_empty());
+}
+
+#[test]
+fn robots_without_failover_use_every_peer_rule() {
+    let registry = RobotRegistry::new(&load_profiles(None).unwrap(), "hopper").unwrap();
+    let target = registry.active();
+    assert!(target.failover.is_none());
+    assert_eq!(registry.peer_rules(&target), target.peer_rules);
+}
+
+#[test]
+fn failover_needs_several_peer_rules() {
+    let result = load_profile(
+        "single",
+        r#"
+name: single
+foxglove_layout_id: "layout"
+tailscale_peers:
+  - exact: hopper
+failover: {}
+"#,
+    );
+    assert!(result.is_err());
+
+    let result = load_profile(
+        "zero",
+        &NUC_PROFILE.replace("telemetry_timeout_ms: 2000", "telemetry_timeout_ms: 0"),
+    );
+    assert!(result.is_err());
+}
@@ -0,0 +1,48 @@
+use deck_robot_remote::{
+    foxglove_clients::foxglove_client_count, foxglove_server::FoxgloveServerConfiguration,
+};
+
+#[test]
+fn on_demand_topics_are_not_telemetry() {
+    let config: FoxgloveServerConfiguration = serde_yaml::from_str(
+        r#"
+protobuf_subscriptions:
+  - topic: hopper/tf
+    proto_type: foxglove.FrameTransforms
+  - topic: hopper/lidar
+    proto_type: foxglove.PointCloud
+    on_demand: true
+compressed_image_subscriptions:
+  - topic: hopper/camera/jpeg
+    format: jpeg
+    on_demand: true
+  - topic: hopper/camera/thumbnail
+    format: jpeg
+"#,
+    )
+    .unwrap();
+    assert!(!config.protobuf_subscriptions[0].on_demand);
+    assert!(config.protobuf_subscriptions[1].on_demand);
+    assert_eq!(config.topics().len(), 4);
+    assert_eq!(
+        config.telemetry_topics(),
+        vec!["hopper/tf", "hopper/camera/thumbnail"]
+    );
+}
+
+#[tokio::test]
+async fn listening_socket_is_not_a_client() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    // no /proc outside of Linux
+    let Some(count) = foxglove_client_count(port) else {
+        return;
+    };
+    assert_eq!(count, 0);
+
+    let _client = tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .unwrap();
+    let (_server, _) = listener.accept().await.unwrap();
+    assert_eq!(foxglove_client_count(port), Some(1));
+}
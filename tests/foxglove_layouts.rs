@@ -0,0 +1,28 @@
+use deck_robot_remote::foxglove_layouts::{parse_layout, Layout};
+use serde_json::json;
+
+#[test]
+fn studio_exports_and_api_layouts_are_read_as_layout_data() {
+    let exported = r#"{"configById": {}, "globalVariables": {}, "layout": "3D!1"}"#;
+    assert_eq!(
+        parse_layout(exported).unwrap(),
+        json!({ "configById": {}, "globalVariables": {}, "layout": "3D!1" })
+    );
+
+    let pulled = r#"{"id": "lay_1", "name": "hopper", "data": {"layout": "3D!1"}}"#;
+    assert_eq!(parse_layout(pulled).unwrap(), json!({ "layout": "3D!1" }));
+
+    assert!(parse_layout("[]").is_err());
+}
+
+#[test]
+fn listed_layouts_have_no_data() {
+    let layouts: Vec<Layout> = serde_json::from_str(
+        r#"[{"id": "lay_1", "name": "hopper", "updatedAt": "2024-05-01T12:00:00Z", "permission": "ORG_WRITE"}]"#,
+    )
+    .unwrap();
+    assert_eq!(layouts[0].id, "lay_1");
+    assert_eq!(layouts[0].name, "hopper");
+    assert!(layouts[0].updated_at.is_some());
+    assert!(layouts[0].data.is_none());
+}
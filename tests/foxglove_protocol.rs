@@ -0,0 +1,216 @@
+use deck_robot_remote::{
+    foxglove_gateway::{asset_path, robot_clock_nanos},
+    foxglove_protocol::{
+        extend_server_info, fetch_asset_response, parameter_values, server_time,
+        service_call_response, ChannelTracker, ClientBinary, ClientChannel, ClientRequest,
+        Parameter,
+    },
+};
+use serde_json::json;
+
+#[test]
+fn advertised_channels_are_unadvertised() {
+    let mut channels = ChannelTracker::default();
+    assert_eq!(channels.unadvertise_all(), None);
+
+    channels.observe(
+        &json!({
+            "op": "advertise",
+            "channels": [
+                { "id": 1, "topic": "hopper/tf", "encoding": "protobuf" },
+                { "id": 2, "topic": "hopper/pose", "encoding": "json" },
+            ],
+        })
+        .to_string(),
+    );
+    channels.observe(
+        &json!({ "op": "serverInfo", "name": "steam-deck", "capabilities": [] }).to_string(),
+    );
+    channels.observe(&json!({ "op": "unadvertise", "channelIds": [1] }).to_string());
+    channels.observe("not json");
+
+    let unadvertise: serde_json::Value =
+        serde_json::from_str(&channels.unadvertise_all().unwrap()).unwrap();
+    assert_eq!(
+        unadvertise,
+        json!({ "op": "unadvertise", "channelIds": [2] })
+    );
+}
+
+#[test]
+fn gateway_capabilities_are_added_to_server_info() {
+    let server_info = json!({
+        "op": "serverInfo",
+        "name": "steam-deck",
+        "capabilities": ["clientPublish"],
+    })
+    .to_string();
+    let extended = extend_server_info(&server_info, &["clientPublish", "services"], &["json"]);
+    let extended: serde_json::Value = serde_json::from_str(&extended.unwrap()).unwrap();
+    assert_eq!(
+        extended,
+        json!({
+            "op": "serverInfo",
+            "name": "steam-deck",
+            "capabilities": ["clientPublish", "services"],
+            "supportedEncodings": ["json"],
+        })
+    );
+
+    let advertise = json!({ "op": "advertise", "channels": [] }).to_string();
+    assert_eq!(extend_server_info(&advertise, &["services"], &[]), None);
+}
+
+#[test]
+fn client_requests_are_parsed() {
+    let advertise = json!({
+        "op": "advertise",
+        "channels": [
+            { "id": 3, "topic": "/cmd_vel", "encoding": "json", "schemaName": "Twist" },
+        ],
+    });
+    let ClientRequest::Advertise { channels } = serde_json::from_value(advertise).unwrap() else {
+        panic!("advertise wasn't parsed");
+    };
+    assert_eq!(
+        channels,
+        vec![ClientChannel {
+            id: 3,
+            topic: "/cmd_vel".to_owned(),
+            encoding: "json".to_owned(),
+            schema_name: "Twist".to_owned(),
+        }]
+    );
+
+    let subscribe = json!({ "op": "subscribe", "subscriptions": [] });
+    assert!(matches!(
+        serde_json::from_value(subscribe).unwrap(),
+        ClientRequest::Other
+    ));
+}
+
+#[test]
+fn client_message_data_is_parsed() {
+    let data = [0x01, 7, 0, 0, 0, b'{', b'}'];
+    assert_eq!(
+        ClientBinary::parse(&data),
+        Some(ClientBinary::MessageData {
+            channel_id: 7,
+            payload: b"{}",
+        })
+    );
+    assert_eq!(ClientBinary::parse(&[0x01, 7, 0]), None);
+    assert_eq!(ClientBinary::parse(&[0x7f, 0, 0, 0, 0]), None);
+    assert_eq!(ClientBinary::parse(&[]), None);
+}
+
+#[test]
+fn service_calls_are_parsed_and_answered() {
+    let mut request = vec![0x02, 1, 0, 0, 0, 9, 0, 0, 0, 4, 0, 0, 0];
+    request.extend_from_slice(b"json{}");
+    assert_eq!(
+        ClientBinary::parse(&request),
+        Some(ClientBinary::ServiceCallRequest {
+            service_id: 1,
+            call_id: 9,
+            encoding: "json",
+            payload: b"{}",
+        })
+    );
+    // encoding longer than the message
+    assert_eq!(
+        ClientBinary::parse(&[0x02, 1, 0, 0, 0, 9, 0, 0, 0, 40, 0, 0, 0]),
+        None
+    );
+
+    let mut response = vec![0x03, 1, 0, 0, 0, 9, 0, 0, 0, 4, 0, 0, 0];
+    response.extend_from_slice(b"json[]");
+    assert_eq!(service_call_response(1, 9, "json", b"[]"), response);
+}
+
+#[test]
+fn parameter_values_carry_the_request_id() {
+    let parameters = [Parameter {
+        name: "gait/step_height".to_owned(),
+        value: json!(0.05),
+    }];
+    let reply: serde_json::Value =
+        serde_json::from_str(&parameter_values(&parameters, Some("get"))).unwrap();
+    assert_eq!(
+        reply,
+        json!({
+            "op": "parameterValues",
+            "parameters": [{ "name": "gait/step_height", "value": 0.05 }],
+            "id": "get",
+        })
+    );
+    let update: serde_json::Value =
+        serde_json::from_str(&parameter_values(&parameters, None)).unwrap();
+    assert!(update.get("id").is_none());
+
+    let set = json!({ "op": "setParameters", "parameters": [{ "name": "gait/step_height" }] });
+    let ClientRequest::SetParameters { parameters, id } = serde_json::from_value(set).unwrap()
+    else {
+        panic!("setParameters wasn't parsed");
+    };
+    assert_eq!(parameters[0].value, serde_json::Value::Null);
+    assert_eq!(id, None);
+}
+
+#[test]
+fn asset_responses_carry_the_asset_or_the_error() {
+    let mut found = vec![0x04, 5, 0, 0, 0, 0, 0, 0, 0, 0];
+    found.extend_from_slice(b"solid");
+    assert_eq!(fetch_asset_response(5, Ok(b"solid")), found);
+
+    let mut missing = vec![0x04, 6, 0, 0, 0, 1, 7, 0, 0, 0];
+    missing.extend_from_slice(b"missing");
+    assert_eq!(fetch_asset_response(6, Err("missing")), missing);
+
+    let fetch = json!({ "op": "fetchAsset", "uri": "package://hopper/base.stl", "requestId": 5 });
+    let ClientRequest::FetchAsset { uri, request_id } = serde_json::from_value(fetch).unwrap()
+    else {
+        panic!("fetchAsset wasn't parsed");
+    };
+    assert_eq!(uri, "package://hopper/base.stl");
+    assert_eq!(request_id, 5);
+}
+
+#[test]
+fn asset_paths_stay_inside_the_asset_directory() {
+    assert_eq!(
+        asset_path("package://hopper/meshes/base.stl"),
+        Some("hopper/meshes/base.stl")
+    );
+    assert_eq!(asset_path("package://hopper/../../etc/passwd"), None);
+    assert_eq!(asset_path("package:///etc/passwd"), None);
+    assert_eq!(asset_path("package://"), None);
+    assert_eq!(asset_path("https://example.com/base.stl"), None);
+}
+
+#[test]
+fn server_time_is_little_endian_nanoseconds() {
+    assert_eq!(
+        server_time(0x0102),
+        vec![0x02, 0x02, 0x01, 0, 0, 0, 0, 0, 0]
+    );
+}
+
+#[test]
+fn robot_clock_stamps_are_parsed() {
+    assert_eq!(
+        robot_clock_nanos(b"1700000000000000500"),
+        Some(1_700_000_000_000_000_500)
+    );
+    assert_eq!(
+        robot_clock_nanos(br#""2023-11-14T22:13:20.000000500Z""#),
+        Some(1_700_000_000_000_000_500)
+    );
+    assert_eq!(
+        robot_clock_nanos(br#"{"time": "2023-11-14T22:13:20Z", "gait": 2}"#),
+        Some(1_700_000_000_000_000_000)
+    );
+    assert_eq!(robot_clock_nanos(b"-5"), None);
+    assert_eq!(robot_clock_nanos(br#"{"stamp": 5}"#), None);
+    assert_eq!(robot_clock_nanos(b"not json"), None);
+}
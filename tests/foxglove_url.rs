@@ -0,0 +1,36 @@
+use deck_robot_remote::foxglove_server::FoxgloveUrlBuilder;
+
+#[test]
+fn default_studio_link_matches_app_foxglove_dev() {
+    let url = FoxgloveUrlBuilder::new("ws", "127.0.0.1", 8765)
+        .user("david-weis")
+        .layout_id("ea22e72c-f654-4743-925a-7143a510d390")
+        .build();
+    assert_eq!(
+        url,
+        "https://app.foxglove.dev/david-weis/view?ds=foxglove-websocket&ds.url=ws://127.0.0.1:8765/&layoutId=ea22e72c-f654-4743-925a-7143a510d390"
+    );
+}
+
+#[test]
+fn self_hosted_studio_without_layout() {
+    let url = FoxgloveUrlBuilder::new("wss", "steamdeck.tail1234.ts.net", 8765)
+        .studio_url("https://lichtblick.example.com/")
+        .build();
+    assert_eq!(
+        url,
+        "https://lichtblick.example.com/?ds=foxglove-websocket&ds.url=wss://steamdeck.tail1234.ts.net:8765/"
+    );
+}
+
+#[test]
+fn studio_url_with_query_appends_parameters() {
+    let url = FoxgloveUrlBuilder::new("ws", "127.0.0.1", 8765)
+        .studio_url("http://studio.local/?theme=dark")
+        .layout_id("layout")
+        .build();
+    assert_eq!(
+        url,
+        "http://studio.local/?theme=dark&ds=foxglove-websocket&ds.url=ws://127.0.0.1:8765/&layoutId=layout"
+    );
+}
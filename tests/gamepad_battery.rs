@@ -0,0 +1,86 @@
+mod common;
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    gamepad_battery::{low_battery_alert, low_battery_rumble, BatteryMonitor},
+    messages::{GamepadMessage, InputMessage, PowerState},
+    remote_control,
+};
+
+fn gamepad(power_state: PowerState, battery_percent: Option<u8>) -> GamepadMessage {
+    GamepadMessage {
+        name: "Steam Deck".to_owned(),
+        connected: true,
+        power_state,
+        battery_percent,
+        ..Default::default()
+    }
+}
+
+fn message(gamepads: Vec<(usize, GamepadMessage)>) -> InputMessage {
+    InputMessageBuilder::default().gamepads(gamepads).build()
+}
+
+#[test]
+fn warns_once_when_discharging_below_threshold() {
+    let mut monitor = BatteryMonitor::new(20);
+    let full = message(vec![(0, gamepad(PowerState::Discharging, Some(50)))]);
+    let low = message(vec![(0, gamepad(PowerState::Discharging, Some(19)))]);
+
+    assert!(monitor.update(&full).is_empty());
+    assert_eq!(monitor.update(&low), vec![0]);
+    assert!(monitor.update(&low).is_empty());
+}
+
+#[test]
+fn warns_again_after_charging() {
+    let mut monitor = BatteryMonitor::new(20);
+    let low = message(vec![(0, gamepad(PowerState::Discharging, Some(10)))]);
+    let charging = message(vec![(0, gamepad(PowerState::Charging, Some(12)))]);
+
+    assert_eq!(monitor.update(&low), vec![0]);
+    assert!(monitor.update(&charging).is_empty());
+    assert_eq!(monitor.update(&low), vec![0]);
+}
+
+#[test]
+fn wired_and_unknown_gamepads_dont_warn() {
+    let mut monitor = BatteryMonitor::new(20);
+    let message = message(vec![
+        (0, gamepad(PowerState::Wired, None)),
+        (1, gamepad(PowerState::Unknown, None)),
+        (2, gamepad(PowerState::Charging, Some(5))),
+        (3, gamepad(PowerState::Discharging, Some(5))),
+    ]);
+    assert_eq!(monitor.update(&message), vec![3]);
+}
+
+#[test]
+fn zero_threshold_never_warns() {
+    let mut monitor = BatteryMonitor::new(0);
+    let empty = message(vec![(0, gamepad(PowerState::Discharging, Some(0)))]);
+    assert!(monitor.update(&empty).is_empty());
+}
+
+#[test]
+fn alert_names_the_gamepad() {
+    let alert = low_battery_alert(1, &gamepad(PowerState::Discharging, Some(15)));
+    assert_eq!(alert, "Gamepad 1 - Steam Deck battery low at 15%");
+
+    let rumble = low_battery_rumble(1);
+    assert_eq!(rumble.gamepad_id, Some(1));
+    assert!(rumble.duration_ms > 0);
+}
+
+#[test]
+fn battery_is_encoded_in_protobuf() {
+    let encoded = remote_control::GamepadMessage::from(&gamepad(PowerState::Charging, Some(42)));
+    assert_eq!(
+        encoded.power_state,
+        remote_control::PowerState::Charging as i32
+    );
+    assert_eq!(encoded.battery_percent, Some(42));
+
+    let encoded = remote_control::GamepadMessage::from(&gamepad(PowerState::Wired, None));
+    assert_eq!(encoded.battery_percent, None);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    gamepad::InputEvent,
+    gamepad_events::{validate_thresholds, AxisThresholds, EventDetector},
+    messages::{Axis, Button, GamepadEvent, GamepadMessage, InputMessage},
+};
+
+fn input_message(left_stick_y: Option<f32>) -> InputMessage {
+    let mut gamepad = GamepadMessage::default();
+    if let Some(value) = left_stick_y {
+        gamepad.axis_state.insert(Axis::LeftStickY, value);
+    }
+    InputMessageBuilder::default().gamepad(0, gamepad).build()
+}
+
+fn axis_changed(value: f32) -> InputEvent {
+    InputEvent::AxisChanged {
+        gamepad_id: 0,
+        axis: Axis::LeftStickY,
+        value,
+    }
+}
+
+fn detector() -> EventDetector {
+    EventDetector::new(AxisThresholds::from([(Axis::LeftStickY, 0.5)]))
+}
+
+#[test]
+fn button_edges_are_events() {
+    let mut detector = detector();
+    let pressed = InputEvent::ButtonPressed {
+        gamepad_id: 0,
+        button: Button::South,
+    };
+    assert_eq!(
+        detector.process(&pressed, &input_message(None)),
+        vec![(
+            0,
+            GamepadEvent::ButtonDown {
+                button: Button::South
+            }
+        )]
+    );
+    let released = InputEvent::ButtonReleased {
+        gamepad_id: 0,
+        button: Button::South,
+    };
+    assert_eq!(
+        detector.process(&released, &input_message(None)),
+        vec![(
+            0,
+            GamepadEvent::ButtonUp {
+                button: Button::South
+            }
+        )]
+    );
+}
+
+#[test]
+fn axis_events_fire_once_per_crossing() {
+    let mut detector = detector();
+    assert!(detector
+        .process(&axis_changed(0.3), &input_message(Some(0.3)))
+        .is_empty());
+    assert_eq!(
+        detector.process(&axis_changed(-0.6), &input_message(Some(-0.6))),
+        vec![(
+            0,
+            GamepadEvent::AxisEngaged {
+                axis: Axis::LeftStickY,
+                value: -0.6
+            }
+        )]
+    );
+    assert!(detector
+        .process(&axis_changed(-0.9), &input_message(Some(-0.9)))
+        .is_empty());
+    assert_eq!(
+        detector.process(&axis_changed(0.1), &input_message(Some(0.1))),
+        vec![(
+            0,
+            GamepadEvent::AxisReleased {
+                axis: Axis::LeftStickY,
+                value: 0.1
+            }
+        )]
+    );
+}
+
+#[test]
+fn disconnected_gamepads_release_their_axes() {
+    let mut detector = detector();
+    detector.process(&axis_changed(0.8), &input_message(Some(0.8)));
+
+    let mut message = input_message(None);
+    message.gamepads.clear();
+    let disconnected = InputEvent::Disconnected { gamepad_id: 0 };
+    assert_eq!(
+        detector.process(&disconnected, &message),
+        vec![(
+            0,
+            GamepadEvent::AxisReleased {
+                axis: Axis::LeftStickY,
+                value: 0.0
+            }
+        )]
+    );
+}
+
+#[test]
+fn thresholds_must_be_within_the_axis_range() {
+    assert!(validate_thresholds(&AxisThresholds::from([(Axis::LeftZ, 0.9)])).is_ok());
+    assert!(validate_thresholds(&AxisThresholds::from([(Axis::LeftZ, 0.0)])).is_err());
+    assert!(validate_thresholds(&AxisThresholds::from([(Axis::LeftZ, 1.5)])).is_err());
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    config::{load_profiles, GamepadSelector},
+    gamepad::role_message,
+    messages::{GamepadMessage, InputMessage},
+};
+
+fn gamepad(name: &str, connected: bool) -> GamepadMessage {
+    GamepadMessage {
+        name: name.to_owned(),
+        connected,
+        ..Default::default()
+    }
+}
+
+fn input_message(gamepads: Vec<(usize, GamepadMessage)>) -> InputMessage {
+    InputMessageBuilder::default().gamepads(gamepads).build()
+}
+
+#[test]
+fn selector_matches_name_case_insensitively_and_id() {
+    let by_name = GamepadSelector {
+        name: Some("steam deck".to_owned()),
+        id: None,
+    };
+    assert!(by_name.matches(3, "Steam Deck Controller"));
+    assert!(!by_name.matches(3, "Xbox Controller"));
+
+    let by_both = GamepadSelector {
+        name: Some("xbox".to_owned()),
+        id: Some(1),
+    };
+    assert!(by_both.matches(1, "Xbox Controller"));
+    assert!(!by_both.matches(2, "Xbox Controller"));
+}
+
+#[test]
+fn role_message_contains_only_the_selected_gamepad() {
+    let message = input_message(vec![
+        (0, gamepad("Steam Deck", true)),
+        (1, gamepad("Xbox Controller", true)),
+    ]);
+    let selector = GamepadSelector {
+        name: Some("xbox".to_owned()),
+        id: None,
+    };
+    let role = role_message(&message, &selector);
+    assert_eq!(role.gamepads.len(), 1);
+    assert_eq!(role.gamepads[&1].name, "Xbox Controller");
+    assert_eq!(role.time, message.time);
+}
+
+#[test]
+fn connected_gamepad_fills_role_before_unplugged_one() {
+    let message = input_message(vec![
+        (0, gamepad("Xbox Controller", false)),
+        (4, gamepad("Xbox Controller", true)),
+    ]);
+    let selector = GamepadSelector {
+        name: Some("xbox".to_owned()),
+        id: None,
+    };
+    let role = role_message(&message, &selector);
+    assert!(role.gamepads.contains_key(&4));
+    assert_eq!(role.gamepads.len(), 1);
+}
+
+#[test]
+fn reserved_role_names_are_rejected() {
+    let profile_dir =
+        std::env::temp_dir().join(format!("deck-robot-remote-roles-{}", std::process::id()));
+    std::fs::create_dir_all(&profile_dir).unwrap();
+    std::fs::write(
+        profile_dir.join("tank.yaml"),
+        r#"
+name: tank
+foxglove_layout_id: "layout"
+tailscale_host_filter: tank
+gamepad_roles:
+  feedback:
+    id: 0
+"#,
+    )
+    .unwrap();
+    let result = load_profiles(Some(&profile_dir));
+    std::fs::remove_dir_all(&profile_dir).unwrap();
+    assert!(result.is_err());
+}
@@ -0,0 +1,67 @@
+use std::{path::PathBuf, time::Duration};
+
+use deck_robot_remote::imu::{IioImu, TiltFilter, Vector3};
+
+fn fake_iio_device(name: &str) -> PathBuf {
+    let device =
+        std::env::temp_dir().join(format!("deck-robot-remote-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&device).unwrap();
+    let files = [
+        ("in_accel_scale", "0.5\n"),
+        ("in_accel_x_raw", "0\n"),
+        ("in_accel_y_raw", "2\n"),
+        ("in_accel_z_raw", "-4\n"),
+        ("in_anglvel_scale", "0.01\n"),
+        ("in_anglvel_x_raw", "100\n"),
+        ("in_anglvel_y_raw", "0\n"),
+        ("in_anglvel_z_raw", "-50\n"),
+    ];
+    for (file, value) in files {
+        std::fs::write(device.join(file), value).unwrap();
+    }
+    device
+}
+
+#[test]
+fn reads_scaled_channels() {
+    let device = fake_iio_device("scaled");
+    let imu = IioImu::open(&device).unwrap();
+    let (angular_velocity, linear_acceleration) = imu.read().unwrap();
+    assert_eq!(
+        linear_acceleration,
+        Vector3 {
+            x: 0.0,
+            y: 1.0,
+            z: -2.0
+        }
+    );
+    assert_eq!(
+        angular_velocity,
+        Vector3 {
+            x: 1.0,
+            y: 0.0,
+            z: -0.5
+        }
+    );
+    std::fs::remove_dir_all(device).unwrap();
+}
+
+#[test]
+fn tilt_starts_from_gravity() {
+    let mut filter = TiltFilter::default();
+    let level = Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: 9.81,
+    };
+    let (roll, pitch) = filter.update(Vector3::default(), level, Duration::ZERO);
+    assert_eq!((roll, pitch), (0.0, 0.0));
+
+    // gyro rate is integrated between readings
+    let rate = Vector3 {
+        x: 1.0,
+        ..Default::default()
+    };
+    let (roll, _) = filter.update(rate, level, Duration::from_millis(100));
+    assert!(roll > 0.09 && roll < 0.1);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    input_pipeline::{AxisFilter, AxisSmoothing, InputPipeline},
+    messages::{Axis, GamepadMessage, InputMessage},
+};
+
+fn input_message(connected: bool, value: f32, estop_engaged: bool) -> InputMessage {
+    let gamepad = GamepadMessage {
+        name: "Steam Deck".to_owned(),
+        connected,
+        axis_state: [(Axis::LeftStickY, value), (Axis::RightStickX, value)]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+    InputMessageBuilder::default()
+        .gamepad(0, gamepad)
+        .estop_engaged(estop_engaged)
+        .build()
+}
+
+fn axis(message: &InputMessage, axis: Axis) -> f32 {
+    message.gamepads[&0].axis_state[&axis]
+}
+
+fn smoothing(yaml: &str) -> AxisSmoothing {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+#[test]
+fn exponential_filter_reaches_63_percent_after_one_time_constant() {
+    let filter = AxisFilter {
+        time_constant_ms: 100,
+        max_rate: None,
+    };
+    let value = filter.step(0.0, 1.0, Duration::from_millis(100));
+    assert!((value - 0.632).abs() < 0.001, "{value}");
+}
+
+#[test]
+fn slew_rate_limits_step() {
+    let filter = AxisFilter {
+        time_constant_ms: 0,
+        max_rate: Some(2.0),
+    };
+    assert_eq!(filter.step(0.0, 1.0, Duration::from_millis(100)), 0.2);
+    assert_eq!(filter.step(0.0, -1.0, Duration::from_millis(100)), -0.2);
+    assert!((filter.step(0.9, 1.0, Duration::from_millis(100)) - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn full_deflection_ramps_up_and_unconfigured_axes_pass_through() {
+    let mut pipeline = InputPipeline::new(smoothing("LeftStickY: { max_rate: 5.0 }"));
+    let start = Instant::now();
+    let message = input_message(true, 1.0, false);
+
+    let first = pipeline.process(&message, start);
+    assert_eq!(axis(&first, Axis::LeftStickY), 0.0);
+    assert_eq!(axis(&first, Axis::RightStickX), 1.0);
+
+    let second = pipeline.process(&message, start + Duration::from_millis(100));
+    assert!((axis(&second, Axis::LeftStickY) - 0.5).abs() < 1e-6);
+
+    let third = pipeline.process(&message, start + Duration::from_millis(300));
+    assert_eq!(axis(&third, Axis::LeftStickY), 1.0);
+}
+
+#[test]
+fn estop_and_disconnect_bypass_smoothing() {
+    let mut pipeline = InputPipeline::new(smoothing("LeftStickY: { time_constant_ms: 500 }"));
+    let start = Instant::now();
+    pipeline.process(&input_message(true, 1.0, true), start);
+    let held = pipeline.process(
+        &input_message(true, 1.0, false),
+        start + Duration::from_secs(10),
+    );
+    assert!(axis(&held, Axis::LeftStickY) > 0.99);
+
+    let estop = pipeline.process(
+        &input_message(true, 0.0, true),
+        start + Duration::from_millis(10_010),
+    );
+    assert_eq!(axis(&estop, Axis::LeftStickY), 0.0);
+
+    pipeline.process(
+        &input_message(true, 1.0, false),
+        start + Duration::from_secs(20),
+    );
+    let disconnected = pipeline.process(
+        &input_message(false, 0.0, false),
+        start + Duration::from_millis(20_010),
+    );
+    assert_eq!(axis(&disconnected, Axis::LeftStickY), 0.0);
+}
+
+#[test]
+fn empty_smoothing_returns_message_unchanged() {
+    let mut pipeline = InputPipeline::new(AxisSmoothing::new());
+    let message = input_message(true, 0.7, false);
+    let output = pipeline.process(&message, Instant::now());
+    assert!(output.same_state(&message));
+}
+
+#[test]
+fn rejects_non_positive_rate() {
+    let filter: AxisFilter = serde_yaml::from_str("max_rate: 0.0").unwrap();
+    assert!(filter.validate().is_err());
+}
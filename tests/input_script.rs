@@ -0,0 +1,82 @@
+mod common;
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    gamepad::InputEvent,
+    input_script::{InputScript, ScriptMessage, ScriptSource},
+    messages::{Axis, Button, GamepadMessage, InputMessage},
+};
+use serde_json::json;
+
+const GAIT_SCRIPT: &str = r#"
+fn on_event(event, state) {
+    if event.type == "button_down" && event.button == "DPadUp" {
+        this.gait = if this.gait == () { 1 } else { this.gait + 1 };
+        publish("hopper/gait", #{ gait: this.gait });
+    }
+}
+
+fn on_tick(state) {
+    for id in state.gamepads.keys() {
+        state.gamepads[id].axis_state.LeftStickY *= 0.5;
+    }
+    state
+}
+"#;
+
+fn input_message(left_stick_y: f32) -> InputMessage {
+    let mut gamepad = GamepadMessage::default();
+    gamepad.axis_state.insert(Axis::LeftStickY, left_stick_y);
+    InputMessageBuilder::default().gamepad(0, gamepad).build()
+}
+
+#[test]
+fn script_state_is_kept_between_events() {
+    let mut script = InputScript::compile(GAIT_SCRIPT).unwrap();
+    let pressed = InputEvent::ButtonPressed {
+        gamepad_id: 0,
+        button: Button::DPadUp,
+    };
+    let message = input_message(0.0);
+    script.on_event(&pressed, &message).unwrap();
+    let published = script.on_event(&pressed, &message).unwrap();
+    assert_eq!(
+        published,
+        vec![ScriptMessage {
+            topic: "hopper/gait".to_owned(),
+            payload: json!({ "gait": 2 }),
+        }]
+    );
+
+    let released = InputEvent::ButtonReleased {
+        gamepad_id: 0,
+        button: Button::DPadUp,
+    };
+    assert!(script.on_event(&released, &message).unwrap().is_empty());
+}
+
+#[test]
+fn on_tick_changes_the_published_message() {
+    let mut script = InputScript::compile(GAIT_SCRIPT).unwrap();
+    let mut message = input_message(0.8);
+    assert!(script.on_tick(&mut message).unwrap().is_empty());
+    assert_eq!(message.gamepads[&0].axis_state[&Axis::LeftStickY], 0.4);
+}
+
+#[test]
+fn scripts_without_handlers_do_nothing() {
+    let mut script = InputScript::compile("let speed = 1;").unwrap();
+    let mut message = input_message(0.8);
+    script.on_tick(&mut message).unwrap();
+    assert!(message.same_state(&input_message(0.8)));
+}
+
+#[test]
+fn runaway_and_broken_scripts_fail() {
+    let mut script = InputScript::compile("fn on_tick(state) { loop {} }").unwrap();
+    assert!(script.on_tick(&mut input_message(0.0)).is_err());
+
+    assert!(InputScript::compile("fn on_tick(state) {").is_err());
+    let source: ScriptSource = serde_yaml::from_str("source: 'fn on_tick(state) {'").unwrap();
+    assert!(source.validate().is_err());
+}
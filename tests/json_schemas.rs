@@ -0,0 +1,36 @@
+use deck_robot_remote::{error::ConfigError, json_schemas::load_json_schemas};
+
+#[test]
+fn builtin_schemas_are_valid_json() {
+    let schemas = load_json_schemas(None).unwrap();
+    assert!(schemas.contains_key("GENERIC_JSON_SCHEMA"));
+    assert!(schemas.contains_key("IKEA_DIMMER_JSON_SCHEMA"));
+    for (name, schema) in &schemas {
+        serde_json::from_str::<serde_json::Value>(schema)
+            .unwrap_or_else(|err| panic!("{name}: {err}"));
+    }
+}
+
+#[test]
+fn schema_dir_adds_and_replaces_schemas_by_file_name() {
+    let dir =
+        std::env::temp_dir().join(format!("deck-robot-remote-schemas-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("AQARA_BUTTON.json"), r#"{"type": "object"}"#).unwrap();
+    std::fs::write(
+        dir.join("GENERIC_JSON_SCHEMA.json"),
+        r#"{"title": "Replaced"}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a schema").unwrap();
+
+    let schemas = load_json_schemas(Some(&dir)).unwrap();
+    assert_eq!(schemas["AQARA_BUTTON"], r#"{"type": "object"}"#);
+    assert_eq!(schemas["GENERIC_JSON_SCHEMA"], r#"{"title": "Replaced"}"#);
+    assert!(!schemas.contains_key("notes"));
+
+    std::fs::write(dir.join("BROKEN.json"), "{").unwrap();
+    let err = load_json_schemas(Some(&dir)).unwrap_err();
+    assert!(matches!(err, ConfigError::Invalid { .. }));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
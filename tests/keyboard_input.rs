@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::KeyCode;
+use deck_robot_remote::{
+    keyboard_input::{key_target, KeyTarget, KeyboardState, KEY_HOLD_TIMEOUT},
+    messages::{Axis, Button},
+};
+
+fn press(state: &mut KeyboardState, code: KeyCode, now: Instant) {
+    state.press(key_target(code).unwrap(), now);
+}
+
+#[test]
+fn wasd_and_arrows_drive_the_left_stick() {
+    assert_eq!(key_target(KeyCode::Char('W')), key_target(KeyCode::Up));
+    assert_eq!(
+        key_target(KeyCode::Char('a')),
+        Some(KeyTarget::Axis {
+            axis: Axis::LeftStickX,
+            positive: false
+        })
+    );
+    assert_eq!(
+        key_target(KeyCode::Esc),
+        Some(KeyTarget::Button(Button::Mode))
+    );
+    assert_eq!(key_target(KeyCode::Char('x')), None);
+}
+
+#[test]
+fn opposite_keys_cancel_out() {
+    let now = Instant::now();
+    let mut state = KeyboardState::default();
+    press(&mut state, KeyCode::Char('w'), now);
+    press(&mut state, KeyCode::Char('d'), now);
+    assert_eq!(state.axis(Axis::LeftStickY), 1.0);
+    assert_eq!(state.axis(Axis::LeftStickX), 1.0);
+
+    press(&mut state, KeyCode::Down, now);
+    assert_eq!(state.axis(Axis::LeftStickY), 0.0);
+
+    state.release(key_target(KeyCode::Char('w')).unwrap());
+    assert_eq!(state.axis(Axis::LeftStickY), -1.0);
+}
+
+#[test]
+fn keys_without_repeat_are_released() {
+    let start = Instant::now();
+    let mut state = KeyboardState::default();
+    press(&mut state, KeyCode::Char(' '), start);
+    press(&mut state, KeyCode::Char('q'), start);
+    // auto repeat keeps the turn key alive
+    press(&mut state, KeyCode::Char('q'), start + KEY_HOLD_TIMEOUT / 2);
+
+    state.release_stale(
+        start + KEY_HOLD_TIMEOUT + Duration::from_millis(1),
+        KEY_HOLD_TIMEOUT,
+    );
+    assert!(!state.pressed(Button::South));
+    assert_eq!(state.axis(Axis::RightStickX), -1.0);
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use deck_robot_remote::link_stats::PingWindow;
+
+#[test]
+fn empty_window_has_no_loss() {
+    let message = PingWindow::default().message(chrono::Utc::now());
+    assert_eq!(message.rtt_ms, None);
+    assert_eq!(message.rtt_avg_ms, None);
+    assert_eq!(message.loss_ratio, 0.0);
+}
+
+#[test]
+fn lost_pings_count_towards_loss() {
+    let mut window = PingWindow::default();
+    window.record(Some(Duration::from_millis(10)));
+    window.record(Some(Duration::from_millis(30)));
+    window.record(None);
+    window.record(Some(Duration::from_millis(20)));
+
+    let message = window.message(chrono::Utc::now());
+    assert_eq!(message.rtt_ms, Some(20.0));
+    assert_eq!(message.rtt_avg_ms, Some(20.0));
+    assert_eq!(message.rtt_max_ms, Some(30.0));
+    assert_eq!(message.loss_ratio, 0.25);
+    assert_eq!(message.window, 4);
+}
+
+#[test]
+fn window_keeps_recent_pings() {
+    let mut window = PingWindow::default();
+    for _ in 0..100 {
+        window.record(None);
+    }
+    window.record(Some(Duration::from_millis(5)));
+
+    let message = window.message(chrono::Utc::now());
+    assert_eq!(message.window, 20);
+    assert_eq!(message.loss_ratio, 0.95);
+}
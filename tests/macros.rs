@@ -0,0 +1,117 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    macros::{MacroConfig, MacroEngine},
+    messages::{Axis, Button, GamepadMessage, InputMessage},
+};
+
+fn input(value: f32, buttons: &[Button], estop_engaged: bool) -> InputMessage {
+    let gamepad = GamepadMessage {
+        name: "Steam Deck".to_owned(),
+        connected: true,
+        axis_state: [(Axis::LeftStickY, value)].into_iter().collect(),
+        button_down: buttons.iter().map(|button| (*button, true)).collect(),
+        ..Default::default()
+    };
+    InputMessageBuilder::default()
+        .gamepad(0, gamepad)
+        .estop_engaged(estop_engaged)
+        .build()
+}
+
+fn engine() -> MacroEngine {
+    MacroEngine::new(MacroConfig {
+        record_button: Button::LeftPaddle,
+        play_button: Button::RightPaddle,
+    })
+}
+
+fn axis(message: &InputMessage) -> f32 {
+    message.gamepads[&0].axis_state[&Axis::LeftStickY]
+}
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+/// Records 0.0, 0.5 and 1.0 at 0, 100 and 200 ms
+fn record(engine: &mut MacroEngine, start: Instant) {
+    for (offset, value) in [(0, 0.0), (100, 0.5), (200, 1.0)] {
+        let live = input(value, &[Button::LeftPaddle], false);
+        assert!(engine.process(&live, start + ms(offset)).is_none());
+        assert!(engine.is_recording());
+    }
+    assert!(engine
+        .process(&input(0.0, &[], false), start + ms(300))
+        .is_none());
+    assert!(!engine.is_recording());
+    assert_eq!(engine.duration(), ms(200));
+}
+
+#[test]
+fn replays_with_original_timing() {
+    let mut engine = engine();
+    let start = Instant::now();
+    record(&mut engine, start);
+
+    let play = start + ms(1000);
+    let first = engine
+        .process(&input(0.0, &[Button::RightPaddle], false), play)
+        .unwrap();
+    assert_eq!(axis(&first), 0.0);
+    assert!(!first.gamepads[&0]
+        .button_down
+        .contains_key(&Button::LeftPaddle));
+
+    let idle = input(0.0, &[], false);
+    assert_eq!(axis(&engine.process(&idle, play + ms(150)).unwrap()), 0.5);
+    assert_eq!(axis(&engine.process(&idle, play + ms(200)).unwrap()), 1.0);
+    assert!(engine.process(&idle, play + ms(250)).is_none());
+    assert!(!engine.is_playing());
+}
+
+#[test]
+fn play_button_toggles_and_estop_aborts() {
+    let mut engine = engine();
+    let start = Instant::now();
+    record(&mut engine, start);
+
+    let play_button = input(0.0, &[Button::RightPaddle], false);
+    let idle = input(0.0, &[], false);
+    engine.process(&play_button, start + ms(1000));
+    // held, not a second press
+    assert!(engine.process(&play_button, start + ms(1050)).is_some());
+    engine.process(&idle, start + ms(1100));
+    assert!(engine.process(&play_button, start + ms(1150)).is_none());
+    assert!(!engine.is_playing());
+
+    engine.process(&idle, start + ms(2000));
+    engine.process(&play_button, start + ms(2050));
+    assert!(engine.is_playing());
+    assert!(engine
+        .process(&input(0.0, &[], true), start + ms(2100))
+        .is_none());
+    assert!(!engine.is_playing());
+}
+
+#[test]
+fn nothing_to_play_without_recording() {
+    let mut engine = engine();
+    let now = Instant::now();
+    assert!(engine
+        .process(&input(0.3, &[Button::RightPaddle], false), now)
+        .is_none());
+    assert!(!engine.is_playing());
+}
+
+#[test]
+fn buttons_must_differ() {
+    let config = MacroConfig {
+        record_button: Button::South,
+        play_button: Button::South,
+    };
+    assert!(config.validate().is_err());
+}
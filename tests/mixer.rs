@@ -0,0 +1,92 @@
+mod common;
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    config::GamepadSelector,
+    messages::{Axis, GamepadMessage, InputMessage},
+    mixer::{mix_twist, TwistMixerConfig},
+};
+
+fn gamepad(name: &str, connected: bool, axes: &[(Axis, f32)]) -> GamepadMessage {
+    GamepadMessage {
+        name: name.to_owned(),
+        connected,
+        axis_state: axes.iter().copied().collect(),
+        ..Default::default()
+    }
+}
+
+fn input_message(gamepads: Vec<(usize, GamepadMessage)>) -> InputMessage {
+    InputMessageBuilder::default().gamepads(gamepads).build()
+}
+
+fn mixer_config() -> TwistMixerConfig {
+    serde_yaml::from_str(
+        r#"
+topic: "hopper/cmd_vel"
+max_linear_velocity: 0.4
+max_angular_velocity: 2.0
+"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn sticks_scale_to_max_velocities() {
+    let message = input_message(vec![(
+        0,
+        gamepad(
+            "Steam Deck",
+            true,
+            &[(Axis::LeftStickY, 0.5), (Axis::RightStickX, 1.0)],
+        ),
+    )]);
+    let twist = mix_twist(&message, &mixer_config());
+    let linear = twist.linear.unwrap();
+    let angular = twist.angular.unwrap();
+    assert!((linear.x - 0.2).abs() < 1e-6);
+    assert_eq!(linear.y, 0.0);
+    // stick right turns clockwise
+    assert!((angular.z + 2.0).abs() < 1e-6);
+}
+
+#[test]
+fn strafing_is_mixed_only_when_configured() {
+    let message = input_message(vec![(
+        0,
+        gamepad("Steam Deck", true, &[(Axis::LeftStickX, 1.0)]),
+    )]);
+    let mut config = mixer_config();
+    assert_eq!(mix_twist(&message, &config).linear.unwrap().y, 0.0);
+
+    config.linear_y_axis = Some(Axis::LeftStickX);
+    assert!((mix_twist(&message, &config).linear.unwrap().y + 0.4).abs() < 1e-6);
+}
+
+#[test]
+fn disconnected_or_unselected_gamepads_command_zero_velocity() {
+    let message = input_message(vec![
+        (0, gamepad("Steam Deck", false, &[(Axis::LeftStickY, 1.0)])),
+        (
+            1,
+            gamepad("Xbox Controller", true, &[(Axis::LeftStickY, 1.0)]),
+        ),
+    ]);
+    let mut config = mixer_config();
+    config.gamepad = Some(GamepadSelector {
+        name: Some("steam deck".to_owned()),
+        id: None,
+    });
+    assert_eq!(mix_twist(&message, &config).linear.unwrap().x, 0.0);
+
+    config.gamepad = None;
+    assert!((mix_twist(&message, &config).linear.unwrap().x - 0.4).abs() < 1e-6);
+}
+
+#[test]
+fn negative_max_velocity_is_rejected() {
+    let mut config = mixer_config();
+    assert!(config.validate().is_ok());
+    config.max_angular_velocity = -1.0;
+    assert!(config.validate().is_err());
+}
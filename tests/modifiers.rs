@@ -0,0 +1,121 @@
+mod common;
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    messages::{Axis, Button, GamepadMessage, InputMessage},
+    modifiers::{Modifier, ModifierConfig},
+};
+
+fn input_message(held: &[Button]) -> InputMessage {
+    let gamepad = GamepadMessage {
+        button_down: held.iter().map(|button| (*button, true)).collect(),
+        axis_state: [(Axis::LeftStickY, 0.8), (Axis::RightZ, 0.5)]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+    InputMessageBuilder::default().gamepad(0, gamepad).build()
+}
+
+fn modifier(name: &str, button: Button, scale: f32) -> Modifier {
+    Modifier {
+        name: name.to_owned(),
+        button,
+        scale,
+    }
+}
+
+fn modifiers() -> ModifierConfig {
+    ModifierConfig {
+        scale: 0.5,
+        axes: vec![Axis::LeftStickY],
+        modes: vec![
+            modifier("turbo", Button::LeftTrigger2, 1.0),
+            modifier("precision", Button::RightTrigger2, 0.25),
+        ],
+    }
+}
+
+fn apply(held: &[Button]) -> GamepadMessage {
+    let live = input_message(held);
+    let mut message = live.clone();
+    modifiers().apply(&live, &mut message);
+    message.gamepads[&0].clone()
+}
+
+#[test]
+fn base_scale_applies_without_a_held_modifier() {
+    let gamepad = apply(&[]);
+    assert_eq!(gamepad.mode, None);
+    assert_eq!(gamepad.axis_state[&Axis::LeftStickY], 0.4);
+    // axes that aren't listed are left alone
+    assert_eq!(gamepad.axis_state[&Axis::RightZ], 0.5);
+}
+
+#[test]
+fn held_modifier_sets_scale_and_mode() {
+    let gamepad = apply(&[Button::LeftTrigger2]);
+    assert_eq!(gamepad.mode.as_deref(), Some("turbo"));
+    assert_eq!(gamepad.axis_state[&Axis::LeftStickY], 0.8);
+
+    let gamepad = apply(&[Button::RightTrigger2]);
+    assert_eq!(gamepad.mode.as_deref(), Some("precision"));
+    assert_eq!(gamepad.axis_state[&Axis::LeftStickY], 0.2);
+}
+
+#[test]
+fn first_listed_modifier_wins() {
+    let gamepad = apply(&[Button::RightTrigger2, Button::LeftTrigger2]);
+    assert_eq!(gamepad.mode.as_deref(), Some("turbo"));
+}
+
+#[test]
+fn only_live_input_selects_a_modifier() {
+    // a replayed message recorded with turbo held
+    let live = input_message(&[]);
+    let mut message = input_message(&[Button::LeftTrigger2]);
+    modifiers().apply(&live, &mut message);
+    assert_eq!(message.gamepads[&0].mode, None);
+    assert_eq!(message.gamepads[&0].axis_state[&Axis::LeftStickY], 0.4);
+}
+
+#[test]
+fn invalid_modifiers_are_rejected() {
+    assert!(modifiers().validate().is_ok());
+
+    let mut config = modifiers();
+    config.scale = 1.5;
+    assert!(config.validate().is_err());
+
+    let mut config = modifiers();
+    config.modes[1].scale = 0.0;
+    assert!(config.validate().is_err());
+
+    let mut config = modifiers();
+    config.modes[1].button = Button::LeftTrigger2;
+    assert!(config.validate().is_err());
+
+    let mut config = modifiers();
+    config.modes[1].name = "turbo".to_owned();
+    assert!(config.validate().is_err());
+
+    let mut config = modifiers();
+    config.modes.clear();
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn parses_profile_section() {
+    let config: ModifierConfig = serde_yaml::from_str(
+        r#"
+axes: [LeftStickY]
+modes:
+  - name: turbo
+    button: LeftTrigger2
+    scale: 1.0
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.scale, 1.0);
+    assert_eq!(config.modes[0].button, Button::LeftTrigger2);
+}
@@ -0,0 +1,15 @@
+use deck_robot_remote::mqtt_bridge::MqttBridgeConfig;
+
+#[test]
+fn mqtt_config_defaults_to_standard_port() {
+    let config: MqttBridgeConfig = serde_yaml::from_str(
+        r#"
+host: "hopper.local"
+topics: ["zigbee2mqtt/#"]
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.port, 1883);
+    assert_eq!(config.client_id, None);
+    assert_eq!(config.topics, vec!["zigbee2mqtt/#"]);
+}
@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use chrono::Utc;
+use deck_robot_remote::operator_station::{parse_wireless_signal, SystemPaths};
+
+const WIRELESS: &str = "\
+Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE
+ face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22
+wlan0: 0000   56.  -54.  -256        0      0      0      0     12        0
+";
+
+fn fake_system(name: &str, capacity: &str, status: &str) -> (PathBuf, SystemPaths) {
+    let root =
+        std::env::temp_dir().join(format!("deck-robot-remote-{name}-{}", std::process::id()));
+    let files = [
+        ("power_supply/ACAD/type", "Mains\n"),
+        ("power_supply/BAT1/type", "Battery\n"),
+        ("power_supply/BAT1/capacity", capacity),
+        ("power_supply/BAT1/status", status),
+        ("thermal/thermal_zone0/temp", "45000\n"),
+        ("thermal/thermal_zone1/temp", "61500\n"),
+        ("wireless", WIRELESS),
+    ];
+    for (file, value) in files {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, value).unwrap();
+    }
+    let paths = SystemPaths {
+        power_supply_dir: root.join("power_supply"),
+        thermal_dir: root.join("thermal"),
+        wireless_file: root.join("wireless"),
+    };
+    (root, paths)
+}
+
+#[test]
+fn reads_battery_temperature_and_wifi() {
+    let (root, paths) = fake_system("station", "82\n", "Discharging\n");
+    let message = paths.read(Utc::now());
+    std::fs::remove_dir_all(root).unwrap();
+    assert_eq!(message.battery_percent, Some(82.0));
+    assert_eq!(message.battery_charging, Some(false));
+    assert!(!message.battery_low);
+    assert_eq!(message.cpu_temperature_celsius, Some(61.5));
+    assert_eq!(message.wifi_signal_dbm, Some(-54.0));
+}
+
+#[test]
+fn low_battery_is_only_reported_while_discharging() {
+    let (root, paths) = fake_system("station-low", "9\n", "Discharging\n");
+    assert!(paths.read(Utc::now()).battery_low);
+    std::fs::write(root.join("power_supply/BAT1/status"), "Charging\n").unwrap();
+    assert!(!paths.read(Utc::now()).battery_low);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn missing_sources_are_empty() {
+    let missing = std::env::temp_dir().join("deck-robot-remote-no-such-dir");
+    let paths = SystemPaths {
+        power_supply_dir: missing.clone(),
+        thermal_dir: missing.clone(),
+        wireless_file: missing,
+    };
+    let message = paths.read(Utc::now());
+    assert_eq!(message.battery_percent, None);
+    assert_eq!(message.cpu_temperature_celsius, None);
+    assert_eq!(message.wifi_signal_dbm, None);
+    // no wireless interface below the header
+    assert_eq!(
+        parse_wireless_signal(&WIRELESS[..WIRELESS.find("wlan0").unwrap()]),
+        None
+    );
+}
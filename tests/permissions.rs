@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+
+use deck_robot_remote::{
+    doctor::CheckStatus,
+    permissions::{
+        has_joystick_buttons, is_valve_hid_device, DeviceAccess, DeviceStatus, PermissionReport,
+    },
+};
+
+fn device(path: &str, group: &str, access: DeviceAccess, gamepad: bool) -> DeviceStatus {
+    DeviceStatus {
+        path: PathBuf::from(path),
+        group: Some(group.to_owned()),
+        access,
+        gamepad,
+    }
+}
+
+/// A Deck with working permissions still has root only power button and lid switch nodes
+fn steam_deck_report() -> PermissionReport {
+    PermissionReport {
+        event_devices: vec![
+            device(
+                "/dev/input/event0",
+                "root",
+                DeviceAccess::PermissionDenied,
+                false,
+            ),
+            device(
+                "/dev/input/event1",
+                "root",
+                DeviceAccess::PermissionDenied,
+                false,
+            ),
+            device("/dev/input/event7", "input", DeviceAccess::Readable, true),
+        ],
+        hidraw_devices: vec![
+            device(
+                "/dev/hidraw0",
+                "root",
+                DeviceAccess::PermissionDenied,
+                false,
+            ),
+            device("/dev/hidraw2", "root", DeviceAccess::Readable, true),
+        ],
+    }
+}
+
+#[test]
+fn devices_that_arent_gamepads_are_ignored() {
+    let report = steam_deck_report();
+    assert!(report.is_ok());
+    assert!(report.denied_groups().is_empty());
+    assert!(!report.needs_udev_rule());
+    assert_eq!(report.doctor_check().status, CheckStatus::Ok);
+}
+
+#[test]
+fn unreadable_gamepad_event_device_needs_group() {
+    let mut report = steam_deck_report();
+    report.event_devices[2].access = DeviceAccess::PermissionDenied;
+    assert!(!report.is_ok());
+    assert_eq!(report.denied_groups(), vec![String::from("input")]);
+
+    let check = report.doctor_check();
+    assert_eq!(check.status, CheckStatus::Failed);
+    assert!(
+        check.detail.contains("/dev/input/event7"),
+        "{}",
+        check.detail
+    );
+    assert!(
+        !check.detail.contains("/dev/input/event0"),
+        "{}",
+        check.detail
+    );
+}
+
+#[test]
+fn unreadable_deck_controls_need_udev_rule() {
+    let mut report = steam_deck_report();
+    report.hidraw_devices[1].access = DeviceAccess::PermissionDenied;
+    assert!(!report.is_ok());
+    assert!(report.needs_udev_rule());
+    assert!(report.denied_groups().is_empty());
+}
+
+#[test]
+fn no_gamepad_fails() {
+    let report = PermissionReport {
+        event_devices: vec![device(
+            "/dev/input/event0",
+            "input",
+            DeviceAccess::Readable,
+            false,
+        )],
+        hidraw_devices: vec![],
+    };
+    assert!(!report.is_ok());
+    assert_eq!(report.doctor_check().status, CheckStatus::Failed);
+    assert!(!PermissionReport::default().is_ok());
+}
+
+#[test]
+fn joystick_buttons_are_found_in_capability_bitmap() {
+    // BTN_GAMEPAD block in the fifth word, with keyboard keys of the Deck's controls below it
+    assert!(has_joystick_buttons("7fff000000000000 0 0 0 ffff0000"));
+    // BTN_JOYSTICK only, as reported by flight sticks
+    assert!(has_joystick_buttons("100000000 0 0 0 0"));
+    // power button, KEY_POWER
+    assert!(!has_joystick_buttons("10000000000000 0"));
+    assert!(!has_joystick_buttons(""));
+}
+
+#[test]
+fn valve_hid_devices_are_recognized() {
+    assert!(is_valve_hid_device(
+        "DRIVER=hid-steam\nHID_ID=0003:000028DE:00001205\nHID_NAME=Steam Deck Controller\n"
+    ));
+    assert!(!is_valve_hid_device(
+        "DRIVER=hid-generic\nHID_ID=0018:000004F3:00003150\n"
+    ));
+    assert!(!is_valve_hid_device("DRIVER=hid-generic\n"));
+}
@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use deck_robot_remote::{
+    foxglove_server::DiscoveredSchema,
+    proto_descriptors::{descriptor_pool, load_descriptor_files},
+    DESCRIPTOR_POOL,
+};
+use prost::Message;
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+};
+
+/// `rover.Battery` with a single float field
+fn battery_descriptor_set() -> FileDescriptorSet {
+    FileDescriptorSet {
+        file: vec![FileDescriptorProto {
+            name: Some("rover/battery.proto".to_owned()),
+            package: Some("rover".to_owned()),
+            syntax: Some("proto3".to_owned()),
+            message_type: vec![DescriptorProto {
+                name: Some("Battery".to_owned()),
+                field: vec![FieldDescriptorProto {
+                    name: Some("voltage".to_owned()),
+                    number: Some(1),
+                    label: Some(Label::Optional as i32),
+                    r#type: Some(Type::Float as i32),
+                    json_name: Some("voltage".to_owned()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    }
+}
+
+fn write_descriptor_set(name: &str, descriptor_set: &FileDescriptorSet) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "deck-robot-remote-descriptors-{name}-{}.bin",
+        std::process::id()
+    ));
+    std::fs::write(&path, descriptor_set.encode_to_vec()).unwrap();
+    path
+}
+
+// one test because the runtime pool is shared by the whole test binary
+#[test]
+fn descriptor_files_extend_and_replace_the_runtime_pool() {
+    let battery = write_descriptor_set("battery", &battery_descriptor_set());
+    load_descriptor_files(&[battery.clone()]).unwrap();
+    let pool = descriptor_pool();
+    assert!(pool.get_message_by_name("rover.Battery").is_some());
+    assert!(pool.get_message_by_name("foxglove.Log").is_some());
+    assert!(matches!(
+        DiscoveredSchema::parse("rover/battery", "rover.Battery"),
+        Some(DiscoveredSchema::Protobuf(_))
+    ));
+    // the pool compiled into the remote is left alone
+    assert!(DESCRIPTOR_POOL
+        .get_message_by_name("rover.Battery")
+        .is_none());
+
+    // files that failed to load keep the previous types
+    let missing = std::env::temp_dir().join("deck-robot-remote-descriptors-missing.bin");
+    assert!(load_descriptor_files(&[missing]).is_err());
+    let garbage = std::env::temp_dir().join(format!(
+        "deck-robot-remote-descriptors-garbage-{}.bin",
+        std::process::id()
+    ));
+    std::fs::write(&garbage, b"not a descriptor set").unwrap();
+    assert!(load_descriptor_files(&[garbage.clone()]).is_err());
+    assert!(descriptor_pool()
+        .get_message_by_name("rover.Battery")
+        .is_some());
+
+    // a reload without the file drops its types
+    load_descriptor_files(&[]).unwrap();
+    assert!(descriptor_pool()
+        .get_message_by_name("rover.Battery")
+        .is_none());
+
+    // sets written with --include_imports repeat built-in files
+    let mut with_imports = battery_descriptor_set();
+    let log_file = DESCRIPTOR_POOL
+        .get_message_by_name("foxglove.Log")
+        .unwrap()
+        .parent_file()
+        .file_descriptor_proto()
+        .clone();
+    with_imports.file.insert(0, log_file);
+    let with_imports_path = write_descriptor_set("imports", &with_imports);
+    load_descriptor_files(&[with_imports_path.clone()]).unwrap();
+    assert!(descriptor_pool()
+        .get_message_by_name("rover.Battery")
+        .is_some());
+    load_descriptor_files(&[]).unwrap();
+
+    for path in [battery, garbage, with_imports_path] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+
+use deck_robot_remote::{
+    messages::{Axis, Button, GamepadMessage},
+    remote_control,
+};
+
+#[test]
+fn raw_controls_are_serialized_as_strings() {
+    assert_eq!(
+        serde_json::to_string(&Button::Raw(305)).unwrap(),
+        r#""Raw(305)""#
+    );
+    assert_eq!(serde_json::to_string(&Axis::Raw(7)).unwrap(), r#""Raw(7)""#);
+    assert_eq!(serde_json::to_string(&Button::South).unwrap(), r#""South""#);
+}
+
+#[test]
+fn raw_controls_round_trip_as_map_keys() {
+    let gamepad = GamepadMessage {
+        button_down: BTreeMap::from([(Button::Raw(305), true), (Button::South, false)]),
+        axis_state: BTreeMap::from([(Axis::Raw(7), 0.5)]),
+        ..Default::default()
+    };
+    let json = serde_json::to_value(&gamepad).unwrap();
+    assert_eq!(json["button_down"]["Raw(305)"], true);
+    assert_eq!(json["axis_state"]["Raw(7)"], 0.5);
+
+    let decoded: GamepadMessage = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded, gamepad);
+
+    let proto = remote_control::GamepadMessage::from(&gamepad);
+    assert_eq!(proto.button_down.get("Raw(305)"), Some(&true));
+}
+
+#[test]
+fn controls_are_parsed_from_their_names() {
+    assert_eq!("Raw(305)".parse::<Button>().unwrap(), Button::Raw(305));
+    assert_eq!("LeftPaddle".parse::<Button>().unwrap(), Button::LeftPaddle);
+    assert_eq!("DPadX".parse::<Axis>().unwrap(), Axis::DPadX);
+    assert!("Raw(-1)".parse::<Button>().is_err());
+    assert!("Paddle".parse::<Button>().is_err());
+    assert!(serde_yaml::from_str::<Axis>("Raw(x)").is_err());
+}
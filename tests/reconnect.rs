@@ -0,0 +1,20 @@
+use deck_robot_remote::reconnect::{LinkTracker, LinkTransition};
+
+#[test]
+fn first_connection_is_not_a_restore() {
+    let mut tracker = LinkTracker::default();
+    assert_eq!(tracker.update(false), None);
+    assert_eq!(tracker.update(true), None);
+    assert_eq!(tracker, LinkTracker::Up);
+}
+
+#[test]
+fn lost_and_restored_are_reported_once() {
+    let mut tracker = LinkTracker::default();
+    tracker.update(true);
+    assert_eq!(tracker.update(false), Some(LinkTransition::Lost));
+    assert_eq!(tracker.update(false), None);
+    assert_eq!(tracker, LinkTracker::Down);
+    assert_eq!(tracker.update(true), Some(LinkTransition::Restored));
+    assert_eq!(tracker.update(true), None);
+}
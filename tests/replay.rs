@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use deck_robot_remote::replay::{parse_json_lines, RecordedPayload, ReplayControls};
+
+#[test]
+fn json_lines_are_sorted_and_relative_to_the_first_sample() {
+    let recording = r#"
+{"log_time_ns": 1000500000, "topic": "remote-control/gamepad", "payload": {"estop_engaged": false}}
+{"log_time_ns": 1000000000, "topic": "hopper/log", "payload": "INFO started"}
+"#;
+    let samples = parse_json_lines(recording).unwrap();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0].offset, Duration::ZERO);
+    assert_eq!(samples[0].topic, "hopper/log");
+    assert_eq!(
+        samples[0].payload,
+        RecordedPayload::Text("INFO started".to_owned())
+    );
+    assert_eq!(samples[1].offset, Duration::from_millis(500));
+    assert_eq!(
+        samples[1].payload,
+        RecordedPayload::Text(r#"{"estop_engaged":false}"#.to_owned())
+    );
+}
+
+#[test]
+fn invalid_lines_report_their_line_number() {
+    let err = parse_json_lines("{\"topic\": \"a\"}\n").unwrap_err();
+    assert!(format!("{err:#}").contains("line 1"));
+}
+
+#[test]
+fn control_topics_match_with_a_robot_namespace() {
+    let controls = ReplayControls {
+        gamepad_topic: Some("remote-control/gamepad".to_owned()),
+        twist_topics: vec!["cmd_vel".to_owned()],
+        ..Default::default()
+    };
+    assert!(controls.is_gamepad_topic("remote-control/gamepad"));
+    assert!(controls.is_gamepad_topic("hopper/remote-control/gamepad"));
+    assert!(!controls.is_gamepad_topic("hopper/my-remote-control/gamepad"));
+    assert!(!controls.is_gamepad_topic("remote-control/gamepad/combos"));
+    assert!(controls.is_twist_topic("hopper/cmd_vel"));
+    assert!(!controls.is_twist_topic("hopper/log"));
+}
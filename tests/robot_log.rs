@@ -0,0 +1,35 @@
+use std::time::{Duration, SystemTime};
+
+use deck_robot_remote::{foxglove::log::Level, foxglove_server::parse_log_line};
+
+#[test]
+fn tracing_json_events_are_parsed() {
+    let line = r#"{"timestamp":"2024-05-01T12:00:00.5Z","level":"WARN","fields":{"message":"battery low","voltage":10.8},"target":"hopper::power","filename":"src/power.rs","line_number":42}"#;
+    let log = parse_log_line(line, "hopper/log", SystemTime::UNIX_EPOCH);
+    assert_eq!(log.level, Level::Warning as i32);
+    assert_eq!(log.message, "battery low voltage=10.8");
+    assert_eq!(log.name, "hopper::power");
+    assert_eq!(log.file, "src/power.rs");
+    assert_eq!(log.line, 42);
+    let timestamp = log.timestamp.unwrap();
+    assert_eq!(timestamp.seconds, 1714564800);
+    assert_eq!(timestamp.nanos, 500_000_000);
+}
+
+#[test]
+fn plain_text_lines_use_the_level_and_topic() {
+    let fallback_time = SystemTime::UNIX_EPOCH + Duration::from_secs(10);
+    let line = "2024-05-01T12:00:00.000Z ERROR hopper: motor 3 overheated\n";
+    let log = parse_log_line(line, "hopper/log", fallback_time);
+    assert_eq!(log.level, Level::Error as i32);
+    assert_eq!(log.message, line.trim_end());
+    assert_eq!(log.name, "hopper/log");
+    assert_eq!(log.timestamp.unwrap().seconds, 10);
+}
+
+#[test]
+fn plain_text_without_level_is_info() {
+    let log = parse_log_line("started", "hopper/log", SystemTime::UNIX_EPOCH);
+    assert_eq!(log.level, Level::Info as i32);
+    assert_eq!(log.message, "started");
+}
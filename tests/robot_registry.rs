@@ -0,0 +1,113 @@
+use deck_robot_remote::{config::load_profiles, robot_registry::RobotRegistry};
+
+fn registry(active: &str) -> RobotRegistry {
+    RobotRegistry::new(&load_profiles(None).unwrap(), active).unwrap()
+}
+
+#[test]
+fn unknown_initial_robot_is_rejected() {
+    assert!(RobotRegistry::new(&load_profiles(None).unwrap(), "bender").is_err());
+}
+
+#[test]
+fn select_by_name() {
+    let registry = registry("hamilton");
+    let mut active = registry.subscribe();
+
+    registry.select("hopper").unwrap();
+    assert!(active.has_changed().unwrap());
+    assert_eq!(active.borrow_and_update().name, "hopper");
+
+    assert!(registry.select("bender").is_err());
+    assert_eq!(registry.active().name, "hopper");
+}
+
+#[test]
+fn selecting_the_active_robot_doesnt_notify() {
+    let registry = registry("hamilton");
+    let active = registry.subscribe();
+    registry.select("hamilton").unwrap();
+    assert!(!active.has_changed().unwrap());
+}
+
+#[test]
+fn select_next_wraps_around() {
+    // profiles are ordered by name: guppy, hamilton, hopper
+    let registry = registry("hopper");
+    registry.select_next(1);
+    assert_eq!(registry.active().name, "guppy");
+    registry.select_next(-1);
+    assert_eq!(registry.active().name, "hopper");
+    registry.select_next(-2);
+    assert_eq!(registry.active().name, "guppy");
+}
+
+#[test]
+fn fixed_selection_refuses_switching() {
+    let registry = registry("hopper").with_fixed_selection();
+    let active = registry.subscribe();
+    registry.select_next(1);
+    assert!(registry.select("guppy").is_err());
+    registry.select("hopper").unwrap();
+    assert_eq!(registry.active().name, "hopper");
+    assert!(!active.has_changed().unwrap());
+}
+
+#[test]
+fn simultaneous_robots_cycle_in_given_order() {
+    let names = ["hopper".to_owned(), "guppy".to_owned()];
+    let registry = RobotRegistry::simultaneous(&load_profiles(None).unwrap(), &names).unwrap();
+    assert!(registry.is_simultaneous());
+    assert_eq!(registry.active().name, "hopper");
+    assert_eq!(registry.connected_targets().len(), 2);
+
+    registry.select_next(1);
+    assert_eq!(registry.active().name, "guppy");
+    registry.select_next(1);
+    assert_eq!(registry.active().name, "hopper");
+    assert!(registry.select("hamilton").is_err());
+}
+
+#[test]
+fn simultaneous_robots_must_be_known_and_distinct() {
+    let profiles = load_profiles(None).unwrap();
+    let unknown = ["hopper".to_owned(), "bender".to_owned()];
+    assert!(RobotRegistry::simultaneous(&profiles, &unknown).is_err());
+    let duplicate = ["hopper".to_owned(), "hopper".to_owned()];
+    assert!(RobotRegistry::simultaneous(&profiles, &duplicate).is_err());
+}
+
+#[test]
+fn only_the_active_robot_is_connected_by_default() {
+    let registry = registry("hamilton");
+    assert!(!registry.is_simultaneous());
+    let connected = registry.connected_targets();
+    assert_eq!(connected.len(), 1);
+    assert_eq!(connected[0].name, "hamilton");
+}
+
+#[test]
+fn key_prefix_falls_back_to_the_robot_name() {
+    let registry = registry("hopper");
+    let mut target = registry.active();
+    target.topic_prefix = None;
+    assert_eq!(target.key_prefix(), "hopper");
+    target.topic_prefix = Some("robots/hopper/".to_owned());
+    assert_eq!(target.key_prefix(), "robots/hopper");
+}
+
+#[test]
+fn gamepad_topic_is_in_the_robot_namespace() {
+    let registry = registry("hopper");
+    let mut target = registry.active();
+    target.topic_prefix = None;
+    assert_eq!(
+        target.gamepad_topic("remote-control/gamepad"),
+        "hopper/remote-control/gamepad"
+    );
+    target.topic_prefix = Some("robots/hopper/".to_owned());
+    assert_eq!(
+        target.gamepad_topic("remote-control/gamepad"),
+        "robots/hopper/remote-control/gamepad"
+    );
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use chrono::{TimeZone, Utc};
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    messages::{Axis, Button, GamepadMessage, InputMessage},
+    ros2::{
+        encode_joy_cdr, joy_message, serialize_joy, JoyAxisConvention, Ros2Encoding, Ros2JoyConfig,
+    },
+};
+
+fn gamepad(name: &str, connected: bool, value: f32) -> GamepadMessage {
+    GamepadMessage {
+        name: name.to_owned(),
+        connected,
+        axis_state: [(Axis::LeftStickY, value)].into_iter().collect(),
+        button_down: [(Button::East, true), (Button::South, false)]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn input_message(gamepads: Vec<(usize, GamepadMessage)>) -> InputMessage {
+    InputMessageBuilder::default()
+        .gamepads(gamepads)
+        .time(Utc.timestamp_opt(1_700_000_000, 500).unwrap())
+        .build()
+}
+
+fn config(yaml: &str) -> Ros2JoyConfig {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+#[test]
+fn defaults_follow_joy_game_controller_order() {
+    let config = config("{}");
+    assert_eq!(config.key_expr, "joy");
+    assert_eq!(config.encoding, Ros2Encoding::Cdr);
+    assert_eq!(config.axes.len(), 6);
+    assert_eq!(config.buttons.len(), 15);
+
+    let joy = joy_message(
+        &input_message(vec![(0, gamepad("Deck", true, 0.5))]),
+        &config,
+    );
+    assert_eq!(joy.axes, vec![0.0, 0.5, 0.0, 0.0, 1.0, 1.0]);
+    assert_eq!(joy.buttons[..3], [0, 1, 0]);
+    assert_eq!(joy.header.stamp.sec, 1_700_000_000);
+    assert_eq!(joy.header.stamp.nanosec, 500);
+    assert_eq!(joy.header.frame_id, "joy");
+}
+
+#[test]
+fn mirrors_lowest_connected_or_selected_gamepad() {
+    let message = input_message(vec![
+        (0, gamepad("Unplugged", false, 0.1)),
+        (1, gamepad("Deck", true, 0.2)),
+        (2, gamepad("Xbox", true, 0.3)),
+    ]);
+    let joy = joy_message(&message, &config("axes: [LeftStickY]"));
+    assert_eq!(joy.axes, vec![0.2]);
+
+    let joy = joy_message(
+        &message,
+        &config("{ axes: [LeftStickY], gamepad: { name: xbox } }"),
+    );
+    assert_eq!(joy.axes, vec![0.3]);
+
+    let joy = joy_message(&input_message(vec![]), &config("axes: [LeftStickY]"));
+    assert_eq!(joy.axes, vec![0.0]);
+}
+
+#[test]
+fn axes_follow_ros_convention_unless_configured() {
+    let message = InputMessageBuilder::default()
+        .gamepads(vec![(
+            0,
+            GamepadMessage {
+                connected: true,
+                axis_state: [
+                    (Axis::LeftStickX, 0.5),
+                    (Axis::LeftStickY, 0.5),
+                    (Axis::RightZ, 1.0),
+                ]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            },
+        )])
+        .build();
+    let axes = "axes: [LeftStickX, LeftStickY, LeftZ, RightZ]";
+
+    let ros = config(axes);
+    assert_eq!(ros.axis_convention, JoyAxisConvention::Ros);
+    let joy = joy_message(&message, &ros);
+    assert_eq!(joy.axes, vec![-0.5, 0.5, 1.0, -1.0]);
+
+    let joy = joy_message(
+        &message,
+        &config(&format!("{{ {axes}, axis_convention: gamepad }}")),
+    );
+    assert_eq!(joy.axes, vec![0.5, 0.5, 0.0, 1.0]);
+}
+
+#[test]
+fn cdr_layout_matches_sensor_msgs_joy() {
+    let joy = joy_message(
+        &input_message(vec![(0, gamepad("Deck", true, 1.0))]),
+        &config("{ frame_id: deck, axes: [LeftStickY], buttons: [East, South] }"),
+    );
+    let mut buffer = Vec::new();
+    encode_joy_cdr(&mut buffer, &joy);
+
+    let mut expected = vec![0x00, 0x01, 0x00, 0x00];
+    expected.extend_from_slice(&1_700_000_000i32.to_le_bytes());
+    expected.extend_from_slice(&500u32.to_le_bytes());
+    // "deck" with its null terminator, padded to 4 bytes
+    expected.extend_from_slice(&5u32.to_le_bytes());
+    expected.extend_from_slice(b"deck\0\0\0\0");
+    expected.extend_from_slice(&1u32.to_le_bytes());
+    expected.extend_from_slice(&1.0f32.to_le_bytes());
+    expected.extend_from_slice(&2u32.to_le_bytes());
+    expected.extend_from_slice(&1i32.to_le_bytes());
+    expected.extend_from_slice(&0i32.to_le_bytes());
+    assert_eq!(buffer, expected);
+}
+
+#[test]
+fn json_has_ros_field_names() {
+    let joy = joy_message(
+        &input_message(vec![(0, gamepad("Deck", true, 1.0))]),
+        &config("{ axes: [LeftStickY], buttons: [East] }"),
+    );
+    let value = serialize_joy(&mut Vec::new(), &joy, Ros2Encoding::Json).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_slice(&Vec::<u8>::try_from(value).unwrap()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "header": {"stamp": {"sec": 1_700_000_000, "nanosec": 500}, "frame_id": "joy"},
+            "axes": [1.0],
+            "buttons": [1],
+        })
+    );
+}
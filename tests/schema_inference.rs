@@ -0,0 +1,68 @@
+use deck_robot_remote::{
+    foxglove_server::FoxgloveServerConfiguration, schema_inference::infer_schema,
+};
+use serde_json::json;
+
+#[test]
+fn fields_of_all_messages_are_merged() {
+    let messages = [
+        json!({ "temperature": 21, "battery": { "voltage": 12.1 } }),
+        json!({ "temperature": 21.5, "humidity": 40, "ranges": [1.0, 2.0] }),
+    ];
+    let schema = infer_schema("Climate", &messages).unwrap();
+    assert_eq!(
+        schema,
+        json!({
+            "title": "Climate",
+            "type": "object",
+            "properties": {
+                "temperature": { "type": "number" },
+                "humidity": { "type": "integer" },
+                "battery": {
+                    "type": "object",
+                    "properties": { "voltage": { "type": "number" } }
+                },
+                "ranges": { "type": "array", "items": { "type": "number" } }
+            }
+        })
+    );
+}
+
+#[test]
+fn conflicting_types_are_all_allowed() {
+    let messages = [
+        json!({ "state": "on", "list": [] }),
+        json!({ "state": null, "list": [true] }),
+    ];
+    let schema = infer_schema("Switch", &messages).unwrap();
+    assert_eq!(
+        schema["properties"]["state"],
+        json!({ "type": ["null", "string"] })
+    );
+    assert_eq!(
+        schema["properties"]["list"],
+        json!({ "type": "array", "items": { "type": "boolean" } })
+    );
+}
+
+#[test]
+fn no_messages_infer_nothing() {
+    assert!(infer_schema("Empty", &[]).is_none());
+}
+
+#[test]
+fn inferred_and_named_schemas_are_exclusive() {
+    for (infer_schema, json_schema_name) in [("0", "null"), ("5", "GENERIC_JSON_SCHEMA")] {
+        let config: FoxgloveServerConfiguration = serde_yaml::from_str(&format!(
+            r#"
+json_subscriptions:
+  - topic: "robot/climate"
+    type_name: "Climate"
+    infer_schema: {infer_schema}
+    json_schema_name: {json_schema_name}
+"#
+        ))
+        .unwrap();
+        assert!(config.validate().is_err());
+    }
+}
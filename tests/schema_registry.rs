@@ -0,0 +1,71 @@
+use deck_robot_remote::{
+    schema_registry::{wants_descriptor, SchemaRegistry, TopicSchema},
+    DESCRIPTOR_POOL,
+};
+use zenoh::prelude::r#async::*;
+
+fn registry() -> SchemaRegistry {
+    let registry = SchemaRegistry::default();
+    registry.register("remote-control/gamepad", TopicSchema::Json("{}".to_owned()));
+    registry.register(
+        "remote-control/twist",
+        TopicSchema::Protobuf(
+            DESCRIPTOR_POOL
+                .get_message_by_name("remote_control.Twist")
+                .unwrap(),
+        ),
+    );
+    registry
+}
+
+fn payload(sample: &Sample) -> Vec<u8> {
+    Vec::<u8>::try_from(sample.value.clone()).unwrap()
+}
+
+#[test]
+fn wildcard_query_replies_for_every_topic() {
+    let key_expr = keyexpr::new("**/__schema__").unwrap();
+    let replies = registry().replies(key_expr, "");
+    let keys: Vec<String> = replies
+        .iter()
+        .map(|sample| sample.key_expr.to_string())
+        .collect();
+    assert_eq!(
+        keys,
+        vec![
+            "remote-control/gamepad/__schema__",
+            "remote-control/twist/__schema__"
+        ]
+    );
+}
+
+#[test]
+fn single_topic_query_replies_once() {
+    let key_expr = keyexpr::new("remote-control/gamepad/__schema__").unwrap();
+    let replies = registry().replies(key_expr, "");
+    assert_eq!(replies.len(), 1);
+    assert_eq!(payload(&replies[0]), b"{}");
+}
+
+#[test]
+fn protobuf_replies_with_full_name_unless_descriptor_requested() {
+    let key_expr = keyexpr::new("remote-control/twist/__schema__").unwrap();
+    let registry = registry();
+
+    let name = registry.replies(key_expr, "");
+    assert_eq!(payload(&name[0]), b"remote_control.Twist");
+
+    let descriptor = registry.replies(key_expr, "descriptor");
+    let pool = prost_reflect::DescriptorPool::decode(payload(&descriptor[0]).as_slice()).unwrap();
+    assert!(pool.get_message_by_name("remote_control.Twist").is_some());
+}
+
+#[test]
+fn descriptor_parameter() {
+    assert!(wants_descriptor("descriptor"));
+    assert!(wants_descriptor("descriptor=true"));
+    assert!(wants_descriptor("foo=bar&descriptor"));
+    assert!(!wants_descriptor("descriptor=false"));
+    assert!(!wants_descriptor(""));
+    assert!(!wants_descriptor("descriptors"));
+}
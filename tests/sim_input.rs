@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use deck_robot_remote::{
+    messages::{Axis, Button},
+    sim_input::{SimAction, SimScript, SimStep},
+};
+
+fn parse_error(script: &str) -> String {
+    format!("{:#}", SimScript::parse(script).unwrap_err())
+}
+
+#[test]
+fn script_steps_are_parsed_in_order() {
+    let script = SimScript::parse(
+        "# time_ms, action, arguments...
+        0, axis, LeftStickX, -0.5
+
+        500, press, South  # jump
+        700, release, South
+        1000, ramp, LeftStickY, 1.0, 2000
+        4000, loop
+        ",
+    )
+    .unwrap();
+    assert_eq!(
+        script.steps,
+        vec![
+            SimStep {
+                at: Duration::ZERO,
+                action: SimAction::Axis(Axis::LeftStickX, -0.5),
+            },
+            SimStep {
+                at: Duration::from_millis(500),
+                action: SimAction::Press(Button::South),
+            },
+            SimStep {
+                at: Duration::from_millis(700),
+                action: SimAction::Release(Button::South),
+            },
+            SimStep {
+                at: Duration::from_millis(1000),
+                action: SimAction::Ramp {
+                    axis: Axis::LeftStickY,
+                    target: 1.0,
+                    duration: Duration::from_secs(2),
+                },
+            },
+            SimStep {
+                at: Duration::from_millis(4000),
+                action: SimAction::Loop,
+            },
+        ]
+    );
+}
+
+#[test]
+fn empty_script_has_no_steps() {
+    assert_eq!(
+        SimScript::parse("\n# nothing\n").unwrap(),
+        SimScript::default()
+    );
+}
+
+#[test]
+fn malformed_steps_name_their_line() {
+    let error = parse_error("0, press, South\n100, jump, South");
+    assert!(error.contains("line 2"), "{error}");
+    assert!(error.contains("Unknown action"), "{error}");
+
+    let error = parse_error("soon, press, South");
+    assert!(error.contains("Invalid time"), "{error}");
+
+    let error = parse_error("0, press, Kick");
+    assert!(error.contains("Unknown button or axis Kick"), "{error}");
+
+    let error = parse_error("0, axis, LeftStickX");
+    assert!(error.contains("Unknown action"), "{error}");
+
+    let error = parse_error("0, ramp, LeftStickX, 1.0, forever");
+    assert!(error.contains("Invalid ramp duration"), "{error}");
+}
+
+#[test]
+fn out_of_range_values_are_rejected() {
+    let error = parse_error("0, axis, LeftStickX, 1.5");
+    assert!(error.contains("outside of -1.0..=1.0"), "{error}");
+
+    let error = parse_error("0, ramp, LeftStickY, -2.0, 1000");
+    assert!(error.contains("outside of -1.0..=1.0"), "{error}");
+
+    let error = parse_error("-5, press, South");
+    assert!(error.contains("Invalid time"), "{error}");
+
+    assert!(SimScript::parse("0, axis, LeftStickX, -1.0\n0, axis, LeftStickX, 1.0").is_ok());
+}
+
+#[test]
+fn steps_out_of_order_are_rejected() {
+    let error = parse_error("500, press, South\n100, release, South");
+    assert!(
+        error.contains("line 2: steps must be in chronological order"),
+        "{error}"
+    );
+}
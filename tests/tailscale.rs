@@ -0,0 +1,329 @@
+use deck_robot_remote::{
+    config::load_profiles,
+    foxglove_server::FoxgloveHost,
+    tailscale::{
+        http_body, peer_endpoints, remaining_endpoints, PeerMatch, TailscalePeer, TailscaleStatus,
+    },
+};
+use proptest::prelude::*;
+use serde_json::{json, Value};
+use zenoh_config::EndPoint;
+
+const STATUS: &[u8] = include_bytes!("fixtures/tailscale/status.json");
+const LOGGED_OUT: &[u8] = include_bytes!("fixtures/tailscale/logged_out.json");
+
+fn contains(host_name: &str) -> Vec<PeerMatch> {
+    vec![PeerMatch::Contains(host_name.to_owned())]
+}
+
+fn peer(host_name: &str, tags: &[&str]) -> TailscalePeer {
+    TailscalePeer {
+        host_name: host_name.to_owned(),
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn parses_current_status() {
+    let status = TailscaleStatus::parse(STATUS).unwrap();
+    assert!(status.tailscale_ip_list.contains("100.101.102.103"));
+    assert_eq!(status.self_status.host_name, "steamdeck");
+    assert_eq!(status.peers.len(), 2);
+
+    let hopper = status
+        .peers
+        .values()
+        .find(|peer| peer.host_name == "hopper")
+        .unwrap();
+    assert!(hopper.tailscale_ip_list.is_empty());
+}
+
+#[test]
+fn released_peers_are_removed_unless_kept() {
+    let endpoint = |address: &str| -> EndPoint { format!("tcp/{address}:7436").parse().unwrap() };
+    let manual = endpoint("10.0.0.1");
+    let hopper = endpoint("100.64.0.1");
+    let router = endpoint("100.64.0.2");
+    let hamilton = endpoint("100.64.0.3");
+
+    let current = [
+        manual.clone(),
+        hopper.clone(),
+        router.clone(),
+        hamilton.clone(),
+    ];
+    let remaining = remaining_endpoints(
+        &current,
+        &[hopper, router.clone()],
+        &[router.clone(), hamilton.clone()],
+    );
+    assert_eq!(remaining, vec![manual, router, hamilton]);
+}
+
+#[test]
+fn parses_logged_out_status() {
+    let status = TailscaleStatus::parse(LOGGED_OUT).unwrap();
+    assert!(status.tailscale_ip_list.is_empty());
+    assert!(status.peers.is_empty());
+}
+
+#[test]
+fn peer_endpoints_match_host_filter() {
+    let status = TailscaleStatus::parse(STATUS).unwrap();
+    let endpoints = peer_endpoints(&status, &contains("Hamilton"), false).unwrap();
+    let endpoints: Vec<String> = endpoints.iter().map(ToString::to_string).collect();
+    assert_eq!(endpoints, vec!["tcp/100.64.0.2:7436"]);
+
+    // offline peers without addresses are picked up once they report some
+    assert!(peer_endpoints(&status, &contains("hopper"), false)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn ipv6_peer_endpoints_are_bracketed() {
+    let status = TailscaleStatus::parse(STATUS).unwrap();
+    let endpoints = peer_endpoints(&status, &contains("hamilton"), true).unwrap();
+    let endpoints: Vec<String> = endpoints.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        endpoints,
+        vec!["tcp/100.64.0.2:7436", "tcp/[fd7a:115c:a1e0::2]:7436"]
+    );
+}
+
+#[test]
+fn skips_peers_with_unexpected_shape() {
+    let mut status: Value = serde_json::from_slice(STATUS).unwrap();
+    status["Peer"]["broken"] = json!({ "HostName": 42 });
+    status["Self"] = json!("not an object");
+
+    let status = TailscaleStatus::parse(&serde_json::to_vec(&status).unwrap()).unwrap();
+    assert_eq!(status.peers.len(), 2);
+    assert!(status.self_status.host_name.is_empty());
+}
+
+#[test]
+fn rejects_non_object() {
+    assert!(TailscaleStatus::parse(b"").is_err());
+    assert!(TailscaleStatus::parse(b"[]").is_err());
+}
+
+fn arbitrary_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        ".*".prop_map(Value::from),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(Value::from),
+            prop::collection::hash_map(
+                prop_oneof![
+                    Just("TailscaleIPs".to_owned()),
+                    Just("Self".to_owned()),
+                    Just("Peer".to_owned()),
+                    Just("HostName".to_owned()),
+                    ".*"
+                ],
+                inner,
+                0..8
+            )
+            .prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+        let _ = TailscaleStatus::parse(&bytes);
+    }
+
+    #[test]
+    fn any_json_object_is_accepted(
+        fields in prop::collection::hash_map(
+            prop_oneof![
+                Just("TailscaleIPs".to_owned()),
+                Just("Self".to_owned()),
+                Just("Peer".to_owned()),
+                ".*"
+            ],
+            arbitrary_json(),
+            0..8
+        )
+    ) {
+        let status = Value::Object(fields.into_iter().collect());
+        let json = serde_json::to_vec(&status).unwrap();
+        prop_assert!(TailscaleStatus::parse(&json).is_ok());
+    }
+}
+
+#[test]
+fn tailscale_host_resolves_to_local_ipv4_and_magic_dns_name() {
+    let status = TailscaleStatus::parse(STATUS).unwrap();
+    let host: FoxgloveHost = "tailscale".parse().unwrap();
+    let (address, url_host) = host.resolve(&status).unwrap();
+    assert_eq!(address.to_string(), "100.101.102.103:8765");
+    assert_eq!(url_host, "steamdeck.tail1234.ts.net");
+
+    let host: FoxgloveHost = "tailscale:9000".parse().unwrap();
+    assert_eq!(host.resolve(&status).unwrap().0.port(), 9000);
+}
+
+#[test]
+fn tailscale_host_requires_tailscale_address() {
+    let status = TailscaleStatus::parse(LOGGED_OUT).unwrap();
+    let host: FoxgloveHost = "tailscale".parse().unwrap();
+    assert!(host.resolve(&status).is_err());
+
+    let host: FoxgloveHost = "127.0.0.1:8765".parse().unwrap();
+    let (address, url_host) = host.resolve(&status).unwrap();
+    assert_eq!(address.port(), 8765);
+    assert_eq!(url_host, "127.0.0.1");
+}
+
+#[test]
+fn peer_state_and_exit_node_are_parsed() {
+    let status = TailscaleStatus::parse(STATUS).unwrap();
+    let exit_node = status.exit_node().unwrap();
+    assert_eq!(exit_node.host_name, "hamilton");
+    assert!(exit_node.online);
+    assert!(exit_node.exit_node_option);
+
+    let hopper = status
+        .peers
+        .values()
+        .find(|peer| peer.host_name == "hopper")
+        .unwrap();
+    assert!(!hopper.online);
+    assert!(!hopper.exit_node);
+}
+
+#[test]
+fn local_api_body_is_extracted() {
+    let response = b"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{\"Peer\":null}";
+    let body = http_body(response).unwrap();
+    assert_eq!(body, b"{\"Peer\":null}");
+    assert!(TailscaleStatus::parse(body).unwrap().peers.is_empty());
+
+    let forbidden = b"HTTP/1.0 403 Forbidden\r\n\r\naccess denied\n";
+    let err = http_body(forbidden).unwrap_err();
+    assert!(err.to_string().contains("403"));
+    assert!(http_body(b"HTTP/1.0 200 OK\r\n").is_err());
+}
+
+#[test]
+fn exact_rule_skips_similar_host_names() {
+    let rule = PeerMatch::Exact("hopper-nuc".to_owned());
+    assert!(rule.matches(&peer("Hopper-NUC", &[])));
+    assert!(!rule.matches(&peer("hopper-cam", &[])));
+    assert!(PeerMatch::Contains("hopper".to_owned()).matches(&peer("hopper-cam", &[])));
+}
+
+#[test]
+fn regex_rule_matches_whole_pattern() {
+    let rule = PeerMatch::Regex("^hopper-(nuc|pi)$".to_owned());
+    assert!(rule.validate().is_ok());
+    assert!(rule.matches(&peer("hopper-nuc", &[])));
+    assert!(rule.matches(&peer("HOPPER-PI", &[])));
+    assert!(!rule.matches(&peer("hopper-cam", &[])));
+
+    assert!(PeerMatch::Regex("hopper-(".to_owned()).validate().is_err());
+}
+
+#[test]
+fn tag_rule_matches_acl_tags() {
+    let rule = PeerMatch::Tag("tag:hopper".to_owned());
+    assert!(rule.matches(&peer("nuc", &["tag:robot", "tag:hopper"])));
+    assert!(!rule.matches(&peer("hopper", &[])));
+}
+
+#[test]
+fn parses_peer_tags() {
+    let mut status: Value = serde_json::from_slice(STATUS).unwrap();
+    let (_, hamilton) = status["Peer"]
+        .as_object_mut()
+        .unwrap()
+        .iter_mut()
+        .find(|(_, peer)| peer["HostName"] == "hamilton")
+        .unwrap();
+    hamilton["Tags"] = json!(["tag:robot"]);
+    let status = TailscaleStatus::parse(&serde_json::to_vec(&status).unwrap()).unwrap();
+
+    let rules = [PeerMatch::Tag("tag:robot".to_owned())];
+    let endpoints = peer_endpoints(&status, &rules, false).unwrap();
+    assert_eq!(endpoints.len(), 1);
+}
+
+fn load_profile(name: &str, yaml: &str) -> anyhow::Result<Vec<PeerMatch>> {
+    let profile_dir = std::env::temp_dir().join(format!(
+        "deck-robot-remote-peers-{name}-{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&profile_dir).unwrap();
+    std::fs::write(profile_dir.join(format!("{name}.yaml")), yaml).unwrap();
+    let result = load_profiles(Some(&profile_dir));
+    std::fs::remove_dir_all(&profile_dir).unwrap();
+    Ok(result?[name].peer_rules())
+}
+
+#[test]
+fn profile_peer_rules() {
+    let rules = load_profile(
+        "nuc",
+        r#"
+name: nuc
+foxglove_layout_id: "layout"
+tailscale_peers:
+  - exact: hopper-nuc
+  - tag: "tag:hopper"
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        rules,
+        vec![
+            PeerMatch::Exact("hopper-nuc".to_owned()),
+            PeerMatch::Tag("tag:hopper".to_owned()),
+        ]
+    );
+
+    let rules = load_profile(
+        "legacy",
+        r#"
+name: legacy
+foxglove_layout_id: "layout"
+tailscale_host_filter: hopper
+"#,
+    )
+    .unwrap();
+    assert_eq!(rules, vec![PeerMatch::Contains("hopper".to_owned())]);
+}
+
+#[test]
+fn profile_needs_exactly_one_kind_of_peer_rule() {
+    let missing = r#"
+name: missing
+foxglove_layout_id: "layout"
+"#;
+    assert!(load_profile("missing", missing).is_err());
+
+    let both = r#"
+name: both
+foxglove_layout_id: "layout"
+tailscale_host_filter: hopper
+tailscale_peers:
+  - exact: hopper-nuc
+"#;
+    assert!(load_profile("both", both).is_err());
+
+    let invalid = r#"
+name: invalid
+foxglove_layout_id: "layout"
+tailscale_peers:
+  - regex: "hopper-("
+"#;
+    assert!(load_profile("invalid", invalid).is_err());
+}
@@ -0,0 +1,19 @@
+use deck_robot_remote::touchpad::normalize_axis;
+
+#[test]
+fn untouched_axis_is_centered() {
+    assert_eq!(normalize_axis(0, -32768, 32767), 0.0);
+}
+
+#[test]
+fn axis_extremes_map_to_unit_range() {
+    assert_eq!(normalize_axis(32767, -32768, 32767), 1.0);
+    assert_eq!(normalize_axis(-32768, -32768, 32767), -1.0);
+    assert_eq!(normalize_axis(16384, -32768, 32767), 16384.0 / 32767.0);
+}
+
+#[test]
+fn out_of_range_values_are_clamped() {
+    assert_eq!(normalize_axis(40000, -32768, 32767), 1.0);
+    assert_eq!(normalize_axis(-40000, -32768, 32767), -1.0);
+}
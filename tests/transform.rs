@@ -0,0 +1,138 @@
+use deck_robot_remote::{
+    foxglove,
+    foxglove_server::FoxgloveServerConfiguration,
+    transform::{output_descriptor, transform_json, transform_proto, PayloadTransform},
+    DESCRIPTOR_POOL,
+};
+use prost::Message;
+use serde_json::json;
+
+fn transforms(yaml: &str) -> Vec<PayloadTransform> {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+#[test]
+fn json_fields_are_renamed_scaled_and_extracted() {
+    let message = json!({
+        "data": { "temp_mc": 21500, "humidity": 40 },
+        "id": 7,
+    });
+    let transforms = transforms(
+        r#"
+- extract: { field: data }
+- rename: { from: temp_mc, to: climate.temperature }
+- scale: { field: climate.temperature, factor: 0.001 }
+"#,
+    );
+    let message = transform_json(message, &transforms).unwrap();
+    assert_eq!(
+        message,
+        json!({ "humidity": 40, "climate": { "temperature": 21.5 } })
+    );
+}
+
+#[test]
+fn json_lists_are_scaled_element_wise() {
+    let transforms = [PayloadTransform::Scale {
+        field: "ranges".to_owned(),
+        factor: 0.01,
+    }];
+    let message = transform_json(json!({ "ranges": [100, 250] }), &transforms).unwrap();
+    assert_eq!(message, json!({ "ranges": [1.0, 2.5] }));
+}
+
+#[test]
+fn missing_json_fields_fail() {
+    let transforms = [PayloadTransform::Extract {
+        field: "data.pose".to_owned(),
+    }];
+    assert!(transform_json(json!({ "data": {} }), &transforms).is_err());
+}
+
+#[test]
+fn extracting_a_proto_field_changes_the_channel_type() {
+    let descriptor = DESCRIPTOR_POOL
+        .get_message_by_name("foxglove.PoseInFrame")
+        .unwrap();
+    let transforms = transforms(
+        r#"
+- scale: { field: pose.position.x, factor: 0.001 }
+- extract: { field: pose }
+- rename: { from: position.z, to: position.y }
+"#,
+    );
+    let output = output_descriptor(&descriptor, &transforms).unwrap();
+    assert_eq!(output.full_name(), "foxglove.Pose");
+
+    let message = foxglove::PoseInFrame {
+        frame_id: "base_link".to_owned(),
+        pose: Some(foxglove::Pose {
+            position: Some(foxglove::Vector3 {
+                x: 1500.0,
+                y: 0.0,
+                z: 3.0,
+            }),
+            orientation: None,
+        }),
+        ..Default::default()
+    };
+    let payload = transform_proto(&message.encode_to_vec(), &descriptor, &transforms).unwrap();
+    let pose = foxglove::Pose::decode(payload.as_slice()).unwrap();
+    let position = pose.position.unwrap();
+    assert_eq!(position.x, 1.5);
+    assert_eq!(position.y, 3.0);
+    assert_eq!(position.z, 0.0);
+}
+
+#[test]
+fn repeated_proto_numbers_are_scaled() {
+    let descriptor = DESCRIPTOR_POOL
+        .get_message_by_name("foxglove.LaserScan")
+        .unwrap();
+    let transforms = [PayloadTransform::Scale {
+        field: "ranges".to_owned(),
+        factor: 0.5,
+    }];
+    output_descriptor(&descriptor, &transforms).unwrap();
+
+    let message = foxglove::LaserScan {
+        ranges: vec![2.0, 4.0],
+        ..Default::default()
+    };
+    let payload = transform_proto(&message.encode_to_vec(), &descriptor, &transforms).unwrap();
+    let scan = foxglove::LaserScan::decode(payload.as_slice()).unwrap();
+    assert_eq!(scan.ranges, vec![1.0, 2.0]);
+}
+
+#[test]
+fn invalid_proto_transforms_are_rejected() {
+    let descriptor = DESCRIPTOR_POOL
+        .get_message_by_name("foxglove.PoseInFrame")
+        .unwrap();
+    for yaml in [
+        "- scale: { field: frame_id, factor: 2.0 }",
+        "- scale: { field: pose.velocity, factor: 2.0 }",
+        "- rename: { from: frame_id, to: pose }",
+        "- extract: { field: frame_id }",
+    ] {
+        assert!(
+            output_descriptor(&descriptor, &transforms(yaml)).is_err(),
+            "{yaml}"
+        );
+    }
+}
+
+#[test]
+fn subscription_transforms_are_validated() {
+    let config: FoxgloveServerConfiguration = serde_yaml::from_str(
+        r#"
+json_subscriptions:
+  - topic: "robot/climate"
+    type_name: "Climate"
+    transforms:
+      - scale: { field: "data..temperature", factor: 0.1 }
+"#,
+    )
+    .unwrap();
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::io::Write;
+
+use common::InputMessageBuilder;
+use deck_robot_remote::{
+    foxglove_clients::count_established_connections,
+    messages::{Axis, Button, GamepadMessage},
+    tui::{render, DashboardState, LogBuffer},
+};
+use ratatui::{backend::TestBackend, Terminal};
+
+const PROC_NET_TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:223D 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 1 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:223D 0100007F:C350 01 00000000:00000000 00:00000000 00000000  1000        0 2 1 0000000000000000 20 4 30 10 -1
+   2: 0100007F:223D 0100007F:C351 01 00000000:00000000 00:00000000 00000000  1000        0 3 1 0000000000000000 20 4 30 10 -1
+   3: 0100007F:C350 0100007F:223D 01 00000000:00000000 00:00000000 00000000  1000        0 4 1 0000000000000000 20 4 30 10 -1
+   4: 0100007F:223D 0100007F:C352 06 00000000:00000000 00:00000000 00000000  1000        0 5 1 0000000000000000 20 4 30 10 -1
+";
+
+#[test]
+fn counts_established_connections_to_local_port() {
+    // 0x223D is 8765, the listening socket and the TIME_WAIT one don't count
+    assert_eq!(count_established_connections(PROC_NET_TCP, 8765), 2);
+    assert_eq!(count_established_connections(PROC_NET_TCP, 8766), 0);
+}
+
+#[test]
+fn log_buffer_splits_lines_and_keeps_the_latest() {
+    let mut log_buffer = LogBuffer::default();
+    log_buffer.write_all(b"first\nsecond\n").unwrap();
+    for index in 0..300 {
+        writeln!(log_buffer, "line {index}").unwrap();
+    }
+    let lines = log_buffer.lines();
+    assert_eq!(lines.len(), 200);
+    assert_eq!(lines.last().unwrap(), "line 299");
+}
+
+fn rendered(state: &DashboardState) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+    terminal.draw(|frame| render(frame, state)).unwrap();
+    let buffer = terminal.backend().buffer().clone();
+    buffer
+        .content
+        .chunks(buffer.area.width as usize)
+        .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn renders_gamepad_state_and_logs() {
+    let gamepad = GamepadMessage {
+        name: "Steam Deck".to_owned(),
+        connected: true,
+        axis_state: [(Axis::LeftStickY, 0.5)].into_iter().collect(),
+        button_down: [(Button::South, true), (Button::East, false)]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+    let state = DashboardState {
+        gamepad_topic: "remote-control/gamepad".to_owned(),
+        gamepad: Some(
+            InputMessageBuilder::default()
+                .gamepad(0, gamepad)
+                .estop_engaged(true)
+                .build(),
+        ),
+        foxglove_clients: Some(2),
+        logs: vec!["INFO started".to_owned()],
+        ..Default::default()
+    };
+    let screen = rendered(&state);
+    assert!(screen.contains("EMERGENCY STOP"), "{screen}");
+    assert!(screen.contains("0: Steam Deck"), "{screen}");
+    assert!(screen.contains("LeftStickY     +0.50"), "{screen}");
+    assert!(screen.contains("pressed South"), "{screen}");
+    assert!(!screen.contains("East"), "{screen}");
+    assert!(screen.contains("foxglove clients 2"), "{screen}");
+    assert!(screen.contains("Waiting for connection state"), "{screen}");
+    assert!(screen.contains("INFO started"), "{screen}");
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use deck_robot_remote::zenoh_auth::ZenohAuth;
+use zenoh::config::Config;
+
+fn credentials() -> ZenohAuth {
+    ZenohAuth {
+        user: Some("operator".to_owned()),
+        password: Some("secret".to_owned()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn user_and_password_are_set_together() {
+    assert!(ZenohAuth::default().validate().is_ok());
+    assert!(credentials().validate().is_ok());
+    let user_only = ZenohAuth {
+        password: None,
+        ..credentials()
+    };
+    assert!(user_only.validate().is_err());
+    let both_passwords = ZenohAuth {
+        password_file: Some(PathBuf::from("password")),
+        ..credentials()
+    };
+    assert!(both_passwords.validate().is_err());
+}
+
+#[test]
+fn certificate_needs_a_private_key() {
+    let auth = ZenohAuth {
+        certificate: Some(PathBuf::from("remote.pem")),
+        ..Default::default()
+    };
+    assert!(auth.validate().is_err());
+}
+
+#[test]
+fn flags_override_the_config_file() {
+    let flags = ZenohAuth {
+        password_file: Some(PathBuf::from("password")),
+        ..Default::default()
+    };
+    let config_file = ZenohAuth {
+        root_ca_certificate: Some(PathBuf::from("ca.pem")),
+        ..credentials()
+    };
+    let auth = flags.or(config_file);
+    assert_eq!(auth.user.as_deref(), Some("operator"));
+    // the password file replaces the password of the config file instead of clashing with it
+    assert_eq!(auth.password, None);
+    assert_eq!(auth.password_file, Some(PathBuf::from("password")));
+    assert_eq!(auth.root_ca_certificate, Some(PathBuf::from("ca.pem")));
+    assert!(auth.validate().is_ok());
+}
+
+#[test]
+fn credentials_are_added_to_the_zenoh_config() {
+    let auth = ZenohAuth {
+        root_ca_certificate: Some(PathBuf::from("/etc/ca.pem")),
+        certificate: Some(PathBuf::from("/etc/remote.pem")),
+        private_key: Some(PathBuf::from("/etc/remote.key")),
+        ..credentials()
+    };
+    let mut config = Config::default();
+    auth.apply(&mut config).unwrap();
+
+    let config = serde_json::to_value(&config).unwrap();
+    // secrets are hidden when the config is serialized
+    assert_eq!(config["transport"]["auth"]["usrpwd"]["user"], "operator");
+    let tls = &config["transport"]["link"]["tls"];
+    assert_eq!(tls["root_ca_certificate"], "/etc/ca.pem");
+    assert_eq!(tls["client_certificate"], "/etc/remote.pem");
+    assert_eq!(tls["server_certificate"], "/etc/remote.pem");
+    assert_eq!(tls["client_auth"], true);
+}